@@ -174,6 +174,13 @@ MIN_WEBSOCKET_PORT: Final[int] = 9000
 MAX_WEBSOCKET_PORT: Final[int] = 9999
 """Maximum WebSocket port number."""
 
+UNHANDLED_EXCEPTION_MARKER: Final[str] = "[tailor] unhandled exception"
+"""Starts the stderr line printed before the traceback of an exception nothing caught.
+
+The Rust side watches for it; a bare ``Traceback`` line also comes from logged
+exceptions the sidecar survives.
+"""
+
 
 # ============================================================================
 # Path Constants
@@ -232,6 +239,9 @@ LOG_DATE_FORMAT: Final[str] = "%Y-%m-%d %H:%M:%S"
 DEFAULT_LOG_LEVEL: Final[str] = "INFO"
 """Default logging level."""
 
+LOG_LEVELS: Final[tuple] = ("DEBUG", "INFO", "WARNING", "ERROR", "CRITICAL")
+"""Levels accepted by ``--log-level`` and the ``set_log_level`` command."""
+
 
 # ============================================================================
 # Vault Configuration Defaults
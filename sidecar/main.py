@@ -17,6 +17,7 @@ from typing import Optional
 from .websocket_server import WebSocketServer
 from .vault_brain import VaultBrain
 from . import utils
+from . import constants
 from . import exceptions
 
 from loguru import logger
@@ -65,7 +66,7 @@ def parse_arguments() -> argparse.Namespace:
     # Optional arguments
     parser.add_argument(
         "--log-level",
-        choices=["DEBUG", "INFO", "WARNING", "ERROR", "CRITICAL"],
+        choices=constants.LOG_LEVELS,
         help="Logging level (default: from environment or INFO)",
     )
     parser.add_argument(
@@ -73,6 +74,16 @@ def parse_arguments() -> argparse.Namespace:
         type=Path,
         help="Log file path (if not specified, logs only to console)",
     )
+    parser.add_argument(
+        "--ready-file",
+        type=Path,
+        help="File to write the bound port to once the sidecar is ready to serve requests",
+    )
+    parser.add_argument(
+        "--control-port",
+        type=int,
+        help="Second WebSocket port reserved for control operations (ping, reload, shutdown)",
+    )
     parser.add_argument(
         "-v",
         "--verbose",
@@ -125,6 +136,7 @@ def main() -> None:
         log_file=log_file,
         verbose=args.verbose,
     )
+    utils.install_excepthook()
 
     logger.info("=" * 60)
     logger.info("Tailor Python Sidecar starting...")
@@ -150,7 +162,11 @@ def main() -> None:
     try:
         # Initialize WebSocket server
         logger.info("Initializing WebSocket server...")
-        ws_server = WebSocketServer(port=args.ws_port)
+        ws_server = WebSocketServer(
+            port=args.ws_port,
+            ready_file=args.ready_file,
+            control_port=args.control_port,
+        )
 
         # Initialize vault brain (creates emitter internally)
         logger.info("Initializing VaultBrain...")
@@ -10,6 +10,16 @@ def test_parse_arguments():
         assert str(args.vault) == "/tmp/vault"
         assert args.ws_port == 8080
         assert args.verbose is True
+        assert args.ready_file is None
+
+    with patch("sys.argv", ["main.py", "--vault", "/tmp/vault", "--ws-port", "8080",
+                            "--ready-file", "/tmp/sidecar.ready"]):
+        assert main.parse_arguments().ready_file == Path("/tmp/sidecar.ready")
+
+    with patch("sys.argv", ["main.py", "--vault", "/tmp/vault", "--ws-port", "8080",
+                            "--control-port", "8081"]):
+        args = main.parse_arguments()
+        assert (args.ws_port, args.control_port) == (8080, 8081)
 
 @pytest.mark.asyncio
 async def test_run_servers():
@@ -2,7 +2,11 @@
 Tests for Utilities.
 """
 
+import sys
+import threading
+
 import pytest
+import sidecar
 from sidecar import utils, exceptions
 
 
@@ -50,7 +54,61 @@ def test_validate_jsonrpc_message_invalid():
         )
 
 
+def test_set_log_level():
+    assert utils.set_log_level("debug") == "DEBUG"
+    with pytest.raises(ValueError):
+        utils.set_log_level("verbose")
+    utils.set_log_level("INFO")
+
+
+def test_install_excepthook(monkeypatch, capsys):
+    seen = []
+    monkeypatch.setattr(sys, "excepthook", lambda *exc: seen.append(exc[0]))
+    monkeypatch.setattr(threading, "excepthook", lambda args: seen.append(args.exc_type))
+    utils.install_excepthook()
+
+    sys.excepthook(ValueError, ValueError("index corrupted"), None)
+    worker = threading.Thread(target=lambda: 1 / 0)
+    worker.start()
+    worker.join()
+    sys.excepthook(KeyboardInterrupt, KeyboardInterrupt(), None)
+
+    assert capsys.readouterr().err.splitlines() == [
+        "[tailor] unhandled exception: ValueError: index corrupted",
+        "[tailor] unhandled exception: ZeroDivisionError: division by zero",
+    ]
+    assert seen == [ValueError, ZeroDivisionError, KeyboardInterrupt]
+
+
+def test_version_info(monkeypatch):
+    monkeypatch.setenv("TAILOR_GIT_COMMIT", "abc123")
+    info = utils.version_info()
+    assert info["version"] == sidecar.__version__
+    assert info["git_commit"] == "abc123"
+    assert info["python"].count(".") == 2
+
+
+def test_build_binary_result():
+    frame = utils.build_binary_result(b"\x00\xff", "ab")
+    assert frame == b"\x00\x02ab\x00\xff"
+    assert utils.build_binary_result(b"", 42) == b"\x00\x0242"
+
+
 # Path Tests
+def test_read_shared_memory():
+    from multiprocessing import shared_memory
+
+    shm = shared_memory.SharedMemory(create=True, size=16)
+    try:
+        shm.buf[:5] = b"hello"
+        descriptor = {"$shm": {"name": shm.name, "size": 5}}
+        assert utils.read_shared_memory(descriptor) == b"hello"
+        assert utils.read_shared_memory(descriptor["$shm"]) == b"hello"
+    finally:
+        shm.close()
+        shm.unlink()
+
+
 def test_validate_vault_path(tmp_path):
     # Valid
     assert utils.validate_vault_path(tmp_path) == tmp_path.resolve()
@@ -66,6 +124,13 @@ def test_validate_vault_path(tmp_path):
         utils.validate_vault_path(f)
 
 
+def test_write_ready_file(tmp_path):
+    ready_file = tmp_path / "sidecar.ready"
+    utils.write_ready_file(ready_file, 9123)
+    assert ready_file.read_text() == "9123"
+    assert not (tmp_path / "sidecar.ready.tmp").exists()
+
+
 # ID Generation
 def test_generate_id():
     id1 = utils.generate_id()
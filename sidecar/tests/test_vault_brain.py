@@ -77,6 +77,23 @@ class TestVaultBrain:
         await brain.initialize()
         assert brain.config["name"] == valid_vault.name
 
+    @pytest.mark.asyncio
+    async def test_get_version_runs_off_the_event_loop(self, valid_vault, mock_ws_server):
+        """system.version may run git, which must not block other clients."""
+        import threading
+
+        loop_thread = threading.get_ident()
+        threads = []
+
+        def version_info():
+            threads.append(threading.get_ident())
+            return {"version": "1.0", "git_commit": None, "python": "3.12.0"}
+
+        brain = VaultBrain(valid_vault, mock_ws_server)
+        with patch("sidecar.utils.version_info", version_info):
+            assert (await brain.get_version())["version"] == "1.0"
+        assert threads and threads[0] != loop_thread
+
     @patch("sidecar.utils.validate_plugin_structure")
     @patch("sidecar.vault_brain.importlib.util.spec_from_file_location")
     @patch("sidecar.vault_brain.importlib.util.module_from_spec")
@@ -155,6 +155,104 @@ class TestWebSocketServer:
                 or "ValueError" in response["error"]["message"]
             )
 
+    @pytest.mark.asyncio
+    async def test_handle_connection_pairs_attachment(self, server, mock_ws):
+        """Test that a binary frame is delivered with its announcing request."""
+        request = utils.build_request("files.upload", {"name": "a.png"}, request_id="1")
+        request["attachment"] = {"size": 3}
+
+        async def mock_iter():
+            yield json.dumps(request)
+            yield b"\x89PN"
+
+        mock_ws.__aiter__.side_effect = mock_iter
+
+        with patch.object(server, "handle_message", AsyncMock()) as handle:
+            await server.handle_connection(mock_ws)
+
+        handle.assert_called_once_with(json.dumps(request), blob=b"\x89PN", reply_to=mock_ws)
+
+    @pytest.mark.asyncio
+    async def test_handle_message_with_blob(self, server):
+        """Test that the attachment reaches the handler as the blob param."""
+        mock_brain = MagicMock()
+        mock_brain.execute_command = AsyncMock(return_value={"status": "ok"})
+
+        with patch.dict(
+            "sys.modules",
+            {
+                "sidecar.vault_brain": MagicMock(
+                    VaultBrain=MagicMock(get=MagicMock(return_value=mock_brain))
+                )
+            },
+        ):
+            server.connection = Mock()
+            server.connection.send = AsyncMock()
+
+            request = utils.build_request("files.upload", {"name": "a.png"}, request_id="1")
+            request["attachment"] = {"size": 3}
+
+            await server.handle_message(json.dumps(request), blob=b"abc")
+
+            mock_brain.execute_command.assert_called_once_with(
+                "files.upload", name="a.png", blob=b"abc"
+            )
+
+    @pytest.mark.asyncio
+    async def test_handle_message_binary_result(self, server):
+        """Test that a requested binary result goes out as one tagged binary frame."""
+        mock_brain = MagicMock()
+        mock_brain.execute_command = AsyncMock(side_effect=[b"\x89PNG", {"not": "bytes"}])
+
+        with patch.dict(
+            "sys.modules",
+            {
+                "sidecar.vault_brain": MagicMock(
+                    VaultBrain=MagicMock(get=MagicMock(return_value=mock_brain))
+                )
+            },
+        ):
+            server.connection = Mock()
+            server.connection.send = AsyncMock()
+
+            request = utils.build_request("graph.render", {}, request_id="7")
+            request["binary_result"] = True
+
+            await server.handle_message(json.dumps(request))
+            frame = server.connection.send.call_args[0][0]
+            assert frame == utils.build_binary_result(b"\x89PNG", "7")
+
+            # A handler that doesn't return bytes gets an error response
+            await server.handle_message(json.dumps(request))
+            response = json.loads(server.connection.send.call_args[0][0])
+            assert response["error"]["code"] == constants.JSONRPC_INTERNAL_ERROR
+            assert response["id"] == "7"
+
+    @pytest.mark.asyncio
+    async def test_control_requests_are_answered_on_their_connection(self, server):
+        """Test that a control socket request is answered there, not on the main connection."""
+        mock_brain = MagicMock()
+        mock_brain.execute_command = AsyncMock(return_value={"plugins_added": []})
+
+        with patch.dict(
+            "sys.modules",
+            {
+                "sidecar.vault_brain": MagicMock(
+                    VaultBrain=MagicMock(get=MagicMock(return_value=mock_brain))
+                )
+            },
+        ):
+            server.connection = Mock()
+            server.connection.send = AsyncMock()
+            control = AsyncMock()
+
+            request = utils.build_request("reload", {}, request_id="9")
+            await server.handle_message(json.dumps(request), reply_to=control)
+
+            server.connection.send.assert_not_called()
+            response = json.loads(control.send.call_args[0][0])
+            assert response == {"jsonrpc": "2.0", "result": {"plugins_added": []}, "id": "9"}
+
     @pytest.mark.asyncio
     async def test_handle_connection(self, server, mock_ws):
         """Test that new connection is stored."""
@@ -169,3 +267,43 @@ class TestWebSocketServer:
 
         # Should have set connection, then cleared it in finally block
         assert server.connection is None
+        assert server.connections == []
+
+    @pytest.mark.asyncio
+    async def test_second_client_does_not_take_the_first_ones_replies(self, server):
+        """Test that a short-lived client (a health ping) leaves the window's socket working."""
+        mock_brain = MagicMock()
+        mock_brain.execute_command = AsyncMock(return_value="pong")
+        window = AsyncMock()
+        window.remote_address = ("127.0.0.1", 1)
+        server.connections.append(window)
+        server.connection = window
+
+        async def ping_iter():
+            if False:
+                yield "msg"  # Connects and goes away
+
+        ping = AsyncMock()
+        ping.remote_address = ("127.0.0.1", 2)
+        ping.__aiter__.side_effect = ping_iter
+        await server.handle_connection(ping)
+        assert server.connection is window
+
+        async def window_iter():
+            yield json.dumps(utils.build_request("echo", {}, request_id="1"))
+
+        window.__aiter__.side_effect = window_iter
+        with patch.dict(
+            "sys.modules",
+            {
+                "sidecar.vault_brain": MagicMock(
+                    VaultBrain=MagicMock(get=MagicMock(return_value=mock_brain))
+                )
+            },
+        ):
+            server.connections.remove(window)
+            await server.handle_connection(window)
+
+        response = json.loads(window.send.call_args[0][0])
+        assert response == {"jsonrpc": "2.0", "result": "pong", "id": "1"}
+        ping.send.assert_not_called()
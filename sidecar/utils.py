@@ -5,15 +5,20 @@ Consolidated utilities for the Sidecar application.
 Includes:
 - Logging Configuration
 - JSON-RPC Utilities
+- Shared Memory
 - Path Utilities
 - ID Generation
 """
 
-from typing import Any, Dict, Optional
+from typing import Any, Dict, Optional, Union
 from pathlib import Path
 import os
+import platform
+import subprocess
 import sys
+import threading
 import time
+import traceback
 
 import random
 import string
@@ -26,6 +31,9 @@ from . import exceptions
 
 from loguru import logger
 
+# Kept so set_log_level() can re-create the handlers at another level
+_log_file: Optional[Path] = None
+
 
 def configure_logging(
     level: Optional[str] = None,
@@ -35,6 +43,9 @@ def configure_logging(
     """
     Configure logging using Loguru.
     """
+    global _log_file
+    _log_file = log_file
+
     # Remove default handler
     logger.remove()
 
@@ -82,6 +93,46 @@ def configure_logging(
     logger.info(f"Logging configured at {log_level} level")
 
 
+def set_log_level(level: str) -> str:
+    """
+    Switch logging to ``level`` (one of ``constants.LOG_LEVELS``, any case),
+    keeping the log file configured at startup. Returns the normalized level.
+    """
+    normalized = level.upper()
+    if normalized not in constants.LOG_LEVELS:
+        raise ValueError(
+            f"Invalid log level '{level}' (expected one of {', '.join(constants.LOG_LEVELS)})"
+        )
+    configure_logging(level=normalized, log_file=_log_file)
+    return normalized
+
+
+def install_excepthook() -> None:
+    """
+    Print ``constants.UNHANDLED_EXCEPTION_MARKER`` to stderr ahead of the
+    traceback of any exception nothing caught, in the main thread or another.
+    """
+    previous_hook = sys.excepthook
+    previous_thread_hook = threading.excepthook
+
+    def mark(exc_type: type, exc_value: BaseException) -> None:
+        summary = "".join(traceback.format_exception_only(exc_type, exc_value)).strip()
+        print(f"{constants.UNHANDLED_EXCEPTION_MARKER}: {summary}", file=sys.stderr, flush=True)
+
+    def excepthook(exc_type, exc_value, exc_traceback):
+        if not issubclass(exc_type, KeyboardInterrupt):
+            mark(exc_type, exc_value)
+        previous_hook(exc_type, exc_value, exc_traceback)
+
+    def thread_excepthook(args):
+        if args.exc_type is not SystemExit:
+            mark(args.exc_type, args.exc_value)
+        previous_thread_hook(args)
+
+    sys.excepthook = excepthook
+    threading.excepthook = thread_excepthook
+
+
 # =============================================================================
 # JSON-RPC Utilities
 # =============================================================================
@@ -121,6 +172,16 @@ def build_response(
     }
 
 
+def build_binary_result(result: bytes, request_id: Union[str, int]) -> bytes:
+    """
+    Frame a binary result for a request sent with ``"binary_result": true``:
+    a 2-byte big-endian length, the request id in UTF-8 (integer ids in decimal),
+    then the bytes.
+    """
+    tag = str(request_id).encode()
+    return len(tag).to_bytes(2, "big") + tag + bytes(result)
+
+
 def build_error(
     code: int,
     message: str,
@@ -245,6 +306,39 @@ def get_params(message: Dict[str, Any]) -> Dict[str, Any]:
     return params if isinstance(params, dict) else {}
 
 
+# =============================================================================
+# Shared Memory
+# =============================================================================
+
+
+def read_shared_memory(descriptor: Dict[str, Any]) -> bytes:
+    """
+    Read a payload the Rust side placed in POSIX shared memory.
+
+    Accepts either the full ``{"$shm": {"name": ..., "size": ...}}`` param
+    value or its inner object. Returns a copy; the Rust side owns the region
+    and unlinks it once the command has returned.
+
+    The descriptor's name has no leading slash, like ``SharedMemory.name``;
+    the OS-level POSIX name is that name with a single ``/`` in front.
+    """
+    from multiprocessing import resource_tracker, shared_memory
+
+    info = descriptor.get("$shm", descriptor)
+    try:
+        shm = shared_memory.SharedMemory(name=info["name"], track=False)
+    except TypeError:
+        # Python < 3.13 always tracks attached segments and would unlink on exit
+        shm = shared_memory.SharedMemory(name=info["name"])
+        # The tracker registered the POSIX name, which `.name` strips the slash from
+        resource_tracker.unregister("/" + shm.name.lstrip("/"), "shared_memory")
+
+    try:
+        return bytes(shm.buf[: info["size"]])
+    finally:
+        shm.close()
+
+
 # =============================================================================
 # Path Utilities
 # =============================================================================
@@ -310,6 +404,17 @@ def ensure_directory(path: Path, create: bool = True) -> Path:
     return resolved
 
 
+def write_ready_file(path: Path, port: int) -> None:
+    """
+    Atomically write the bound port to the readiness file watched by the Rust side.
+
+    Written to a sibling temp file and renamed, so the file never appears half-written.
+    """
+    tmp_path = path.with_name(path.name + ".tmp")
+    tmp_path.write_text(str(port), encoding="utf-8")
+    os.replace(tmp_path, path)
+
+
 def get_vault_config_path(vault_path: Path) -> Path:
     """Get the path to the vault configuration file."""
     return vault_path / constants.VAULT_CONFIG_FILE
@@ -342,3 +447,34 @@ def generate_id(prefix: str = "") -> str:
     if prefix:
         return f"{prefix}{timestamp}_{random_suffix}"
     return f"{timestamp}_{random_suffix}"
+
+
+def version_info() -> Dict[str, Any]:
+    """
+    The sidecar's version, git commit and Python version, answered by the
+    ``system.version`` command. The commit comes from ``TAILOR_GIT_COMMIT`` or,
+    in a checkout, ``git rev-parse HEAD``; it is None if neither is available.
+    Blocks for up to 2s while git runs, so async callers should use
+    ``asyncio.to_thread``.
+    """
+    from . import __version__
+
+    commit = os.environ.get("TAILOR_GIT_COMMIT")
+    if not commit:
+        try:
+            result = subprocess.run(
+                ["git", "rev-parse", "HEAD"],
+                cwd=Path(__file__).parent,
+                capture_output=True,
+                text=True,
+                timeout=2,
+            )
+            commit = result.stdout.strip() if result.returncode == 0 else None
+        except (OSError, subprocess.SubprocessError):
+            commit = None
+
+    return {
+        "version": __version__,
+        "git_commit": commit or None,
+        "python": platform.python_version(),
+    }
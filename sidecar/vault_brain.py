@@ -488,6 +488,21 @@ class VaultBrain:
         """List all registered commands."""
         return {"status": "success", "commands": list(self.commands.keys())}
 
+    @command("system.version", constants.CORE_PLUGIN_NAME)
+    async def get_version(self) -> Dict[str, Any]:
+        """Report the sidecar's version and build info (asked for by the Rust manager)."""
+        # May run git; keep it off the event loop the other clients share
+        return await asyncio.to_thread(utils.version_info)
+
+    @command("set_log_level", constants.CORE_PLUGIN_NAME)
+    async def set_log_level(self, level: str = "", **kwargs) -> Dict[str, Any]:
+        """Change the log level without restarting (sent by the Rust manager)."""
+        try:
+            level = utils.set_log_level(level)
+        except ValueError as e:
+            return {"status": "error", "error": str(e)}
+        return {"status": "success", "level": level}
+
     @command("system.list_tools", constants.CORE_PLUGIN_NAME)
     async def list_tools(self) -> Dict[str, Any]:
         """List all registered LangGraph tools and their schemas."""
@@ -6,7 +6,9 @@ manages command/event exchange using JSON-RPC 2.0 protocol.
 """
 
 import asyncio
+import contextlib
 import json
+from pathlib import Path
 from typing import Optional, Dict, Any, Callable, Awaitable
 import websockets
 from websockets.exceptions import ConnectionClosed
@@ -30,23 +32,52 @@ class WebSocketServer:
     Handlers can be registered for specific methods, and messages
     are routed to the appropriate handler.
 
+    A request carrying an ``"attachment": {"size": N}`` member is followed
+    by a single binary frame holding the raw bytes; the pair is handled as
+    one request, with the bytes passed to the handler as ``blob``.
+
+    Several clients may connect to the main socket at once (the window, the
+    Rust manager). Each request is answered on the socket it came in on;
+    events go to the most recent client still connected.
+
+    With a ``control_port``, a second listener serves control operations
+    (health pings, reload, shutdown) so they aren't queued behind large
+    requests on the main socket. Its clients never become the connection
+    events are sent to.
+
+    A request carrying ``"binary_result": true`` is answered with a single
+    binary frame (see ``utils.build_binary_result``) instead of a JSON
+    response when its handler returns bytes; errors are still JSON.
+
     Example:
         >>> server = WebSocketServer(port=9001)
         >>> server.register_handler("chat.send", handle_chat)
         >>> await server.start()
     """
 
-    def __init__(self, port: int, host: str = constants.DEFAULT_WEBSOCKET_HOST):
+    def __init__(
+        self,
+        port: int,
+        host: str = constants.DEFAULT_WEBSOCKET_HOST,
+        ready_file: Optional[Path] = None,
+        control_port: Optional[int] = None,
+    ):
         """
         Initialize WebSocket server.
 
         Args:
             port: Port to listen on
             host: Host address to bind to (default: localhost)
+            ready_file: File to write the port to once listening (readiness handshake)
+            control_port: Port for the control socket, if any
         """
         self.port = port
         self.host = host
+        self.ready_file = ready_file
+        self.control_port = control_port
         self.connection: Optional[Any] = None
+        # Open main socket clients, most recent last; ``connection`` is the last one
+        self.connections: list[Any] = []
         self.message_queue: asyncio.Queue = asyncio.Queue()
         self.pending_messages: list[Dict[str, Any]] = []
         self.brain = None  # Will be set by VaultBrain after initialization
@@ -62,12 +93,25 @@ class WebSocketServer:
         """
         logger.info(f"Starting WebSocket server on ws://{self.host}:{self.port}")
 
-        async with websockets.serve(
-            self.handle_connection,
-            self.host,
-            self.port,
-        ):
+        async with contextlib.AsyncExitStack() as stack:
+            await stack.enter_async_context(
+                websockets.serve(self.handle_connection, self.host, self.port)
+            )
             logger.info(f"WebSocket server listening on ws://{self.host}:{self.port}")
+            if self.control_port is not None:
+                await stack.enter_async_context(
+                    websockets.serve(
+                        self.handle_control_connection, self.host, self.control_port
+                    )
+                )
+                logger.info(
+                    f"Control socket listening on ws://{self.host}:{self.control_port}"
+                )
+
+            # Tell the Rust side we are ready; plugins were initialized before start()
+            if self.ready_file:
+                utils.write_ready_file(self.ready_file, self.port)
+                logger.debug(f"Wrote ready file: {self.ready_file}")
 
             # Send any pending messages that were queued before server started
             if self.pending_messages and self.connection:
@@ -88,11 +132,24 @@ class WebSocketServer:
         """
         client_addr = websocket.remote_address
         logger.info(f"Client connected from {client_addr}")
+        self.connections.append(websocket)
         self.connection = websocket
 
+        # Text frame of a request whose binary attachment frame is still to come
+        awaiting_blob: Optional[str] = None
+
         try:
             async for message in websocket:
-                await self.handle_message(message)
+                if isinstance(message, bytes):
+                    if awaiting_blob is None:
+                        logger.warning("Ignoring binary frame without an attachment header")
+                        continue
+                    text, awaiting_blob = awaiting_blob, None
+                    await self.handle_message(text, blob=message, reply_to=websocket)
+                elif self._expects_attachment(message):
+                    awaiting_blob = message
+                else:
+                    await self.handle_message(message, reply_to=websocket)
 
         except ConnectionClosed as e:
             logger.info(f"Client disconnected: {e.code} - {e.reason}")
@@ -101,10 +158,45 @@ class WebSocketServer:
             logger.exception(f"WebSocket error: {e}")
 
         finally:
-            self.connection = None
+            self.connections.remove(websocket)
+            # Events fall back to whoever is still connected
+            self.connection = self.connections[-1] if self.connections else None
             logger.debug("Connection closed")
 
-    async def handle_message(self, message: str) -> None:
+    async def handle_control_connection(self, websocket: Any) -> None:
+        """
+        Handle a connection to the control socket, answering on it.
+
+        Args:
+            websocket: WebSocket connection instance
+        """
+        logger.debug(f"Control client connected from {websocket.remote_address}")
+        try:
+            async for message in websocket:
+                if isinstance(message, bytes):
+                    logger.warning("Ignoring binary frame on the control socket")
+                    continue
+                await self.handle_message(message, reply_to=websocket)
+        except ConnectionClosed:
+            pass
+        except Exception as e:
+            logger.exception(f"Control socket error: {e}")
+
+    @staticmethod
+    def _expects_attachment(message: str) -> bool:
+        """Check whether a text frame announces a following binary frame."""
+        try:
+            data = json.loads(message)
+        except json.JSONDecodeError:
+            return False
+        return isinstance(data, dict) and isinstance(data.get("attachment"), dict)
+
+    async def handle_message(
+        self,
+        message: str,
+        blob: Optional[bytes] = None,
+        reply_to: Optional[Any] = None,
+    ) -> None:
         """
         Handle incoming message from Rust.
 
@@ -113,6 +205,9 @@ class WebSocketServer:
 
         Args:
             message: JSON-RPC message string
+            blob: Binary attachment announced by the message, if any
+            reply_to: Connection the message came in on; None answers on
+                ``connection``
         """
         request_id: Optional[str] = None
 
@@ -144,12 +239,38 @@ class WebSocketServer:
 
             logger.debug(f"Received command: {method}")
 
+            if blob is not None:
+                expected = data["attachment"].get("size")
+                if expected != len(blob):
+                    logger.error(f"Attachment size mismatch for '{method}'")
+                    await self._reply(
+                        utils.build_error(
+                            constants.JSONRPC_INVALID_PARAMS,
+                            f"Attachment size mismatch: expected {expected}, got {len(blob)}",
+                            request_id=request_id,
+                        ),
+                        reply_to,
+                    )
+                    return
+                params = {**params, "blob": blob}
+
             try:
                 result = await self._execute_request(method, params, request_id)
 
+                if data.get("binary_result"):
+                    if not isinstance(result, (bytes, bytearray, memoryview)):
+                        raise TypeError(
+                            f"Binary result requested but handler returned {type(result).__name__}"
+                        )
+                    await self._reply(
+                        utils.build_binary_result(result, request_id), reply_to
+                    )
+                    logger.debug(f"Command '{method}' executed successfully")
+                    return
+
                 # Send success response
                 response = utils.build_response(result, request_id=request_id)
-                await self.send(response)
+                await self._reply(response, reply_to)
                 logger.debug(f"Command '{method}' executed successfully")
 
             except exceptions.MethodNotFoundError:
@@ -158,7 +279,7 @@ class WebSocketServer:
                     method=method,
                     request_id=request_id,
                 )
-                await self.send(error_response)
+                await self._reply(error_response, reply_to)
 
             except Exception as e:
                 logger.exception(f"Execution error for '{method}': {e}")
@@ -170,7 +291,7 @@ class WebSocketServer:
                     },
                     request_id=request_id,
                 )
-                await self.send(error_response)
+                await self._reply(error_response, reply_to)
 
         except exceptions.WebSocketMessageError as e:
             logger.error(f"Message handling error: {e.message}")
@@ -216,6 +337,42 @@ class WebSocketServer:
         else:
             logger.warning("No active connection, cannot send message")
 
+    async def _reply(self, data: Any, reply_to: Optional[Any]) -> None:
+        """
+        Send a response (a JSON-RPC message or a binary frame) to Rust.
+
+        Args:
+            data: Response message or frame bytes
+            reply_to: Connection the request came in on, if known
+        """
+        if reply_to is None:
+            if isinstance(data, bytes):
+                await self.send_bytes(data)
+            else:
+                await self.send(data)
+            return
+        try:
+            await reply_to.send(data if isinstance(data, bytes) else json.dumps(data))
+        except Exception as e:
+            logger.exception(f"Reply send error: {e}")
+
+    async def send_bytes(self, data: bytes) -> None:
+        """
+        Send a binary frame to Rust.
+
+        Args:
+            data: Frame payload
+        """
+        if self.is_connected():
+            try:
+                await self.connection.send(data)
+                logger.debug(f"Sent binary frame of {len(data)} bytes")
+            except Exception as e:
+                logger.exception(f"Send error: {e}")
+                self.close()
+        else:
+            logger.warning("No active connection, cannot send message")
+
     def close(self) -> None:
         """
         Close the WebSocket connection.
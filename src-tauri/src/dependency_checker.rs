@@ -1,16 +1,116 @@
-use std::path::PathBuf;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 use anyhow::Result;
+use futures::StreamExt;
+use serde::Serialize;
+
+/// Vaults inspected at once by `preflight`
+const PREFLIGHT_CONCURRENCY: usize = 4;
+
+/// Virtual environment in the vault root that the pip backend installs with
+const VENV_DIR: &str = ".venv";
+
+/// Which tool installed a vault's requirements
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InstallBackend {
+    /// `pip` run inside the pixi environment of the vault's (or the project's) `pixi.toml`
+    Pixi,
+    /// `pip` from a virtual environment in the vault, created on first use
+    Pip,
+}
+
+/// Outcome of `check_and_install`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct InstallReport {
+    /// `None` when the vault has no `plugins/requirements.txt`
+    pub backend: Option<InstallBackend>,
+    /// The requirements now installed in `lib`
+    pub requirements: Vec<String>,
+}
+
+/// How to get a `pip` for the vault, picked by `resolve_backend`
+#[derive(Debug, Clone, PartialEq)]
+enum Backend {
+    /// `pixi` is the program found for the pixi backend
+    Pixi { pixi: String, manifest: PathBuf },
+    /// `python` creates `venv` unless it already has an interpreter
+    Pip { python: String, venv: PathBuf },
+}
+
+/// Whether a vault's `plugins/requirements.txt` is satisfied by its `lib` directory
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "status", content = "detail", rename_all = "snake_case")]
+pub enum DependencyStatus {
+    UpToDate,
+    /// Requirements with no matching distribution in `lib`
+    NeedsInstall(Vec<String>),
+    /// The vault or its requirements could not be inspected
+    Broken(String),
+}
 
 pub struct DependencyChecker;
 
 impl DependencyChecker {
-    /// Check and install dependencies for a vault
-    pub async fn check_and_install(vault_path: &str) -> Result<()> {
-        // Dependency management is now handled by pixi at the project level.
-        // We no longer install per-vault requirements.txt.
-        println!("Skipping per-vault dependency check for: {} (handled by pixi)", vault_path);
-        Ok(())
+    /// Install a vault's `plugins/requirements.txt` into its `lib` directory with the first
+    /// available backend: pixi, when a `pixi.toml` is in the vault or `project_root` and the
+    /// `pixi` program (a name looked up on `PATH`, or a path) runs, else pip from the vault's
+    /// `.venv`, created with `python` (or `python3`/`python` on `PATH`) if missing. Fails listing
+    /// what each backend lacked if neither is available.
+    pub async fn check_and_install(
+        vault_path: &str,
+        project_root: Option<&Path>,
+        python: Option<&str>,
+        pixi: &str,
+    ) -> Result<InstallReport> {
+        let vault_path = PathBuf::from(vault_path);
+        let project_root = project_root.map(Path::to_path_buf);
+        let python = python.map(str::to_string);
+        let pixi = pixi.to_string();
+        tokio::task::spawn_blocking(move || Self::install(&vault_path, project_root.as_deref(), python.as_deref(), &pixi))
+            .await
+            .map_err(|e| anyhow::anyhow!("Dependency install panicked: {}", e))?
+    }
+
+    fn install(vault_path: &Path, project_root: Option<&Path>, python: Option<&str>, pixi: &str) -> Result<InstallReport> {
+        let requirements_file = vault_path.join("plugins").join("requirements.txt");
+        if !requirements_file.exists() {
+            return Ok(InstallReport { backend: None, requirements: Vec::new() });
+        }
+        let requirements = parse_requirements(&std::fs::read_to_string(&requirements_file)?);
+        let backend = resolve_backend(vault_path, project_root, python, pixi).map_err(|missing| {
+            anyhow::anyhow!("No way to install dependencies for {}: {}", vault_path.display(), missing.join("; "))
+        })?;
+
+        let mut command = match &backend {
+            Backend::Pixi { pixi, manifest } => {
+                println!("Installing dependencies for {} with pixi ({})", vault_path.display(), manifest.display());
+                let mut command = Command::new(pixi);
+                command.arg("run").arg("--manifest-path").arg(manifest).arg("python");
+                command
+            }
+            Backend::Pip { python, venv } => {
+                let venv_python = venv_python(venv);
+                if !venv_python.is_file() {
+                    println!("Creating virtual environment {} with {}", venv.display(), python);
+                    run(Command::new(python).arg("-m").arg("venv").arg(venv), "python -m venv")?;
+                }
+                println!("Installing dependencies for {} with pip ({})", vault_path.display(), venv.display());
+                Command::new(venv_python)
+            }
+        };
+        command.args(["-m", "pip", "install", "--upgrade", "--target"])
+            .arg(vault_path.join("lib"))
+            .arg("-r")
+            .arg(&requirements_file);
+        run(&mut command, "pip install")?;
+
+        let backend = match backend {
+            Backend::Pixi { .. } => InstallBackend::Pixi,
+            Backend::Pip { .. } => InstallBackend::Pip,
+        };
+        Ok(InstallReport { backend: Some(backend), requirements })
     }
 
 
@@ -35,4 +135,234 @@ impl DependencyChecker {
         // In production, you'd want to parse requirements.txt and check installed versions
         Ok(false)
     }
+
+    /// Compare a vault's requirements against the distributions installed in its `lib` directory
+    pub async fn needs_update_detailed(vault_path: &str) -> DependencyStatus {
+        let vault_path = PathBuf::from(vault_path);
+        tokio::task::spawn_blocking(move || Self::inspect(&vault_path))
+            .await
+            .unwrap_or_else(|e| DependencyStatus::Broken(format!("Dependency check panicked: {}", e)))
+    }
+
+    /// Check several vaults concurrently (a few at a time), returning statuses in input order
+    pub async fn preflight(vaults: &[String]) -> Vec<(String, DependencyStatus)> {
+        futures::stream::iter(vaults.iter().cloned())
+            .map(|vault| async move {
+                let status = Self::needs_update_detailed(&vault).await;
+                (vault, status)
+            })
+            .buffered(PREFLIGHT_CONCURRENCY)
+            .collect()
+            .await
+    }
+
+    fn inspect(vault_path: &Path) -> DependencyStatus {
+        if !vault_path.is_dir() {
+            return DependencyStatus::Broken(format!("Vault not found: {}", vault_path.display()));
+        }
+        let requirements_file = vault_path.join("plugins").join("requirements.txt");
+        if !requirements_file.exists() {
+            return DependencyStatus::UpToDate;
+        }
+        let requirements = match std::fs::read_to_string(&requirements_file) {
+            Ok(contents) => parse_requirements(&contents),
+            Err(e) => {
+                return DependencyStatus::Broken(format!("Failed to read {}: {}", requirements_file.display(), e))
+            }
+        };
+
+        let installed = match installed_distributions(&vault_path.join("lib")) {
+            Ok(installed) => installed,
+            Err(e) => return DependencyStatus::Broken(format!("Failed to read lib directory: {}", e)),
+        };
+        let missing: Vec<String> = requirements
+            .into_iter()
+            .filter(|name| !installed.contains(&normalize_name(name)))
+            .collect();
+        if missing.is_empty() {
+            DependencyStatus::UpToDate
+        } else {
+            DependencyStatus::NeedsInstall(missing)
+        }
+    }
+}
+
+/// The first usable backend for the vault, or what each one is missing
+fn resolve_backend(
+    vault_path: &Path,
+    project_root: Option<&Path>,
+    python: Option<&str>,
+    pixi: &str,
+) -> std::result::Result<Backend, Vec<String>> {
+    let mut missing = Vec::new();
+
+    let manifest = std::iter::once(vault_path)
+        .chain(project_root)
+        .map(|dir| dir.join("pixi.toml"))
+        .find(|manifest| manifest.is_file());
+    let pixi_found = Command::new(pixi).arg("--version").output().is_ok_and(|output| output.status.success());
+    match (manifest, pixi_found) {
+        (Some(manifest), true) => return Ok(Backend::Pixi { pixi: pixi.to_string(), manifest }),
+        (None, _) => missing.push("pixi: no pixi.toml in the vault or project root".to_string()),
+        (Some(_), false) => missing.push(format!("pixi: {} not found", pixi)),
+    }
+
+    let venv = vault_path.join(VENV_DIR);
+    if venv_python(&venv).is_file() {
+        return Ok(Backend::Pip { python: venv_python(&venv).to_string_lossy().to_string(), venv });
+    }
+    #[cfg(target_os = "windows")]
+    let candidates = ["python.exe", "python3.exe"];
+    #[cfg(not(target_os = "windows"))]
+    let candidates = ["python3", "python"];
+    let python = python.into_iter()
+        .chain(candidates)
+        .find(|python| Command::new(python).arg("--version").output().is_ok_and(|output| output.status.success()));
+    match python {
+        Some(python) => Ok(Backend::Pip { python: python.to_string(), venv }),
+        None => {
+            missing.push("pip: no Python interpreter to create a virtual environment with".to_string());
+            Err(missing)
+        }
+    }
+}
+
+/// The interpreter inside virtual environment `venv`
+fn venv_python(venv: &Path) -> PathBuf {
+    if cfg!(target_os = "windows") {
+        venv.join("Scripts").join("python.exe")
+    } else {
+        venv.join("bin").join("python")
+    }
+}
+
+/// Run `command` to completion, failing with the tail of its stderr
+fn run(command: &mut Command, what: &str) -> Result<()> {
+    let output = command.output().map_err(|e| anyhow::anyhow!("Failed to run {}: {}", what, e))?;
+    if output.status.success() {
+        return Ok(());
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let tail: Vec<&str> = stderr.lines().rev().take(10).collect();
+    let tail: Vec<&str> = tail.into_iter().rev().collect();
+    anyhow::bail!("{} failed ({}): {}", what, output.status, tail.join("\n"))
+}
+
+/// Package names from a requirements file, skipping comments, blank lines and pip options
+fn parse_requirements(contents: &str) -> Vec<String> {
+    contents
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or_default().trim())
+        .filter(|line| !line.is_empty() && !line.starts_with('-'))
+        .filter_map(|line| {
+            let end = line
+                .find(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.')))
+                .unwrap_or(line.len());
+            let name = &line[..end];
+            (!name.is_empty()).then(|| name.to_string())
+        })
+        .collect()
+}
+
+/// Normalized names of the distributions pip installed into `lib_dir` (none if it is missing)
+fn installed_distributions(lib_dir: &Path) -> std::io::Result<HashSet<String>> {
+    if !lib_dir.exists() {
+        return Ok(HashSet::new());
+    }
+    let mut installed = HashSet::new();
+    for entry in std::fs::read_dir(lib_dir)? {
+        let file_name = entry?.file_name();
+        let file_name = file_name.to_string_lossy();
+        let Some(stem) = file_name
+            .strip_suffix(".dist-info")
+            .or_else(|| file_name.strip_suffix(".egg-info"))
+        else {
+            continue;
+        };
+        // `<name>-<version>.dist-info`
+        let name = stem.split('-').next().unwrap_or(stem);
+        installed.insert(normalize_name(name));
+    }
+    Ok(installed)
+}
+
+/// PEP 503 normalization, so `Foo.Bar`, `foo-bar` and `foo_bar` compare equal
+fn normalize_name(name: &str) -> String {
+    name.to_ascii_lowercase().replace(['-', '.'], "_")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_requirements() {
+        let names = parse_requirements(
+            "# comment\nrequests>=2.0\n\n-r other.txt\nPyYAML[extra] ; python_version>'3'\nnumpy  # pinned\n",
+        );
+        assert_eq!(names, ["requests", "PyYAML", "numpy"]);
+    }
+
+    #[test]
+    #[ignore = "spawns Python; run with --include-ignored"]
+    fn test_backend_falls_back_from_pixi_to_pip() {
+        let vault = std::env::temp_dir().join(format!("tailor-backend-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&vault).unwrap();
+        let python = crate::sidecar_manager::python::resolve(None, None, None, None).unwrap().path;
+
+        // A manifest with a working pixi picks pixi
+        std::fs::write(vault.join("pixi.toml"), "[workspace]\n").unwrap();
+        let backend = resolve_backend(&vault, None, Some(&python), &python).unwrap();
+        assert_eq!(backend, Backend::Pixi { pixi: python.clone(), manifest: vault.join("pixi.toml") });
+
+        // A manifest without the pixi binary (or neither) falls through to a venv made with `python`
+        let backend = resolve_backend(&vault, None, Some(&python), "/nonexistent/pixi").unwrap();
+        assert_eq!(backend, Backend::Pip { python: python.clone(), venv: vault.join(VENV_DIR) });
+
+        // An existing venv is used as is
+        let venv_python = venv_python(&vault.join(VENV_DIR));
+        std::fs::create_dir_all(venv_python.parent().unwrap()).unwrap();
+        std::fs::write(&venv_python, "").unwrap();
+        std::fs::remove_file(vault.join("pixi.toml")).unwrap();
+        let backend = resolve_backend(&vault, None, Some("/nonexistent/python"), "/nonexistent/pixi").unwrap();
+        assert_eq!(backend, Backend::Pip { python: venv_python.to_string_lossy().to_string(), venv: vault.join(VENV_DIR) });
+        let _ = std::fs::remove_dir_all(&vault);
+    }
+
+    #[tokio::test]
+    async fn test_install_without_requirements_is_a_no_op() {
+        let vault = std::env::temp_dir().join(format!("tailor-install-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&vault).unwrap();
+        let report = DependencyChecker::check_and_install(&vault.to_string_lossy(), None, None, "pixi").await.unwrap();
+        assert_eq!(report, InstallReport { backend: None, requirements: Vec::new() });
+        let _ = std::fs::remove_dir_all(&vault);
+    }
+
+    #[tokio::test]
+    async fn test_preflight_reports_each_vault() {
+        let root = std::env::temp_dir().join(format!("tailor-preflight-{}", uuid::Uuid::new_v4()));
+        let ok = root.join("ok");
+        let missing = root.join("missing");
+        for vault in [&ok, &missing] {
+            std::fs::create_dir_all(vault.join("plugins")).unwrap();
+            std::fs::write(vault.join("plugins").join("requirements.txt"), "requests\npy-yaml==6\n").unwrap();
+        }
+        std::fs::create_dir_all(ok.join("lib").join("requests-2.31.0.dist-info")).unwrap();
+        std::fs::create_dir_all(ok.join("lib").join("Py_YAML-6.0.dist-info")).unwrap();
+
+        let vaults: Vec<String> = [&ok, &missing, &root.join("gone")]
+            .iter()
+            .map(|p| p.to_string_lossy().to_string())
+            .collect();
+        let statuses: Vec<DependencyStatus> = DependencyChecker::preflight(&vaults)
+            .await
+            .into_iter()
+            .map(|(_, status)| status)
+            .collect();
+
+        assert_eq!(statuses[0], DependencyStatus::UpToDate);
+        assert_eq!(statuses[1], DependencyStatus::NeedsInstall(vec!["requests".into(), "py-yaml".into()]));
+        assert!(matches!(statuses[2], DependencyStatus::Broken(_)));
+        let _ = std::fs::remove_dir_all(&root);
+    }
 }
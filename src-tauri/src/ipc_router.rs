@@ -1,7 +1,8 @@
-use crate::{AppState, dependency_checker::DependencyChecker};
+use crate::{AppState, dependency_checker::{DependencyChecker, DependencyStatus}, sidecar_manager::{Coalesced, ConnStats, EnvUpdate, HealthSummary, JobId, JobStatus, LatencyStats, MemoryUsage, OrphanInfo, QuarantineEntry, RateLimitStats, Rebind, ReloadReport, SidecarError, SidecarHealth, SidecarSignal, SidecarState, SpawnCancel, SpawnOptions, SpawnPreview, StartupStats, VersionInfo}};
 use tauri::{AppHandle, State, Manager, Emitter};
 use serde::{Deserialize, Serialize};
 use anyhow::Result;
+use std::collections::HashMap;
 use std::path::PathBuf;
 use std::fs;
 
@@ -17,30 +18,71 @@ pub struct VaultInfo {
 pub async fn open_vault(
     app: AppHandle,
     vault_path: String,
+    port: Option<u16>,
+    labels: Option<HashMap<String, String>>,
     state: State<'_, AppState>,
 ) -> Result<VaultInfo, String> {
     println!("Opening vault: {}", vault_path);
 
-    // Step 1: Check and install dependencies
-    DependencyChecker::check_and_install(&vault_path)
-        .await
-        .map_err(|e| format!("Failed to install dependencies: {}", e))?;
-
-    // Step 2: Create window
+    // Step 1: Create window
     let window_label = state.window_manager
         .lock()
         .await
         .create_vault_window(&app, vault_path.clone())
         .map_err(|e| format!("Failed to create window: {}", e))?;
 
-    // Step 3: Spawn sidecar
-    let ws_port = state.sidecar_manager
-        .spawn_sidecar(window_label.clone(), vault_path.clone())
-        .await
-        .map_err(|e| format!("Failed to spawn sidecar: {}", e))?;
+    // Step 2: Install missing dependencies and spawn the sidecar, on a port from
+    // `reserve_sidecar_ports` when restoring a session; `cancel_vault_spawn` aborts it until
+    // the sidecar is ready
+    let options = SpawnOptions {
+        port,
+        labels: labels.unwrap_or_default(),
+        install_dependencies: true,
+        ..Default::default()
+    };
+    // Stream the sidecar's boot output so the window can show it while it starts
+    let (startup_logs, mut startup_rx) = tokio::sync::mpsc::unbounded_channel();
+    let log_app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        while let Some(line) = startup_rx.recv().await {
+            let _ = log_app.emit("sidecar://startup-log", &line);
+        }
+    });
+    let spawned = state.sidecar_manager
+        .spawn_sidecar_with_startup_logs(window_label.clone(), vault_path.clone(), options, SpawnCancel::new(), startup_logs)
+        .await;
+    let ws_port = match spawned {
+        Ok(ws_port) => ws_port,
+        Err(e) => {
+            // The window was only there to show the install and boot log; don't leave it
+            // open and tracked without a sidecar
+            if let Some(window) = app.get_webview_window(&window_label) {
+                let _ = window.destroy();
+            }
+            state.window_manager.lock().await.remove_window(&window_label);
+            return Err(format!("Failed to spawn sidecar: {}", e));
+        }
+    };
 
     println!("Vault opened successfully: window={}, port={}", window_label, ws_port);
 
+    // Forward lifecycle changes so the window follows its sidecar without polling
+    let mut sidecar_state = state.sidecar_manager.watch_state(&window_label);
+    let state_app = app.clone();
+    let state_label = window_label.clone();
+    tauri::async_runtime::spawn(async move {
+        while sidecar_state.changed().await.is_ok() {
+            let current = *sidecar_state.borrow_and_update();
+            let _ = state_app.emit("sidecar://state", serde_json::json!({
+                "window_label": state_label,
+                "state": current,
+            }));
+            if current == SidecarState::Terminated {
+                break;
+            }
+        }
+    });
+
     // Register vault in registry
     let vault_path_buf = PathBuf::from(&vault_path);
     let config_path = vault_path_buf.join(".vault.toml");
@@ -80,35 +122,709 @@ pub async fn open_vault(
     })
 }
 
-/// Send command to sidecar
+/// Send command to sidecar. With `refresh_capabilities`, a "method not found" answer re-fetches
+/// the sidecar's command list and retries once if the method has appeared.
 #[tauri::command]
 pub async fn send_to_sidecar(
     window_label: String,
     method: String,
     params: serde_json::Value,
+    refresh_capabilities: Option<bool>,
     state: State<'_, AppState>,
 ) -> Result<serde_json::Value, String> {
     // println!("Sending command to sidecar '{}': {}", window_label, method);
 
+    let manager = &state.sidecar_manager;
+    let result = match refresh_capabilities.unwrap_or(false) {
+        true => manager.send_command_with_capability_refresh(&window_label, &method, params).await,
+        false => manager.send_command(&window_label, &method, params).await,
+    };
+    result.map_err(|e| format!("Sidecar error: {}", e))
+}
+
+/// Like `send_to_sidecar`, but waits `debounce_ms` (150 by default) first and gives up with
+/// `{"status": "superseded"}` if a newer call with the same method and `coalesce_key` comes in
+/// meanwhile, e.g. for search-as-you-type
+#[tauri::command]
+pub async fn send_to_sidecar_coalesced(
+    window_label: String,
+    method: String,
+    params: serde_json::Value,
+    coalesce_key: String,
+    debounce_ms: Option<u64>,
+    state: State<'_, AppState>,
+) -> Result<Coalesced, String> {
+    let debounce = debounce_ms
+        .map(std::time::Duration::from_millis)
+        .unwrap_or(crate::sidecar_manager::coalesce::DEFAULT_DEBOUNCE);
+    state.sidecar_manager
+        .send_command_coalesced(&window_label, &method, params, &coalesce_key, debounce)
+        .await
+        .map_err(|e| format!("Sidecar error: {}", e))
+}
+
+/// Window label, method and params of a command whose body is raw bytes, sent as
+/// `invoke(cmd, bytes, { headers })` with the `Tailor-Window-Label`, `Tailor-Method` and
+/// `Tailor-Params` (ASCII JSON, `{}` if absent) headers, plus the bytes themselves
+fn raw_command(request: &tauri::ipc::Request<'_>) -> Result<(String, String, serde_json::Value, Vec<u8>), String> {
+    let header = |name: &str| {
+        request.headers().get(name)
+            .map(|value| value.to_str().map(str::to_string).map_err(|_| format!("Header {} must be ASCII", name)))
+            .transpose()
+    };
+    let window_label = header("Tailor-Window-Label")?.ok_or("Missing Tailor-Window-Label header")?;
+    let method = header("Tailor-Method")?.ok_or("Missing Tailor-Method header")?;
+    let params = match header("Tailor-Params")? {
+        Some(params) => serde_json::from_str(&params).map_err(|e| format!("Invalid Tailor-Params header: {}", e))?,
+        None => serde_json::json!({}),
+    };
+    let tauri::ipc::InvokeBody::Raw(bytes) = request.body() else {
+        return Err("Expected a raw byte body".to_string());
+    };
+    Ok((window_label, method, params, bytes.clone()))
+}
+
+/// Like `send_to_sidecar`, but ships the request body to the handler as its `blob` param in a
+/// binary WebSocket frame, e.g. an image or PDF the frontend read, without base64 in between
+#[tauri::command]
+pub async fn send_to_sidecar_with_blob(
+    request: tauri::ipc::Request<'_>,
+    state: State<'_, AppState>,
+) -> Result<serde_json::Value, String> {
+    let (window_label, method, params, blob) = raw_command(&request)?;
+    state.sidecar_manager
+        .send_command_with_blob(&window_label, &method, params, blob)
+        .await
+        .map_err(|e| format!("Sidecar error: {}", e))
+}
+
+/// Like `send_to_sidecar`, for methods answering with raw bytes (a rendered image, an exported
+/// file); the frontend receives an `ArrayBuffer` instead of base64 inside JSON
+#[tauri::command]
+pub async fn send_to_sidecar_binary(
+    window_label: String,
+    method: String,
+    params: serde_json::Value,
+    state: State<'_, AppState>,
+) -> Result<tauri::ipc::Response, String> {
+    state.sidecar_manager
+        .send_command_binary(&window_label, &method, params)
+        .await
+        .map(tauri::ipc::Response::new)
+        .map_err(|e| format!("Sidecar error: {}", e))
+}
+
+/// Like `send_to_sidecar`, but hands the request body to the sidecar through POSIX shared
+/// memory as the `payload` param (or the `Tailor-Payload-Key` header's), for documents too
+/// large to serialize over the WebSocket. Needs the `shared-memory` feature on Unix.
+#[tauri::command]
+pub async fn send_to_sidecar_shared(
+    request: tauri::ipc::Request<'_>,
+    state: State<'_, AppState>,
+) -> Result<serde_json::Value, String> {
+    let (window_label, method, params, payload) = raw_command(&request)?;
+    #[cfg(all(unix, feature = "shared-memory"))]
+    {
+        let key = request.headers().get("Tailor-Payload-Key")
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or("payload");
+        state.sidecar_manager
+            .send_command_shared(&window_label, &method, params, key, &payload)
+            .await
+            .map_err(|e| format!("Sidecar error: {}", e))
+    }
+    #[cfg(not(all(unix, feature = "shared-memory")))]
+    {
+        let _ = (window_label, method, params, payload, state);
+        Err("Shared memory payloads need the shared-memory feature on Unix".to_string())
+    }
+}
+
+/// Like `send_to_sidecar`, with extra WebSocket handshake headers for this command only, e.g. a
+/// trace id; it gets a connection of its own
+#[tauri::command]
+pub async fn send_to_sidecar_with_headers(
+    window_label: String,
+    method: String,
+    params: serde_json::Value,
+    headers: HashMap<String, String>,
+    state: State<'_, AppState>,
+) -> Result<serde_json::Value, String> {
+    let headers: Vec<(String, String)> = headers.into_iter().collect();
+    state.sidecar_manager
+        .send_command_with_headers(&window_label, &method, params, &headers)
+        .await
+        .map_err(|e| format!("Sidecar error: {}", e))
+}
+
+/// Like `send_to_sidecar`, but fails at once if the sidecar isn't ready or has no free command
+/// slot, so the UI can fall back to cached data
+#[tauri::command]
+pub async fn try_send_to_sidecar(
+    window_label: String,
+    method: String,
+    params: serde_json::Value,
+    state: State<'_, AppState>,
+) -> Result<serde_json::Value, String> {
+    state.sidecar_manager
+        .try_send_command(&window_label, &method, params)
+        .await
+        .map_err(|e| format!("Sidecar error: {}", e))
+}
+
+/// Forward the notifications a window's sidecar pushes to the frontend as
+/// `sidecar://notification` events (`window_label` plus the `SidecarNotification`) until the
+/// sidecar is terminated. With `subscribe_method` (e.g. `events.subscribe`), that RPC is sent
+/// now and after every restart so the sidecar keeps pushing. Call once per window.
+#[tauri::command]
+pub async fn subscribe_sidecar_notifications(
+    app: AppHandle,
+    window_label: String,
+    subscribe_method: Option<String>,
+    subscribe_params: Option<serde_json::Value>,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    let manager = &state.sidecar_manager;
+    let mut notifications = manager.subscribe_notifications(&window_label)
+        .await
+        .map_err(|e| format!("Failed to subscribe to notifications: {}", e))?;
+    if let Some(method) = subscribe_method {
+        manager.add_subscription(&window_label, &method, subscribe_params.unwrap_or_else(|| serde_json::json!({})))
+            .await
+            .map_err(|e| format!("Failed to subscribe to notifications: {}", e))?;
+    }
+    tauri::async_runtime::spawn(async move {
+        use tokio::sync::broadcast::error::RecvError;
+        loop {
+            match notifications.recv().await {
+                Ok(notification) => {
+                    let _ = app.emit("sidecar://notification", serde_json::json!({
+                        "window_label": window_label,
+                        "notification": notification,
+                    }));
+                }
+                Err(RecvError::Lagged(skipped)) => {
+                    eprintln!("Notification forwarder for window '{}' lagged, skipped {}", window_label, skipped);
+                }
+                Err(RecvError::Closed) => break,
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Start a long-running job on a window's sidecar, returning its job id right away
+#[tauri::command]
+pub async fn submit_sidecar_job(
+    window_label: String,
+    method: String,
+    params: serde_json::Value,
+    state: State<'_, AppState>,
+) -> Result<JobId, String> {
+    state.sidecar_manager
+        .submit_job(&window_label, &method, params)
+        .await
+        .map_err(|e| format!("Failed to submit job: {}", e))
+}
+
+/// Wait up to `timeout_ms` for a submitted job's result
+#[tauri::command]
+pub async fn await_sidecar_job(
+    window_label: String,
+    job_id: String,
+    timeout_ms: u64,
+    state: State<'_, AppState>,
+) -> Result<serde_json::Value, String> {
     state.sidecar_manager
-        .send_command(&window_label, &method, params)
+        .await_job(&window_label, &JobId::parse(&job_id), std::time::Duration::from_millis(timeout_ms))
         .await
         .map_err(|e| format!("Sidecar error: {}", e))
 }
 
-/// Close a vault window and terminate its sidecar
+/// Whether a submitted job is running, completed or failed
+#[tauri::command]
+pub async fn get_sidecar_job_status(
+    job_id: String,
+    state: State<'_, AppState>,
+) -> Result<Option<JobStatus>, String> {
+    Ok(state.sidecar_manager.job_status(&JobId::parse(&job_id)))
+}
+
+/// Send several `[method, params]` commands to one sidecar as separate requests. Returns one
+/// `{result}` or `{error}` entry per command, in order; a failed command doesn't stop the others.
+#[tauri::command]
+pub async fn send_all_to_sidecar(
+    window_label: String,
+    commands: Vec<(String, serde_json::Value)>,
+    state: State<'_, AppState>,
+) -> Result<Vec<serde_json::Value>, String> {
+    let results = state.sidecar_manager.send_all(&window_label, commands).await;
+    Ok(results
+        .into_iter()
+        .map(|result| match result {
+            Ok(response) => serde_json::json!({ "result": response }),
+            Err(e) => serde_json::json!({ "error": e.to_string() }),
+        })
+        .collect())
+}
+
+/// Send a command to every open vault's sidecar, a few at a time.
+/// Returns one `{window_label, result}` or `{window_label, error}` entry per sidecar.
+#[tauri::command]
+pub async fn broadcast_to_sidecars(
+    method: String,
+    params: serde_json::Value,
+    state: State<'_, AppState>,
+) -> Result<Vec<serde_json::Value>, String> {
+    let results = state.sidecar_manager.broadcast_command(&method, params).await;
+    Ok(broadcast_entries(results))
+}
+
+/// Window labels of the sidecars opened with the label `key = value`
+#[tauri::command]
+pub async fn list_sidecars_by_label(
+    key: String,
+    value: String,
+    state: State<'_, AppState>,
+) -> Result<Vec<String>, String> {
+    Ok(state.sidecar_manager.list_sidecars_by_label(&key, &value).await)
+}
+
+/// Window labels of the sidecars already serving `vault_path`, e.g. to focus that window
+/// instead of opening the vault twice
+#[tauri::command]
+pub async fn find_sidecars_by_vault(
+    vault_path: String,
+    state: State<'_, AppState>,
+) -> Result<Vec<String>, String> {
+    Ok(state.sidecar_manager.find_by_vault(&vault_path).await)
+}
+
+/// Like `broadcast_to_sidecars`, limited to the sidecars labelled `key = value`
+#[tauri::command]
+pub async fn broadcast_to_label(
+    key: String,
+    value: String,
+    method: String,
+    params: serde_json::Value,
+    state: State<'_, AppState>,
+) -> Result<Vec<serde_json::Value>, String> {
+    let results = state.sidecar_manager.broadcast_to_label(&key, &value, &method, params).await;
+    Ok(broadcast_entries(results))
+}
+
+fn broadcast_entries(results: Vec<(String, Result<serde_json::Value, SidecarError>)>) -> Vec<serde_json::Value> {
+    results
+        .into_iter()
+        .map(|(window_label, result)| match result {
+            Ok(response) => serde_json::json!({ "window_label": window_label, "result": response }),
+            Err(e) => serde_json::json!({ "window_label": window_label, "error": e.to_string() }),
+        })
+        .collect()
+}
+
+/// Restart a window's sidecar, returning its (usually unchanged) WebSocket port
+#[tauri::command]
+pub async fn restart_sidecar(
+    window_label: String,
+    state: State<'_, AppState>,
+) -> Result<u16, String> {
+    state.sidecar_manager
+        .restart_sidecar(&window_label)
+        .await
+        .map_err(|e| format!("Failed to restart sidecar: {}", e))
+}
+
+/// Hold `count` ports for the vaults of a session being restored, to pass to `open_vault`
+#[tauri::command]
+pub async fn reserve_sidecar_ports(
+    count: usize,
+    state: State<'_, AppState>,
+) -> Result<Vec<u16>, String> {
+    state.sidecar_manager
+        .reserve_ports(count)
+        .await
+        .map_err(|e| format!("Failed to reserve {} ports: {}", count, e))
+}
+
+/// Give back a reserved port that won't be used
+#[tauri::command]
+pub async fn release_port_reservation(
+    port: u16,
+    state: State<'_, AppState>,
+) -> Result<bool, String> {
+    Ok(state.sidecar_manager.release_reservation(port))
+}
+
+/// Free a port held by a leaked sidecar from a previous run ("fix stuck vault")
+#[tauri::command]
+pub async fn reclaim_port(
+    port: u16,
+    state: State<'_, AppState>,
+) -> Result<bool, String> {
+    state.sidecar_manager
+        .reclaim_port(port)
+        .await
+        .map_err(|e| format!("Failed to reclaim port {}: {}", port, e))
+}
+
+/// Point a window at its vault's new location after the directory was moved or renamed
+/// (`vault://path-invalid`), switching the sidecar over or restarting it there
+#[tauri::command]
+pub async fn rebind_vault(
+    app: AppHandle,
+    window_label: String,
+    new_path: String,
+    state: State<'_, AppState>,
+) -> Result<Rebind, String> {
+    let rebind = state.sidecar_manager
+        .rebind_vault(&window_label, &new_path)
+        .await
+        .map_err(|e| format!("Failed to rebind vault: {}", e))?;
+    state.window_manager
+        .lock()
+        .await
+        .set_vault_path(&app, &window_label, new_path)
+        .map_err(|e| format!("Failed to update window: {}", e))?;
+    Ok(rebind)
+}
+
+/// Apply config and plugin changes without restarting Python, if the sidecar supports it
+#[tauri::command]
+pub async fn reload_sidecar(
+    window_label: String,
+    state: State<'_, AppState>,
+) -> Result<ReloadReport, String> {
+    state.sidecar_manager
+        .reload(&window_label)
+        .await
+        .map_err(|e| format!("Failed to reload sidecar: {}", e))
+}
+
+/// Serialize a sidecar's in-memory state; it is restored into the next process when the sidecar
+/// was spawned with `restore_state`
+#[tauri::command]
+pub async fn snapshot_sidecar_state(
+    window_label: String,
+    state: State<'_, AppState>,
+) -> Result<serde_json::Value, String> {
+    state.sidecar_manager
+        .snapshot_state(&window_label)
+        .await
+        .map_err(|e| format!("Failed to snapshot sidecar state: {}", e))
+}
+
+/// The version, git commit and Python version a sidecar reports; `unknown` for sidecars that
+/// don't report them
+#[tauri::command]
+pub async fn get_sidecar_version(
+    window_label: String,
+    state: State<'_, AppState>,
+) -> Result<VersionInfo, String> {
+    state.sidecar_manager
+        .sidecar_version(&window_label)
+        .await
+        .map_err(|e| format!("Failed to get sidecar version: {}", e))
+}
+
+/// Set an environment variable for a sidecar, live if it supports that; `"needs-restart"` means
+/// it takes effect on the next restart
+#[tauri::command]
+pub async fn set_sidecar_env(
+    window_label: String,
+    key: String,
+    value: String,
+    state: State<'_, AppState>,
+) -> Result<EnvUpdate, String> {
+    state.sidecar_manager
+        .set_env(&window_label, &key, &value)
+        .await
+        .map_err(|e| format!("Failed to set sidecar environment: {}", e))
+}
+
+/// Change a sidecar's log level (e.g. `"DEBUG"` while investigating one vault), live if it
+/// supports that; the level is kept across restarts
+#[tauri::command]
+pub async fn set_sidecar_log_level(
+    window_label: String,
+    level: String,
+    state: State<'_, AppState>,
+) -> Result<EnvUpdate, String> {
+    state.sidecar_manager
+        .set_log_level(&window_label, &level)
+        .await
+        .map_err(|e| format!("Failed to set sidecar log level: {}", e))
+}
+
+/// The role a sidecar runs as (e.g. `"indexer"`), if any
+#[tauri::command]
+pub async fn get_sidecar_role(window_label: String, state: State<'_, AppState>) -> Result<Option<String>, String> {
+    state.sidecar_manager
+        .role(&window_label)
+        .await
+        .map_err(|e| format!("Failed to get sidecar role: {}", e))
+}
+
+/// Move a sidecar to another registered role, or to none with `null`; kept across restarts
+#[tauri::command]
+pub async fn set_sidecar_role(
+    window_label: String,
+    role: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    state.sidecar_manager
+        .set_role(&window_label, role)
+        .await
+        .map_err(|e| format!("Failed to set sidecar role: {}", e))
+}
+
+/// Vaults disabled after their sidecar repeatedly failed to start
+#[tauri::command]
+pub async fn list_quarantined_vaults(state: State<'_, AppState>) -> Result<Vec<QuarantineEntry>, String> {
+    Ok(state.sidecar_manager.quarantined_vaults())
+}
+
+/// Re-enable a quarantined vault so it can be opened again; returns whether it was quarantined
+#[tauri::command]
+pub async fn clear_vault_quarantine(vault_path: String, state: State<'_, AppState>) -> Result<bool, String> {
+    Ok(state.sidecar_manager.clear_quarantine(&vault_path))
+}
+
+/// Check a vault's dependencies on its next open even if they passed recently, e.g. after its
+/// plugins were edited by hand; returns whether a result was cached
+#[tauri::command]
+pub async fn invalidate_dependency_cache(vault_path: String, state: State<'_, AppState>) -> Result<bool, String> {
+    Ok(state.sidecar_manager.invalidate_dependency_cache(&vault_path))
+}
+
+/// Send an OS signal (`"hup"`, `"usr1"`, ... or `{"other": n}`) to a sidecar
+#[tauri::command]
+pub async fn signal_sidecar(
+    window_label: String,
+    signal: SidecarSignal,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    state.sidecar_manager
+        .signal_sidecar(&window_label, signal)
+        .await
+        .map_err(|e| format!("Failed to signal sidecar: {}", e))
+}
+
+/// Health of every running sidecar, for the vaults status panel
+#[tauri::command]
+pub async fn get_sidecar_health(state: State<'_, AppState>) -> Result<Vec<SidecarHealth>, String> {
+    Ok(state.sidecar_manager.health_report().await)
+}
+
+/// Combined memory of all sidecars and the budget it is held to
+#[tauri::command]
+pub async fn get_sidecar_memory_usage(state: State<'_, AppState>) -> Result<MemoryUsage, String> {
+    Ok(state.sidecar_manager.memory_usage().await)
+}
+
+/// Healthy, unresponsive and failed sidecar counts, for a top-level "environment broken" banner
+#[tauri::command]
+pub async fn get_sidecar_health_summary(state: State<'_, AppState>) -> Result<HealthSummary, String> {
+    Ok(state.sidecar_manager.health_summary().await)
+}
+
+/// List leaked sidecar processes this app isn't tracking
+#[tauri::command]
+pub async fn find_orphaned_sidecars(state: State<'_, AppState>) -> Result<Vec<OrphanInfo>, String> {
+    Ok(state.sidecar_manager.find_orphaned_sidecars().await)
+}
+
+/// Kill leaked sidecar processes ("clean up stuck python"); returns the ones that exited
+#[tauri::command]
+pub async fn kill_orphaned_sidecars(state: State<'_, AppState>) -> Result<Vec<OrphanInfo>, String> {
+    state.sidecar_manager
+        .kill_orphans()
+        .await
+        .map_err(|e| format!("Failed to kill orphaned sidecars: {}", e))
+}
+
+/// Pin the Python interpreter used for future sidecar spawns
+#[tauri::command]
+pub async fn set_python_executable(
+    path: String,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    state.sidecar_manager
+        .set_python_executable(&path)
+        .map_err(|e| format!("Invalid Python interpreter: {}", e))
+}
+
+/// Switch to another Python interpreter and restart every sidecar under it, one at a time;
+/// progress arrives as `python://migration` events
+#[tauri::command]
+pub async fn migrate_sidecars_to_python(
+    path: String,
+    state: State<'_, AppState>,
+) -> Result<Vec<serde_json::Value>, String> {
+    let results = state.sidecar_manager
+        .migrate_all(&path)
+        .await
+        .map_err(|e| format!("Invalid Python interpreter: {}", e))?;
+    Ok(results
+        .into_iter()
+        .map(|(window_label, result)| match result {
+            Ok(ws_port) => serde_json::json!({ "window_label": window_label, "ws_port": ws_port }),
+            Err(e) => serde_json::json!({ "window_label": window_label, "error": e.to_string() }),
+        })
+        .collect())
+}
+
+/// Override how long sidecar commands for `method` may take before timing out
+#[tauri::command]
+pub async fn set_method_timeout(
+    method: String,
+    timeout_ms: u64,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    state.sidecar_manager
+        .set_method_timeout(&method, std::time::Duration::from_millis(timeout_ms));
+    Ok(())
+}
+
+/// Lay `changes` (keyed as in the settings file, e.g. `{"kill_timeout_ms": 5000}`) over the
+/// sidecar manager's settings and save them to its settings file; they apply from the next start
+#[tauri::command]
+pub async fn update_sidecar_config(
+    changes: serde_json::Value,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    let app_data_dir = app.path().app_data_dir()
+        .map_err(|e| format!("Failed to get app data dir: {}", e))?;
+    fs::create_dir_all(&app_data_dir)
+        .map_err(|e| format!("Failed to create app data dir: {}", e))?;
+    let config = crate::sidecar_manager::merge_config(state.sidecar_manager.config(), changes)
+        .map_err(|e| format!("Invalid sidecar settings: {:#}", e))?;
+    let path = app_data_dir.join(crate::sidecar_manager::config_file::CONFIG_FILE_NAME);
+    crate::sidecar_manager::save_config(&config, &path)
+        .map_err(|e| format!("Failed to save sidecar settings: {:#}", e))
+}
+
+/// Export a diagnostic bundle for a window's sidecar, optionally writing it to a JSON file
+#[tauri::command]
+pub async fn export_sidecar_diagnostics(
+    window_label: String,
+    output_path: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<serde_json::Value, String> {
+    let diagnostics = state.sidecar_manager
+        .export_diagnostics(&window_label)
+        .await
+        .map_err(|e| format!("Failed to export diagnostics: {}", e))?;
+
+    if let Some(path) = output_path {
+        let contents = serde_json::to_string_pretty(&diagnostics)
+            .map_err(|e| format!("Failed to serialize diagnostics: {}", e))?;
+        fs::write(&path, contents)
+            .map_err(|e| format!("Failed to write diagnostics file: {}", e))?;
+        println!("Wrote sidecar diagnostics for '{}' to {}", window_label, path);
+    }
+
+    Ok(diagnostics)
+}
+
+/// Connection counters (reconnects, bytes and messages on the wire) for a window's sidecar
+#[tauri::command]
+pub async fn get_connection_stats(
+    window_label: String,
+    state: State<'_, AppState>,
+) -> Result<Option<ConnStats>, String> {
+    Ok(state.sidecar_manager.connection_stats(&window_label).await)
+}
+
+/// Round-trip latency percentiles per RPC method for a window's sidecar
+#[tauri::command]
+pub async fn get_method_latencies(
+    window_label: String,
+    state: State<'_, AppState>,
+) -> Result<Option<HashMap<String, LatencyStats>>, String> {
+    Ok(state.sidecar_manager.method_latencies(&window_label).await)
+}
+
+/// What the window's sidecar answered its init method (e.g. its server capabilities), if any
+#[tauri::command]
+pub async fn get_init_result(
+    window_label: String,
+    state: State<'_, AppState>,
+) -> Result<Option<serde_json::Value>, String> {
+    state.sidecar_manager
+        .init_result(&window_label)
+        .await
+        .map_err(|e| format!("Failed to get init result: {}", e))
+}
+
+/// Token bucket state (available tokens, waiting and throttled commands) for a window's
+/// rate-limited sidecar
+#[tauri::command]
+pub async fn get_rate_limit_stats(
+    window_label: String,
+    state: State<'_, AppState>,
+) -> Result<Option<RateLimitStats>, String> {
+    Ok(state.sidecar_manager.rate_limit_stats(&window_label).await)
+}
+
+/// The interpreter, command line, working directory and environment a spawn of `vault_path`
+/// for the window would use, without starting it; for reproducing a failing launch in a shell
+#[tauri::command]
+pub async fn spawn_command_preview(
+    window_label: String,
+    vault_path: String,
+    state: State<'_, AppState>,
+) -> Result<SpawnPreview, String> {
+    state.sidecar_manager
+        .spawn_command_preview(&window_label, &vault_path)
+        .await
+        .map_err(|e| format!("Failed to preview spawn command: {}", e))
+}
+
+/// Pause or resume capturing a window's sidecar output
+#[tauri::command]
+pub async fn set_log_capture(
+    window_label: String,
+    enabled: bool,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    state.sidecar_manager
+        .set_log_capture(&window_label, enabled)
+        .await
+        .map_err(|e| format!("Failed to set log capture: {}", e))
+}
+
+/// Spawn and terminate a throwaway sidecar `iterations` times and report its startup timings
+#[tauri::command]
+pub async fn benchmark_sidecar_startup(
+    vault_path: String,
+    iterations: usize,
+    state: State<'_, AppState>,
+) -> Result<StartupStats, String> {
+    state.sidecar_manager
+        .benchmark_startup(&vault_path, iterations)
+        .await
+        .map_err(|e| format!("Startup benchmark failed: {}", e))
+}
+
+/// Close a vault window and terminate its sidecar, allowing it `grace_ms` (default
+/// `terminate_grace`) to exit after SIGTERM
 #[tauri::command]
 pub async fn close_vault(
     window_label: String,
+    grace_ms: Option<u64>,
     state: State<'_, AppState>,
 ) -> Result<(), String> {
     println!("Closing vault window: {}", window_label);
 
     // Step 1: Terminate sidecar
-    state.sidecar_manager
-        .terminate_sidecar(&window_label)
-        .await
-        .map_err(|e| format!("Failed to terminate sidecar: {}", e))?;
+    let manager = &state.sidecar_manager;
+    let terminated = match grace_ms {
+        Some(ms) => manager.terminate_sidecar_with_grace(&window_label, std::time::Duration::from_millis(ms)).await,
+        None => manager.terminate_sidecar(&window_label).await,
+    };
+    terminated.map_err(|e| format!("Failed to terminate sidecar: {}", e))?;
 
     // Step 2: Remove window from tracking
     state.window_manager
@@ -121,6 +837,17 @@ pub async fn close_vault(
     Ok(())
 }
 
+/// Abort the launch `open_vault` is waiting on for a window (dependency install, process start
+/// or readiness wait); its sidecar is stopped and `open_vault` fails. Returns false if the
+/// window has no launch in progress.
+#[tauri::command]
+pub async fn cancel_vault_spawn(
+    window_label: String,
+    state: State<'_, AppState>,
+) -> Result<bool, String> {
+    Ok(state.sidecar_manager.cancel_spawn(&window_label))
+}
+
 /// Get the current window's vault information
 #[tauri::command]
 pub async fn get_current_vault_info(
@@ -151,6 +878,13 @@ pub async fn get_current_vault_info(
 }
 
 
+/// Check the Python dependencies of several vaults at once, e.g. every `list_vaults` entry
+/// at startup, without opening them
+#[tauri::command]
+pub async fn preflight_vaults(vault_paths: Vec<String>) -> Result<Vec<(String, DependencyStatus)>, String> {
+    Ok(DependencyChecker::preflight(&vault_paths).await)
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct VaultListItem {
     pub name: String,
@@ -8,11 +8,11 @@ mod ipc_router;
 mod event_bus;
 
 use std::sync::Arc;
-use tauri::Manager;
+use tauri::{Emitter, Manager};
 use tokio::sync::Mutex;
 
 use window_manager::WindowManager;
-use sidecar_manager::SidecarManager;
+use sidecar_manager::{DegradedConfig, FixtureRecorder, RpcId, SidecarManager, SidecarManagerConfig};
 use event_bus::EventBus;
 
 #[derive(Default)]
@@ -29,9 +29,120 @@ fn main() {
         .setup(|app| {
             // Initialize application state
             let window_manager = Arc::new(Mutex::new(WindowManager::new()));
-            let sidecar_manager = Arc::new(SidecarManager::new());
+            let handle = app.handle().clone();
+            let mut config = SidecarManagerConfig {
+                degraded: Some(DegradedConfig::default()),
+                ..Default::default()
+            };
+            match app.path().app_data_dir() {
+                Ok(dir) if std::fs::create_dir_all(&dir).is_ok() => {
+                    // Settings tuned by the user are laid over the defaults above
+                    let settings = dir.join(sidecar_manager::config_file::CONFIG_FILE_NAME);
+                    if settings.is_file() {
+                        match sidecar_manager::load_config(&settings, &config) {
+                            Ok(loaded) => config = loaded,
+                            Err(e) => eprintln!("Ignoring sidecar settings: {:#}", e),
+                        }
+                    }
+                    // Quarantined vaults stay disabled across app restarts
+                    config.quarantine.file.get_or_insert_with(|| dir.join("sidecar_quarantine.json"));
+                    // Sidecars outlive the app process `cargo tauri dev` restarts on every rebuild
+                    if cfg!(debug_assertions) {
+                        config.reattach_file.get_or_insert_with(|| dir.join("sidecar_reattach.json"));
+                    }
+                }
+                _ => eprintln!("No app data directory, sidecar quarantine will not be persisted"),
+            }
+            let sidecar_manager = Arc::new(SidecarManager::with_config(config)
+                .with_event_emitter(Arc::new(move |event: &str, payload: serde_json::Value| {
+                    if let Err(e) = handle.emit(event, payload) {
+                        eprintln!("Failed to emit '{}': {}", event, e);
+                    }
+                }))
+                .with_log_parser(Arc::new(sidecar_manager::log_parser::parse_json_line))
+                // Sequential ids read more easily than UUIDs in sidecar logs and show request order
+                .with_id_generator({
+                    let next = Arc::new(std::sync::atomic::AtomicI64::new(1));
+                    Arc::new(move || RpcId::Num(next.fetch_add(1, std::sync::atomic::Ordering::Relaxed)))
+                }));
+            // Tell the window about a Python crash as soon as it is printed. The sidecar marks
+            // exceptions nothing caught; a bare traceback may just be a logged, survived one.
+            let crash_handle = app.handle().clone();
+            let traceback = Arc::new(move |window_label: &str, line: &str| {
+                let _ = crash_handle.emit("sidecar://traceback", serde_json::json!({
+                    "window_label": window_label,
+                    "line": line,
+                }));
+            });
+            if let Err(e) = sidecar_manager.on_output_match(r"^\[tailor\] unhandled exception: ", traceback) {
+                eprintln!("Failed to watch sidecar output for tracebacks: {}", e);
+            }
+            // Capture every command of the session for replaying in tests
+            if let Some(path) = std::env::var_os("TAILOR_RECORD_FIXTURES") {
+                match FixtureRecorder::new(std::path::Path::new(&path)) {
+                    Ok(recorder) => sidecar_manager.register_middleware(Box::new(recorder)),
+                    Err(e) => eprintln!("Not recording fixtures to {}: {}", path.to_string_lossy(), e),
+                }
+            }
             let event_bus = Arc::new(EventBus::new());
 
+            // Forward live sidecar output to the frontend console
+            let mut logs = sidecar_manager.subscribe_logs();
+            let log_handle = app.handle().clone();
+            tauri::async_runtime::spawn(async move {
+                use tokio::sync::broadcast::error::RecvError;
+                loop {
+                    match logs.recv().await {
+                        Ok(line) => {
+                            let _ = log_handle.emit("sidecar://log", line);
+                        }
+                        Err(RecvError::Lagged(skipped)) => {
+                            eprintln!("Log forwarder lagged, skipped {} lines", skipped);
+                        }
+                        Err(RecvError::Closed) => break,
+                    }
+                }
+            });
+
+            // No-op unless `total_memory_budget` is set
+            let budget_manager = sidecar_manager.clone();
+            tauri::async_runtime::spawn(async move { budget_manager.run_memory_budget().await });
+            // Emits `sidecars://degraded` when many sidecars stop answering at once
+            let health_manager = sidecar_manager.clone();
+            tauri::async_runtime::spawn(async move { health_manager.run_health_monitor().await });
+            // Reloads sidecars spawned with `watch_plugins` when their plugins change
+            let plugin_manager = sidecar_manager.clone();
+            tauri::async_runtime::spawn(async move { plugin_manager.run_plugin_watcher().await });
+            // Restarts crashed sidecars only with `crash_policy = "restart_and_retry"`
+            let crash_manager = sidecar_manager.clone();
+            tauri::async_runtime::spawn(async move { crash_manager.run_crash_handler().await });
+            // Terminates sidecars idle past their role's `idle_timeout`
+            let idle_manager = sidecar_manager.clone();
+            tauri::async_runtime::spawn(async move { idle_manager.run_idle_monitor().await });
+            // Flags sidecars whose vault directory was moved or renamed
+            let vault_manager = sidecar_manager.clone();
+            tauri::async_runtime::spawn(async move { vault_manager.run_vault_monitor().await });
+
+            // Adopt the sidecars of the previous dev build and reopen their windows
+            let reattach_manager = sidecar_manager.clone();
+            let reattach_windows = window_manager.clone();
+            let reattach_handle = app.handle().clone();
+            tauri::async_runtime::spawn(async move {
+                let adopted = match reattach_manager.reattach_all().await {
+                    Ok(adopted) => adopted,
+                    Err(e) => {
+                        eprintln!("Failed to reattach sidecars: {}", e);
+                        return;
+                    }
+                };
+                for (window_label, _) in adopted.into_iter().filter(|(_, result)| result.is_ok()) {
+                    let Some(vault_path) = reattach_manager.get_vault_path(&window_label).await else { continue };
+                    if let Err(e) = reattach_windows.lock().await.open_vault_window(&reattach_handle, &window_label, vault_path) {
+                        eprintln!("Failed to reopen window '{}' for its reattached sidecar: {}", window_label, e);
+                    }
+                }
+            });
+
             // Store state in app
             app.manage(AppState {
                 window_manager: window_manager.clone(),
@@ -45,9 +156,59 @@ fn main() {
         .invoke_handler(tauri::generate_handler![
             ipc_router::open_vault,
             ipc_router::send_to_sidecar,
+            ipc_router::try_send_to_sidecar,
+            ipc_router::send_to_sidecar_coalesced,
+            ipc_router::send_to_sidecar_with_headers,
+            ipc_router::send_to_sidecar_with_blob,
+            ipc_router::send_to_sidecar_binary,
+            ipc_router::send_to_sidecar_shared,
+            ipc_router::send_all_to_sidecar,
+            ipc_router::broadcast_to_sidecars,
+            ipc_router::list_sidecars_by_label,
+            ipc_router::find_sidecars_by_vault,
+            ipc_router::broadcast_to_label,
+            ipc_router::restart_sidecar,
+            ipc_router::reload_sidecar,
+            ipc_router::rebind_vault,
+            ipc_router::snapshot_sidecar_state,
+            ipc_router::get_sidecar_version,
+            ipc_router::set_sidecar_env,
+            ipc_router::set_sidecar_log_level,
+            ipc_router::get_sidecar_role,
+            ipc_router::set_sidecar_role,
+            ipc_router::list_quarantined_vaults,
+            ipc_router::clear_vault_quarantine,
+            ipc_router::invalidate_dependency_cache,
+            ipc_router::signal_sidecar,
+            ipc_router::export_sidecar_diagnostics,
+            ipc_router::get_connection_stats,
+            ipc_router::get_method_latencies,
+            ipc_router::get_rate_limit_stats,
+            ipc_router::get_init_result,
+            ipc_router::spawn_command_preview,
+            ipc_router::subscribe_sidecar_notifications,
+            ipc_router::submit_sidecar_job,
+            ipc_router::await_sidecar_job,
+            ipc_router::get_sidecar_job_status,
+            ipc_router::get_sidecar_health,
+            ipc_router::get_sidecar_health_summary,
+            ipc_router::get_sidecar_memory_usage,
+            ipc_router::set_log_capture,
+            ipc_router::benchmark_sidecar_startup,
+            ipc_router::reclaim_port,
+            ipc_router::reserve_sidecar_ports,
+            ipc_router::release_port_reservation,
+            ipc_router::find_orphaned_sidecars,
+            ipc_router::kill_orphaned_sidecars,
+            ipc_router::set_python_executable,
+            ipc_router::migrate_sidecars_to_python,
+            ipc_router::set_method_timeout,
+            ipc_router::update_sidecar_config,
             ipc_router::close_vault,
+            ipc_router::cancel_vault_spawn,
             ipc_router::get_current_vault_info,
             ipc_router::list_vaults,
+            ipc_router::preflight_vaults,
             ipc_router::get_vault_info,
             ipc_router::update_plugin_config,
             ipc_router::create_vault,
@@ -71,7 +232,7 @@ fn main() {
             if let tauri::RunEvent::Exit = event {
                 println!("Application exiting - performing cleanup");
                 let state = app.state::<AppState>();
-                state.sidecar_manager.shutdown_all();
+                tauri::async_runtime::block_on(state.sidecar_manager.shutdown());
             }
         });
 }
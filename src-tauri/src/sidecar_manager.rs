@@ -1,301 +1,7170 @@
-use std::collections::HashMap;
-use std::process::{Child, Command, Stdio};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::ffi::{OsStr, OsString};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
 use std::sync::Arc;
-use tokio::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::sync::{broadcast, mpsc, watch, Mutex};
+use tokio::task::JoinHandle;
 use anyhow::{Result, Context, anyhow};
-use tokio_tungstenite::{connect_async, tungstenite::protocol::Message};
-use futures::{SinkExt, StreamExt};
-use url::Url;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
 
+pub mod args;
+pub mod audit;
+pub mod benchmark;
+pub mod capabilities;
+pub mod capture;
+pub mod coalesce;
+pub mod config_file;
+pub mod connection;
+pub mod crash;
+pub mod dependency_cache;
+pub mod error;
+pub mod health;
+pub mod jobs;
+pub mod latency;
+pub mod log_parser;
+pub mod memory_budget;
+pub mod method_policy;
+pub mod middleware;
+pub mod notifications;
+pub mod output_match;
+pub mod plugin_watch;
+pub mod preview;
+pub mod process;
+pub mod python;
+pub mod quarantine;
+pub mod rate_limit;
+pub mod reattach;
+pub mod reload;
+pub mod replay;
+pub mod role;
+#[cfg(all(unix, feature = "shared-memory"))]
+pub mod shm;
+pub mod shutdown_order;
+pub mod snapshot;
+pub mod soft_shutdown;
+pub mod spawn_progress;
+pub mod state;
+pub mod stdio;
+pub mod transport;
+pub mod vault_config;
+pub mod vault_path;
+pub mod version;
+
+pub use args::ArgTemplate;
+pub use benchmark::StartupStats;
+pub use capture::CaptureMode;
+pub use coalesce::Coalesced;
+pub use crash::{CrashPolicy, CrashReport, RestartLimit};
+pub use config_file::{load_config, merge_config, save_config};
+pub use error::SidecarError;
+pub use health::{DegradedConfig, HealthSummary, SidecarHealth};
+pub use jobs::{JobId, JobStatus};
+pub use latency::LatencyStats;
+pub use log_parser::{LineParser, ParsedLog};
+pub use memory_budget::{MemoryBudgetConfig, MemoryUsage};
+pub use method_policy::MethodPolicy;
+pub use middleware::{FixtureRecorder, RpcCall, RpcMiddleware};
+pub use notifications::SidecarNotification;
+pub use preview::SpawnPreview;
+pub use process::{OrphanInfo, ProcessPriority, SidecarSignal};
+pub use rate_limit::{RateLimitConfig, RateLimitStats};
+pub use reload::ReloadReport;
+pub use role::RoleProfile;
+pub use state::SidecarState;
+pub use stdio::Transport;
+pub use vault_config::load_vault_config;
+pub use vault_path::Rebind;
+pub use version::VersionInfo;
+
+use dependency_cache::DependencyCache;
+use connection::{ClientOptions, ConnectionCounters, KeepaliveConfig, PersistentConnection, Reply, RequestIds};
+use jobs::JobTracker;
+use method_policy::MethodFilter;
+use middleware::Middlewares;
+pub use connection::{ConnStats, DuplicateResponses, IdGenerator, NullIdErrors, RequestFormat, RpcId};
+use notifications::NotificationHub;
+use output_match::OutputMatchers;
+pub use output_match::OutputCallback;
+use plugin_watch::{PluginChange, PluginWatcher};
+use process::SidecarChild;
+use python::{PixiLocator, PythonInterpreter, VersionConstraint};
+use quarantine::{Quarantine, QuarantineConfig};
+use reattach::ReattachRecord;
+pub use quarantine::QuarantineEntry;
+use replay::{Parked, ReplayConfig, ReplayQueue};
+use role::Roles;
+use soft_shutdown::{SoftShutdownConfig, SoftShutdownOutcome};
+pub use spawn_progress::SpawnCancel;
+use spawn_progress::{SpawnProgress, SpawnStage};
+use state::StateRegistry;
+use stdio::StdioTransport;
+use crate::dependency_checker::{DependencyChecker, DependencyStatus};
+
+/// Number of stdout/stderr lines retained per sidecar
+const LOG_BUFFER_CAPACITY: usize = 500;
+
+/// Lines buffered per log subscriber before it starts lagging
+const LOG_CHANNEL_CAPACITY: usize = 1024;
+
+/// Number of command failures retained per sidecar
+const ERROR_BUFFER_CAPACITY: usize = 50;
+
+/// Output records read from one pipe and waiting to be logged. Past this the reader drops
+/// records rather than stop draining the pipe, which would block the sidecar's writes.
+const PENDING_RECORDS: usize = 1024;
+
+/// How long a freshly spawned sidecar has to write its ready file
+const READINESS_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Delay between checks for the ready file
+const READINESS_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// RPC applying an environment variable change in a running sidecar (`{"key", "value"}`)
+const SET_ENV_METHOD: &str = "set_env";
+
+/// RPC changing a running sidecar's log level (`{"level"}`)
+const SET_LOG_LEVEL_METHOD: &str = "set_log_level";
+
+/// Delay between connection attempts while benchmarking startup; finer than the readiness poll
+const BENCHMARK_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Backoff schedule for re-checking a just-freed port before giving up on reusing it
+const PORT_RELEASE_BACKOFF: [Duration; 5] = [
+    Duration::from_millis(50),
+    Duration::from_millis(100),
+    Duration::from_millis(200),
+    Duration::from_millis(400),
+    Duration::from_millis(800),
+];
+
+/// Ports handed out to sidecars, wrapping around at the end
+const PORT_RANGE: std::ops::RangeInclusive<u16> = 9000..=19000;
+
+/// The port after `port` in [`PORT_RANGE`], wrapping around at the end
+fn port_after(port: u16) -> u16 {
+    if port >= *PORT_RANGE.end() { *PORT_RANGE.start() } else { port + 1 }
+}
+
+/// How long a sidecar gets to exit after SIGTERM before it is killed
+const DEFAULT_TERMINATE_GRACE: Duration = Duration::from_secs(3);
+
+/// The same for `shutdown`, which runs while the app is exiting
+const DEFAULT_SHUTDOWN_GRACE: Duration = Duration::from_secs(1);
+
+/// How long to wait for a killed sidecar to be reaped before giving up
+const DEFAULT_KILL_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How long `shutdown` waits for each background task before aborting it
+const TASK_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How long to wait for a stopped sidecar's output readers to drain
+const READER_JOIN_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Sidecars working on a broadcast command at once, unless configured otherwise
+const DEFAULT_BROADCAST_CONCURRENCY: usize = 4;
+
+/// Callback used to surface manager events (e.g. `sidecar://ready`) to the frontend
+pub type EventEmitter = Arc<dyn Fn(&str, serde_json::Value) + Send + Sync>;
+
+/// Working directory for sidecar processes. In a vault config file it is written
+/// `working_dir = "vault"`, `"project-root"` or `{ path = "data" }` (relative to the vault).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum WorkingDir {
+    /// The repository root (parent of `src-tauri`)
+    #[default]
+    ProjectRoot,
+    /// The vault the sidecar serves
+    Vault,
+    /// A fixed directory
+    Path(PathBuf),
+}
+
+/// How `spawn_sidecar` treats a window whose sidecar is still running
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DuplicateSpawn {
+    /// Fail with `SidecarError::AlreadyRunning`, leaving the running sidecar alone
+    #[default]
+    Reject,
+    /// Return the running sidecar's port if it serves the same vault; otherwise reject
+    Reuse,
+    /// Terminate the running sidecar, then spawn a new one
+    Replace,
+}
+
+/// Manager-wide settings, loadable from a file with [`load_config`] (see [`config_file`])
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SidecarManagerConfig {
+    /// Time between SIGTERM and SIGKILL when terminating or restarting a sidecar, unless
+    /// `terminate_sidecar_with_grace` says otherwise
+    #[serde(with = "config_file::duration_ms", rename = "terminate_grace_ms")]
+    pub terminate_grace: Duration,
+    /// `terminate_grace` for [`SidecarManager::shutdown`], shorter so app exit stays quick
+    #[serde(with = "config_file::duration_ms", rename = "shutdown_grace_ms")]
+    pub shutdown_grace: Duration,
+    /// Time to wait after SIGKILL before reporting `TerminationFailed`
+    #[serde(with = "config_file::duration_ms", rename = "kill_timeout_ms")]
+    pub kill_timeout: Duration,
+    /// Interpreter shipped with the app, tried after `TAILOR_PYTHON` and before `PATH`
+    pub bundled_python: Option<String>,
+    /// Interpreters, environments or directories of environments searched for one satisfying a
+    /// sidecar's `SpawnOptions::python_version`, before pyenv and the pixi environments
+    pub python_search_paths: Vec<PathBuf>,
+    /// The `pixi` program that installs dependencies for `SpawnOptions::install_dependencies`
+    /// when the vault or project has a `pixi.toml`: a name looked up on `PATH` (the default,
+    /// `pixi`) or a path
+    pub pixi_executable: String,
+    /// The sidecar's arguments after the interpreter and `SpawnOptions::python_flags`, with
+    /// `{vault}`, `{port}`, `{ready_file}`, `{control_port}` and `{log_level}` placeholders (see
+    /// [`args`]). Defaults to `-u -m sidecar --vault {vault} --ws-port {port} ...`.
+    pub sidecar_args: ArgTemplate,
+    /// Reuse one WebSocket per sidecar for commands instead of connecting per request.
+    ///
+    /// Off by default: the sidecar currently sends responses and events to its most recently
+    /// connected client, so a long-lived manager connection would take over the frontend's
+    /// event stream.
+    pub persistent_connections: bool,
+    /// Open the persistent connection as soon as a sidecar is ready, emitting
+    /// `sidecar://connected`, so the first command doesn't pay for the handshake. Off by default
+    /// (connections open on first use); ignored without `persistent_connections`.
+    pub preconnect: bool,
+    /// Ping schedule for persistent connections
+    pub keepalive: KeepaliveConfig,
+    /// Response deadline for methods without an override (see `set_method_timeout`). None (the
+    /// default) waits as long as the sidecar takes, so long-running methods such as `reindex`
+    /// aren't cut off; quick ones can get a deadline of their own.
+    #[serde(with = "config_file::option_duration_ms", rename = "request_timeout_ms")]
+    pub request_timeout: Option<Duration>,
+    /// Commands one sidecar works on at once; more wait for a slot, while
+    /// `try_send_command` fails with `SidecarError::Busy`. Unlimited when None (the default).
+    pub max_in_flight: Option<usize>,
+    /// Token bucket every sidecar's commands pass through, unless `SpawnOptions::rate_limit`
+    /// says otherwise (see [`rate_limit`]). Unlimited when None (the default).
+    pub rate_limit: Option<RateLimitConfig>,
+    /// Commands parked and replayed when their sidecar is down (see [`replay`])
+    pub replay: ReplayConfig,
+    /// What becomes of a sidecar that crashes after becoming ready, and of its in-flight
+    /// commands (see [`crash`]). Defaults to failing them fast.
+    pub crash_policy: CrashPolicy,
+    /// How many crashes `RestartAndRetry` restarts and how long it waits before each; by
+    /// default 5 within 10 minutes, backing off from 1s up to 30s
+    pub restart_limit: RestartLimit,
+    /// What spawning a window that already has a live sidecar does. Defaults to refusing.
+    pub duplicate_spawn: DuplicateSpawn,
+    /// Directory containing the `sidecar` package, prepended to the sidecar's `PYTHONPATH`
+    /// so `-m sidecar` resolves whatever the working directory. Defaults to the project root.
+    pub sidecar_root: Option<PathBuf>,
+    pub working_dir: WorkingDir,
+    /// Most sidecars [`SidecarManager::broadcast_command`] keeps busy at once
+    pub broadcast_concurrency: usize,
+    /// A port only counts as free if it can be bound on every one of these; match them to
+    /// the addresses the sidecar may listen on (add `0.0.0.0` for a wildcard bind)
+    pub port_check_addrs: Vec<IpAddr>,
+    /// Flush RPC sent before SIGTERM, letting the sidecar save state while reporting progress
+    /// (see [`soft_shutdown`]). Off by default.
+    pub soft_shutdown: Option<SoftShutdownConfig>,
+    /// Host sidecars are reached on unless `SpawnOptions::host` overrides it. Non-loopback hosts
+    /// are connected to over TLS (`wss://`).
+    pub connect_host: String,
+    /// Stop sidecars one at a time in `shutdown`, in [`SpawnOptions::shutdown_order`] then spawn
+    /// order, awaiting each before the next. Off by default: they are all stopped at once, apart
+    /// from the waits [`SpawnOptions::depends_on`] imposes.
+    pub sequential_shutdown: bool,
+    /// Longest record captured from a sidecar's binary output: the raw chunk cap, and the frame
+    /// size beyond which length-prefixed capture of that stream ends (see [`capture`])
+    pub max_log_record: usize,
+    /// Longest captured text line; the rest of a longer line is drained from the pipe and
+    /// replaced by a `...[truncated N bytes]` marker in the buffered and broadcast copy
+    pub max_log_line: usize,
+    /// HTTP headers added to every WebSocket upgrade request, e.g. an `Authorization` token or a
+    /// trace id; `X-Tailor-Client-Version` is always sent. See also [`SidecarManager::send_command_with_headers`].
+    pub handshake_headers: Vec<(String, String)>,
+    /// Handling of a repeated response for an already answered request id
+    pub duplicate_responses: DuplicateResponses,
+    /// Handling of an error response with `"id": null`, see [`NullIdErrors`]
+    pub null_id_errors: NullIdErrors,
+    /// Methods commands may and may not call, see [`method_policy`]
+    pub method_policy: MethodPolicy,
+    /// Largest state snapshot [`SidecarManager::snapshot_state`] accepts, as serialized JSON
+    pub max_snapshot_bytes: usize,
+    /// Budget for a whole launch, from resolving Python to the end of warmup. Each stage is
+    /// reported as a `spawn://stage` event; a launch that runs out fails with
+    /// [`SidecarError::SpawnTimeout`] naming the stage it was stuck on (see [`spawn_progress`]).
+    #[serde(with = "config_file::duration_ms", rename = "spawn_deadline_ms")]
+    pub spawn_deadline: Duration,
+    /// Flap detection for vaults whose sidecar keeps failing to start (see [`quarantine`])
+    pub quarantine: QuarantineConfig,
+    /// Give each sidecar a second WebSocket port (`--control-port`) for control operations:
+    /// health pings, `reload` and the soft-shutdown RPC, which then stay responsive while the RPC
+    /// socket is busy with large requests. Off by default.
+    pub control_socket: bool,
+    /// How request JSON is written: compact, pretty for debugging, or a custom serializer (which
+    /// can't be saved to a file)
+    pub request_format: RequestFormat,
+    /// Quiet period before a sidecar watching its plugins (`SpawnOptions::watch_plugins`) is
+    /// reloaded after they change
+    #[serde(with = "config_file::duration_ms", rename = "plugin_watch_debounce_ms")]
+    pub plugin_watch_debounce: Duration,
+    /// Emit `sidecars://degraded` when this share of sidecars is unresponsive or dead at once
+    /// (see [`health::DegradedConfig`]). None (the default) disables the alarm.
+    pub degraded: Option<DegradedConfig>,
+    /// Cap on the combined memory of all sidecars, enforced by terminating idle ones (see
+    /// [`memory_budget`]). None (the default) disables it.
+    pub total_memory_budget: Option<MemoryBudgetConfig>,
+    /// Directory receiving a JSONL audit trail of every command per sidecar (see [`audit`]).
+    /// None (the default) disables auditing.
+    pub audit_log_dir: Option<PathBuf>,
+    /// Param keys whose values are written as `[redacted]` in the audit trail, at any depth
+    /// and regardless of case
+    pub audit_redact: Vec<String>,
+    /// How often [`SidecarManager::run_vault_monitor`] checks that each vault directory still
+    /// exists (see [`vault_path`])
+    #[serde(with = "config_file::duration_ms", rename = "vault_check_interval_ms")]
+    pub vault_check_interval: Duration,
+    /// File recording the running sidecars, so a manager in a reloaded process can adopt them
+    /// with [`SidecarManager::reattach_all`] (see [`reattach`]). For `cargo tauri dev`; None (the
+    /// default) records nothing.
+    pub reattach_file: Option<PathBuf>,
+    /// How long a vault's passed dependency check lets later spawns with
+    /// `SpawnOptions::install_dependencies` skip it, unless its requirements or `lib` directory
+    /// changed (see [`dependency_cache`]). Zero checks on every spawn.
+    #[serde(with = "config_file::duration_ms", rename = "dependency_cache_ttl_ms")]
+    pub dependency_cache_ttl: Duration,
+    /// Profiles for `SpawnOptions::role` by name, registered when the manager is created (see
+    /// [`role`]); [`SidecarManager::register_role`] adds more later
+    pub roles: HashMap<String, RoleProfile>,
+}
+
+impl Default for SidecarManagerConfig {
+    fn default() -> Self {
+        Self {
+            terminate_grace: DEFAULT_TERMINATE_GRACE,
+            shutdown_grace: DEFAULT_SHUTDOWN_GRACE,
+            kill_timeout: DEFAULT_KILL_TIMEOUT,
+            bundled_python: None,
+            python_search_paths: Vec::new(),
+            pixi_executable: "pixi".to_string(),
+            sidecar_args: ArgTemplate::default(),
+            persistent_connections: false,
+            preconnect: false,
+            keepalive: KeepaliveConfig::default(),
+            request_timeout: None,
+            max_in_flight: None,
+            rate_limit: None,
+            replay: ReplayConfig::default(),
+            crash_policy: CrashPolicy::default(),
+            restart_limit: RestartLimit::default(),
+            duplicate_spawn: DuplicateSpawn::default(),
+            sidecar_root: None,
+            working_dir: WorkingDir::default(),
+            broadcast_concurrency: DEFAULT_BROADCAST_CONCURRENCY,
+            port_check_addrs: vec![Ipv4Addr::LOCALHOST.into(), Ipv6Addr::LOCALHOST.into()],
+            soft_shutdown: None,
+            connect_host: "127.0.0.1".to_string(),
+            sequential_shutdown: false,
+            max_log_record: capture::DEFAULT_MAX_RECORD_LEN,
+            max_log_line: capture::DEFAULT_MAX_LINE_LEN,
+            handshake_headers: Vec::new(),
+            duplicate_responses: DuplicateResponses::default(),
+            null_id_errors: NullIdErrors::default(),
+            method_policy: MethodPolicy::default(),
+            max_snapshot_bytes: snapshot::DEFAULT_MAX_SNAPSHOT_BYTES,
+            spawn_deadline: spawn_progress::DEFAULT_SPAWN_DEADLINE,
+            quarantine: QuarantineConfig::default(),
+            control_socket: false,
+            request_format: RequestFormat::default(),
+            total_memory_budget: None,
+            degraded: None,
+            plugin_watch_debounce: plugin_watch::DEFAULT_DEBOUNCE,
+            vault_check_interval: vault_path::DEFAULT_CHECK_INTERVAL,
+            audit_log_dir: None,
+            audit_redact: audit::DEFAULT_REDACT.iter().map(|key| key.to_string()).collect(),
+            reattach_file: None,
+            dependency_cache_ttl: dependency_cache::DEFAULT_TTL,
+            roles: HashMap::new(),
+        }
+    }
+}
+
+/// Per-spawn options. Unset fields are filled from the vault's config file (see [`vault_config::VaultConfig`]).
+#[derive(Debug, Clone, Default)]
+pub struct SpawnOptions {
+    /// RPC methods fired once the sidecar is listening, before `sidecar://ready` is emitted.
+    /// Lets the Python side import heavy modules and build caches ahead of the first real request.
+    pub warmup_methods: Vec<String>,
+    /// Command prefix wrapping the interpreter, e.g. `["bwrap", "--ro-bind", "/usr", "/usr", "--"]`,
+    /// so the sidecar runs as `<launcher> <python> -u -m sidecar ...`.
+    ///
+    /// The sidecar is started in its own process group and terminated by signalling the group,
+    /// so the wrapper must `exec` (or at least stay in the foreground of) the interpreter rather
+    /// than double-forking; a daemonized child escapes the group and cannot be killed.
+    pub launcher: Option<Vec<String>>,
+    /// Extra arguments appended after the sidecar's own arguments
+    pub extra_args: Vec<String>,
+    /// Extra environment variables for the sidecar process
+    pub env: HashMap<String, String>,
+    /// Passed as `--log-level` (one of [`vault_config::LOG_LEVELS`])
+    pub log_level: Option<String>,
+    /// CPU cores (zero-based) to pin the sidecar to, e.g. to keep an indexing sidecar off the
+    /// cores the UI uses. Empty leaves scheduling to the OS; ignored where unsupported.
+    pub cpu_affinity: Vec<usize>,
+    /// Scheduling priority, e.g. `Low` for a background indexing sidecar. If the OS refuses the
+    /// change (raising priority without privileges), it is logged and the sidecar runs as is.
+    pub priority: ProcessPriority,
+    /// Host the sidecar's WebSocket is reached on, for sidecars started elsewhere (e.g. through
+    /// an `ssh` launcher). Defaults to `SidecarManagerConfig::connect_host`. Non-loopback hosts
+    /// skip the local port checks and are reached over TLS (`wss://`), so the remote sidecar
+    /// must sit behind a TLS-terminating proxy with a certificate valid for the host.
+    pub host: Option<String>,
+    /// Port to run on instead of allocating one, typically from [`SidecarManager::reserve_ports`]
+    /// (the reservation is handed to the sidecar). Kept across restarts while it stays free.
+    pub port: Option<u16>,
+    /// Position in `shutdown`: lower values stop first, ties in spawn order. Sidecars that
+    /// depend on another are better declared in `depends_on`, which takes precedence.
+    pub shutdown_order: i32,
+    /// Window labels of the sidecars this one needs running until it has stopped, e.g. a shared
+    /// embedding sidecar; `shutdown` stops it before them (see [`shutdown_order`]). A spawn that
+    /// would close a dependency cycle fails with `SidecarError::InvalidOptions`.
+    pub depends_on: Vec<String>,
+    /// Free-form tags such as `workspace = "notes"`, for acting on a group of sidecars with
+    /// [`SidecarManager::list_sidecars_by_label`] and [`SidecarManager::broadcast_to_label`]
+    pub labels: HashMap<String, String>,
+    /// How stdout/stderr are cut into log records. Use `Raw` or `LengthPrefixed` for sidecars
+    /// writing binary output; the line parser only runs on `Lines`.
+    pub capture: CaptureMode,
+    /// Snapshot the sidecar's state before `restart_sidecar` and restore it into the new process
+    /// (see [`snapshot`]). If the old process can't answer, e.g. after a crash, its last snapshot
+    /// from [`SidecarManager::snapshot_state`] is used.
+    pub restore_state: bool,
+    /// RPC the sidecar needs before any other method works, LSP-style (e.g. `initialize` with
+    /// workspace roots and client capabilities). Sent once it is listening, before the snapshot
+    /// restore and warmup; an error fails the launch with `SidecarError::InitializeFailed`. The
+    /// result is kept for [`SidecarManager::init_result`].
+    pub init_method: Option<String>,
+    /// Params for `init_method`; `{}` when unset
+    pub init_params: Option<serde_json::Value>,
+    /// Python version the vault needs, e.g. `3.12` or `>=3.10, <3.13` (see
+    /// [`python::VersionConstraint`]). When the usual interpreter doesn't satisfy it, the newest
+    /// matching one under the search locations is used; none matching fails the spawn with
+    /// `SidecarError::PythonNotFound`.
+    pub python_version: Option<String>,
+    /// Interpreter options placed before `-u -m sidecar`, e.g. `["-O", "-X", "importtime"]`;
+    /// see [`python::validate_flags`] for what is accepted
+    pub python_flags: Vec<String>,
+    /// This sidecar's working directory, e.g. `Vault` for plugins that resolve paths relative to
+    /// it; `SidecarManagerConfig::working_dir` when unset. `PYTHONPATH` still points at
+    /// `sidecar_root`, so the `sidecar` package imports from anywhere.
+    pub working_dir: Option<WorkingDir>,
+    /// Reload the sidecar when files under the vault's `plugins/` directory change (see
+    /// [`plugin_watch`]); needs [`SidecarManager::run_plugin_watcher`] running. For plugin development.
+    pub watch_plugins: bool,
+    /// `Stdio` talks `Content-Length` framed JSON-RPC over the child's stdin/stdout instead of a
+    /// WebSocket (see [`stdio`]); such sidecars get no port (reported as 0) and are ready as
+    /// soon as they start
+    pub transport: Transport,
+    /// Check the vault's plugin requirements before launching and install what is missing (see
+    /// [`DependencyChecker`]), reporting `deps://progress` events. A failed check or install
+    /// fails the spawn with [`SidecarError::Dependencies`]. Restarts don't repeat it.
+    pub install_dependencies: bool,
+    /// This sidecar's command rate limit; `SidecarManagerConfig::rate_limit` when unset
+    pub rate_limit: Option<RateLimitConfig>,
+    /// What the sidecar is for, e.g. `indexer`; must be in `SidecarManagerConfig::roles` or
+    /// registered with [`SidecarManager::register_role`], whose [`RoleProfile`] supplies its defaults (see [`role`])
+    pub role: Option<String>,
+}
+
+/// Outcome of [`SidecarManager::set_env`] and [`SidecarManager::set_log_level`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum EnvUpdate {
+    /// The running sidecar applied the change
+    Applied,
+    /// Recorded for the next launch; the running sidecar still has the old value
+    NeedsRestart,
+}
+
+/// Which pipe a captured log line came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogStream {
+    Stdout,
+    Stderr,
+}
+
+/// A single record of sidecar output: a line, or a chunk/frame in the binary capture modes
+#[derive(Debug, Clone, Serialize)]
+pub struct LogLine {
+    pub window_label: String,
+    pub stream: LogStream,
+    /// The line, or a `[N bytes]` placeholder for binary records
+    pub text: String,
+    /// The record's bytes in the binary [`CaptureMode`]s
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub raw: Option<Vec<u8>>,
+    /// Set when the manager's line parser recognised the line
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parsed: Option<ParsedLog>,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// A failed command, kept for diagnostics
+#[derive(Debug, Clone, Serialize)]
+pub struct ErrorRecord {
+    pub method: String,
+    pub message: String,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// Fixed-capacity buffer that drops the oldest entry when full
+#[derive(Debug)]
+pub struct RingBuffer<T> {
+    items: VecDeque<T>,
+    capacity: usize,
+}
+
+impl<T: Clone> RingBuffer<T> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            items: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    pub fn push(&mut self, item: T) {
+        if self.items.len() == self.capacity {
+            self.items.pop_front();
+        }
+        self.items.push_back(item);
+    }
+
+    pub fn snapshot(&self) -> Vec<T> {
+        self.items.iter().cloned().collect()
+    }
+}
+
+/// Runtime bookkeeping shared between the manager, reader threads and in-flight commands
+pub struct SidecarTelemetry {
+    window_label: String,
+    log_tx: broadcast::Sender<LogLine>,
+    output_matchers: OutputMatchers,
+    pub logs: std::sync::Mutex<RingBuffer<LogLine>>,
+    pub errors: std::sync::Mutex<RingBuffer<ErrorRecord>>,
+    pub in_flight: AtomicUsize,
+    /// Slots for commands in flight, with `SidecarManagerConfig::max_in_flight`
+    slots: Option<Arc<tokio::sync::Semaphore>>,
+    /// Paces commands with a rate limit (see [`rate_limit`])
+    rate_limiter: Option<rate_limit::RateLimiter>,
+    /// Where commands are recorded with `SidecarManagerConfig::audit_log_dir`
+    audit: Option<audit::AuditLog>,
+    /// While false, reader threads discard output instead of logging and buffering it
+    pub log_capture: AtomicBool,
+    /// Records discarded while capture was paused or logging fell behind the sidecar's output
+    pub dropped_lines: AtomicU64,
+    /// Wire traffic across every connection to this sidecar
+    pub connection: Arc<ConnectionCounters>,
+    /// When a command last started or finished, for evicting idle sidecars
+    pub last_used: std::sync::Mutex<Instant>,
+    /// Round-trip times of recent commands, per method
+    pub latencies: std::sync::Mutex<latency::MethodLatencies>,
+    /// The window's lifecycle state, flipped between `Ready` and `Busy` by in-flight commands
+    state: watch::Sender<SidecarState>,
+    /// Set once the process is found to have exited on its own (see [`crash`])
+    crash: watch::Sender<Option<CrashReport>>,
+}
+
+impl SidecarTelemetry {
+    fn new(
+        window_label: &str,
+        log_tx: broadcast::Sender<LogLine>,
+        output_matchers: OutputMatchers,
+        state: watch::Sender<SidecarState>,
+        max_in_flight: Option<usize>,
+        rate_limit: Option<RateLimitConfig>,
+        audit: Option<audit::AuditLog>,
+    ) -> Self {
+        Self {
+            window_label: window_label.to_string(),
+            log_tx,
+            output_matchers,
+            state,
+            logs: std::sync::Mutex::new(RingBuffer::new(LOG_BUFFER_CAPACITY)),
+            errors: std::sync::Mutex::new(RingBuffer::new(ERROR_BUFFER_CAPACITY)),
+            in_flight: AtomicUsize::new(0),
+            slots: max_in_flight.map(|max| Arc::new(tokio::sync::Semaphore::new(max.max(1)))),
+            rate_limiter: rate_limit.map(rate_limit::RateLimiter::new),
+            audit,
+            log_capture: AtomicBool::new(true),
+            dropped_lines: AtomicU64::new(0),
+            connection: Arc::default(),
+            last_used: std::sync::Mutex::new(Instant::now()),
+            latencies: Default::default(),
+            crash: watch::channel(None).0,
+        }
+    }
+
+    /// The crash report once the process is found to have exited on its own
+    async fn crashed(&self) -> CrashReport {
+        let mut crash = self.crash.subscribe();
+        let report = crash.wait_for(Option::is_some).await.ok().and_then(|report| report.clone());
+        match report {
+            Some(report) => report,
+            // `self` holds the sender, so the channel never closes
+            None => std::future::pending().await,
+        }
+    }
+
+    /// Take a command slot as `admission` says; None when commands aren't limited
+    async fn admit(&self, admission: Admission) -> Result<Option<tokio::sync::OwnedSemaphorePermit>, SidecarError> {
+        let busy = |state| SidecarError::Busy { window_label: self.window_label.clone(), state };
+        if admission == Admission::Immediate {
+            let state = *self.state.borrow();
+            if !matches!(state, SidecarState::Ready | SidecarState::Busy) {
+                return Err(busy(state));
+            }
+        }
+        if admission == Admission::Control {
+            return Ok(None);
+        }
+        let slot = match (self.slots.clone(), admission) {
+            (None, _) => None,
+            (Some(slots), Admission::Immediate) => {
+                Some(slots.try_acquire_owned().map_err(|_| busy(SidecarState::Busy))?)
+            }
+            // The semaphore is never closed
+            (Some(slots), _) => slots.acquire_owned().await.ok(),
+        };
+        match (&self.rate_limiter, admission) {
+            (None, _) => {}
+            (Some(limiter), Admission::Immediate) => limiter.try_acquire().map_err(|retry_after| {
+                SidecarError::RateLimited { window_label: self.window_label.clone(), retry_after }
+            })?,
+            (Some(limiter), _) => limiter.acquire().await,
+        }
+        Ok(slot)
+    }
+
+    /// Whether a freshly read line should be forwarded; counts it as dropped if not
+    fn capturing(&self) -> bool {
+        let capturing = self.log_capture.load(Ordering::Relaxed);
+        if !capturing {
+            self.dropped_lines.fetch_add(1, Ordering::Relaxed);
+        }
+        capturing
+    }
+
+    fn record_log(&self, stream: LogStream, text: String, raw: Option<Vec<u8>>, parsed: Option<ParsedLog>) {
+        if raw.is_none() {
+            self.output_matchers.apply(&self.window_label, &text);
+        }
+        let line = LogLine {
+            window_label: self.window_label.clone(),
+            stream,
+            text,
+            raw,
+            parsed,
+            timestamp: Utc::now(),
+        };
+        if let Ok(mut logs) = self.logs.lock() {
+            logs.push(line.clone());
+        }
+        // No subscribers is not an error
+        let _ = self.log_tx.send(line);
+    }
+
+    fn touch(&self) {
+        *self.last_used.lock().unwrap_or_else(|e| e.into_inner()) = Instant::now();
+    }
+
+    fn record_error(&self, method: &str, message: String) {
+        if let Ok(mut errors) = self.errors.lock() {
+            errors.push(ErrorRecord {
+                method: method.to_string(),
+                message,
+                timestamp: Utc::now(),
+            });
+        }
+    }
+}
+
+/// How a command gets one of its sidecar's `max_in_flight` slots
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Admission {
+    /// Wait for a slot
+    Queue,
+    /// Fail with `SidecarError::Busy` unless the sidecar is ready and a slot is free
+    Immediate,
+    /// Control-socket calls, which don't compete with commands and take no slot
+    Control,
+}
+
+/// Where a command comes from, which decides whether `SidecarManagerConfig::method_policy`
+/// applies to it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Caller {
+    /// The public send methods, i.e. the UI through IPC; checked against the policy
+    Client,
+    /// The manager's own RPCs (snapshots, capabilities, versions, live settings, job polling)
+    Manager,
+}
+
+/// Decrements the in-flight counter when a command finishes, however it finishes
+struct InFlightGuard(Arc<SidecarTelemetry>);
+
+impl InFlightGuard {
+    fn new(telemetry: Arc<SidecarTelemetry>) -> Self {
+        telemetry.touch();
+        if telemetry.in_flight.fetch_add(1, Ordering::SeqCst) == 0 {
+            state::transition(&telemetry.state, SidecarState::Ready, SidecarState::Busy);
+        }
+        Self(telemetry)
+    }
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        self.0.touch();
+        if self.0.in_flight.fetch_sub(1, Ordering::SeqCst) == 1 {
+            state::transition(&self.0.state, SidecarState::Busy, SidecarState::Ready);
+        }
+    }
+}
 
 pub struct SidecarProcess {
-    pub child: Child,
-    #[allow(dead_code)]
+    /// Spawned by this manager, or adopted from a previous one by `reattach_all`
+    pub child: SidecarChild,
     pub vault_path: String,
+    /// Where the sidecar's WebSocket is reached, with `ws_port`
+    pub host: String,
     pub ws_port: u16,
+    /// The sidecar's control socket, with `SidecarManagerConfig::control_socket`
+    pub control_port: Option<u16>,
+    pub python: PythonInterpreter,
+    pub started_at: DateTime<Utc>,
+    pub started_instant: Instant,
+    pub telemetry: Arc<SidecarTelemetry>,
+    /// Options the sidecar was spawned with, reused on restart
+    pub options: SpawnOptions,
+    /// What the sidecar answered `SpawnOptions::init_method` this launch, once it did
+    init_result: Option<serde_json::Value>,
+    /// When this window's sidecar was previously restarted
+    pub restarts: Vec<DateTime<Utc>>,
+    /// Distinguishes successive launches for the same window (see [`replay`])
+    generation: u64,
+    /// When the window first spawned relative to others; kept across restarts
+    spawn_seq: u64,
+    /// Threads forwarding stdout/stderr; they end once the process's pipes close
+    readers: Vec<std::thread::JoinHandle<()>>,
+    /// Written by the sidecar (`--ready-file`) once it is fully initialized and listening
+    pub ready_file: PathBuf,
+    /// Shared command connection when `persistent_connections` is enabled, reopened once dead
+    connection: Arc<Mutex<Option<PersistentConnection>>>,
+    /// The window's notification channel, shared with the processes before and after it
+    notifications: broadcast::Sender<SidecarNotification>,
+    /// `options` changed in a way only a restart applies, see [`SidecarManager::set_env`]
+    pub needs_restart: bool,
+    /// What the sidecar reported from [`version::VERSION_METHOD`], once asked
+    pub version: Option<VersionInfo>,
+    /// Set with `SpawnOptions::watch_plugins`; dropped (stopping the watch) with the process
+    _plugin_watcher: Option<PluginWatcher>,
+    /// The stdin/stdout channel of a `Transport::Stdio` sidecar
+    stdio: Option<StdioTransport>,
+}
+
+impl SidecarProcess {
+    fn connection_target(&self) -> ConnectionTarget {
+        ConnectionTarget {
+            host: self.host.clone(),
+            port: self.ws_port,
+            slot: self.connection.clone(),
+            counters: self.telemetry.connection.clone(),
+            notifications: self.notifications.clone(),
+            stdio: self.stdio.clone(),
+        }
+    }
+
+    /// Port for control operations: the control socket if there is one, else the RPC socket
+    fn control_port(&self) -> u16 {
+        self.control_port.unwrap_or(self.ws_port)
+    }
+
+    /// Sort key for `SidecarManager::shutdown`
+    fn shutdown_rank(&self) -> (i32, u64) {
+        (self.options.shutdown_order, self.spawn_seq)
+    }
+
+    /// Launched sidecars lead their own process group and are signalled as a group
+    fn signals_group(&self) -> bool {
+        self.options.launcher.is_some()
+    }
+
+    /// What a manager in a reloaded process needs to adopt this sidecar
+    fn reattach_record(&self, window_label: &str) -> ReattachRecord {
+        ReattachRecord {
+            window_label: window_label.to_string(),
+            vault_path: self.vault_path.clone(),
+            pid: self.child.id(),
+            host: self.host.clone(),
+            ws_port: self.ws_port,
+            control_port: self.control_port,
+            python: self.python.clone(),
+            started_at: self.started_at,
+            ready_file: self.ready_file.clone(),
+            launcher: self.options.launcher.clone(),
+            labels: self.options.labels.clone(),
+            shutdown_order: self.options.shutdown_order,
+            depends_on: self.options.depends_on.clone(),
+            role: self.options.role.clone(),
+        }
+    }
+}
+
+/// Where one sidecar's persistent connection lives and what it feeds
+struct ConnectionTarget {
+    host: String,
+    port: u16,
+    slot: Arc<Mutex<Option<PersistentConnection>>>,
+    counters: Arc<ConnectionCounters>,
+    notifications: broadcast::Sender<SidecarNotification>,
+    stdio: Option<StdioTransport>,
+}
+
+/// Manager settings and handles needed to open persistent connections from background tasks
+struct ConnectionContext {
+    keepalive: KeepaliveConfig,
+    client: ClientOptions,
+    shutdown: watch::Receiver<bool>,
+    tasks: Arc<std::sync::Mutex<Vec<JoinHandle<()>>>>,
+}
+
+pub struct SidecarManager {
+    processes: Arc<Mutex<HashMap<String, SidecarProcess>>>,
+    next_port: Arc<Mutex<u16>>,
+    /// Patterns watched for in sidecar output, see [`Self::on_output_match`]
+    output_matchers: OutputMatchers,
+    /// Hooks around every JSON command, see [`Self::register_middleware`]
+    middlewares: Middlewares,
+    /// `SidecarManagerConfig::method_policy`, compiled
+    method_filter: MethodFilter,
+    /// Profiles for `SpawnOptions::role`, see [`Self::register_role`]
+    roles: Roles,
+    /// Ports held for later spawns by `reserve_ports`, each bound so no other app can take it
+    reserved_ports: std::sync::Mutex<HashMap<u16, std::net::TcpListener>>,
+    emitter: Option<EventEmitter>,
+    log_parser: Option<LineParser>,
+    request_ids: RequestIds,
+    config: SidecarManagerConfig,
+    log_tx: broadcast::Sender<LogLine>,
+    python_override: std::sync::Mutex<Option<PythonInterpreter>>,
+    /// Finds pixi environments with `SidecarManagerConfig::pixi_executable`
+    pixi: Arc<PixiLocator>,
+    method_timeouts: std::sync::Mutex<HashMap<String, Duration>>,
+    /// Flipped to `true` by `shutdown`; background tasks watch it and exit
+    shutdown_tx: watch::Sender<bool>,
+    /// Background tasks (readiness, persistent connections) awaited by `shutdown`
+    tasks: Arc<std::sync::Mutex<Vec<JoinHandle<()>>>>,
+    /// Per-window notification hubs; they outlive restarts and go away on terminate
+    notifications: Arc<std::sync::Mutex<HashMap<String, NotificationHub>>>,
+    replay: Arc<ReplayQueue>,
+    next_generation: AtomicU64,
+    next_spawn_seq: AtomicU64,
+    /// Windows whose sidecar is between stop and relaunch in `restart_sidecar`
+    restarting: std::sync::Mutex<HashSet<String>>,
+    /// Launches in progress in `spawn_sidecar_cancellable`, see [`Self::cancel_spawn`]
+    spawn_cancels: std::sync::Mutex<HashMap<String, SpawnCancel>>,
+    states: StateRegistry,
+    /// Latest state snapshot per window, restored on restart; dropped on terminate
+    snapshots: std::sync::Mutex<HashMap<String, serde_json::Value>>,
+    /// Commands each window's sidecar reported (see [`capabilities`]); kept across restarts
+    /// until refreshed, dropped on terminate
+    capabilities: std::sync::Mutex<HashMap<String, HashSet<String>>>,
+    /// Coalesced commands still in their debounce (see [`coalesce`])
+    coalescer: coalesce::Coalescer,
+    quarantine: Arc<Quarantine>,
+    /// Vaults whose dependency check passed recently, see [`Self::invalidate_dependency_cache`]
+    dependency_cache: DependencyCache,
+    /// Whether the last health report was over the `degraded` threshold
+    degraded: AtomicBool,
+    /// Sidecars that crashed after becoming ready, restarted by `run_crash_handler`
+    crashes: mpsc::UnboundedSender<(String, u64)>,
+    crashes_rx: std::sync::Mutex<Option<mpsc::UnboundedReceiver<(String, u64)>>>,
+    /// Debounced plugin changes from every sidecar's watcher, handled by `run_plugin_watcher`
+    plugin_changes: mpsc::UnboundedSender<PluginChange>,
+    plugin_changes_rx: std::sync::Mutex<Option<mpsc::UnboundedReceiver<PluginChange>>>,
+    /// Jobs started with `submit_job`, see [`jobs`]
+    jobs: Arc<JobTracker>,
+    /// Windows whose notifications are watched for job completions
+    job_listeners: Arc<std::sync::Mutex<HashSet<String>>>,
+}
+
+impl Default for SidecarManager {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
-pub struct SidecarManager {
-    processes: Arc<Mutex<HashMap<String, SidecarProcess>>>,
-    next_port: Arc<Mutex<u16>>,
-}
+impl SidecarManager {
+    pub fn new() -> Self {
+        Self::with_config(SidecarManagerConfig::default())
+    }
+
+    /// The settings this manager was created with
+    pub fn config(&self) -> &SidecarManagerConfig {
+        &self.config
+    }
+
+    pub fn with_config(config: SidecarManagerConfig) -> Self {
+        let replay = Arc::new(ReplayQueue::new(config.replay.clone()));
+        let quarantine = Arc::new(Quarantine::new(config.quarantine.clone()));
+        let dependency_cache = DependencyCache::new(config.dependency_cache_ttl);
+        let method_filter = MethodFilter::new(&config.method_policy);
+        let pixi = Arc::new(PixiLocator::new(&config.pixi_executable));
+        let (plugin_changes, plugin_changes_rx) = mpsc::unbounded_channel();
+        let (crashes, crashes_rx) = mpsc::unbounded_channel();
+        let roles = config.roles.clone();
+        let manager = Self {
+            processes: Arc::new(Mutex::new(HashMap::new())),
+            next_port: Arc::new(Mutex::new(9000)),
+            reserved_ports: Default::default(),
+            output_matchers: OutputMatchers::default(),
+            middlewares: Middlewares::default(),
+            method_filter,
+            roles: Roles::default(),
+            emitter: None,
+            log_parser: None,
+            request_ids: RequestIds::default(),
+            config,
+            log_tx: broadcast::channel(LOG_CHANNEL_CAPACITY).0,
+            python_override: std::sync::Mutex::new(None),
+            pixi,
+            method_timeouts: std::sync::Mutex::new(HashMap::new()),
+            shutdown_tx: watch::channel(false).0,
+            tasks: Arc::default(),
+            notifications: Arc::default(),
+            replay,
+            next_generation: AtomicU64::new(1),
+            next_spawn_seq: AtomicU64::new(0),
+            restarting: std::sync::Mutex::new(HashSet::new()),
+            spawn_cancels: std::sync::Mutex::new(HashMap::new()),
+            states: StateRegistry::default(),
+            snapshots: std::sync::Mutex::new(HashMap::new()),
+            capabilities: std::sync::Mutex::new(HashMap::new()),
+            coalescer: Default::default(),
+            quarantine,
+            dependency_cache,
+            degraded: AtomicBool::new(false),
+            crashes,
+            crashes_rx: std::sync::Mutex::new(Some(crashes_rx)),
+            plugin_changes,
+            plugin_changes_rx: std::sync::Mutex::new(Some(plugin_changes_rx)),
+            jobs: Arc::default(),
+            job_listeners: Arc::default(),
+        };
+        for (role, profile) in roles {
+            manager.register_role(&role, profile);
+        }
+        manager
+    }
+
+    /// Live stream of every sidecar's stdout/stderr lines.
+    ///
+    /// Standard broadcast semantics apply: a subscriber that falls more than
+    /// `LOG_CHANNEL_CAPACITY` lines behind gets `RecvError::Lagged(n)` and skips ahead.
+    pub fn subscribe_logs(&self) -> broadcast::Receiver<LogLine> {
+        self.log_tx.subscribe()
+    }
+
+    /// Call `callback` with the window label and line whenever a sidecar prints a line matching
+    /// the regex `pattern`, e.g. `^Traceback` to report a crash as it happens. Applies to running
+    /// sidecars too; see [`output_match`].
+    pub fn on_output_match(&self, pattern: &str, callback: OutputCallback) -> Result<(), SidecarError> {
+        let pattern = regex::Regex::new(pattern)
+            .map_err(|e| SidecarError::InvalidOptions(format!("Invalid output pattern: {}", e)))?;
+        self.output_matchers.add(pattern, callback);
+        Ok(())
+    }
+
+    /// Run every JSON command sent to a sidecar through `middleware`, after the middleware
+    /// registered before it, e.g. to redact or validate them or to answer them with fixtures in
+    /// tests; see [`middleware`]
+    pub fn register_middleware(&self, middleware: Box<dyn RpcMiddleware>) {
+        self.middlewares.add(middleware);
+    }
+
+    /// Define (or redefine) the profile behind `SpawnOptions::role == role`. Sidecars already
+    /// running with it get the new idle timeout and restart cap at once, its priority on their
+    /// next launch.
+    pub fn register_role(&self, role: &str, profile: RoleProfile) {
+        self.roles.register(role, profile);
+    }
+
+    /// Parse sidecar output lines with `parser` (e.g. [`log_parser::parse_json_line`]);
+    /// recognised lines are logged by level and broadcast with [`LogLine::parsed`] set
+    pub fn with_log_parser(mut self, parser: LineParser) -> Self {
+        self.log_parser = Some(parser);
+        self
+    }
+
+    /// Take request ids from `generator` instead of random UUIDs, e.g. a counter for
+    /// deterministic tests or ids from an external tracing system
+    pub fn with_id_generator(mut self, generator: IdGenerator) -> Self {
+        self.request_ids = RequestIds::Custom(generator);
+        self
+    }
+
+    /// Route manager events through `emitter` (typically Tauri's `AppHandle::emit`)
+    pub fn with_event_emitter(mut self, emitter: EventEmitter) -> Self {
+        self.emitter = Some(emitter);
+        self
+    }
+
+    /// Spawn a Python sidecar process for a vault
+    pub async fn spawn_sidecar(
+        &self,
+        window_label: String,
+        vault_path: String,
+    ) -> Result<u16, SidecarError> {
+        self.spawn_sidecar_with_options(window_label, vault_path, SpawnOptions::default())
+            .await
+    }
+
+    /// Spawn a Python sidecar process for a vault with explicit options
+    pub async fn spawn_sidecar_with_options(
+        &self,
+        window_label: String,
+        vault_path: String,
+        options: SpawnOptions,
+    ) -> Result<u16, SidecarError> {
+        self.start_sidecar(window_label, vault_path, options, &SpawnCancel::new()).await
+    }
+
+    /// Like [`Self::spawn_sidecar_cancellable`], also sending `logs` the stdout/stderr records
+    /// the sidecar writes while it boots, e.g. for a "starting vault" dialog to show a slow
+    /// import or model download live. Records flow until the sidecar is ready or its launch has
+    /// failed or been cancelled, then `logs` is dropped; a failed launch still gets whatever it
+    /// printed.
+    pub async fn spawn_sidecar_with_startup_logs(
+        &self,
+        window_label: String,
+        vault_path: String,
+        options: SpawnOptions,
+        cancel: SpawnCancel,
+        logs: mpsc::UnboundedSender<LogLine>,
+    ) -> Result<u16, SidecarError> {
+        // Subscribed first, so nothing printed before the process is tracked is missed
+        let mut lines = self.log_tx.subscribe();
+        let spawn = self.spawn_sidecar_cancellable(window_label.clone(), vault_path, options, cancel);
+        tokio::pin!(spawn);
+        let mut forwarding = true;
+        let result = loop {
+            tokio::select! {
+                result = &mut spawn => break result,
+                line = lines.recv(), if forwarding => match line {
+                    Ok(line) if line.window_label == window_label => forwarding = logs.send(line).is_ok(),
+                    Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => {}
+                    Err(broadcast::error::RecvError::Closed) => forwarding = false,
+                },
+            }
+        };
+        // Records printed just before the launch settled may still be queued
+        if forwarding {
+            forward_pending_logs(&mut lines, &window_label, &logs);
+        }
+        result
+    }
+
+    /// Like [`Self::spawn_sidecar_with_options`], but returns only once the sidecar is ready, and
+    /// `cancel` aborts the launch at any point before that: dependency install, process start or
+    /// the readiness wait. A process already started is killed, which frees its port, and the
+    /// call fails with [`SidecarError::Cancelled`]. [`Self::cancel_spawn`] cancels it by window.
+    pub async fn spawn_sidecar_cancellable(
+        &self,
+        window_label: String,
+        vault_path: String,
+        options: SpawnOptions,
+        cancel: SpawnCancel,
+    ) -> Result<u16, SidecarError> {
+        self.spawn_cancels.lock().unwrap_or_else(|e| e.into_inner()).insert(window_label.clone(), cancel.clone());
+        let result = self.start_and_await_ready(window_label.clone(), vault_path, options, &cancel).await;
+        let mut spawn_cancels = self.spawn_cancels.lock().unwrap_or_else(|e| e.into_inner());
+        if spawn_cancels.get(&window_label).is_some_and(|current| current.same_launch(&cancel)) {
+            spawn_cancels.remove(&window_label);
+        }
+        result
+    }
+
+    /// Cancel the launch of `window_label`'s sidecar if one is in progress through
+    /// [`Self::spawn_sidecar_cancellable`]; false if there was none
+    pub fn cancel_spawn(&self, window_label: &str) -> bool {
+        match self.spawn_cancels.lock().unwrap_or_else(|e| e.into_inner()).get(window_label) {
+            Some(cancel) => {
+                cancel.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    async fn start_and_await_ready(
+        &self,
+        window_label: String,
+        vault_path: String,
+        options: SpawnOptions,
+        cancel: &SpawnCancel,
+    ) -> Result<u16, SidecarError> {
+        let port = self.start_sidecar(window_label.clone(), vault_path, options, cancel).await?;
+        let mut state = self.watch_state(&window_label);
+        let settled = tokio::select! {
+            settled = state.wait_for(|state| *state != SidecarState::Starting) => settled.map(|state| *state).ok(),
+            _ = cancel.cancelled() => None,
+        };
+        if cancel.is_cancelled() && settled != Some(SidecarState::Ready) {
+            println!("Launch of sidecar for window '{}' was cancelled, stopping it", window_label);
+            // Settle the state first, so the readiness check doesn't count a crash against the vault
+            state::transition(&self.states.sender(&window_label), SidecarState::Starting, SidecarState::Terminated);
+            if let Err(e) = self.terminate_sidecar_with_grace(&window_label, self.config.shutdown_grace).await {
+                eprintln!("Failed to stop cancelled sidecar for window '{}': {}", window_label, e);
+            }
+            return Err(SidecarError::Cancelled);
+        }
+        match settled {
+            Some(SidecarState::Ready) => Ok(port),
+            Some(SidecarState::Failed) => {
+                let processes = self.processes.lock().await;
+                let reason = processes.get(&window_label)
+                    .and_then(|process| process.telemetry.errors.lock().ok()?.snapshot().pop())
+                    .map(|error| error.message)
+                    .unwrap_or_else(|| "sidecar never became ready".to_string());
+                Err(SidecarError::SpawnFailed(reason))
+            }
+            state => Err(SidecarError::SpawnFailed(format!("sidecar went {:?} during startup", state))),
+        }
+    }
+
+    async fn start_sidecar(
+        &self,
+        window_label: String,
+        vault_path: String,
+        options: SpawnOptions,
+        cancel: &SpawnCancel,
+    ) -> Result<u16, SidecarError> {
+        if self.is_shutting_down() {
+            return Err(SidecarError::ShuttingDown);
+        }
+        if let Some(port) = self.resolve_duplicate(&window_label, &vault_path).await? {
+            return Ok(port);
+        }
+        self.quarantine.check(&vault_path)?;
+        if options.launcher.as_ref().is_some_and(|launcher| launcher.is_empty()) {
+            return Err(SidecarError::InvalidOptions("Launcher command must not be empty".to_string()));
+        }
+        if let Some(level) = &options.log_level {
+            if !vault_config::LOG_LEVELS.contains(&level.as_str()) {
+                return Err(SidecarError::InvalidOptions(format!(
+                    "Invalid log level '{}' (expected one of {})",
+                    level, vault_config::LOG_LEVELS.join(", "))));
+            }
+        }
+
+        if options.init_method.as_ref().is_some_and(|method| method.trim().is_empty()) {
+            return Err(SidecarError::InvalidOptions("Init method must not be empty".to_string()));
+        }
+        python::validate_flags(&options.python_flags)
+            .map_err(|reason| SidecarError::InvalidOptions(format!("Invalid Python flags: {}", reason)))?;
+        if let Some(version) = &options.python_version {
+            VersionConstraint::parse(version).map_err(SidecarError::InvalidOptions)?;
+        }
+        self.config.sidecar_args.validate()
+            .map_err(|reason| SidecarError::InvalidOptions(format!("Invalid sidecar arguments: {}", reason)))?;
+        if let Some(rate_limit) = options.rate_limit.or(self.config.rate_limit) {
+            rate_limit.validate()
+                .map_err(|reason| SidecarError::InvalidOptions(format!("Invalid rate limit: {}", reason)))?;
+        }
+        // Refused early here, before installing anything; checked again when the process is tracked
+        Self::check_dependency_cycle(&*self.processes.lock().await, &window_label, &options.depends_on)?;
+        let host = self.host_for(&options)?;
+        if options.install_dependencies {
+            tokio::select! {
+                installed = self.ensure_dependencies(&window_label, &vault_path) => installed?,
+                _ = cancel.cancelled() => return Err(SidecarError::Cancelled),
+            }
+        }
+        cancel.check()?;
+
+        // Allocate port
+        let ws_port = match (options.transport, options.port) {
+            (Transport::Stdio, _) => 0,
+            (Transport::WebSocket, Some(port)) => self.claim_port(&host, port)?,
+            (Transport::WebSocket, None) => self.allocate_port(&host).await?,
+        };
+
+        let spawn_seq = self.next_spawn_seq.fetch_add(1, Ordering::SeqCst);
+        self.launch(window_label, vault_path, options, ws_port, Vec::new(), spawn_seq).await
+    }
+
+    /// Install the vault's missing requirements, emitting `deps://progress` with `stage`
+    /// `checking`, then `installing` (with the `missing` packages) if needed, and finally `ready`
+    /// (with the `backend` that installed them, if any) or `failed` (with the `error`). A vault
+    /// that passed within `dependency_cache_ttl` goes straight to `ready` with `cached` set.
+    async fn ensure_dependencies(&self, window_label: &str, vault_path: &str) -> Result<(), SidecarError> {
+        let progress = |stage: &str, detail: Option<(&str, serde_json::Value)>| {
+            if let Some(emitter) = &self.emitter {
+                let mut payload = serde_json::json!({
+                    "window_label": window_label,
+                    "vault_path": vault_path,
+                    "stage": stage,
+                });
+                if let Some((key, value)) = detail {
+                    payload[key] = value;
+                }
+                emitter("deps://progress", payload);
+            }
+        };
+
+        let vault = canonical_vault_path(vault_path);
+        if self.dependency_cache.is_fresh(&vault) {
+            progress("ready", Some(("cached", true.into())));
+            return Ok(());
+        }
+        progress("checking", None);
+        let result = match DependencyChecker::needs_update_detailed(vault_path).await {
+            DependencyStatus::UpToDate => Ok(None),
+            DependencyStatus::NeedsInstall(missing) => {
+                println!("Installing {} missing package(s) for vault {}", missing.len(), vault_path);
+                progress("installing", Some(("missing", serde_json::json!(missing))));
+                let python = self.get_python_executable().await.ok().map(|python| python.path);
+                let project_root = project_root().ok();
+                DependencyChecker::check_and_install(vault_path, project_root.as_deref(), python.as_deref(),
+                                                     &self.config.pixi_executable)
+                    .await
+                    .map(|report| report.backend)
+                    .map_err(|e| format!("{:#}", e))
+            }
+            DependencyStatus::Broken(reason) => Err(reason),
+        };
+        match result {
+            Ok(backend) => {
+                self.dependency_cache.record(&vault);
+                progress("ready", backend.map(|backend| ("backend", serde_json::json!(backend))));
+                Ok(())
+            }
+            Err(reason) => {
+                eprintln!("Dependencies for vault {} are not satisfied: {}", vault_path, reason);
+                progress("failed", Some(("error", serde_json::json!(reason))));
+                Err(SidecarError::Dependencies(reason))
+            }
+        }
+    }
+
+    /// Apply `SidecarManagerConfig::duplicate_spawn` to a window already tracked; the port to
+    /// hand back when the running sidecar is reused. A tracked process that has exited is
+    /// cleaned up and the spawn goes ahead.
+    async fn resolve_duplicate(&self, window_label: &str, vault_path: &str) -> Result<Option<u16>, SidecarError> {
+        let running = {
+            let mut processes = self.processes.lock().await;
+            let Some(process) = processes.get_mut(window_label) else {
+                return Ok(None);
+            };
+            let alive = matches!(process.child.try_wait(), Ok(None));
+            alive.then(|| (process.ws_port, canonical_vault_path(&process.vault_path)))
+        };
+        let Some((ws_port, running_vault)) = running else {
+            println!("Sidecar for window '{}' has exited, cleaning it up before spawning again", window_label);
+            self.terminate_sidecar(window_label).await?;
+            return Ok(None);
+        };
+        let already_running = SidecarError::AlreadyRunning { window_label: window_label.to_string(), ws_port };
+        match self.config.duplicate_spawn {
+            DuplicateSpawn::Reject => Err(already_running),
+            DuplicateSpawn::Reuse if running_vault == canonical_vault_path(vault_path) => {
+                println!("Sidecar for window '{}' is already running on port {}, reusing it", window_label, ws_port);
+                Ok(Some(ws_port))
+            }
+            DuplicateSpawn::Reuse => Err(already_running),
+            DuplicateSpawn::Replace => {
+                println!("Replacing the running sidecar for window '{}'", window_label);
+                self.terminate_sidecar(window_label).await?;
+                Ok(None)
+            }
+        }
+    }
+
+    /// Refuse `depends_on` for `window_label` if it closes a cycle with the `processes`'
+    fn check_dependency_cycle(
+        processes: &HashMap<String, SidecarProcess>,
+        window_label: &str,
+        depends_on: &[String],
+    ) -> Result<(), SidecarError> {
+        let providers: HashMap<String, Vec<String>> = processes
+            .iter()
+            .filter(|(label, _)| label.as_str() != window_label)
+            .map(|(label, process)| (label.clone(), process.options.depends_on.clone()))
+            .collect();
+        match shutdown_order::find_cycle(&providers, window_label, depends_on) {
+            Some(cycle) => Err(SidecarError::InvalidOptions(format!("Dependency cycle: {}", cycle.join(" -> ")))),
+            None => Ok(()),
+        }
+    }
+
+    /// Resolve everything about a launch of `vault_path` with `options` short of its ports: the
+    /// vault config merged in, the interpreter, the working directory and `PYTHONPATH`
+    async fn plan_launch(&self, vault_path: &str, options: &SpawnOptions) -> Result<LaunchPlan, SidecarError> {
+        // Merge the vault's config file on every launch so restarts pick up edits
+        let mut effective = options.clone();
+        load_vault_config(Path::new(vault_path))
+            .map_err(|e| SidecarError::InvalidOptions(format!("Invalid vault sidecar config: {:#}", e)))?
+            .apply_to(&mut effective);
+        self.roles.apply_to(&mut effective).map_err(SidecarError::InvalidOptions)?;
+
+        let python = match &effective.python_version {
+            Some(version) => self.get_python_matching(version).await?,
+            None => self.get_python_executable().await?,
+        };
+        let sidecar_root = match &self.config.sidecar_root {
+            Some(root) => root.clone(),
+            None => project_root()?,
+        };
+        let working_dir = match effective.working_dir.as_ref().unwrap_or(&self.config.working_dir) {
+            WorkingDir::ProjectRoot => project_root()?,
+            WorkingDir::Vault => PathBuf::from(vault_path),
+            WorkingDir::Path(path) => path.clone(),
+        };
+        // An explicit PYTHONPATH in the vault config or our own environment is kept, after ours
+        let inherited = effective.env.get("PYTHONPATH").map(OsString::from)
+            .or_else(|| std::env::var_os("PYTHONPATH"));
+        let python_path = sidecar_python_path(&sidecar_root, inherited.as_deref())?;
+        Ok(LaunchPlan { effective, python, sidecar_root, working_dir, python_path })
+    }
+
+    /// The template's arguments for one launch, with `--stdio` for stdio sidecars
+    fn sidecar_args(&self, vault_path: &str, effective: &SpawnOptions, ws_port: u16, ready_file: &Path,
+                    control_port: Option<u16>) -> Vec<OsString> {
+        let websocket = effective.transport == Transport::WebSocket;
+        let mut sidecar_args = self.config.sidecar_args.render(&args::ArgValues {
+            vault: vault_path,
+            port: websocket.then_some(ws_port),
+            ready_file: websocket.then_some(ready_file),
+            control_port,
+            log_level: effective.log_level.as_deref(),
+        });
+        if !websocket {
+            sidecar_args.push("--stdio".into());
+        }
+        sidecar_args
+    }
+
+    /// The interpreter, command line, working directory and extra environment a spawn for
+    /// `window_label` on `vault_path` would use, resolved exactly as a launch would without
+    /// starting anything. A running window's spawn options and ports are used; otherwise the
+    /// default options and the ports the next spawn would get, without taking them (a port
+    /// taken in between is skipped by the real spawn).
+    pub async fn spawn_command_preview(&self, window_label: &str, vault_path: &str) -> Result<SpawnPreview, SidecarError> {
+        let running = self.processes.lock().await
+            .get(window_label)
+            .map(|process| (process.options.clone(), process.ws_port, process.control_port));
+        let options = running.as_ref().map(|(options, ..)| options.clone()).unwrap_or_default();
+        let plan = self.plan_launch(vault_path, &options).await?;
+        let (ws_port, control_port) = match running {
+            Some((_, ws_port, control_port)) => (ws_port, control_port),
+            None => {
+                // Looked up without moving the allocation cursor, so previews don't change the
+                // ports the next spawn gets
+                let host = self.host_for(&options)?;
+                let start = *self.next_port.lock().await;
+                let mut free = self.free_ports(start, &host);
+                let ws_port = match (options.transport, options.port) {
+                    (Transport::Stdio, _) => 0,
+                    (Transport::WebSocket, Some(port)) => port,
+                    (Transport::WebSocket, None) => free.next().ok_or(SidecarError::PortExhausted)?,
+                };
+                let control_port = match self.config.control_socket && options.transport == Transport::WebSocket {
+                    true => Some(free.next().ok_or(SidecarError::PortExhausted)?),
+                    false => None,
+                };
+                (ws_port, control_port)
+            }
+        };
+        let sidecar_args = self.sidecar_args(vault_path, &plan.effective, ws_port, &new_ready_file(), control_port);
+        let argv = plan.argv(sidecar_args);
+        let mut env: BTreeMap<String, String> = plan.effective.env.clone().into_iter().collect();
+        env.insert("PYTHONPATH".to_string(), plan.python_path.to_string_lossy().to_string());
+        Ok(SpawnPreview::new(plan.python, &argv, plan.working_dir, env))
+    }
+
+    /// Start the sidecar process on `ws_port` and begin tracking it, publishing `Starting`, or
+    /// `Failed` if it can't be started
+    async fn launch(
+        &self,
+        window_label: String,
+        vault_path: String,
+        options: SpawnOptions,
+        ws_port: u16,
+        restarts: Vec<DateTime<Utc>>,
+        spawn_seq: u64,
+    ) -> Result<u16, SidecarError> {
+        let state = self.states.sender(&window_label);
+        state.send_replace(SidecarState::Starting);
+        let result = self.start_process(window_label, vault_path, options, ws_port, restarts, spawn_seq).await;
+        if result.is_err() {
+            state.send_replace(SidecarState::Failed);
+        }
+        result
+    }
+
+    async fn start_process(
+        &self,
+        window_label: String,
+        vault_path: String,
+        options: SpawnOptions,
+        ws_port: u16,
+        restarts: Vec<DateTime<Utc>>,
+        spawn_seq: u64,
+    ) -> Result<u16, SidecarError> {
+        let mut progress = SpawnProgress::new(&window_label, self.emitter.clone(), self.config.spawn_deadline);
+
+        let plan = self.plan_launch(&vault_path, &options).await?;
+        progress.check()?;
+        progress.reach(SpawnStage::PythonResolved);
+        let host = self.host_for(&options)?;
+
+        println!("Spawning sidecar for window '{}': vault={}, port={}", 
+                 window_label, vault_path, ws_port);
+        println!("Python executable: {} ({})", plan.python.path, plan.python.version);
+        println!("Sidecar root: {}", plan.sidecar_root.display());
+        println!("Working directory: {}", plan.working_dir.display());
+        if python::ignores_pythonpath(&options.python_flags) {
+            println!("Python flags {:?} ignore PYTHONPATH; the sidecar package must be installed in the interpreter's environment",
+                     options.python_flags);
+        }
+        if let Some(launcher) = &options.launcher {
+            println!("Launcher: {}", launcher.join(" "));
+        }
+
+        let control_port = match self.config.control_socket && options.transport == Transport::WebSocket {
+            true => Some(self.allocate_port(&host).await?),
+            false => None,
+        };
+
+        // Fresh per launch so a stale file from a previous process can't signal readiness
+        let ready_file = new_ready_file();
+
+        let sidecar_args = self.sidecar_args(&vault_path, &plan.effective, ws_port, &ready_file, control_port);
+        println!("Sidecar arguments: {}", args::display(&sidecar_args));
+        let argv = plan.argv(sidecar_args);
+        let mut command = Command::new(&argv[0]);
+        command.args(&argv[1..]);
+        if options.launcher.is_some() {
+            process::set_new_process_group(&mut command);
+        }
+        if options.transport == Transport::Stdio {
+            command.stdin(Stdio::piped());
+        }
+
+        let LaunchPlan { effective, python, working_dir, python_path, .. } = plan;
+        let mut child = command
+            .envs(&effective.env)
+            .env("PYTHONPATH", &python_path)
+            .current_dir(&working_dir)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| SidecarError::SpawnFailed(e.to_string()))?;
+
+        let pid = child.id();
+        println!("Sidecar spawned with PID: {}", pid);
+        progress.reach(SpawnStage::ProcessStarted);
+        if !effective.cpu_affinity.is_empty() {
+            match process::set_cpu_affinity(&child, &effective.cpu_affinity) {
+                Ok(true) => println!("Pinned sidecar {} to cores {:?}", pid, effective.cpu_affinity),
+                Ok(false) => println!("CPU affinity is not supported on this platform, ignoring"),
+                Err(e) => eprintln!("Failed to pin sidecar {} to cores {:?}: {}", pid, effective.cpu_affinity, e),
+            }
+        }
+        if effective.priority != ProcessPriority::Normal {
+            match process::set_priority(&child, effective.priority) {
+                Ok(true) => println!("Set sidecar {} priority to {:?}", pid, effective.priority),
+                Ok(false) => println!("Process priorities are not supported on this platform, ignoring"),
+                Err(e) => eprintln!("Failed to set sidecar {} priority to {:?}: {}", pid, effective.priority, e),
+            }
+        }
+
+        let state = self.states.sender(&window_label);
+        let telemetry = Arc::new(SidecarTelemetry::new(&window_label, self.log_tx.clone(), self.output_matchers.clone(),
+                                                       state.clone(), self.config.max_in_flight,
+                                                       effective.rate_limit.or(self.config.rate_limit),
+                                                       self.open_audit_log(&window_label)));
+        let counters = telemetry.connection.clone();
+        let generation = self.next_generation.fetch_add(1, Ordering::SeqCst);
+        let mut readers = Vec::new();
+        // Each output reader holds a sender; once all are gone, see whether the process died
+        let (pipe_open, pipes_closed) = mpsc::unbounded_channel();
+        let notifications = self.notifications.lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry(window_label.clone())
+            .or_default()
+            .tx
+            .clone();
+
+        // Capture stdout and stderr for debugging, unless stdout carries the sidecar's RPC
+        let mut stdio = None;
+        if options.transport == Transport::Stdio {
+            if let (Some(stdin), Some(stdout)) = (child.stdin.take(), child.stdout.take()) {
+                let (transport, reader) = StdioTransport::start(&window_label, stdin, stdout, counters.clone(),
+                                                                notifications.clone(), self.config.null_id_errors);
+                stdio = Some(transport);
+                readers.push(reader);
+            }
+        }
+        let max_record = match effective.capture {
+            CaptureMode::Lines => self.config.max_log_line,
+            _ => self.config.max_log_record,
+        };
+        if let Some(stdout) = child.stdout.take() {
+            readers.push(spawn_reader(stdout, LogStream::Stdout, effective.capture, max_record,
+                                      telemetry.clone(), self.log_parser.clone(), pipe_open.clone()));
+        }
+        if let Some(stderr) = child.stderr.take() {
+            readers.push(spawn_reader(stderr, LogStream::Stderr, effective.capture, max_record,
+                                      telemetry.clone(), self.log_parser.clone(), pipe_open));
+        }
+
+        let plugin_watcher = match effective.watch_plugins {
+            true => self.watch_plugins(&window_label, &vault_path),
+            false => None,
+        };
+
+        // Store process
+        let process = SidecarProcess {
+            child: SidecarChild::Spawned(child),
+            vault_path: vault_path.clone(),
+            host: host.clone(),
+            ws_port,
+            control_port,
+            python,
+            started_at: Utc::now(),
+            started_instant: Instant::now(),
+            telemetry,
+            options: options.clone(),
+            restarts,
+            generation,
+            spawn_seq,
+            readers,
+            ready_file: ready_file.clone(),
+            connection: Arc::new(Mutex::new(None)),
+            notifications,
+            needs_restart: false,
+            version: None,
+            init_result: None,
+            _plugin_watcher: plugin_watcher,
+            stdio: stdio.clone(),
+        };
+        let target = process.connection_target();
+        // Subscribers can only exist from an earlier launch, and only they need reattaching
+        let reattach = !process.restarts.is_empty() && self.config.persistent_connections && stdio.is_none();
+        let preconnect = self.config.preconnect && self.config.persistent_connections && stdio.is_none();
+
+        let mut processes = self.processes.lock().await;
+        // Under the same lock as the insert, so two concurrent spawns depending on each other
+        // can't both pass the check made before they launched
+        if let Err(e) = Self::check_dependency_cycle(&processes, &window_label, &process.options.depends_on) {
+            drop(processes);
+            if let Err(stop_error) = self.stop_process(&window_label, process, self.config.terminate_grace).await {
+                eprintln!("Failed to stop the refused sidecar for window '{}': {}", window_label, stop_error);
+            }
+            return Err(e);
+        }
+        let displaced = processes.insert(window_label.clone(), process);
+        drop(processes);
+        if let Some(displaced) = displaced {
+            // A concurrent spawn for the window got here first; stop its process rather than leak it
+            eprintln!("Another sidecar was started for window '{}' meanwhile, stopping it", window_label);
+            if let Err(e) = self.stop_process(&window_label, displaced, self.config.terminate_grace).await {
+                eprintln!("Failed to stop the displaced sidecar for window '{}': {}", window_label, e);
+            }
+        }
+        Self::save_reattach_state(&self.processes, self.config.reattach_file.as_deref()).await;
+        self.track_task(tokio::spawn(Self::watch_for_crash(self.processes.clone(), self.emitter.clone(),
+                                                           self.crashes.clone(), self.quarantine.clone(),
+                                                           window_label.clone(), generation, pipes_closed)));
+
+        // Readiness and warmup run in the background so the window can look up its port immediately
+        let processes = self.processes.clone();
+        let emitter = self.emitter.clone();
+        let replay = self.replay.clone();
+        let hubs = self.notifications.clone();
+        let context = self.connection_context();
+        let client = self.client_options();
+        let quarantine = self.quarantine.clone();
+        let tracked = self.processes.clone();
+        let reattach_file = self.config.reattach_file.clone();
+        let init = options.init_method.clone()
+            .map(|method| (method, options.init_params.clone().unwrap_or_else(|| serde_json::json!({}))));
+        let restore = match options.restore_state {
+            true => self.snapshots.lock().unwrap_or_else(|e| e.into_inner()).get(&window_label).cloned(),
+            false => None,
+        };
+        let mut shutdown = self.shutdown_tx.subscribe();
+        self.track_task(tokio::spawn(async move {
+            tokio::select! {
+                _ = async {
+                    let ready = Self::await_readiness(processes, emitter.clone(), state, progress, window_label.clone(),
+                                                      &host, ws_port, stdio.as_ref(), &client, ready_file, init,
+                                                      restore, effective.warmup_methods).await;
+                    // Commands go wherever the sidecar really listens
+                    let mut target = target;
+                    let preconnect = preconnect && ready.is_ok();
+                    let ws_port = match &ready {
+                        Ok(bound) => {
+                            if *bound != ws_port {
+                                Self::save_reattach_state(&tracked, reattach_file.as_deref()).await;
+                            }
+                            *bound
+                        }
+                        Err(e) => {
+                            Self::record_crash(&quarantine, emitter.as_ref(), &vault_path, &e.to_string());
+                            ws_port
+                        }
+                    };
+                    target.port = ws_port;
+                    if preconnect {
+                        Self::preconnect(&window_label, &target, &context, emitter.as_ref()).await;
+                    }
+                    match &ready {
+                        Ok(_) => Self::replay_parked(&replay, &window_label, generation, &counters, &host, ws_port,
+                                                     stdio.as_ref(), &client).await,
+                        Err(e) => Self::fail_parked(&replay, &window_label, e),
+                    }
+                    if reattach && target.notifications.receiver_count() > 0 {
+                        Self::reattach_notifications(&hubs, &window_label, &target, &context).await;
+                    }
+                } => {}
+                _ = shutdown.wait_for(|stop| *stop) => {}
+            }
+        }));
+
+        Ok(ws_port)
+    }
+
+    /// Start watching the vault's plugins directory; a failure (e.g. no `plugins/`) only disables
+    /// the watch
+    fn watch_plugins(&self, window_label: &str, vault_path: &str) -> Option<PluginWatcher> {
+        let started = PluginWatcher::start(window_label, Path::new(vault_path), self.config.plugin_watch_debounce,
+                                           self.plugin_changes.clone(), self.shutdown_tx.subscribe());
+        match started {
+            Ok((watcher, task)) => {
+                println!("Watching plugins of vault {} for window '{}'", vault_path, window_label);
+                self.track_task(task);
+                Some(watcher)
+            }
+            Err(e) => {
+                eprintln!("Failed to watch plugins of vault {}: {}", vault_path, e);
+                None
+            }
+        }
+    }
+
+    /// Reload sidecars whose plugins changed (see [`plugin_watch`]) until `shutdown`, emitting
+    /// `sidecar://plugins-reloaded` with the changed paths and the [`ReloadReport`] or error.
+    /// Only the first call does anything.
+    pub async fn run_plugin_watcher(&self) {
+        let Some(mut changes) = self.plugin_changes_rx.lock().unwrap_or_else(|e| e.into_inner()).take() else {
+            return;
+        };
+        let mut shutdown = self.shutdown_tx.subscribe();
+        loop {
+            let change = tokio::select! {
+                change = changes.recv() => change,
+                _ = shutdown.wait_for(|stop| *stop) => return,
+            };
+            let Some(PluginChange { window_label, paths }) = change else { return };
+            println!("{} plugin file(s) changed for window '{}', reloading", paths.len(), window_label);
+            let mut payload = serde_json::json!({ "window_label": window_label, "paths": paths });
+            match self.reload(&window_label).await {
+                Ok(report) => payload["report"] = serde_json::json!(report),
+                Err(e) => {
+                    eprintln!("Failed to reload window '{}' after plugin changes: {}", window_label, e);
+                    payload["error"] = e.to_string().into();
+                }
+            }
+            if let Some(emitter) = &self.emitter {
+                emitter("sidecar://plugins-reloaded", payload);
+            }
+        }
+    }
+
+    /// Once the sidecar's output pipes have closed, check whether its process exited while
+    /// this launch was still tracked, i.e. the manager didn't stop it. If so, record a
+    /// [`CrashReport`] (failing its in-flight commands), mark it `Failed` and emit
+    /// `sidecar://crashed`; a sidecar that had become ready is also queued for `run_crash_handler`.
+    async fn watch_for_crash(
+        processes: Arc<Mutex<HashMap<String, SidecarProcess>>>,
+        emitter: Option<EventEmitter>,
+        crashes: mpsc::UnboundedSender<(String, u64)>,
+        quarantine: Arc<Quarantine>,
+        window_label: String,
+        generation: u64,
+        mut pipes_closed: mpsc::UnboundedReceiver<()>,
+    ) {
+        while pipes_closed.recv().await.is_some() {}
+        let deadline = Instant::now() + crash::EXIT_CONFIRM_TIMEOUT;
+        let (status, telemetry, vault_path) = loop {
+            {
+                let mut processes = processes.lock().await;
+                // Stopped or replaced by the manager
+                let Some(process) = processes.get_mut(&window_label).filter(|p| p.generation == generation) else {
+                    return;
+                };
+                if let Ok(Some(status)) = process.child.try_wait() {
+                    break (status, process.telemetry.clone(), process.vault_path.clone());
+                }
+            }
+            // Closed its pipes but lives on
+            if Instant::now() >= deadline {
+                return;
+            }
+            tokio::time::sleep(READINESS_POLL_INTERVAL).await;
+        };
+
+        let logs = telemetry.logs.lock().map(|logs| logs.snapshot()).unwrap_or_default();
+        let report = CrashReport::new(&window_label, status, &logs);
+        eprintln!("{}", report);
+        telemetry.record_error("crash", report.to_string());
+        telemetry.crash.send_replace(Some(report.clone()));
+        // A launch that never became ready is failed by its readiness check instead
+        let was_up = telemetry.state.send_if_modified(|state| {
+            let up = matches!(state, SidecarState::Ready | SidecarState::Busy | SidecarState::Suspended);
+            if up {
+                *state = SidecarState::Failed;
+            }
+            up
+        });
+        if let Some(emitter) = &emitter {
+            emitter("sidecar://crashed", serde_json::json!(report));
+        }
+        if was_up {
+            Self::record_crash(&quarantine, emitter.as_ref(), &vault_path, &report.to_string());
+            let _ = crashes.send((window_label, generation));
+        }
+    }
+
+    /// Count a failed launch or a crash after readiness towards `vault_path`'s quarantine
+    fn record_crash(quarantine: &Quarantine, emitter: Option<&EventEmitter>, vault_path: &str, reason: &str) {
+        if let Some(entry) = quarantine.record_crash(vault_path, reason) {
+            eprintln!("Quarantined vault {} after {} sidecar crashes", vault_path, entry.crashes);
+            if let Some(emitter) = emitter {
+                emitter("sidecar://quarantined", serde_json::json!(entry));
+            }
+        }
+    }
+
+    /// Restart sidecars that crashed after becoming ready under [`CrashPolicy::RestartAndRetry`],
+    /// until `shutdown`, backing off and giving up per `SidecarManagerConfig::restart_limit`;
+    /// in-flight commands parked for replay go to the new process. Only the first call does
+    /// anything.
+    pub async fn run_crash_handler(&self) {
+        let Some(mut crashes) = self.crashes_rx.lock().unwrap_or_else(|e| e.into_inner()).take() else {
+            return;
+        };
+        let mut shutdown = self.shutdown_tx.subscribe();
+        // Restarts waiting out their backoff: (due, window, generation that crashed)
+        let mut scheduled: Vec<(tokio::time::Instant, String, u64)> = Vec::new();
+        loop {
+            let next_due = scheduled.iter().map(|(due, _, _)| *due).min();
+            // None when a scheduled restart is due
+            let crash = tokio::select! {
+                crash = crashes.recv() => Some(crash),
+                _ = tokio::time::sleep_until(next_due.unwrap_or_else(tokio::time::Instant::now)), if next_due.is_some() => None,
+                _ = shutdown.wait_for(|stop| *stop) => return,
+            };
+            let Some(crash) = crash else {
+                let now = tokio::time::Instant::now();
+                let (due, later) = scheduled.into_iter().partition(|(due, _, _)| *due <= now);
+                scheduled = later;
+                for (_, window_label, generation) in due {
+                    self.restart_crashed(&window_label, generation).await;
+                }
+                continue;
+            };
+            let Some((window_label, generation)) = crash else { return };
+            if self.config.crash_policy != CrashPolicy::RestartAndRetry {
+                continue;
+            }
+            // Unless it was terminated or restarted meanwhile
+            let Some((current, role, vault_path, restarts)) = self.processes.lock().await
+                .get(&window_label)
+                .map(|p| (p.generation, p.options.role.clone(), p.vault_path.clone(), p.restarts.clone())) else {
+                continue;
+            };
+            if current != generation {
+                continue;
+            }
+            if let Err(e) = self.quarantine.check(&vault_path) {
+                eprintln!("Not restarting crashed sidecar for window '{}': {}", window_label, e);
+                continue;
+            }
+            let limit = &self.config.restart_limit;
+            let recent = limit.recent(&restarts, Utc::now());
+            match self.roles.get(role.as_deref()).and_then(|profile| profile.max_restarts) {
+                Some(max) if restarts.len() >= max => {
+                    eprintln!("Not restarting crashed sidecar for window '{}': its role allows {} restarts",
+                              window_label, max);
+                    continue;
+                }
+                None if recent >= limit.max_restarts => {
+                    eprintln!("Not restarting crashed sidecar for window '{}': it was restarted {} times within {:?}",
+                              window_label, recent, limit.window);
+                    continue;
+                }
+                _ => {}
+            }
+            let delay = limit.backoff(recent);
+            println!("Sidecar for window '{}' crashed, restarting it in {:?}", window_label, delay);
+            scheduled.push((tokio::time::Instant::now() + delay, window_label, generation));
+        }
+    }
+
+    /// Restart `window_label`'s sidecar for `run_crash_handler`, unless it was terminated or
+    /// restarted since crashing as `generation`
+    async fn restart_crashed(&self, window_label: &str, generation: u64) {
+        let current = self.processes.lock().await.get(window_label).map(|p| p.generation);
+        if current != Some(generation) {
+            return;
+        }
+        println!("Restarting crashed sidecar for window '{}'", window_label);
+        if let Err(e) = self.restart_sidecar(window_label).await {
+            eprintln!("Failed to restart crashed sidecar for window '{}': {}", window_label, e);
+        }
+    }
+
+    /// Wait for the sidecar's ready file, restore its state snapshot and run its warmup methods,
+    /// then emit `sidecar://ready`; all within what is left of the spawn deadline. Fails if the
+    /// launch went `Failed`, not if it was terminated meanwhile.
+    #[allow(clippy::too_many_arguments)]
+    async fn await_readiness(
+        processes: Arc<Mutex<HashMap<String, SidecarProcess>>>,
+        emitter: Option<EventEmitter>,
+        state: watch::Sender<SidecarState>,
+        mut progress: SpawnProgress,
+        window_label: String,
+        host: &str,
+        ws_port: u16,
+        stdio: Option<&StdioTransport>,
+        client: &ClientOptions,
+        ready_file: PathBuf,
+        init: Option<(String, serde_json::Value)>,
+        restore: Option<serde_json::Value>,
+        warmup_methods: Vec<String>,
+    ) -> Result<u16, SidecarError> {
+        let remaining = progress.remaining();
+        let assigned_port = ws_port;
+        let startup = Self::start_serving(&processes, &mut progress, &window_label, host, ws_port, stdio, client,
+                                          &ready_file, init, restore, &warmup_methods);
+        let (ws_port, init_result, warmup) = match tokio::time::timeout(remaining, startup).await {
+            Ok(Ok(started)) => started,
+            result => {
+                let error = match result {
+                    Ok(Err(e)) => e,
+                    _ => progress.timed_out(),
+                };
+                eprintln!("Sidecar for window '{}' never became ready: {}", window_label, error);
+                if let Some(process) = processes.lock().await.get(&window_label) {
+                    process.telemetry.record_error("spawn", error.to_string());
+                }
+                progress.fail(&error);
+                return match state::transition(&state, SidecarState::Starting, SidecarState::Failed) {
+                    true => Err(error),
+                    false => Ok(ws_port),
+                };
+            }
+        };
+        progress.reach(SpawnStage::WarmedUp);
+        if init_result.is_some() {
+            if let Some(process) = processes.lock().await.get_mut(&window_label) {
+                process.init_result = init_result;
+            }
+        }
+
+        println!("Sidecar for window '{}' is ready on port {}", window_label, ws_port);
+        if ws_port != assigned_port {
+            emit_port_changed(emitter.as_ref(), &window_label, assigned_port, ws_port);
+        }
+        state::transition(&state, SidecarState::Starting, SidecarState::Ready);
+        if let Some(emitter) = &emitter {
+            emitter("sidecar://ready", serde_json::json!({
+                "window_label": window_label,
+                "ws_port": ws_port,
+                "warmup": warmup,
+            }));
+        }
+        Ok(ws_port)
+    }
+
+    /// The readiness steps bounded by [`Self::await_readiness`], returning the port the sidecar
+    /// listens on, its answer to the init method and the warmup report
+    #[allow(clippy::too_many_arguments)]
+    async fn start_serving(
+        processes: &Mutex<HashMap<String, SidecarProcess>>,
+        progress: &mut SpawnProgress,
+        window_label: &str,
+        host: &str,
+        ws_port: u16,
+        stdio: Option<&StdioTransport>,
+        client: &ClientOptions,
+        ready_file: &Path,
+        init: Option<(String, serde_json::Value)>,
+        restore: Option<serde_json::Value>,
+        warmup_methods: &[String],
+    ) -> Result<(u16, Option<serde_json::Value>, Vec<serde_json::Value>), SidecarError> {
+        // Requests to a stdio sidecar wait in the pipe until it reads them
+        let ws_port = match stdio {
+            Some(_) => ws_port,
+            None => Self::wait_until_ready(processes, window_label, ready_file, ws_port, READINESS_TIMEOUT).await?,
+        };
+        progress.reach(SpawnStage::PortListening);
+
+        let telemetry = processes.lock().await
+            .get(window_label)
+            .map(|p| p.telemetry.clone());
+        let counters = telemetry.as_ref().map(|t| t.connection.clone()).unwrap_or_default();
+
+        let init_result = match init {
+            Some((method, params)) => {
+                let mut response = Self::send_once(&counters, host, ws_port, stdio, client, &method, params, None).await
+                    .map_err(|e| SidecarError::InitializeFailed { method: method.clone(), reason: e.to_string() })?;
+                println!("Initialized sidecar for window '{}' with '{}'", window_label, method);
+                Some(response["result"].take())
+            }
+            None => None,
+        };
+
+        if let Some(snapshot) = restore {
+            // A sidecar that can't restore still works, it just starts from scratch
+            let params = serde_json::json!({ "state": snapshot });
+            match Self::send_once(&counters, host, ws_port, stdio, client, snapshot::RESTORE_METHOD, params, None).await {
+                Ok(_) => println!("Restored state snapshot for window '{}'", window_label),
+                Err(e) => {
+                    eprintln!("Failed to restore state for window '{}': {}", window_label, e);
+                    if let Some(telemetry) = &telemetry {
+                        telemetry.record_error(snapshot::RESTORE_METHOD, e.to_string());
+                    }
+                }
+            }
+        }
+
+        let mut warmup = Vec::new();
+        for method in warmup_methods {
+            let started = Instant::now();
+            // Warmup failures are logged but never prevent the sidecar from being used
+            let error = match Self::send_once(&counters, host, ws_port, stdio, client, method, serde_json::json!({}), None).await {
+                Ok(_) => None,
+                Err(e) => Some(e.to_string()),
+            };
+            if let Some(error) = &error {
+                eprintln!("Warmup '{}' failed for window '{}': {}", method, window_label, error);
+                if let Some(telemetry) = &telemetry {
+                    telemetry.record_error(method, error.clone());
+                }
+            }
+            warmup.push(serde_json::json!({
+                "method": method,
+                "ok": error.is_none(),
+                "error": error,
+                "duration_ms": started.elapsed().as_millis() as u64,
+            }));
+        }
+        Ok((ws_port, init_result, warmup))
+    }
+
+    /// Mark a launch ready for the replay queue and run the commands parked while it was down
+    #[allow(clippy::too_many_arguments)]
+    async fn replay_parked(
+        replay: &ReplayQueue,
+        window_label: &str,
+        generation: u64,
+        counters: &ConnectionCounters,
+        host: &str,
+        ws_port: u16,
+        stdio: Option<&StdioTransport>,
+        client: &ClientOptions,
+    ) {
+        let parked = replay.mark_ready(window_label, generation);
+        if !parked.is_empty() {
+            println!("Replaying {} parked command(s) for window '{}'", parked.len(), window_label);
+        }
+        for command in parked {
+            let result =
+                Self::send_once(counters, host, ws_port, stdio, client, &command.method, command.params, command.blob).await;
+            let _ = command.reply.send(result);
+        }
+    }
+
+    /// Fail the commands parked for a launch that never became ready with its readiness error,
+    /// instead of sending them to a sidecar that isn't there
+    fn fail_parked(replay: &ReplayQueue, window_label: &str, error: &SidecarError) {
+        for command in replay.take_parked(window_label) {
+            let _ = command.reply.send(Err(SidecarError::SpawnFailed(format!("sidecar did not become ready: {}", error))));
+        }
+    }
+
+    /// Open a ready sidecar's persistent connection ahead of its first command and emit
+    /// `sidecar://connected`; a failure is logged and the first command connects instead
+    async fn preconnect(window_label: &str, target: &ConnectionTarget, context: &ConnectionContext,
+                        emitter: Option<&EventEmitter>) {
+        let started = Instant::now();
+        match Self::ensure_connection(target, context).await {
+            Ok(_) => {
+                println!("Opened connection to sidecar for window '{}' in {:?}", window_label, started.elapsed());
+                if let Some(emitter) = emitter {
+                    emitter("sidecar://connected", serde_json::json!({
+                        "window_label": window_label,
+                        "ws_port": target.port,
+                        "connect_ms": started.elapsed().as_millis() as u64,
+                    }));
+                }
+            }
+            Err(e) => eprintln!("Failed to pre-open connection for window '{}', connecting on first command: {}",
+                                window_label, e),
+        }
+    }
+
+    /// Give a restarted sidecar's notification subscribers a live stream again: reconnect,
+    /// re-send their subscription RPCs, then tell them with [`SidecarNotification::Reconnected`]
+    async fn reattach_notifications(
+        hubs: &std::sync::Mutex<HashMap<String, NotificationHub>>,
+        window_label: &str,
+        target: &ConnectionTarget,
+        context: &ConnectionContext,
+    ) {
+        let connection = match Self::ensure_connection(target, context).await {
+            Ok(connection) => connection,
+            Err(e) => {
+                eprintln!("Failed to reattach notifications for window '{}': {}", window_label, e);
+                return;
+            }
+        };
+        let subscriptions = hubs.lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(window_label)
+            .map(|hub| hub.subscriptions.clone())
+            .unwrap_or_default();
+        for (method, params) in subscriptions {
+            let result = connection.request(&method, params, None).await.and_then(error::check_response);
+            if let Err(e) = result {
+                eprintln!("Re-subscribing '{}' for window '{}' failed: {}", method, window_label, e);
+            }
+        }
+        println!("Notifications reattached for window '{}'", window_label);
+        let _ = target.notifications.send(SidecarNotification::Reconnected { ws_port: target.port });
+    }
+
+    /// Poll until the sidecar writes its ready file, bailing early if it exits or is terminated.
+    ///
+    /// The file may be empty or hold the port the sidecar bound, which is returned. A sidecar
+    /// that lost `ws_port` to another process in the meantime may have bound a fallback; the
+    /// tracked process is then moved to the reported port.
+    async fn wait_until_ready(
+        processes: &Mutex<HashMap<String, SidecarProcess>>,
+        window_label: &str,
+        ready_file: &Path,
+        ws_port: u16,
+        timeout: Duration,
+    ) -> Result<u16> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if let Ok(contents) = tokio::fs::read_to_string(ready_file).await {
+                let contents = contents.trim();
+                if contents.is_empty() {
+                    return Ok(ws_port);
+                }
+                let port = contents.parse::<u16>()
+                    .map_err(|_| anyhow!("unexpected ready file contents: {:?}", contents))?;
+                if port != ws_port {
+                    eprintln!("Warning: sidecar for window '{}' was assigned port {} but bound {}; using {}",
+                              window_label, ws_port, port, port);
+                    if let Some(process) = processes.lock().await.get_mut(window_label) {
+                        if process.ws_port == ws_port {
+                            process.ws_port = port;
+                        }
+                    }
+                }
+                return Ok(port);
+            }
+
+            match processes.lock().await.get_mut(window_label) {
+                Some(process) => {
+                    if let Ok(Some(status)) = process.child.try_wait() {
+                        anyhow::bail!("process exited during startup ({})", status);
+                    }
+                }
+                None => anyhow::bail!("sidecar was terminated during startup"),
+            }
+
+            if Instant::now() >= deadline {
+                anyhow::bail!("timed out after {:?} waiting for {}", timeout, ready_file.display());
+            }
+            tokio::time::sleep(READINESS_POLL_INTERVAL).await;
+        }
+    }
+
+    /// Spawn and terminate a sidecar for `vault_path` `iterations` times, timing how long each
+    /// takes to start its process, accept a WebSocket connection and answer a ping
+    pub async fn benchmark_startup(&self, vault_path: &str, iterations: usize) -> Result<StartupStats, SidecarError> {
+        self.benchmark_startup_with_options(vault_path, iterations, SpawnOptions::default()).await
+    }
+
+    /// Like [`Self::benchmark_startup`], spawning with `options`
+    pub async fn benchmark_startup_with_options(
+        &self,
+        vault_path: &str,
+        iterations: usize,
+        options: SpawnOptions,
+    ) -> Result<StartupStats, SidecarError> {
+        if iterations == 0 {
+            return Err(SidecarError::InvalidOptions("Benchmark needs at least one iteration".to_string()));
+        }
+
+        let mut samples = Vec::with_capacity(iterations);
+        for iteration in 1..=iterations {
+            let window_label = format!("benchmark-{}", uuid::Uuid::new_v4());
+            let started = Instant::now();
+            let ws_port = self
+                .spawn_sidecar_with_options(window_label.clone(), vault_path.to_string(), options.clone())
+                .await?;
+            let process_start = started.elapsed();
+            let host = self.host_for(&options)?;
+            let probed = self.probe_startup(&window_label, &host, ws_port, started).await;
+
+            // Clean up before reporting, so a failed iteration leaves nothing behind either
+            self.terminate_sidecar(&window_label).await?;
+            if connection::is_loopback_host(&host) && !self.wait_for_port_release(ws_port).await {
+                return Err(SidecarError::Other(anyhow!(
+                    "port {} still in use after benchmark iteration {}", ws_port, iteration)));
+            }
+
+            let (port_listen, first_ping) = probed?;
+            println!("Startup benchmark {}/{}: process {:?}, listening {:?}, first ping {:?}",
+                     iteration, iterations, process_start, port_listen, first_ping);
+            samples.push(benchmark::StartupSample { process_start, port_listen, first_ping });
+        }
+
+        Ok(StartupStats::from_samples(&samples))
+    }
+
+    /// Poll the sidecar's WebSocket until it accepts a connection, then time a ping on it.
+    /// Both durations are measured from `started`.
+    async fn probe_startup(
+        &self,
+        window_label: &str,
+        host: &str,
+        ws_port: u16,
+        started: Instant,
+    ) -> Result<(Duration, Duration), SidecarError> {
+        let deadline = started + READINESS_TIMEOUT;
+        let mut ws_stream = loop {
+            if let Ok(ws_stream) = connection::open(host, ws_port, &self.config.handshake_headers).await {
+                break ws_stream;
+            }
+            if let Some(process) = self.processes.lock().await.get_mut(window_label) {
+                if let Ok(Some(status)) = process.child.try_wait() {
+                    return Err(SidecarError::SpawnFailed(format!("process exited during startup ({})", status)));
+                }
+            }
+            if Instant::now() >= deadline {
+                return Err(SidecarError::ConnectTimeout { port: ws_port });
+            }
+            tokio::time::sleep(BENCHMARK_POLL_INTERVAL).await;
+        };
+        let port_listen = started.elapsed();
+
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        tokio::time::timeout(timeout, connection::ping(&mut ws_stream))
+            .await
+            .unwrap_or_else(|_| Err(SidecarError::ResponseTimeout { method: "ping".to_string(), timeout }))?;
+        let first_ping = started.elapsed();
+
+        let _ = ws_stream.close(None).await;
+        Ok((port_listen, first_ping))
+    }
+
+    /// Terminate a sidecar process.
+    ///
+    /// With `soft_shutdown` configured, first runs the flush RPC for as long as the sidecar keeps
+    /// reporting progress. Then escalates SIGTERM → `terminate_grace` → SIGKILL → `kill_timeout`; a sidecar still alive
+    /// after that yields a [`process::TerminationFailed`] error carrying its PID.
+    pub async fn terminate_sidecar(&self, window_label: &str) -> Result<(), SidecarError> {
+        self.terminate_sidecar_with_grace(window_label, self.config.terminate_grace).await
+    }
+
+    /// Like [`Self::terminate_sidecar`], waiting `grace` instead of `terminate_grace` between
+    /// SIGTERM and SIGKILL, e.g. longer when the user closes a vault and a clean save matters
+    pub async fn terminate_sidecar_with_grace(&self, window_label: &str, grace: Duration) -> Result<(), SidecarError> {
+        // Release the map lock before waiting on the process
+        let process = self.processes.lock().await.remove(window_label);
+        Self::save_reattach_state(&self.processes, self.config.reattach_file.as_deref()).await;
+        // Subscribers see the channel close; a restart keeps it
+        self.notifications.lock().unwrap_or_else(|e| e.into_inner()).remove(window_label);
+        self.snapshots.lock().unwrap_or_else(|e| e.into_inner()).remove(window_label);
+        self.capabilities.lock().unwrap_or_else(|e| e.into_inner()).remove(window_label);
+        self.job_listeners.lock().unwrap_or_else(|e| e.into_inner()).remove(window_label);
+        self.jobs.fail_window(window_label, "Sidecar terminated");
+
+        if let Some(process) = process {
+            let result = self.stop_process(window_label, process, grace).await;
+            let state = if result.is_ok() { SidecarState::Terminated } else { SidecarState::Failed };
+            self.states.terminated(window_label, state);
+            result?;
+        }
+
+        Ok(())
+    }
+
+    /// Live lifecycle state of a window's sidecar. The receiver holds the current state and its
+    /// first `changed()` resolves immediately, so awaiting a transition can't miss one that
+    /// happened before subscribing. Windows without a sidecar read as `Terminated`, and keep
+    /// their channel across restarts.
+    pub fn watch_state(&self, window_label: &str) -> watch::Receiver<SidecarState> {
+        self.states.subscribe(window_label)
+    }
+
+    /// Restart a sidecar with the same vault and options, keeping its port when possible
+    pub async fn restart_sidecar(&self, window_label: &str) -> Result<u16, SidecarError> {
+        if self.is_shutting_down() {
+            return Err(SidecarError::ShuttingDown);
+        }
+        // Lets replayable commands park instead of failing with `NotFound` while we relaunch
+        self.restarting.lock().unwrap_or_else(|e| e.into_inner()).insert(window_label.to_string());
+        let result = self.relaunch(window_label).await;
+        self.restarting.lock().unwrap_or_else(|e| e.into_inner()).remove(window_label);
+        result
+    }
+
+    /// Have the sidecar re-read its config and plugins in-process (the [`reload::RELOAD_METHOD`]
+    /// RPC), falling back to [`Self::restart_sidecar`] when it doesn't support that
+    pub async fn reload(&self, window_label: &str) -> Result<ReloadReport, SidecarError> {
+        match self.send_control(window_label, reload::RELOAD_METHOD, serde_json::json!({})).await {
+            Ok(result) => {
+                let report = ReloadReport::from_response(result)?;
+                println!("Reloaded window '{}': +{:?} -{:?}, {} error(s)", window_label,
+                         report.plugins_added, report.plugins_removed, report.errors.len());
+                Ok(report)
+            }
+            Err(e) if reload::is_unsupported(&e) => {
+                println!("Sidecar for window '{}' can't reload in-process, restarting it", window_label);
+                self.restart_sidecar(window_label).await?;
+                Ok(ReloadReport { restarted: true, ..Default::default() })
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Ask the sidecar to serialize its in-memory state ([`snapshot::SNAPSHOT_METHOD`]). The state
+    /// is kept as the window's latest snapshot, which a restart feeds to the new process when
+    /// [`SpawnOptions::restore_state`] is set. Snapshots over `max_snapshot_bytes` are refused.
+    pub async fn snapshot_state(&self, window_label: &str) -> Result<serde_json::Value, SidecarError> {
+        let response = self.send_internal(window_label, snapshot::SNAPSHOT_METHOD, serde_json::json!({})).await?;
+        let state = snapshot::from_response(response, self.config.max_snapshot_bytes)?;
+        self.snapshots.lock().unwrap_or_else(|e| e.into_inner()).insert(window_label.to_string(), state.clone());
+        Ok(state)
+    }
+
+    /// The commands the window's sidecar implements, fetched on first use and then cached
+    pub async fn capabilities(&self, window_label: &str) -> Result<HashSet<String>, SidecarError> {
+        let cached = self.capabilities.lock().unwrap_or_else(|e| e.into_inner()).get(window_label).cloned();
+        match cached {
+            Some(commands) => Ok(commands),
+            None => self.refresh_capabilities(window_label).await,
+        }
+    }
+
+    /// Re-fetch the sidecar's command list, replacing the cached one
+    pub async fn refresh_capabilities(&self, window_label: &str) -> Result<HashSet<String>, SidecarError> {
+        let response = self.send_internal(window_label, capabilities::CAPABILITIES_METHOD, serde_json::json!({})).await?;
+        let commands = capabilities::from_response(&response)?;
+        self.capabilities.lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(window_label.to_string(), commands.clone());
+        Ok(commands)
+    }
+
+    /// What the window's sidecar answered its `SpawnOptions::init_method` (typically its server
+    /// capabilities), for gating features on them; `None` without an init method or while the
+    /// sidecar is still starting
+    pub async fn init_result(&self, window_label: &str) -> Result<Option<serde_json::Value>, SidecarError> {
+        self.processes.lock().await
+            .get(window_label)
+            .map(|process| process.init_result.clone())
+            .ok_or_else(|| SidecarError::NotFound { window_label: window_label.to_string() })
+    }
+
+    /// The version and build the window's sidecar reports (see [`version`]), asked once per
+    /// launch. A sidecar without the RPC gives `VersionInfo::Unknown` rather than an error.
+    pub async fn sidecar_version(&self, window_label: &str) -> Result<VersionInfo, SidecarError> {
+        let generation = {
+            let processes = self.processes.lock().await;
+            let process = processes
+                .get(window_label)
+                .ok_or_else(|| SidecarError::NotFound { window_label: window_label.to_string() })?;
+            if let Some(version) = &process.version {
+                return Ok(version.clone());
+            }
+            process.generation
+        };
+        let version = match self.send_internal(window_label, version::VERSION_METHOD, serde_json::json!({})).await {
+            Ok(response) => version::from_response(&response),
+            Err(e) if e.is_method_not_found() => VersionInfo::Unknown,
+            Err(e) => return Err(e),
+        };
+        // Unless the sidecar was restarted meanwhile
+        if let Some(process) = self.processes.lock().await.get_mut(window_label) {
+            if process.generation == generation {
+                process.version = Some(version.clone());
+            }
+        }
+        Ok(version)
+    }
+
+    /// Set an environment variable for the window's sidecar. The change is kept in its spawn
+    /// options so every later launch has it, and sent to the running process with a `set_env`
+    /// RPC. A sidecar without that RPC is flagged `needs_restart` (also on `sidecar://needs-restart`)
+    /// so the UI can offer a restart. The value is never logged.
+    pub async fn set_env(&self, window_label: &str, key: &str, value: &str) -> Result<EnvUpdate, SidecarError> {
+        if key.is_empty() || key.contains(['=', '\0']) {
+            return Err(SidecarError::InvalidOptions(format!("Invalid environment variable name '{}'", key)));
+        }
+        if value.contains('\0') {
+            return Err(SidecarError::InvalidOptions(format!("Value of '{}' contains a NUL byte", key)));
+        }
+        self.processes.lock().await
+            .get_mut(window_label)
+            .ok_or_else(|| SidecarError::NotFound { window_label: window_label.to_string() })?
+            .options.env.insert(key.to_string(), value.to_string());
+
+        let params = serde_json::json!({ "key": key, "value": value });
+        self.apply_live(window_label, SET_ENV_METHOD, params, key).await
+    }
+
+    /// Change the window's sidecar log level (one of [`vault_config::LOG_LEVELS`], any case).
+    /// Like [`Self::set_env`], the level is kept for later launches (`--log-level`) and sent
+    /// with a `set_log_level` RPC. Log capture is resumed if it was paused, so the new output
+    /// shows up in [`Self::subscribe_logs`]; set the level back the same way.
+    pub async fn set_log_level(&self, window_label: &str, level: &str) -> Result<EnvUpdate, SidecarError> {
+        let level = level.to_ascii_uppercase();
+        if !vault_config::LOG_LEVELS.contains(&level.as_str()) {
+            return Err(SidecarError::InvalidOptions(format!(
+                "Invalid log level '{}' (expected one of {})", level, vault_config::LOG_LEVELS.join(", "))));
+        }
+        self.processes.lock().await
+            .get_mut(window_label)
+            .ok_or_else(|| SidecarError::NotFound { window_label: window_label.to_string() })?
+            .options.log_level = Some(level.clone());
+        self.set_log_capture(window_label, true).await?;
+
+        let params = serde_json::json!({ "level": level });
+        self.apply_live(window_label, SET_LOG_LEVEL_METHOD, params, &format!("log level {}", level)).await
+    }
+
+    /// Send a setting already recorded in the spawn options to the running sidecar, flagging it
+    /// `needs_restart` when the RPC isn't supported or fails
+    async fn apply_live(
+        &self,
+        window_label: &str,
+        method: &str,
+        params: serde_json::Value,
+        what: &str,
+    ) -> Result<EnvUpdate, SidecarError> {
+        match self.send_control(window_label, method, params).await {
+            Ok(_) => {
+                println!("Set {} for window '{}'", what, window_label);
+                Ok(EnvUpdate::Applied)
+            }
+            Err(e) if e.is_method_not_found() => {
+                println!("Sidecar for window '{}' can't set {} live, it needs a restart", window_label, what);
+                self.mark_needs_restart(window_label).await;
+                Ok(EnvUpdate::NeedsRestart)
+            }
+            Err(e) => {
+                self.mark_needs_restart(window_label).await;
+                Err(e)
+            }
+        }
+    }
+
+    async fn mark_needs_restart(&self, window_label: &str) {
+        if let Some(process) = self.processes.lock().await.get_mut(window_label) {
+            process.needs_restart = true;
+        }
+        if let Some(emitter) = &self.emitter {
+            emitter("sidecar://needs-restart", serde_json::json!({ "window_label": window_label }));
+        }
+    }
+
+    /// Vaults refused by `spawn_sidecar` after repeatedly failing to start (see [`quarantine`])
+    pub fn quarantined_vaults(&self) -> Vec<QuarantineEntry> {
+        self.quarantine.list()
+    }
+
+    /// Make the next spawn of `vault_path` check its dependencies even if it passed within
+    /// `dependency_cache_ttl`, e.g. after the user edited its plugins by hand; returns whether a
+    /// result was cached
+    pub fn invalidate_dependency_cache(&self, vault_path: &str) -> bool {
+        let invalidated = self.dependency_cache.invalidate(&canonical_vault_path(vault_path));
+        if invalidated {
+            println!("Dropped cached dependency check of vault {}", vault_path);
+        }
+        invalidated
+    }
+
+    /// Let a quarantined vault be spawned again; returns whether it was quarantined
+    pub fn clear_quarantine(&self, vault_path: &str) -> bool {
+        let cleared = self.quarantine.clear(vault_path);
+        if cleared {
+            println!("Cleared quarantine of vault {}", vault_path);
+        }
+        cleared
+    }
+
+    async fn relaunch(&self, window_label: &str) -> Result<u16, SidecarError> {
+        let restore_state = self.processes.lock().await
+            .get(window_label)
+            .is_some_and(|p| p.options.restore_state);
+        if restore_state {
+            if let Err(e) = self.snapshot_state(window_label).await {
+                eprintln!("Failed to snapshot window '{}' before restarting, restoring its last snapshot: {}",
+                          window_label, e);
+            }
+        }
+
+        let process = self.processes.lock().await
+            .remove(window_label)
+            .ok_or_else(|| SidecarError::NotFound { window_label: window_label.to_string() })?;
+
+        let vault_path = process.vault_path.clone();
+        let options = process.options.clone();
+        let old_port = process.ws_port;
+        let host = process.host.clone();
+        let mut restarts = process.restarts.clone();
+        let spawn_seq = process.spawn_seq;
+        restarts.push(Utc::now());
+
+        self.states.set(window_label, SidecarState::Restarting);
+        if let Err(e) = self.stop_process(window_label, process, self.config.terminate_grace).await {
+            self.states.set(window_label, SidecarState::Failed);
+            return Err(e);
+        }
+
+        // A remote port can't be checked from here; assume the restarted sidecar can rebind it
+        let ws_port = if !connection::is_loopback_host(&host) || self.wait_for_port_release(old_port).await {
+            old_port
+        } else {
+            let port = self.allocate_port(&host).await?;
+            println!("Port {} still busy after restart, moving window '{}' to port {}",
+                     old_port, window_label, port);
+            port
+        };
+
+        let port = self.launch(window_label.to_string(), vault_path, options, ws_port, restarts, spawn_seq).await?;
+        if port != old_port {
+            emit_port_changed(self.emitter.as_ref(), window_label, old_port, port);
+        }
+        Ok(port)
+    }
+
+    /// Escalate termination of an already-untracked process without blocking the runtime,
+    /// allowing it `grace` between SIGTERM and SIGKILL
+    async fn stop_process(&self, window_label: &str, mut process: SidecarProcess, grace: Duration) -> Result<(), SidecarError> {
+        println!("Terminating sidecar for window '{}'", window_label);
+        self.replay.mark_down(window_label);
+
+        // Disconnect cleanly first, so neither side logs the kill's connection reset
+        let connection = process.connection.lock().await.take();
+        if let Some(connection) = connection.filter(PersistentConnection::is_alive) {
+            if !connection.close().await {
+                println!("Sidecar '{}' did not answer the connection close, terminating anyway", window_label);
+            }
+        }
+
+        if let Some(config) = self.config.soft_shutdown.as_ref().filter(|_| process.stdio.is_none()) {
+            if matches!(process.child.try_wait(), Ok(None)) {
+                let outcome = soft_shutdown::run(
+                    window_label,
+                    &process.host,
+                    process.control_port(),
+                    &self.client_options(),
+                    &process.telemetry.connection,
+                    config,
+                    self.emitter.as_ref(),
+                ).await;
+                match outcome {
+                    SoftShutdownOutcome::Completed => {}
+                    SoftShutdownOutcome::Stalled => println!(
+                        "Sidecar '{}' made no shutdown progress for {:?}, escalating",
+                        window_label, config.stall_timeout
+                    ),
+                    SoftShutdownOutcome::Failed(e) => eprintln!("Soft shutdown of '{}' failed: {}", window_label, e),
+                }
+            }
+        }
+
+        let label = window_label.to_string();
+        let ready_file = process.ready_file.clone();
+        let group = process.signals_group();
+        let kill_timeout = self.config.kill_timeout;
+        tokio::task::spawn_blocking(move || {
+            let result = process.child.terminate(&label, group, grace, kill_timeout);
+            join_readers(std::mem::take(&mut process.readers), READER_JOIN_TIMEOUT);
+            result
+        })
+        .await
+        .context("Termination task panicked")??;
+        remove_ready_file(&ready_file);
+
+        println!("Sidecar terminated for window '{}'", window_label);
+        Ok(())
+    }
+
+    /// Deterministic teardown for app exit: signal background tasks, wait for them to finish
+    /// (aborting stragglers after `TASK_SHUTDOWN_TIMEOUT`), then terminate every sidecar, at once
+    /// or, with `sequential_shutdown`, one by one in `SpawnOptions::shutdown_order`, then spawn, order,
+    /// each given `shutdown_grace` after SIGTERM. Either way a sidecar is only stopped after those
+    /// listing it in `SpawnOptions::depends_on`. Spawns and restarts are refused from here on.
+    pub async fn shutdown(&self) {
+        println!("Shutting down all sidecars...");
+        self.shutdown_tx.send_replace(true);
+        self.replay.clear();
+        self.notifications.lock().unwrap_or_else(|e| e.into_inner()).clear();
+        self.snapshots.lock().unwrap_or_else(|e| e.into_inner()).clear();
+        self.capabilities.lock().unwrap_or_else(|e| e.into_inner()).clear();
+        self.reserved_ports.lock().unwrap_or_else(|e| e.into_inner()).clear();
+
+        let tasks = std::mem::take(&mut *self.tasks.lock().unwrap_or_else(|e| e.into_inner()));
+        for mut task in tasks {
+            if tokio::time::timeout(TASK_SHUTDOWN_TIMEOUT, &mut task).await.is_err() {
+                eprintln!("Background task did not stop within {:?}, aborting", TASK_SHUTDOWN_TIMEOUT);
+                task.abort();
+            }
+        }
+
+        let mut processes: HashMap<_, _> = self.processes.lock().await.drain().collect();
+        Self::save_reattach_state(&self.processes, self.config.reattach_file.as_deref()).await;
+        let ranked: Vec<_> = processes.iter()
+            .map(|(label, process)| (label.clone(), process.options.depends_on.clone(), process.shutdown_rank()))
+            .collect();
+        for wave in shutdown_order::waves(&ranked, !self.config.sequential_shutdown) {
+            let stops = wave.into_iter()
+                .filter_map(|label| processes.remove(&label).map(|process| (label, process)))
+                .map(|(label, process)| async move { self.stop_for_shutdown(&label, process).await });
+            futures::future::join_all(stops).await;
+        }
+    }
+
+    async fn stop_for_shutdown(&self, window_label: &str, process: SidecarProcess) {
+        match self.stop_process(window_label, process, self.config.shutdown_grace).await {
+            Ok(()) => self.states.terminated(window_label, SidecarState::Terminated),
+            Err(e) => {
+                eprintln!("{}", e);
+                self.states.terminated(window_label, SidecarState::Failed);
+            }
+        }
+    }
+
+    fn client_options(&self) -> ClientOptions {
+        ClientOptions {
+            headers: self.config.handshake_headers.clone(),
+            duplicate_responses: self.config.duplicate_responses,
+            null_id_errors: self.config.null_id_errors,
+            format: self.config.request_format.clone(),
+            ids: self.request_ids.clone(),
+        }
+    }
+
+    fn is_shutting_down(&self) -> bool {
+        *self.shutdown_tx.borrow()
+    }
+
+    fn track_task(&self, task: JoinHandle<()>) {
+        track(&self.tasks, task);
+    }
+
+    fn connection_context(&self) -> ConnectionContext {
+        ConnectionContext {
+            keepalive: self.config.keepalive,
+            client: self.client_options(),
+            shutdown: self.shutdown_tx.subscribe(),
+            tasks: self.tasks.clone(),
+        }
+    }
+
+    /// Subscribe to the notifications a window's sidecar pushes (JSON-RPC messages without an
+    /// `id`). The subscription survives restarts, which are marked by
+    /// [`SidecarNotification::Reconnected`]; it closes when the sidecar is terminated.
+    ///
+    /// Needs `SidecarManagerConfig::persistent_connections`, since notifications arrive on the
+    /// manager's connection.
+    pub async fn subscribe_notifications(
+        &self,
+        window_label: &str,
+    ) -> Result<broadcast::Receiver<SidecarNotification>, SidecarError> {
+        let target = self.processes.lock().await
+            .get(window_label)
+            .map(SidecarProcess::connection_target)
+            .ok_or_else(|| SidecarError::NotFound { window_label: window_label.to_string() })?;
+        let receiver = target.notifications.subscribe();
+        // A stdio sidecar's messages all arrive on its one channel
+        if target.stdio.is_some() {
+            return Ok(receiver);
+        }
+        if !self.config.persistent_connections {
+            return Err(SidecarError::InvalidOptions(
+                "Notification subscriptions need persistent connections".to_string()));
+        }
+        Self::ensure_connection(&target, &self.connection_context()).await?;
+        Ok(receiver)
+    }
+
+    /// Send a subscription RPC (e.g. `events.subscribe`) now and again after every restart,
+    /// so the sidecar resumes pushing to [`Self::subscribe_notifications`] receivers
+    pub async fn add_subscription(
+        &self,
+        window_label: &str,
+        method: &str,
+        params: serde_json::Value,
+    ) -> Result<serde_json::Value, SidecarError> {
+        let response = self.send_command(window_label, method, params.clone()).await?;
+        let mut hubs = self.notifications.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(hub) = hubs.get_mut(window_label) {
+            let subscription = (method.to_string(), params);
+            if !hub.subscriptions.contains(&subscription) {
+                hub.subscriptions.push(subscription);
+            }
+        }
+        Ok(response)
+    }
+
+    /// Start a long-running job with a command the sidecar answers with `{"job_id": ...}`
+    /// right away, then wait for it with [`Self::await_job`] (see [`jobs`])
+    pub async fn submit_job(
+        &self,
+        window_label: &str,
+        method: &str,
+        params: serde_json::Value,
+    ) -> Result<JobId, SidecarError> {
+        // Listen before submitting so a quick completion isn't missed
+        let notified = self.listen_for_jobs(window_label).await;
+        let response = self.send_command(window_label, method, params).await?;
+        let remote_id = jobs::remote_id_from_response(&response)?;
+        Ok(self.jobs.insert(window_label, remote_id, notified))
+    }
+
+    /// Wait up to `timeout` for a submitted job, returning its result. A failed job yields
+    /// [`SidecarError::JobFailed`]; running out of time ([`SidecarError::JobTimeout`]) leaves it
+    /// tracked, so it can be awaited again.
+    pub async fn await_job(
+        &self,
+        window_label: &str,
+        job_id: &JobId,
+        timeout: Duration,
+    ) -> Result<serde_json::Value, SidecarError> {
+        let not_found = || SidecarError::JobNotFound { job_id: job_id.to_string() };
+        let mut job = self.jobs.watch(window_label, job_id).ok_or_else(not_found)?;
+        let settled = async {
+            while *job.status.borrow_and_update() == JobStatus::Running {
+                if job.notified {
+                    if job.status.changed().await.is_err() {
+                        break;
+                    }
+                    continue;
+                }
+                let params = serde_json::json!({ "job_id": job.remote_id });
+                let response = self.send_internal(window_label, jobs::JOB_STATUS_METHOD, params).await?;
+                match jobs::status_from_response(&response)? {
+                    JobStatus::Running => tokio::time::sleep(jobs::JOB_POLL_INTERVAL).await,
+                    status => self.jobs.finish(job_id, status),
+                }
+            }
+            Ok::<_, SidecarError>(())
+        };
+        tokio::time::timeout(timeout, settled).await
+            .map_err(|_| SidecarError::JobTimeout { job_id: job_id.to_string(), timeout })??;
+
+        match self.jobs.status(job_id).ok_or_else(not_found)? {
+            JobStatus::Completed { result } => Ok(result),
+            JobStatus::Failed { error } => Err(SidecarError::JobFailed { job_id: job_id.to_string(), message: error }),
+            JobStatus::Running => Err(not_found()),
+        }
+    }
+
+    /// Where a submitted job stands; None once it is unknown or long forgotten
+    pub fn job_status(&self, job_id: &JobId) -> Option<JobStatus> {
+        self.jobs.status(job_id)
+    }
+
+    /// Start settling the window's jobs from [`jobs::JOB_COMPLETED_METHOD`] notifications, if it
+    /// has a notification stream. False means its jobs have to be polled.
+    async fn listen_for_jobs(&self, window_label: &str) -> bool {
+        if self.job_listeners.lock().unwrap_or_else(|e| e.into_inner()).contains(window_label) {
+            return true;
+        }
+        let Ok(mut events) = self.subscribe_notifications(window_label).await else {
+            return false;
+        };
+        if !self.job_listeners.lock().unwrap_or_else(|e| e.into_inner()).insert(window_label.to_string()) {
+            return true;
+        }
+
+        let label = window_label.to_string();
+        let tracker = self.jobs.clone();
+        let mut shutdown = self.shutdown_tx.subscribe();
+        self.track_task(tokio::spawn(async move {
+            loop {
+                let notification = tokio::select! {
+                    notification = events.recv() => notification,
+                    _ = shutdown.wait_for(|stopping| *stopping) => break,
+                };
+                match notification {
+                    Ok(SidecarNotification::Event { method, params }) if method == jobs::JOB_COMPLETED_METHOD => {
+                        match jobs::completion_from_params(&params) {
+                            Some((remote_id, status)) => tracker.finish_remote(&label, &remote_id, status),
+                            None => eprintln!("Ignoring job completion without a job id from sidecar '{}'", label),
+                        }
+                    }
+                    Ok(SidecarNotification::Reconnected { .. }) => {
+                        tracker.fail_window(&label, "Sidecar restarted while the job was running");
+                    }
+                    Ok(_) => {}
+                    Err(broadcast::error::RecvError::Lagged(missed)) => {
+                        eprintln!("Missed {} notifications from sidecar '{}'; job completions may be lost", missed, label);
+                    }
+                    // Terminated; `terminate_sidecar` settled its jobs
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        }));
+        true
+    }
+
+    /// Get WebSocket port for a sidecar
+    pub async fn get_ws_port(&self, window_label: &str) -> Option<u16> {
+        self.processes.lock().await
+            .get(window_label)
+            .map(|p| p.ws_port)
+    }
+
+    /// Get the vault a sidecar serves
+    pub async fn get_vault_path(&self, window_label: &str) -> Option<String> {
+        self.processes.lock().await
+            .get(window_label)
+            .map(|p| p.vault_path.clone())
+    }
+
+    /// Allocate next available port by actually checking port availability. Ports on a remote
+    /// `host` can't be checked from here and are handed out in sequence.
+    async fn allocate_port(&self, host: &str) -> Result<u16, SidecarError> {
+        let mut cursor = self.next_port.lock().await;
+        let port = self.free_ports(*cursor, host).next().ok_or(SidecarError::PortExhausted)?;
+        *cursor = port_after(port);
+        Ok(port)
+    }
+
+    /// The ports [`Self::allocate_port`] would hand out from `start`, in order, without taking
+    /// any: unreserved and, on a loopback `host`, free now. Gives up after one full lap.
+    fn free_ports<'a>(&'a self, start: u16, host: &'a str) -> impl Iterator<Item = u16> + 'a {
+        std::iter::successors(Some(start), |&port| Some(port_after(port)))
+            .take(PORT_RANGE.len())
+            .filter(move |&port| {
+                !self.is_reserved(port) && (!connection::is_loopback_host(host) || self.is_port_available(port))
+            })
+    }
+
+    /// Allocate `count` ports and hold each one with a bound listener until it is passed to a
+    /// spawn as `SpawnOptions::port` or released with [`Self::release_reservation`]. Meant for
+    /// restoring a multi-vault session without racing other local apps mid-restore.
+    pub async fn reserve_ports(&self, count: usize) -> Result<Vec<u16>, SidecarError> {
+        let mut ports = Vec::with_capacity(count);
+        for _ in PORT_RANGE {
+            if ports.len() == count {
+                break;
+            }
+            let Ok(port) = self.allocate_port("127.0.0.1").await else { break };
+            // A port taken since it was checked is skipped
+            if let Ok(listener) = std::net::TcpListener::bind((Ipv4Addr::LOCALHOST, port)) {
+                self.reserved_ports.lock().unwrap_or_else(|e| e.into_inner()).insert(port, listener);
+                ports.push(port);
+            }
+        }
+        if ports.len() < count {
+            for port in ports {
+                self.release_reservation(port);
+            }
+            return Err(SidecarError::PortExhausted);
+        }
+        println!("Reserved sidecar ports {:?}", ports);
+        Ok(ports)
+    }
+
+    /// Give a port from [`Self::reserve_ports`] back; returns whether it was reserved
+    pub fn release_reservation(&self, port: u16) -> bool {
+        self.reserved_ports.lock().unwrap_or_else(|e| e.into_inner()).remove(&port).is_some()
+    }
+
+    fn is_reserved(&self, port: u16) -> bool {
+        self.reserved_ports.lock().unwrap_or_else(|e| e.into_inner()).contains_key(&port)
+    }
+
+    /// Use `port` as requested in `SpawnOptions::port`, freeing its reservation for the sidecar
+    fn claim_port(&self, host: &str, port: u16) -> Result<u16, SidecarError> {
+        if self.release_reservation(port) || !connection::is_loopback_host(host) || self.is_port_available(port) {
+            return Ok(port);
+        }
+        Err(SidecarError::InvalidOptions(format!("Port {} is not available", port)))
+    }
+    
+    /// Adopt the sidecars a manager in the previous process recorded in
+    /// `SidecarManagerConfig::reattach_file`, e.g. after `cargo tauri dev` rebuilt and restarted
+    /// the app, instead of respawning them (see [`reattach`]). A sidecar is adopted only if its
+    /// PID still carries the sidecar signature for its port and it answers a ping; it is then
+    /// `Ready` and announced with `sidecar://reattached`. The others are dropped from the file and
+    /// left to [`Self::kill_orphans`]. Returns each recorded window's port or why it wasn't
+    /// adopted; nothing without a reattach file.
+    pub async fn reattach_all(&self) -> Result<Vec<(String, Result<u16, SidecarError>)>, SidecarError> {
+        let Some(file) = self.config.reattach_file.clone() else {
+            return Ok(Vec::new());
+        };
+        if self.is_shutting_down() {
+            return Err(SidecarError::ShuttingDown);
+        }
+        let mut results = Vec::new();
+        for record in reattach::load(&file) {
+            let label = record.window_label.clone();
+            let result = self.reattach(record).await;
+            match &result {
+                Ok(port) => println!("Reattached sidecar for window '{}' on port {}", label, port),
+                Err(e) => eprintln!("Not reattaching sidecar for window '{}': {}", label, e),
+            }
+            results.push((label, result));
+        }
+        Self::save_reattach_state(&self.processes, Some(&file)).await;
+        Ok(results)
+    }
+
+    /// Adopt one recorded sidecar after checking it is alive and answering. Needs `/proc`.
+    async fn reattach(&self, record: ReattachRecord) -> Result<u16, SidecarError> {
+        #[cfg(target_os = "linux")]
+        {
+            if !reattach::still_running(&record, &self.config.sidecar_args) {
+                return Err(anyhow!("PID {} is no longer a sidecar on port {}", record.pid, record.ws_port).into());
+            }
+            // On the control socket if it has one, so the ping can't become the RPC socket's
+            // event sink or take the frontend's replies
+            let ping_port = record.control_port.unwrap_or(record.ws_port);
+            let ping = async {
+                let mut ws_stream = connection::open(&record.host, ping_port, &self.config.handshake_headers).await?;
+                connection::ping(&mut ws_stream).await?;
+                let _ = ws_stream.close(None).await;
+                Ok::<_, SidecarError>(())
+            };
+            tokio::time::timeout(health::HEALTH_PING_TIMEOUT, ping)
+                .await
+                .map_err(|_| SidecarError::ConnectTimeout { port: ping_port })??;
+
+            let label = record.window_label;
+            let state = self.states.sender(&label);
+            let telemetry = Arc::new(SidecarTelemetry::new(&label, self.log_tx.clone(), self.output_matchers.clone(),
+                                                           state.clone(), self.config.max_in_flight,
+                                                           self.config.rate_limit, self.open_audit_log(&label)));
+            let counters = telemetry.connection.clone();
+            let generation = self.next_generation.fetch_add(1, Ordering::SeqCst);
+            let notifications = self.notifications.lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .entry(label.clone())
+                .or_default()
+                .tx
+                .clone();
+            let uptime = (Utc::now() - record.started_at).to_std().unwrap_or_default();
+            let (host, ws_port) = (record.host, record.ws_port);
+            let process = SidecarProcess {
+                child: SidecarChild::Adopted(record.pid),
+                vault_path: record.vault_path,
+                host: host.clone(),
+                ws_port,
+                control_port: record.control_port,
+                python: record.python,
+                started_at: record.started_at,
+                started_instant: Instant::now().checked_sub(uptime).unwrap_or_else(Instant::now),
+                telemetry,
+                options: SpawnOptions {
+                    launcher: record.launcher,
+                    labels: record.labels,
+                    shutdown_order: record.shutdown_order,
+                    depends_on: record.depends_on,
+                    role: record.role,
+                    ..Default::default()
+                },
+                init_result: None,
+                restarts: Vec::new(),
+                generation,
+                spawn_seq: self.next_spawn_seq.fetch_add(1, Ordering::SeqCst),
+                readers: Vec::new(),
+                ready_file: record.ready_file,
+                connection: Arc::new(Mutex::new(None)),
+                notifications,
+                needs_restart: false,
+                version: None,
+                _plugin_watcher: None,
+                stdio: None,
+            };
+            {
+                let mut processes = self.processes.lock().await;
+                if let Some(running) = processes.get(&label) {
+                    return Err(SidecarError::AlreadyRunning { window_label: label, ws_port: running.ws_port });
+                }
+                processes.insert(label.clone(), process);
+            }
+            state.send_replace(SidecarState::Ready);
+            Self::replay_parked(&self.replay, &label, generation, &counters, &host, ws_port, None,
+                                &self.client_options()).await;
+            if let Some(emitter) = &self.emitter {
+                emitter("sidecar://reattached", serde_json::json!({
+                    "window_label": label,
+                    "ws_port": ws_port,
+                    "pid": record.pid,
+                }));
+            }
+            Ok(ws_port)
+        }
+
+        #[cfg(not(target_os = "linux"))]
+        {
+            let _ = record;
+            Err(SidecarError::Unsupported("reattaching sidecars needs /proc".to_string()))
+        }
+    }
+
+    /// Record the running WebSocket sidecars in `file` (`SidecarManagerConfig::reattach_file`),
+    /// if there is one
+    async fn save_reattach_state(processes: &Mutex<HashMap<String, SidecarProcess>>, file: Option<&Path>) {
+        let Some(file) = file else { return };
+        let mut records: Vec<_> = processes.lock().await
+            .iter()
+            .filter(|(_, process)| process.stdio.is_none())
+            .map(|(label, process)| process.reattach_record(label))
+            .collect();
+        records.sort_by(|a, b| a.window_label.cmp(&b.window_label));
+        reattach::save(file, &records);
+    }
+
+    /// Running processes launched from our `SidecarManagerConfig::sidecar_args` (by default
+    /// `-u -m sidecar ... --ws-port <port>`) that this manager doesn't track, e.g. left behind by a crashed app. Descendants of a tracked sidecar
+    /// (its interpreter behind a launcher) count as tracked. Scans the process table with
+    /// `sysinfo`, so it works on every platform.
+    pub async fn find_orphaned_sidecars(&self) -> Vec<OrphanInfo> {
+        let tracked: HashSet<u32> = self.processes.lock().await
+            .values()
+            .map(|p| p.child.id())
+            .collect();
+        let own_pid = std::process::id();
+        let template = self.config.sidecar_args.clone();
+        tokio::task::spawn_blocking(move || process::find_sidecar_processes(&tracked, &template))
+            .await
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|orphan| orphan.pid != own_pid)
+            .collect()
+    }
+
+    /// Kill every process [`Self::find_orphaned_sidecars`] reports, with the usual SIGTERM →
+    /// SIGKILL escalation. Returns the ones that exited; survivors are logged.
+    pub async fn kill_orphans(&self) -> Result<Vec<OrphanInfo>, SidecarError> {
+        let mut killed = Vec::new();
+        for orphan in self.find_orphaned_sidecars().await {
+            if self.kill_orphan(&orphan).await? {
+                killed.push(orphan);
+            } else {
+                eprintln!("Orphaned sidecar PID {} (port {}) did not exit", orphan.pid, orphan.ws_port);
+            }
+        }
+        Ok(killed)
+    }
+
+    /// Kill one orphan, re-checking its signature first in case the PID was reused
+    async fn kill_orphan(&self, orphan: &OrphanInfo) -> Result<bool, SidecarError> {
+        let still_ours = process::read_cmdline(orphan.pid)
+            .is_some_and(|args| process::is_sidecar_cmdline(&args, &self.config.sidecar_args, orphan.ws_port));
+        if !still_ours {
+            return Ok(true);
+        }
+
+        println!("Killing orphaned sidecar PID {} on port {}", orphan.pid, orphan.ws_port);
+        let (pid, grace, kill_timeout) = (orphan.pid, self.config.terminate_grace, self.config.kill_timeout);
+        Ok(tokio::task::spawn_blocking(move || process::kill_pid(pid, grace, kill_timeout))
+            .await
+            .context("Orphan kill task panicked")?)
+    }
+
+    /// Free a port held by a leaked sidecar from a previous run. Returns whether the port was
+    /// reclaimed.
+    ///
+    /// Deliberately conservative: the owner is only killed when its command line was launched
+    /// from our `sidecar_args` with `port` as its `{port}` and it is not a sidecar this manager
+    /// is tracking. Owner lookup uses `/proc`, so on other platforms this always returns `false`.
+    pub async fn reclaim_port(&self, port: u16) -> Result<bool, SidecarError> {
+        if self.is_port_available(port) {
+            return Ok(true);
+        }
+
+        #[cfg(target_os = "linux")]
+        {
+            let Some(pid) = process::find_port_owner(port) else {
+                println!("Port {} is busy but its owner is not visible to us", port);
+                return Ok(false);
+            };
+
+            let tracked = self.processes.lock().await
+                .values()
+                .any(|p| p.child.id() == pid);
+            if tracked {
+                println!("Port {} belongs to a live tracked sidecar (PID {}), not reclaiming", port, pid);
+                return Ok(false);
+            }
+
+            let is_ours = process::read_cmdline(pid)
+                .is_some_and(|args| process::is_sidecar_cmdline(&args, &self.config.sidecar_args, port));
+            if !is_ours {
+                println!("Port {} is held by PID {} which is not a Tailor sidecar, not reclaiming", port, pid);
+                return Ok(false);
+            }
+
+            println!("Killing orphaned sidecar PID {} holding port {}", pid, port);
+            let grace = self.config.terminate_grace;
+            let kill_timeout = self.config.kill_timeout;
+            let exited = tokio::task::spawn_blocking(move || process::kill_pid(pid, grace, kill_timeout))
+                .await
+                .context("Reclaim task panicked")?;
+            if !exited {
+                return Err(anyhow!("Orphaned sidecar PID {} on port {} did not exit", pid, port).into());
+            }
+
+            // Freed ports become eligible again for allocate_port
+            Ok(self.wait_for_port_release(port).await)
+        }
+
+        #[cfg(not(target_os = "linux"))]
+        {
+            println!("Cannot identify the owner of port {} on this platform, not reclaiming", port);
+            Ok(false)
+        }
+    }
+
+    /// The host a sidecar spawned with `options` is reached on; remote ones only over TLS (see
+    /// [`connection::ws_url`])
+    fn host_for(&self, options: &SpawnOptions) -> Result<String, SidecarError> {
+        let host = options.host.as_deref().unwrap_or(&self.config.connect_host).trim();
+        if host.is_empty() {
+            return Err(SidecarError::InvalidOptions("Sidecar host must not be empty".to_string()));
+        }
+        Ok(host.to_string())
+    }
+
+    /// Re-check a just-freed port with backoff; the old process may take a moment to release it
+    async fn wait_for_port_release(&self, port: u16) -> bool {
+        for delay in PORT_RELEASE_BACKOFF {
+            if self.is_port_available(port) {
+                return true;
+            }
+            tokio::time::sleep(delay).await;
+        }
+        self.is_port_available(port)
+    }
+
+    /// Check if a port is available on every address in `SidecarManagerConfig::port_check_addrs`
+    fn is_port_available(&self, port: u16) -> bool {
+        port_available_on(&self.config.port_check_addrs, port)
+    }
+
+    /// Pin the interpreter used for future spawns, taking precedence over `TAILOR_PYTHON`,
+    /// a bundled interpreter and `PATH`. The path is validated with `--version` up front.
+    pub fn set_python_executable(&self, path: &str) -> Result<(), SidecarError> {
+        let interpreter = python::validate(path, python::PythonSource::Explicit)
+            .map_err(|e| SidecarError::PythonNotFound(e.to_string()))?;
+        println!("Python override set to '{}' ({})", interpreter.path, interpreter.version);
+        *self.python_override.lock().unwrap_or_else(|e| e.into_inner()) = Some(interpreter);
+        Ok(())
+    }
+
+    /// Switch to the interpreter at `path` like [`Self::set_python_executable`], then restart
+    /// every running sidecar under it one at a time, in spawn order, waiting for each to be ready
+    /// again before the next so the others keep serving. Restarts keep each sidecar's options,
+    /// vault config and (with `restore_state`) state. Emits `python://migration` before and after
+    /// each restart; a failed one doesn't stop the rest. Returns each window's port or error.
+    pub async fn migrate_all(&self, path: &str) -> Result<Vec<(String, Result<u16, SidecarError>)>, SidecarError> {
+        self.set_python_executable(path)?;
+        let mut windows: Vec<_> = self.processes.lock().await
+            .iter()
+            .map(|(label, process)| (process.spawn_seq, label.clone()))
+            .collect();
+        windows.sort();
+
+        let total = windows.len();
+        let emit = |label: &str, index: usize, status: &str, error: Option<String>| {
+            if let Some(emitter) = &self.emitter {
+                emitter("python://migration", serde_json::json!({
+                    "window_label": label,
+                    "python": path,
+                    "index": index,
+                    "total": total,
+                    "status": status,
+                    "error": error,
+                }));
+            }
+        };
+        let mut results = Vec::with_capacity(total);
+        for (index, (_, label)) in windows.into_iter().enumerate() {
+            emit(&label, index, "restarting", None);
+            let result = match self.restart_sidecar(&label).await {
+                Ok(port) => self.settled(&label).await.map(|()| port),
+                Err(e) => Err(e),
+            };
+            match &result {
+                Ok(_) => emit(&label, index, "migrated", None),
+                Err(e) => {
+                    eprintln!("Failed to move window '{}' to Python '{}': {}", label, path, e);
+                    emit(&label, index, "failed", Some(e.to_string()));
+                }
+            }
+            results.push((label, result));
+        }
+        Ok(results)
+    }
+
+    /// Wait for a (re)started sidecar to finish starting; an error if it didn't become ready
+    async fn settled(&self, window_label: &str) -> Result<(), SidecarError> {
+        let mut state = self.watch_state(window_label);
+        let settled = state.wait_for(|state| !matches!(state, SidecarState::Starting | SidecarState::Restarting))
+            .await
+            .map(|state| *state)
+            .unwrap_or(SidecarState::Terminated);
+        match settled {
+            SidecarState::Ready | SidecarState::Busy => Ok(()),
+            state => Err(SidecarError::SpawnFailed(format!("sidecar is {:?} after restarting", state))),
+        }
+    }
+
+    /// Resolve the Python interpreter (see [`python`] for the precedence order) on the blocking
+    /// pool, since it may run pixi
+    async fn get_python_executable(&self) -> Result<PythonInterpreter, SidecarError> {
+        let explicit = self.python_override.lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+        let pixi = self.pixi.clone();
+        let bundled = self.config.bundled_python.clone();
+        tokio::task::spawn_blocking(move || {
+            let project_root = project_root().ok();
+            python::resolve(
+                explicit,
+                std::env::var(python::PYTHON_ENV_VAR).ok(),
+                project_root.as_deref().map(|root| (&*pixi, root)),
+                bundled.as_deref(),
+            )
+        })
+        .await
+        .context("Python resolution task panicked")?
+        .map_err(|e| SidecarError::PythonNotFound(e.to_string()))
+    }
+
+    /// The usual interpreter if it satisfies `version`, else the newest one under the search
+    /// locations that does
+    async fn get_python_matching(&self, version: &str) -> Result<PythonInterpreter, SidecarError> {
+        let constraint = VersionConstraint::parse(version).map_err(SidecarError::InvalidOptions)?;
+        let mut locations = self.config.python_search_paths.clone();
+        locations.extend(python::default_search_paths(project_root().ok().as_deref()));
+        let preferred = self.get_python_executable().await.ok();
+        tokio::task::spawn_blocking(move || python::resolve_matching(preferred, &constraint, &locations))
+            .await
+            .context("Python resolution task panicked")?
+            .map_err(|e| SidecarError::PythonNotFound(e.to_string()))
+    }
+
+    /// Gather everything known about a sidecar into a single JSON bundle for support tickets
+    pub async fn export_diagnostics(&self, window_label: &str) -> Result<serde_json::Value, SidecarError> {
+        // Cached on the process if it answers; a hung sidecar mustn't hold up the bundle
+        let _ = tokio::time::timeout(health::HEALTH_PING_TIMEOUT, self.sidecar_version(window_label)).await;
+        let mut processes = self.processes.lock().await;
+        let process = processes
+            .get_mut(window_label)
+            .ok_or_else(|| SidecarError::NotFound { window_label: window_label.to_string() })?;
+
+        let exit_status = match process.child.try_wait() {
+            Ok(Some(status)) => serde_json::json!({
+                "code": status.code(),
+                "success": status.success(),
+            }),
+            Ok(None) => serde_json::Value::Null,
+            Err(e) => serde_json::json!({ "error": e.to_string() }),
+        };
+
+        let telemetry = &process.telemetry;
+        let recent_logs = telemetry.logs.lock()
+            .map(|logs| logs.snapshot())
+            .unwrap_or_default();
+        let recent_errors = telemetry.errors.lock()
+            .map(|errors| errors.snapshot())
+            .unwrap_or_default();
+        let method_latencies = telemetry.latencies.lock().unwrap_or_else(|e| e.into_inner()).stats();
+
+        Ok(serde_json::json!({
+            "window_label": window_label,
+            "vault_path": process.vault_path,
+            "pid": process.child.id(),
+            "ws_port": process.ws_port,
+            "control_port": process.control_port,
+            "needs_restart": process.needs_restart,
+            "python": process.python,
+            "sidecar_version": process.version,
+            "launcher": process.options.launcher,
+            "transport": process.options.transport,
+            "labels": process.options.labels,
+            "started_at": process.started_at,
+            "uptime_secs": process.started_instant.elapsed().as_secs_f64(),
+            "restart_history": process.restarts,
+            "exit_status": exit_status,
+            "in_flight": telemetry.in_flight.load(Ordering::SeqCst),
+            "connection_stats": telemetry.connection.snapshot(),
+            "method_latencies": method_latencies,
+            "rate_limit": telemetry.rate_limiter.as_ref().map(|limiter| limiter.stats()),
+            "log_capture": telemetry.log_capture.load(Ordering::Relaxed),
+            "dropped_log_lines": telemetry.dropped_lines.load(Ordering::Relaxed),
+            "recent_logs": recent_logs,
+            "recent_errors": recent_errors,
+            "generated_at": Utc::now(),
+        }))
+    }
+
+    /// Health of every sidecar, sorted by window label. Each one is pinged concurrently and
+    /// given [`health::HEALTH_PING_TIMEOUT`] to answer, so a hung sidecar can't hold up the report.
+    pub async fn health_report(&self) -> Vec<SidecarHealth> {
+        let snapshots: Vec<_> = self.processes.lock().await
+            .iter_mut()
+            .map(|(label, process)| {
+                let pid = process.child.id();
+                let telemetry = &process.telemetry;
+                let health = SidecarHealth {
+                    window_label: label.clone(),
+                    vault_path: process.vault_path.clone(),
+                    pid,
+                    ws_port: process.ws_port,
+                    control_port: process.control_port,
+                    python: process.python.clone(),
+                    alive: matches!(process.child.try_wait(), Ok(None)),
+                    responsive: false,
+                    ping_ms: None,
+                    in_flight: telemetry.in_flight.load(Ordering::SeqCst),
+                    memory_bytes: process::resident_memory(pid),
+                    uptime_secs: process.started_instant.elapsed().as_secs_f64(),
+                    restart_count: process.restarts.len(),
+                    needs_restart: process.needs_restart,
+                    role: process.options.role.clone(),
+                    last_error: telemetry.errors.lock()
+                        .ok()
+                        .and_then(|errors| errors.snapshot().pop()),
+                };
+                (health, process.host.clone(), process.control_port(), process.stdio.clone())
+            })
+            .collect();
+
+        let headers = &self.config.handshake_headers;
+        let checks = snapshots.into_iter().map(|(mut health, host, port, stdio)| async move {
+            if let Some(stdio) = stdio {
+                // No ping frames on a pipe: responsive while the sidecar keeps its stdout open
+                health.responsive = health.alive && stdio.is_open();
+            } else if health.alive {
+                // Without a control socket this is a connection of its own to the main port; the
+                // sidecar answers each socket on itself, so the window's connection is unaffected
+                let started = Instant::now();
+                let ping = async {
+                    let mut ws_stream = connection::open(&host, port, headers).await?;
+                    connection::ping(&mut ws_stream).await?;
+                    let _ = ws_stream.close(None).await;
+                    Ok::<_, SidecarError>(())
+                };
+                if let Ok(Ok(())) = tokio::time::timeout(health::HEALTH_PING_TIMEOUT, ping).await {
+                    health.responsive = true;
+                    health.ping_ms = Some(started.elapsed().as_secs_f64() * 1000.0);
+                }
+            }
+            health
+        });
+        let mut report = futures::future::join_all(checks).await;
+        report.sort_by(|a, b| a.window_label.cmp(&b.window_label));
+        self.check_degraded(&report);
+        report
+    }
+
+    /// Healthy, unresponsive and failed counts over a fresh [`Self::health_report`]
+    pub async fn health_summary(&self) -> HealthSummary {
+        HealthSummary::from_report(&self.health_report().await, self.config.degraded.as_ref())
+    }
+
+    /// Emit `sidecars://degraded` when `report` crosses the threshold and `sidecars://recovered`
+    /// once it drops back below
+    fn check_degraded(&self, report: &[SidecarHealth]) {
+        let Some(config) = &self.config.degraded else { return };
+        let summary = HealthSummary::from_report(report, Some(config));
+        if self.degraded.swap(summary.degraded, Ordering::SeqCst) == summary.degraded {
+            return;
+        }
+        let event = match summary.degraded {
+            true => {
+                eprintln!("{} of {} sidecars are unresponsive or dead, the Python environment may be broken",
+                          summary.unresponsive + summary.failed, summary.total);
+                "sidecars://degraded"
+            }
+            false => {
+                println!("Sidecars recovered: {} of {} healthy", summary.healthy, summary.total);
+                "sidecars://recovered"
+            }
+        };
+        if let Some(emitter) = &self.emitter {
+            emitter(event, serde_json::json!(summary));
+        }
+    }
+
+    /// Check health every `DegradedConfig::interval` until `shutdown`, so the degraded alarm
+    /// fires without anyone asking for a report. Returns at once when the alarm is disabled.
+    pub async fn run_health_monitor(&self) {
+        let Some(interval) = self.config.degraded.as_ref().map(|config| config.interval) else {
+            return;
+        };
+        let mut shutdown = self.shutdown_tx.subscribe();
+        let mut ticks = tokio::time::interval(interval);
+        ticks.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            tokio::select! {
+                _ = ticks.tick() => {}
+                _ = shutdown.wait_for(|stop| *stop) => return,
+            }
+            self.health_report().await;
+        }
+    }
+
+    /// Check every `SidecarManagerConfig::vault_check_interval` until `shutdown` that each
+    /// sidecar's vault directory still exists (see [`vault_path`]). A ready sidecar whose vault
+    /// vanished goes `PathLost` with `vault://path-invalid`; it is `Ready` again, with
+    /// `vault://path-restored`, if the directory comes back.
+    pub async fn run_vault_monitor(&self) {
+        let mut shutdown = self.shutdown_tx.subscribe();
+        let mut ticks = tokio::time::interval(self.config.vault_check_interval);
+        ticks.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            tokio::select! {
+                _ = ticks.tick() => {}
+                _ = shutdown.wait_for(|stop| *stop) => return,
+            }
+            self.check_vault_paths().await;
+        }
+    }
+
+    async fn check_vault_paths(&self) {
+        let sidecars: Vec<_> = self.processes.lock().await
+            .iter()
+            .map(|(label, process)| (label.clone(), process.vault_path.clone(), process.telemetry.clone()))
+            .collect();
+        for (window_label, vault_path, telemetry) in sidecars {
+            let present = vault_path::is_present(&vault_path);
+            let changed = telemetry.state.send_if_modified(|state| {
+                let next = match (*state, present) {
+                    (SidecarState::Ready | SidecarState::Busy, false) => SidecarState::PathLost,
+                    (SidecarState::PathLost, true) if telemetry.in_flight.load(Ordering::SeqCst) > 0 => SidecarState::Busy,
+                    (SidecarState::PathLost, true) => SidecarState::Ready,
+                    _ => return false,
+                };
+                *state = next;
+                true
+            });
+            if !changed {
+                continue;
+            }
+            let event = match present {
+                true => {
+                    println!("Vault {} of window '{}' is back", vault_path, window_label);
+                    "vault://path-restored"
+                }
+                false => {
+                    eprintln!("Vault {} of window '{}' is gone, was it moved or renamed?", vault_path, window_label);
+                    "vault://path-invalid"
+                }
+            };
+            if let Some(emitter) = &self.emitter {
+                emitter(event, serde_json::json!({ "window_label": window_label, "vault_path": vault_path }));
+            }
+        }
+    }
+
+    /// Point the window's sidecar at its vault's new location, e.g. after `vault://path-invalid`:
+    /// switched in-process with [`vault_path::REBIND_METHOD`], or by restarting it there when the
+    /// sidecar doesn't support that (the bundled one doesn't). Later restarts use the new path.
+    pub async fn rebind_vault(&self, window_label: &str, new_path: &str) -> Result<Rebind, SidecarError> {
+        if !vault_path::is_present(new_path) {
+            return Err(SidecarError::InvalidOptions(format!("Vault directory not found: {}", new_path)));
+        }
+        let (old_path, telemetry) = {
+            let mut processes = self.processes.lock().await;
+            let process = processes.get_mut(window_label)
+                .ok_or_else(|| SidecarError::NotFound { window_label: window_label.to_string() })?;
+            (std::mem::replace(&mut process.vault_path, new_path.to_string()), process.telemetry.clone())
+        };
+        // Let the RPC through; the next check flags the sidecar again should it fail
+        state::transition(&telemetry.state, SidecarState::PathLost, SidecarState::Ready);
+
+        let params = serde_json::json!({ "vault_path": new_path });
+        let rebind = match self.send_control(window_label, vault_path::REBIND_METHOD, params).await {
+            Ok(_) => Ok(Rebind::HotSwitched),
+            Err(e) if e.is_method_not_found() => {
+                println!("Sidecar for window '{}' can't switch vaults in-process, restarting it", window_label);
+                self.restart_sidecar(window_label).await.map(|_| Rebind::Restarted)
+            }
+            Err(e) => Err(e),
+        };
+        match &rebind {
+            Ok(_) => println!("Rebound window '{}' from vault {} to {}", window_label, old_path, new_path),
+            Err(_) => {
+                if let Some(process) = self.processes.lock().await.get_mut(window_label) {
+                    process.vault_path = old_path;
+                }
+            }
+        }
+        rebind
+    }
+
+    /// Combined resident memory of all sidecars against `SidecarManagerConfig::total_memory_budget`
+    pub async fn memory_usage(&self) -> MemoryUsage {
+        let min_idle = self.config.total_memory_budget.as_ref().map(|budget| budget.min_idle).unwrap_or_default();
+        let mut sidecars: Vec<_> = self.processes.lock().await
+            .iter()
+            .map(|(label, process)| {
+                let telemetry = &process.telemetry;
+                let idle = telemetry.last_used.lock().unwrap_or_else(|e| e.into_inner()).elapsed();
+                memory_budget::SidecarMemory {
+                    window_label: label.clone(),
+                    memory_bytes: process::resident_memory(process.child.id()),
+                    idle_secs: idle.as_secs_f64(),
+                    evictable: *telemetry.state.borrow() == SidecarState::Ready
+                        && telemetry.in_flight.load(Ordering::SeqCst) == 0
+                        && idle >= min_idle,
+                }
+            })
+            .collect();
+        sidecars.sort_by(|a, b| a.window_label.cmp(&b.window_label));
+        MemoryUsage {
+            total_bytes: sidecars.iter().filter_map(|sidecar| sidecar.memory_bytes).sum(),
+            budget_bytes: self.config.total_memory_budget.as_ref().map(|budget| budget.total_bytes),
+            sidecars,
+        }
+    }
+
+    /// Terminate least recently used idle sidecars while the combined memory is over budget,
+    /// emitting `sidecar://memory-evicted` for each; returns their window labels
+    pub async fn enforce_memory_budget(&self) -> Vec<String> {
+        let Some(budget) = self.config.total_memory_budget.as_ref().map(|budget| budget.total_bytes) else {
+            return Vec::new();
+        };
+        let usage = self.memory_usage().await;
+        if usage.total_bytes <= budget {
+            return Vec::new();
+        }
+        let evictions = usage.evictions(budget);
+        if evictions.is_empty() {
+            eprintln!("Sidecars use {} bytes, over the {} byte budget, but none are idle", usage.total_bytes, budget);
+        }
+        let mut evicted = Vec::new();
+        for sidecar in evictions {
+            let memory_bytes = sidecar.memory_bytes.unwrap_or_default();
+            println!("Evicting idle sidecar '{}' ({} bytes) to stay within the {} byte memory budget",
+                     sidecar.window_label, memory_bytes, budget);
+            if let Err(e) = self.terminate_sidecar(&sidecar.window_label).await {
+                eprintln!("Failed to evict sidecar '{}': {}", sidecar.window_label, e);
+                continue;
+            }
+            if let Some(emitter) = &self.emitter {
+                emitter("sidecar://memory-evicted", serde_json::json!({
+                    "window_label": sidecar.window_label,
+                    "memory_bytes": memory_bytes,
+                    "total_bytes": usage.total_bytes,
+                    "budget_bytes": budget,
+                }));
+            }
+            evicted.push(sidecar.window_label.clone());
+        }
+        evicted
+    }
+
+    /// Sample and enforce the memory budget every `sample_interval` until `shutdown`. Returns at
+    /// once when no budget is configured or resident memory can't be read on this platform.
+    pub async fn run_memory_budget(&self) {
+        let Some(interval) = self.config.total_memory_budget.as_ref().map(|budget| budget.sample_interval) else {
+            return;
+        };
+        if !sysinfo::IS_SUPPORTED_SYSTEM {
+            eprintln!("Sidecar memory budget is not supported on this platform: resident memory can't be read");
+            return;
+        }
+        let mut shutdown = self.shutdown_tx.subscribe();
+        let mut ticks = tokio::time::interval(interval);
+        ticks.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            tokio::select! {
+                _ = ticks.tick() => {}
+                _ = shutdown.wait_for(|stop| *stop) => return,
+            }
+            self.enforce_memory_budget().await;
+        }
+    }
+
+    /// Terminate the sidecars that have been ready with nothing in flight for longer than their
+    /// role's `idle_timeout`, emitting `sidecar://idle-terminated` for each; returns their window
+    /// labels
+    pub async fn terminate_idle(&self) -> Vec<String> {
+        let processes = self.processes.lock().await;
+        // Sidecars others depend on (`SpawnOptions::depends_on`) stay up however idle they are
+        let providers: HashSet<&String> = processes.values()
+            .flat_map(|process| &process.options.depends_on)
+            .collect();
+        let idle: Vec<_> = processes
+            .iter()
+            .filter(|(label, _)| !providers.contains(label))
+            .filter_map(|(label, process)| {
+                let timeout = self.roles.get(process.options.role.as_deref())?.idle_timeout?;
+                let telemetry = &process.telemetry;
+                let idle = telemetry.last_used.lock().unwrap_or_else(|e| e.into_inner()).elapsed();
+                (*telemetry.state.borrow() == SidecarState::Ready
+                    && telemetry.in_flight.load(Ordering::SeqCst) == 0
+                    && idle >= timeout)
+                    .then(|| (label.clone(), process.options.role.clone(), idle))
+            })
+            .collect();
+        drop(processes);
+        let mut terminated = Vec::new();
+        for (window_label, role, idle) in idle {
+            println!("Terminating sidecar '{}', idle for {:?}", window_label, idle);
+            if let Err(e) = self.terminate_sidecar(&window_label).await {
+                eprintln!("Failed to terminate idle sidecar '{}': {}", window_label, e);
+                continue;
+            }
+            if let Some(emitter) = &self.emitter {
+                emitter("sidecar://idle-terminated", serde_json::json!({
+                    "window_label": window_label,
+                    "role": role,
+                    "idle_secs": idle.as_secs_f64(),
+                }));
+            }
+            terminated.push(window_label);
+        }
+        terminated
+    }
+
+    /// Terminate sidecars past their role's idle timeout every [`role::IDLE_CHECK_INTERVAL`]
+    /// until `shutdown`
+    pub async fn run_idle_monitor(&self) {
+        let mut shutdown = self.shutdown_tx.subscribe();
+        let mut ticks = tokio::time::interval(role::IDLE_CHECK_INTERVAL);
+        ticks.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            tokio::select! {
+                _ = ticks.tick() => {}
+                _ = shutdown.wait_for(|stop| *stop) => return,
+            }
+            self.terminate_idle().await;
+        }
+    }
+
+    /// Send `signal` to a sidecar (its whole process group when it runs behind a launcher),
+    /// e.g. `Usr1` for a sidecar-defined state dump. Only `Kill` is available on Windows.
+    pub async fn signal_sidecar(&self, window_label: &str, signal: SidecarSignal) -> Result<(), SidecarError> {
+        let mut processes = self.processes.lock().await;
+        let process = processes
+            .get_mut(window_label)
+            .ok_or_else(|| SidecarError::NotFound { window_label: window_label.to_string() })?;
+        let group = process.signals_group();
+        process.child.signal(group, signal).map_err(|e| match e.kind() {
+            std::io::ErrorKind::Unsupported => SidecarError::Unsupported(e.to_string()),
+            _ => SidecarError::Other(anyhow!("Failed to send {:?} to window '{}': {}", signal, window_label, e)),
+        })?;
+        println!("Sent {:?} to sidecar for window '{}'", signal, window_label);
+        match signal.suspends() {
+            Some(true) => self.states.set(window_label, SidecarState::Suspended),
+            Some(false) => {
+                state::transition(&process.telemetry.state, SidecarState::Suspended, SidecarState::Ready);
+            }
+            None => {}
+        }
+        Ok(())
+    }
+
+    /// Pause or resume forwarding a sidecar's stdout/stderr to the console, ring buffer and
+    /// [`Self::subscribe_logs`]. Output is still read (and discarded) while paused.
+    pub async fn set_log_capture(&self, window_label: &str, enabled: bool) -> Result<(), SidecarError> {
+        let processes = self.processes.lock().await;
+        let telemetry = &processes
+            .get(window_label)
+            .ok_or_else(|| SidecarError::NotFound { window_label: window_label.to_string() })?
+            .telemetry;
+        if telemetry.log_capture.swap(enabled, Ordering::Relaxed) != enabled {
+            if enabled {
+                println!("Resumed log capture for window '{}' ({} line(s) dropped while paused)",
+                         window_label, telemetry.dropped_lines.load(Ordering::Relaxed));
+            } else {
+                println!("Paused log capture for window '{}'", window_label);
+            }
+        }
+        Ok(())
+    }
+
+    /// Reconnects and traffic totals for the window's sidecar, if one is tracked
+    pub async fn connection_stats(&self, window_label: &str) -> Option<ConnStats> {
+        self.processes.lock().await
+            .get(window_label)
+            .map(|p| p.telemetry.connection.snapshot())
+    }
+
+    /// p50/p95/p99 round-trip times of each method recently sent to the window's sidecar, if
+    /// one is tracked
+    pub async fn method_latencies(&self, window_label: &str) -> Option<HashMap<String, LatencyStats>> {
+        self.processes.lock().await
+            .get(window_label)
+            .map(|p| p.telemetry.latencies.lock().unwrap_or_else(|e| e.into_inner()).stats())
+    }
+
+    /// The state of the window's rate limiter, if its sidecar is tracked and has one
+    pub async fn rate_limit_stats(&self, window_label: &str) -> Option<RateLimitStats> {
+        self.processes.lock().await
+            .get(window_label)
+            .and_then(|p| p.telemetry.rate_limiter.as_ref().map(|limiter| limiter.stats()))
+    }
+
+    /// Override the response deadline for `method`, e.g. minutes for `reindex`
+    pub fn set_method_timeout(&self, method: &str, timeout: Duration) {
+        self.method_timeouts.lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(method.to_string(), timeout);
+    }
+
+    /// Response deadline for `method`: its override, else `SidecarManagerConfig::request_timeout`;
+    /// None waits indefinitely
+    pub fn method_timeout(&self, method: &str) -> Option<Duration> {
+        self.method_timeouts.lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(method)
+            .copied()
+            .or(self.config.request_timeout)
+    }
+
+    /// Send a command to every running sidecar, with at most
+    /// `SidecarManagerConfig::broadcast_concurrency` working on it at once; the rest wait their
+    /// turn in order. Results are sorted by window label, however the sends finish.
+    pub async fn broadcast_command(
+        &self,
+        method: &str,
+        params: serde_json::Value,
+    ) -> Vec<(String, Result<serde_json::Value, SidecarError>)> {
+        let mut labels: Vec<String> = self.processes.lock().await.keys().cloned().collect();
+        labels.sort();
+        self.broadcast_to(labels, method, params).await
+    }
+
+    /// Window labels of the running sidecars spawned with `key = value` in
+    /// [`SpawnOptions::labels`], sorted
+    pub async fn list_sidecars_by_label(&self, key: &str, value: &str) -> Vec<String> {
+        let mut labels: Vec<String> = self.processes.lock().await.iter()
+            .filter(|(_, process)| process.options.labels.get(key).is_some_and(|v| v == value))
+            .map(|(label, _)| label.clone())
+            .collect();
+        labels.sort();
+        labels
+    }
+
+    /// The role `window_label`'s sidecar runs as, see [`SpawnOptions::role`]
+    pub async fn role(&self, window_label: &str) -> Result<Option<String>, SidecarError> {
+        self.processes.lock().await
+            .get(window_label)
+            .map(|process| process.options.role.clone())
+            .ok_or_else(|| SidecarError::NotFound { window_label: window_label.to_string() })
+    }
+
+    /// Move a running sidecar to another registered role, or to none. Its idle timeout and
+    /// restart cap follow at once, as does the new role's priority unless the spawn options set
+    /// one; clearing the role keeps the current priority until the next launch. Kept across
+    /// restarts.
+    pub async fn set_role(&self, window_label: &str, role: Option<String>) -> Result<(), SidecarError> {
+        self.roles.check(role.as_deref()).map_err(SidecarError::InvalidOptions)?;
+        let mut processes = self.processes.lock().await;
+        let process = processes
+            .get_mut(window_label)
+            .ok_or_else(|| SidecarError::NotFound { window_label: window_label.to_string() })?;
+        let profile = self.roles.get(role.as_deref());
+        process.options.role = role;
+        let priority = profile.map(|profile| profile.priority).unwrap_or_default();
+        if process.options.priority == ProcessPriority::Normal && priority != ProcessPriority::Normal {
+            if let SidecarChild::Spawned(child) = &process.child {
+                if let Err(e) = process::set_priority(child, priority) {
+                    eprintln!("Failed to set sidecar {} priority to {:?}: {}", child.id(), priority, e);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Window labels of the running sidecars serving `vault_path`, sorted. Paths are compared
+    /// canonicalized, so `~/notes/../notes` or a symlink finds the sidecar opened as `~/notes`.
+    pub async fn find_by_vault(&self, vault_path: &str) -> Vec<String> {
+        let wanted = canonical_vault_path(vault_path);
+        let mut labels: Vec<String> = self.processes.lock().await.iter()
+            .filter(|(_, process)| canonical_vault_path(&process.vault_path) == wanted)
+            .map(|(label, _)| label.clone())
+            .collect();
+        labels.sort();
+        labels
+    }
+
+    /// Like [`Self::broadcast_command`], limited to the sidecars labelled `key = value`
+    pub async fn broadcast_to_label(
+        &self,
+        key: &str,
+        value: &str,
+        method: &str,
+        params: serde_json::Value,
+    ) -> Vec<(String, Result<serde_json::Value, SidecarError>)> {
+        let labels = self.list_sidecars_by_label(key, value).await;
+        self.broadcast_to(labels, method, params).await
+    }
+
+    async fn broadcast_to(
+        &self,
+        labels: Vec<String>,
+        method: &str,
+        params: serde_json::Value,
+    ) -> Vec<(String, Result<serde_json::Value, SidecarError>)> {
+        fan_out(labels, self.config.broadcast_concurrency, |label| {
+            let params = params.clone();
+            async move {
+                let result = self.send_command(&label, method, params).await;
+                (label, result)
+            }
+        })
+        .await
+    }
+
+    /// Send a command to the sidecar via WebSocket.
+    ///
+    /// A JSON-RPC error response comes back as [`SidecarError::Rpc`]; any other response is
+    /// returned whole. Gives up with [`SidecarError::ResponseTimeout`] after [`Self::method_timeout`],
+    /// if the method has one.
+    pub async fn send_command(
+        &self,
+        window_label: &str,
+        method: &str,
+        params: serde_json::Value,
+    ) -> Result<serde_json::Value, SidecarError> {
+        self.send_or_park(window_label, method, params, None, Caller::Client).await
+    }
+
+    /// [`Self::send_command`] for the manager's own RPCs, which the method policy doesn't apply to
+    async fn send_internal(
+        &self,
+        window_label: &str,
+        method: &str,
+        params: serde_json::Value,
+    ) -> Result<serde_json::Value, SidecarError> {
+        self.send_or_park(window_label, method, params, None, Caller::Manager).await
+    }
+
+    /// Send each `(method, params)` to the window's sidecar as its own request, for sidecars
+    /// without batch support, with no more in flight at once than `max_in_flight` allows.
+    /// Returns one result per command in the given order; a failure doesn't stop the rest.
+    pub async fn send_all(
+        &self,
+        window_label: &str,
+        commands: Vec<(String, serde_json::Value)>,
+    ) -> Vec<Result<serde_json::Value, SidecarError>> {
+        let limit = self.config.max_in_flight.unwrap_or(commands.len());
+        fan_out(commands, limit, |(method, params)| async move {
+            self.send_command(window_label, &method, params).await
+        })
+        .await
+    }
+
+    /// Like [`Self::send_command`], but fails with [`SidecarError::Busy`] right away instead of
+    /// waiting when the sidecar isn't `Ready` (starting, restarting, suspended...) or already
+    /// has `max_in_flight` commands running, e.g. so the UI can show cached data instead. Never
+    /// parked for replay.
+    pub async fn try_send_command(
+        &self,
+        window_label: &str,
+        method: &str,
+        params: serde_json::Value,
+    ) -> Result<serde_json::Value, SidecarError> {
+        self.dispatch(window_label, method, params, None, &[], Admission::Immediate, Caller::Client).await
+    }
+
+    /// Send `method` once `debounce` has passed, unless another coalesced call for the same
+    /// window, method and `key` comes in first: then this one resolves as
+    /// [`Coalesced::Superseded`] without reaching the sidecar (see [`coalesce`]). Meant for
+    /// rapid-fire UI requests such as search-as-you-type, where only the latest one matters.
+    pub async fn send_command_coalesced(
+        &self,
+        window_label: &str,
+        method: &str,
+        params: serde_json::Value,
+        key: &str,
+        debounce: Duration,
+    ) -> Result<Coalesced, SidecarError> {
+        if !self.coalescer.debounce(window_label, method, key, debounce).await {
+            return Ok(Coalesced::Superseded);
+        }
+        self.send_command(window_label, method, params).await.map(Coalesced::Sent)
+    }
+
+    /// Like [`Self::send_command`], but ships `blob` as a raw binary frame instead of
+    /// base64-encoding it into the params; the handler receives it as its `blob` param.
+    /// See [`connection`] for the framing.
+    pub async fn send_command_with_blob(
+        &self,
+        window_label: &str,
+        method: &str,
+        params: serde_json::Value,
+        blob: Vec<u8>,
+    ) -> Result<serde_json::Value, SidecarError> {
+        self.send_or_park(window_label, method, params, Some(blob), Caller::Client).await
+    }
+
+    /// Like [`Self::send_command`], but places `payload` in a POSIX shared memory region and
+    /// passes its descriptor as the `key` param, for payloads of hundreds of MB. The handler
+    /// reads it with `sidecar.utils.read_shared_memory()`; the region is unlinked once the
+    /// command has returned (see [`shm`]).
+    #[cfg(all(unix, feature = "shared-memory"))]
+    pub async fn send_command_shared(
+        &self,
+        window_label: &str,
+        method: &str,
+        mut params: serde_json::Value,
+        key: &str,
+        payload: &[u8],
+    ) -> Result<serde_json::Value, SidecarError> {
+        let region = shm::SharedMemoryRegion::from_bytes(payload)?;
+        match params.as_object_mut() {
+            Some(object) => { object.insert(key.to_string(), region.descriptor()); }
+            None => return Err(SidecarError::InvalidOptions("Shared memory params must be an object".to_string())),
+        }
+        let result = self.send_command(window_label, method, params).await;
+        drop(region);
+        result
+    }
+
+    /// Like [`Self::send_command`], but a "method not found" answer refreshes the cached
+    /// [`Self::capabilities`] and, if the method is listed now (e.g. the sidecar was just
+    /// restarted with another version), sends it once more. Otherwise it fails with
+    /// [`SidecarError::MethodNotSupported`]. Opt-in, so a mistyped method isn't sent twice.
+    pub async fn send_command_with_capability_refresh(
+        &self,
+        window_label: &str,
+        method: &str,
+        params: serde_json::Value,
+    ) -> Result<serde_json::Value, SidecarError> {
+        let not_supported = || SidecarError::MethodNotSupported { method: method.to_string() };
+        match self.send_command(window_label, method, params.clone()).await {
+            Err(e) if e.is_method_not_found() => {
+                let Ok(commands) = self.refresh_capabilities(window_label).await else {
+                    return Err(e);
+                };
+                if !commands.contains(method) {
+                    return Err(not_supported());
+                }
+                println!("'{}' is listed after refreshing capabilities of window '{}', retrying", method, window_label);
+                match self.send_command(window_label, method, params).await {
+                    Err(e) if e.is_method_not_found() => Err(not_supported()),
+                    result => result,
+                }
+            }
+            result => result,
+        }
+    }
+
+    /// Like [`Self::send_command`], adding `headers` to the WebSocket upgrade request after the
+    /// manager's `handshake_headers` (replacing any of the same name). The command always gets
+    /// its own connection, even with `persistent_connections`, and is never parked for replay.
+    pub async fn send_command_with_headers(
+        &self,
+        window_label: &str,
+        method: &str,
+        params: serde_json::Value,
+        headers: &[(String, String)],
+    ) -> Result<serde_json::Value, SidecarError> {
+        self.dispatch(window_label, method, params, None, headers, Admission::Queue, Caller::Client).await
+    }
+
+    /// Send once; if a replayable method fails because its sidecar is down or restarting,
+    /// park it and wait for the replay (or fail with the original error after the TTL)
+    async fn send_or_park(
+        &self,
+        window_label: &str,
+        method: &str,
+        params: serde_json::Value,
+        blob: Option<Vec<u8>>,
+        caller: Caller,
+    ) -> Result<serde_json::Value, SidecarError> {
+        if !self.replay.accepts(method) {
+            return self.dispatch(window_label, method, params, blob, &[], Admission::Queue, caller).await;
+        }
+
+        let attempted = self.processes.lock().await.get(window_label).map(|p| p.generation);
+        let retry = (params.clone(), blob.clone());
+        let error = match self.dispatch(window_label, method, params, blob, &[], Admission::Queue, caller).await {
+            Err(e) if self.should_park(window_label, &e) => e,
+            result => return result,
+        };
+
+        let (params, blob) = retry;
+        match self.replay.park(window_label, attempted, method, params.clone(), blob.clone()) {
+            Parked::Waiting(response) => {
+                println!("Sidecar for window '{}' unavailable, parked '{}' for replay", window_label, method);
+                match tokio::time::timeout(self.replay.ttl(), response).await {
+                    Ok(Ok(result)) => result,
+                    _ => Err(error),
+                }
+            }
+            Parked::RetryNow => self.dispatch(window_label, method, params, blob, &[], Admission::Queue, caller).await,
+            Parked::Full => {
+                eprintln!("Replay queue full, failing '{}' for window '{}'", method, window_label);
+                Err(error)
+            }
+        }
+    }
+
+    fn should_park(&self, window_label: &str, error: &SidecarError) -> bool {
+        match error {
+            SidecarError::NotFound { .. } => self.restarting.lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .contains(window_label),
+            SidecarError::Crashed(_) => self.config.crash_policy == CrashPolicy::RestartAndRetry,
+            e => e.is_transient(),
+        }
+    }
+
+    /// The window's audit file with `SidecarManagerConfig::audit_log_dir`; a sidecar whose file
+    /// can't be opened runs without one
+    fn open_audit_log(&self, window_label: &str) -> Option<audit::AuditLog> {
+        let dir = self.config.audit_log_dir.as_ref()?;
+        match audit::AuditLog::open(dir, window_label) {
+            Ok(log) => Some(log),
+            Err(e) => {
+                eprintln!("Failed to open audit log for window '{}' in {}: {}", window_label, dir.display(), e);
+                None
+            }
+        }
+    }
+
+    /// What the audit trail records about a request, if auditing is on
+    fn audit_request(&self, method: &str, params: &serde_json::Value) -> Option<audit::Request> {
+        self.config.audit_log_dir.as_ref()?;
+        Some(audit::Request::new(method, params, &self.config.audit_redact))
+    }
+
+    /// Send one command through the registered middleware, if the method policy allows it for
+    /// `caller`; `extra_headers` forces a dedicated connection carrying them
+    #[allow(clippy::too_many_arguments)]
+    async fn dispatch(
+        &self,
+        window_label: &str,
+        method: &str,
+        mut params: serde_json::Value,
+        blob: Option<Vec<u8>>,
+        extra_headers: &[(String, String)],
+        admission: Admission,
+        caller: Caller,
+    ) -> Result<serde_json::Value, SidecarError> {
+        if caller == Caller::Client {
+            self.method_filter.check(method)?;
+        }
+        let chain = self.middlewares.snapshot();
+        if chain.is_empty() {
+            return self.send_json(window_label, method, params, blob, extra_headers, admission).await;
+        }
+        let vault_path = self.processes.lock().await.get(window_label).map(|p| p.vault_path.clone());
+        let call = RpcCall { window_label, vault_path: vault_path.as_deref(), method };
+        let (ran, synthetic) = middleware::request(&chain, &call, &mut params);
+        let mut result = match synthetic {
+            Some(response) => error::check_response(response),
+            None => self.send_json(window_label, method, params, blob, extra_headers, admission).await,
+        };
+        middleware::response(&chain, ran, &call, &mut result);
+        result
+    }
+
+    /// Send one command as it leaves the middleware
+    async fn send_json(
+        &self,
+        window_label: &str,
+        method: &str,
+        params: serde_json::Value,
+        blob: Option<Vec<u8>>,
+        extra_headers: &[(String, String)],
+        admission: Admission,
+    ) -> Result<serde_json::Value, SidecarError> {
+        let audit = self.audit_request(method, &params);
+        self.run_command(window_label, method, admission, audit, |host, port, telemetry, target| async move {
+            if let Some(stdio) = &target.stdio {
+                if !extra_headers.is_empty() {
+                    return Err(SidecarError::InvalidOptions("Handshake headers need the WebSocket transport".to_string()));
+                }
+                Self::send_over_stdio(stdio, &self.client_options(), method, params, blob).await
+            } else if self.config.persistent_connections && extra_headers.is_empty() {
+                self.send_over_persistent(&target, method, params, blob).await
+            } else {
+                let mut client = self.client_options();
+                client.headers.extend_from_slice(extra_headers);
+                Self::send_over_websocket(&telemetry.connection, &host, port, &client, method, params, blob).await
+            }
+        }).await
+    }
+
+    /// Call `method` for a binary result (a rendered image, an exported file), returned as raw
+    /// bytes instead of base64 inside JSON; see [`connection`] for the framing. JSON-RPC errors
+    /// still come back as [`SidecarError::Rpc`]. Never parked for replay.
+    pub async fn send_command_binary(
+        &self,
+        window_label: &str,
+        method: &str,
+        params: serde_json::Value,
+    ) -> Result<Vec<u8>, SidecarError> {
+        self.method_filter.check(method)?;
+        let audit = self.audit_request(method, &params);
+        self.run_command(window_label, method, Admission::Queue, audit, |host, port, telemetry, target| async move {
+            if target.stdio.is_some() {
+                Err(SidecarError::InvalidOptions("Binary results need the WebSocket transport".to_string()))
+            } else if self.config.persistent_connections {
+                let connection = Self::ensure_connection(&target, &self.connection_context()).await?;
+                connection.request_binary(method, params).await
+            } else {
+                let client = self.client_options();
+                let request = connection::encode_binary_request(method, params, &client)?;
+                Self::exchange_over_websocket(&telemetry.connection, &host, port, &client, method, request, None)
+                    .await?
+                    .into_binary()
+            }
+        }).await
+    }
+
+    /// Call a control-plane `method` over the sidecar's control socket, so it isn't stuck behind
+    /// large requests on the RPC socket; like [`Self::send_command`] when there is none
+    async fn send_control(
+        &self,
+        window_label: &str,
+        method: &str,
+        params: serde_json::Value,
+    ) -> Result<serde_json::Value, SidecarError> {
+        let control_port = self.processes.lock().await.get(window_label).and_then(|p| p.control_port);
+        let Some(control_port) = control_port else {
+            return self.send_internal(window_label, method, params).await;
+        };
+        let audit = self.audit_request(method, &params);
+        self.run_command(window_label, method, Admission::Control, audit, |host, _, telemetry, _| async move {
+            let client = self.client_options();
+            Self::send_over_websocket(&telemetry.connection, &host, control_port, &client, method, params, None).await
+        }).await
+    }
+
+    /// Run `request` against the window's sidecar (host, port, telemetry, persistent connection
+    /// slot) once `admission` grants a command slot, with the command counted as in flight and
+    /// under `method`'s timeout; failures are recorded in the sidecar's telemetry, and `audit`
+    /// and the outcome in its audit trail. Fails with [`SidecarError::Crashed`] as soon as the
+    /// sidecar is found to have crashed. A command for a sidecar still `Starting` (e.g. sent right
+    /// after `spawn_sidecar`) first waits for it to become ready, within the method's timeout.
+    async fn run_command<T, Fut>(
+        &self,
+        window_label: &str,
+        method: &str,
+        admission: Admission,
+        audit: Option<audit::Request>,
+        request: impl FnOnce(String, u16, Arc<SidecarTelemetry>, ConnectionTarget) -> Fut,
+    ) -> Result<T, SidecarError>
+    where
+        T: audit::ResultSize,
+        Fut: std::future::Future<Output = Result<T, SidecarError>>,
+    {
+        let lookup = || async {
+            self.processes.lock().await
+                .get(window_label)
+                .map(|p| (p.host.clone(), p.ws_port, p.telemetry.clone(), p.connection_target()))
+                .ok_or_else(|| SidecarError::NotFound { window_label: window_label.to_string() })
+        };
+        // Without a deadline the timeouts below never fire (tokio saturates the instant)
+        let timeout = self.method_timeout(method).unwrap_or(Duration::MAX);
+        let arrived = Instant::now();
+        let (mut host, mut port, mut telemetry, mut target) = lookup().await?;
+        let starting = *telemetry.state.borrow() == SidecarState::Starting;
+        if starting && admission != Admission::Immediate {
+            let mut state = telemetry.state.subscribe();
+            let settled = tokio::time::timeout(timeout, state.wait_for(|state| *state != SidecarState::Starting))
+                .await
+                .map(|settled| settled.map(|state| *state).unwrap_or(SidecarState::Terminated));
+            match settled {
+                Ok(SidecarState::Ready | SidecarState::Busy) => {}
+                Ok(state) => return Err(SidecarError::SpawnFailed(format!("sidecar is {:?} after starting", state))),
+                Err(_) => return Err(SidecarError::ResponseTimeout { method: method.to_string(), timeout }),
+            }
+            // Readiness may have moved the sidecar to a fallback port
+            (host, port, telemetry, target) = lookup().await?;
+        }
+        if *telemetry.state.borrow() == SidecarState::PathLost {
+            let vault_path = self.processes.lock().await.get(window_label).map(|p| p.vault_path.clone());
+            return Err(SidecarError::VaultPathLost {
+                window_label: window_label.to_string(),
+                vault_path: vault_path.unwrap_or_default(),
+            });
+        }
+
+        let _slot = telemetry.admit(admission).await?;
+        let _in_flight = InFlightGuard::new(telemetry.clone());
+        let started = Instant::now();
+        let audited = audit.zip(telemetry.audit.as_ref()).map(|(request, log)| (log.request(&request), log));
+        let command = async {
+            tokio::select! {
+                biased;
+                report = telemetry.crashed() => Err(SidecarError::Crashed(report)),
+                result = request(host, port, telemetry.clone(), target) => match result {
+                    Err(e @ SidecarError::ConnectionClosed(_)) => {
+                        match tokio::time::timeout(crash::REPORT_GRACE, telemetry.crashed()).await {
+                            Ok(report) => Err(SidecarError::Crashed(report)),
+                            Err(_) => Err(e),
+                        }
+                    }
+                    result => result,
+                },
+            }
+        };
+        let result = tokio::time::timeout(timeout.saturating_sub(arrived.elapsed()), command)
+            .await
+            .unwrap_or_else(|_| Err(SidecarError::ResponseTimeout { method: method.to_string(), timeout }));
+        telemetry.latencies.lock().unwrap_or_else(|e| e.into_inner()).record(method, started.elapsed());
+        if let Some((seq, log)) = audited {
+            log.response(seq, method, &result, started.elapsed());
+        }
+        if let Err(e) = &result {
+            telemetry.record_error(method, e.to_string());
+        }
+        result
+    }
+
+    /// Send on the sidecar's shared connection, reconnecting if the previous one died
+    async fn send_over_persistent(
+        &self,
+        target: &ConnectionTarget,
+        method: &str,
+        params: serde_json::Value,
+        blob: Option<Vec<u8>>,
+    ) -> Result<serde_json::Value, SidecarError> {
+        let connection = Self::ensure_connection(target, &self.connection_context()).await?;
+        error::check_response(connection.request(method, params, blob).await?)
+    }
+
+    /// The target's live connection, or a fresh one (its task tracked for `shutdown`) if the
+    /// previous one died or there never was one
+    async fn ensure_connection(
+        target: &ConnectionTarget,
+        context: &ConnectionContext,
+    ) -> Result<PersistentConnection, SidecarError> {
+        let mut slot = target.slot.lock().await;
+        if let Some(connection) = slot.as_ref().filter(|c| c.is_alive()) {
+            return Ok(connection.clone());
+        }
+        let (connection, task) = PersistentConnection::connect(
+            &target.host,
+            target.port,
+            &context.client,
+            context.keepalive,
+            context.shutdown.clone(),
+            target.counters.clone(),
+            target.notifications.clone(),
+        ).await?;
+        target.counters.record_connect(slot.is_some());
+        track(&context.tasks, task);
+        *slot = Some(connection.clone());
+        Ok(connection)
+    }
+
+    /// One request outside [`Self::run_command`] (startup, replay): over the sidecar's stdio
+    /// channel if it has one, else on a fresh WebSocket connection
+    #[allow(clippy::too_many_arguments)]
+    async fn send_once(
+        counters: &ConnectionCounters,
+        host: &str,
+        port: u16,
+        stdio: Option<&StdioTransport>,
+        client: &ClientOptions,
+        method: &str,
+        params: serde_json::Value,
+        blob: Option<Vec<u8>>,
+    ) -> Result<serde_json::Value, SidecarError> {
+        match stdio {
+            Some(stdio) => Self::send_over_stdio(stdio, client, method, params, blob).await,
+            None => Self::send_over_websocket(counters, host, port, client, method, params, blob).await,
+        }
+    }
+
+    async fn send_over_stdio(
+        stdio: &StdioTransport,
+        client: &ClientOptions,
+        method: &str,
+        params: serde_json::Value,
+        blob: Option<Vec<u8>>,
+    ) -> Result<serde_json::Value, SidecarError> {
+        if blob.is_some() {
+            return Err(SidecarError::InvalidOptions("Binary attachments need the WebSocket transport".to_string()));
+        }
+        error::check_response(stdio.request(method, params, client).await?)
+    }
+
+    /// Perform a single JSON-RPC round trip on a fresh WebSocket connection, tallying the
+    /// traffic in `counters`
+    async fn send_over_websocket(
+        counters: &ConnectionCounters,
+        host: &str,
+        port: u16,
+        client: &ClientOptions,
+        method: &str,
+        params: serde_json::Value,
+        blob: Option<Vec<u8>>,
+    ) -> Result<serde_json::Value, SidecarError> {
+        let request = connection::encode_request(method, params, blob.as_ref().map(Vec::len), client)?;
+        Self::exchange_over_websocket(counters, host, port, client, method, request, blob).await?.into_json()
+    }
+
+    /// Send an encoded `(id, text)` request on a fresh WebSocket connection and wait for the
+    /// text or binary frame answering it
+    async fn exchange_over_websocket(
+        counters: &ConnectionCounters,
+        host: &str,
+        port: u16,
+        client: &ClientOptions,
+        method: &str,
+        (request_id, request_text): (String, String),
+        blob: Option<Vec<u8>>,
+    ) -> Result<Reply, SidecarError> {
+        // 2. Connect
+        let mut ws_stream = connection::open(host, port, &client.headers).await?;
+        counters.record_connect(false);
+
+        // 3. Send Request and await its response
+        connection::exchange_once(&mut ws_stream, counters, client, method, (request_id, request_text), blob).await
+    }
+}
+
+/// How a sidecar is run, from [`SidecarManager::plan_launch`]
+struct LaunchPlan {
+    /// The spawn options with the vault config merged in
+    effective: SpawnOptions,
+    python: PythonInterpreter,
+    sidecar_root: PathBuf,
+    working_dir: PathBuf,
+    python_path: OsString,
+}
+
+impl LaunchPlan {
+    /// Program and arguments: the launcher (if any), the interpreter and its flags, then
+    /// `sidecar_args` and the extra args
+    fn argv(&self, sidecar_args: Vec<OsString>) -> Vec<OsString> {
+        let mut argv: Vec<OsString> = self.effective.launcher.iter().flatten().map(OsString::from).collect();
+        argv.push(OsString::from(&self.python.path));
+        argv.extend(self.effective.python_flags.iter().map(OsString::from));
+        argv.extend(sidecar_args);
+        argv.extend(self.effective.extra_args.iter().map(OsString::from));
+        argv
+    }
+}
+
+/// Send `logs` the window's records already queued on `lines`
+fn forward_pending_logs(lines: &mut broadcast::Receiver<LogLine>, window_label: &str, logs: &mpsc::UnboundedSender<LogLine>) {
+    loop {
+        match lines.try_recv() {
+            Ok(line) if line.window_label == window_label => {
+                if logs.send(line).is_err() {
+                    return;
+                }
+            }
+            Ok(_) | Err(broadcast::error::TryRecvError::Lagged(_)) => {}
+            Err(_) => return,
+        }
+    }
+}
+
+/// A fresh ready-file path for one launch
+fn new_ready_file() -> PathBuf {
+    std::env::temp_dir().join(format!("tailor-sidecar-{}.ready", uuid::Uuid::new_v4()))
+}
+
+/// `vault_path` with symlinks and `..` resolved, or as given if it can't be (e.g. it was deleted)
+fn canonical_vault_path(vault_path: &str) -> PathBuf {
+    std::fs::canonicalize(vault_path).unwrap_or_else(|_| PathBuf::from(vault_path))
+}
+
+/// The repository root (parent of `src-tauri`): where the `sidecar` package and `pixi.toml`
+/// live unless configured otherwise
+fn project_root() -> Result<PathBuf, SidecarError> {
+    resolve_project_root(std::env::current_dir(), std::env::current_exe)
+}
+
+/// The parent of `current_dir`, or the executable's directory when the working directory is
+/// gone (e.g. the directory the app was launched from was deleted)
+fn resolve_project_root(
+    current_dir: std::io::Result<PathBuf>,
+    current_exe: impl FnOnce() -> std::io::Result<PathBuf>,
+) -> Result<PathBuf, SidecarError> {
+    let dir_error = match current_dir {
+        Ok(dir) => {
+            return dir.parent()
+                .map(Path::to_path_buf)
+                .ok_or_else(|| SidecarError::SpawnFailed("Failed to get parent directory".to_string()));
+        }
+        Err(e) => e,
+    };
+    let exe = current_exe().map_err(|e| SidecarError::NoProjectRoot {
+        current_dir: dir_error.to_string(),
+        current_exe: e.to_string(),
+    })?;
+    let root = exe.parent().map(Path::to_path_buf).ok_or_else(|| SidecarError::NoProjectRoot {
+        current_dir: dir_error.to_string(),
+        current_exe: format!("{} has no parent directory", exe.display()),
+    })?;
+    eprintln!("Current directory unavailable ({}), using the executable's directory {} as project root",
+              dir_error, root.display());
+    Ok(root)
+}
+
+/// Whether `port` can be bound on each of `addrs`. An address this host can't bind at all
+/// (e.g. `::1` with IPv6 disabled) is skipped instead of making every port look busy.
+fn port_available_on(addrs: &[IpAddr], port: u16) -> bool {
+    use std::io::ErrorKind;
+    addrs.iter().all(|addr| match std::net::TcpListener::bind((*addr, port)) {
+        Ok(_) => true,
+        Err(e) => !matches!(e.kind(), ErrorKind::AddrInUse | ErrorKind::PermissionDenied),
+    })
+}
+
+/// Keep a background task's handle for `shutdown`, dropping handles of finished tasks
+fn track(tasks: &std::sync::Mutex<Vec<JoinHandle<()>>>, task: JoinHandle<()>) {
+    let mut tasks = tasks.lock().unwrap_or_else(|e| e.into_inner());
+    tasks.retain(|t| !t.is_finished());
+    tasks.push(task);
+}
+
+/// Run `f` on every item with at most `limit` running at once, returning results in `items`
+/// order. Waiting items get their turn first-come first-served.
+async fn fan_out<T, R, F, Fut>(items: Vec<T>, limit: usize, f: F) -> Vec<R>
+where
+    F: Fn(T) -> Fut,
+    Fut: std::future::Future<Output = R>,
+{
+    let permits = tokio::sync::Semaphore::new(limit.max(1));
+    let tasks = items.into_iter().map(|item| {
+        let permits = &permits;
+        let task = f(item);
+        async move {
+            // Never closed, so acquiring only waits
+            let _permit = permits.acquire().await;
+            task.await
+        }
+    });
+    futures::future::join_all(tasks).await
+}
+
+/// `PYTHONPATH` for a sidecar: `sidecar_root` first, then any `inherited` entries
+fn sidecar_python_path(sidecar_root: &Path, inherited: Option<&OsStr>) -> Result<OsString, SidecarError> {
+    let mut paths = vec![sidecar_root.to_path_buf()];
+    paths.extend(inherited.into_iter().flat_map(std::env::split_paths));
+    std::env::join_paths(paths)
+        .map_err(|e| SidecarError::SpawnFailed(format!("Invalid PYTHONPATH entry: {}", e)))
+}
+
+/// Join reader threads that finish within `timeout`; stuck ones (a grandchild still holding the
+/// pipe) are left detached
+fn join_readers(readers: Vec<std::thread::JoinHandle<()>>, timeout: Duration) {
+    let deadline = Instant::now() + timeout;
+    for reader in readers {
+        while !reader.is_finished() && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        if reader.is_finished() {
+            let _ = reader.join();
+        }
+    }
+}
+
+/// ` key=value` pairs for a parsed log's fields, or nothing if it has none
+fn format_fields(log: &ParsedLog) -> String {
+    log.fields.iter().map(|(key, value)| format!(" {}={}", key, value)).collect()
+}
+
+/// Start forwarding one of a sidecar's output pipes to the console and its telemetry. One
+/// thread only drains the pipe and cuts records, so the child never blocks on a full pipe; a
+/// second one logs them, and records it can't keep up with are dropped. The handle is the
+/// logging thread's, which finishes once the pipe is closed and everything read is logged;
+/// `pipe_open` is dropped then.
+fn spawn_reader(
+    pipe: impl std::io::Read + Send + 'static,
+    stream: LogStream,
+    mode: CaptureMode,
+    max_len: usize,
+    telemetry: Arc<SidecarTelemetry>,
+    parser: Option<LineParser>,
+    pipe_open: mpsc::UnboundedSender<()>,
+) -> std::thread::JoinHandle<()> {
+    let (records, pending) = std::sync::mpsc::sync_channel::<Vec<u8>>(PENDING_RECORDS);
+    let drain_telemetry = telemetry.clone();
+    std::thread::spawn(move || {
+        let mut behind = false;
+        capture::read_records(pipe, mode, max_len, |record| {
+            // Keep draining the pipe while paused so the child never blocks on a full one
+            if !drain_telemetry.capturing() {
+                return;
+            }
+            match records.try_send(record) {
+                Ok(()) => behind = false,
+                Err(_) => {
+                    drain_telemetry.dropped_lines.fetch_add(1, Ordering::Relaxed);
+                    if !std::mem::replace(&mut behind, true) {
+                        eprintln!("Logging can't keep up with sidecar '{}' {:?}, dropping output",
+                                  drain_telemetry.window_label, stream);
+                    }
+                }
+            }
+        });
+    });
+    std::thread::spawn(move || {
+        let _pipe_open = pipe_open;
+        for record in pending {
+            let print = |text: String| match stream {
+                LogStream::Stdout => println!("{}", text),
+                LogStream::Stderr => eprintln!("{}", text),
+            };
+            if mode != CaptureMode::Lines {
+                let text = format!("[{} bytes]", record.len());
+                print(format!("[Sidecar] {}", text));
+                telemetry.record_log(stream, text, Some(record), None);
+                continue;
+            }
+            let line = String::from_utf8_lossy(&record).into_owned();
+            let parsed = parser.as_ref().and_then(|parse| parse(&line));
+            match &parsed {
+                Some(log) => print(format!("[Sidecar {}] {}{}", log.level, log.message, format_fields(log))),
+                None if stream == LogStream::Stderr => print(format!("[Sidecar Error] {}", line)),
+                None => print(format!("[Sidecar] {}", line)),
+            }
+            telemetry.record_log(stream, line, None, parsed);
+        }
+    })
+}
+
+/// Tell the frontend a window's sidecar moved to another port, so it drops the cached one
+fn emit_port_changed(emitter: Option<&EventEmitter>, window_label: &str, old_port: u16, new_port: u16) {
+    if let Some(emitter) = emitter {
+        emitter("sidecar://port-changed", serde_json::json!({
+            "window_label": window_label,
+            "old_port": old_port,
+            "new_port": new_port,
+        }));
+    }
+}
+
+/// Delete a sidecar's ready file; it may never have been written
+fn remove_ready_file(path: &Path) {
+    if let Err(e) = std::fs::remove_file(path) {
+        if e.kind() != std::io::ErrorKind::NotFound {
+            eprintln!("Failed to remove ready file {}: {}", path.display(), e);
+        }
+    }
+}
+
+impl Drop for SidecarManager {
+    fn drop(&mut self) {
+        // Teardown happens in `shutdown()`; here we only release background tasks
+        self.shutdown_tx.send_replace(true);
+        if let Ok(processes) = self.processes.try_lock() {
+            if !processes.is_empty() {
+                eprintln!("SidecarManager dropped with {} sidecar(s) running; call shutdown() first",
+                          processes.len());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Launcher that stands in for the real sidecar: a stdlib-only Python script that
+    /// accepts TCP connections on `--ws-port` until killed, writing `--ready-file` once bound
+    fn fake_sidecar_launcher() -> FakeLauncher {
+        fake_launcher(r#"
+import socket, sys
+port = int(sys.argv[sys.argv.index("--ws-port") + 1])
+server = socket.socket()
+server.setsockopt(socket.SOL_SOCKET, socket.SO_REUSEADDR, 1)
+server.bind(("127.0.0.1", port))
+server.listen()
+with open(sys.argv[sys.argv.index("--ready-file") + 1], "w") as ready:
+    ready.write(str(port))
+while True:
+    conn, _ = server.accept()
+    conn.close()
+"#)
+    }
+
+    /// Like [`fake_sidecar_launcher`], but completes the WebSocket handshake and pushes a
+    /// `tick` notification every 50ms to each client
+    fn fake_ws_sidecar_launcher() -> FakeLauncher {
+        fake_ws_launcher(r#"
+def serve(conn):
+    handshake(conn)
+    try:
+        while True:
+            send_json(conn, {"jsonrpc": "2.0", "method": "tick", "params": {}})
+            time.sleep(0.05)
+    except OSError:
+        pass
+"#)
+    }
+
+    /// Fake sidecar that answers WebSocket pings and ignores everything else
+    fn fake_pong_sidecar_launcher() -> FakeLauncher {
+        fake_ws_launcher(r#"
+def serve(conn):
+    handshake(conn)
+    while True:
+        opcode, payload = recv_frame(conn)
+        if opcode == 0x9:
+            conn.sendall(bytes([0x8A, len(payload)]) + payload)
+"#)
+    }
+
+    /// Fake sidecar answering the soft-shutdown RPC after `steps` progress reports 100ms apart,
+    /// or going silent after them when `stall` is set
+    fn fake_flushing_sidecar_launcher(steps: usize, stall: bool) -> FakeLauncher {
+        let script = format!("STEPS, STALL = {}, {}\n", steps, if stall { "True" } else { "False" });
+        fake_ws_launcher(&(script + r#"
+def serve(conn):
+    handshake(conn)
+    request = recv_json(conn)
+    for step in range(STEPS):
+        send_json(conn, {"jsonrpc": "2.0", "method": "shutdown.progress", "params": {"step": step}})
+        time.sleep(0.1)
+    if STALL:
+        time.sleep(3600)
+    send_json(conn, {"jsonrpc": "2.0", "result": "flushed", "id": request["id"]})
+"#))
+    }
+
+    /// Launcher for a stdlib WebSocket server on `--ws-port` that runs `serve(conn)`, defined by
+    /// `script`, on each connection. `script` can use `handshake`, `send_json`, `recv_frame` and
+    /// `recv_json`.
+    fn fake_ws_launcher(script: &str) -> FakeLauncher {
+        fake_launcher(&format!("{}{}{}", FAKE_WS_PRELUDE, script, r#"
+with open(sys.argv[sys.argv.index("--ready-file") + 1], "w") as ready:
+    ready.write(str(port))
+while True:
+    conn, _ = server.accept()
+    threading.Thread(target=serve, args=(conn,), daemon=True).start()
+"#))
+    }
+
+    const FAKE_WS_PRELUDE: &str = r#"
+import base64, hashlib, json, socket, struct, sys, threading, time
+port = int(sys.argv[sys.argv.index("--ws-port") + 1])
+server = socket.socket()
+server.setsockopt(socket.SOL_SOCKET, socket.SO_REUSEADDR, 1)
+server.bind(("127.0.0.1", port))
+server.listen()
+
+def handshake(conn):
+    request = conn.recv(4096).decode()
+    key = next(l.split(":", 1)[1].strip() for l in request.split("\r\n") if l.lower().startswith("sec-websocket-key"))
+    accept = base64.b64encode(hashlib.sha1((key + "258EAFA5-E914-47DA-95CA-C5AB0DC85B11").encode()).digest()).decode()
+    conn.sendall(("HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n"
+                  "Sec-WebSocket-Accept: " + accept + "\r\n\r\n").encode())
+
+def send_json(conn, message):
+    payload = json.dumps(message).encode()
+    header = bytes([0x81, len(payload)]) if len(payload) < 126 else bytes([0x81, 126]) + struct.pack(">H", len(payload))
+    conn.sendall(header + payload)
+
+def recv_exact(conn, n):
+    data = b""
+    while len(data) < n:
+        chunk = conn.recv(n - len(data))
+        if not chunk:
+            raise OSError("connection closed")
+        data += chunk
+    return data
+
+def recv_frame(conn):
+    header = recv_exact(conn, 2)
+    length = header[1] & 0x7F
+    if length == 126:
+        length = struct.unpack(">H", recv_exact(conn, 2))[0]
+    mask = recv_exact(conn, 4)
+    payload = recv_exact(conn, length)
+    return header[0] & 0x0F, bytes(b ^ mask[i % 4] for i, b in enumerate(payload))
+
+def recv_json(conn):
+    return json.loads(recv_frame(conn)[1])
+"#;
+
+    /// Launcher running a script with the test machine's Python. The script is deleted on drop,
+    /// so keep this alive as long as the sidecar may (re)start.
+    struct FakeLauncher {
+        argv: Vec<String>,
+        script: PathBuf,
+    }
+
+    impl FakeLauncher {
+        fn argv(&self) -> Vec<String> {
+            self.argv.clone()
+        }
+    }
+
+    impl Drop for FakeLauncher {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.script);
+        }
+    }
+
+    /// Launcher running `script` with the test machine's Python
+    fn fake_launcher(script: &str) -> FakeLauncher {
+        let path = std::env::temp_dir().join(format!("tailor-fake-sidecar-{}.py", uuid::Uuid::new_v4()));
+        std::fs::write(&path, script).unwrap();
+        let python = python::resolve(None, None, None, None).unwrap().path;
+        FakeLauncher { argv: vec![python, path.to_string_lossy().to_string()], script: path }
+    }
+
+    /// The system temp dir, standing in for a vault
+    fn temp_vault() -> String {
+        std::env::temp_dir().to_string_lossy().to_string()
+    }
+
+    async fn wait_ready(manager: &SidecarManager, label: &str, port: u16) {
+        let ready_file = manager.processes.lock().await[label].ready_file.clone();
+        SidecarManager::wait_until_ready(&manager.processes, label, &ready_file, port, Duration::from_secs(10))
+            .await
+            .unwrap();
+    }
+
+    #[test]
+    fn test_is_port_available() {
+        let manager = SidecarManager::new();
+        // Find an open port by binding 0
+        let listener = std::net::TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        
+        // Port should be unavailable because we bound to it
+        assert!(!manager.is_port_available(port));
+        
+        // Drop the listener to free the port
+        drop(listener);
+        
+        // Now it should be available
+        assert!(manager.is_port_available(port));
+    }
+
+    #[test]
+    fn test_ipv6_listener_makes_port_unavailable() {
+        // Hosts without IPv6 loopback can't run this
+        let Ok(listener) = std::net::TcpListener::bind((Ipv6Addr::LOCALHOST, 0)) else { return };
+        let port = listener.local_addr().unwrap().port();
+
+        assert!(!SidecarManager::new().is_port_available(port));
+        assert!(port_available_on(&[Ipv4Addr::LOCALHOST.into()], port), "IPv4-only check can't see it");
+        drop(listener);
+        assert!(SidecarManager::new().is_port_available(port));
+    }
+
+    #[tokio::test]
+    async fn test_remote_hosts_are_reached_over_tls() {
+        let manager = SidecarManager::new();
+        let with_host = |host: &str| SpawnOptions { host: Some(host.to_string()), ..Default::default() };
+
+        assert_eq!(manager.host_for(&SpawnOptions::default()).unwrap(), "127.0.0.1");
+        assert_eq!(manager.host_for(&with_host("::1")).unwrap(), "::1");
+        assert_eq!(manager.host_for(&with_host("LOCALHOST")).unwrap(), "LOCALHOST");
+        assert_eq!(manager.host_for(&with_host("10.0.0.7")).unwrap(), "10.0.0.7");
+        assert!(matches!(manager.host_for(&with_host(" ")), Err(SidecarError::InvalidOptions(_))));
+        assert_eq!(connection::ws_url("::1", 9000), "ws://[::1]:9000");
+        assert_eq!(connection::ws_url("localhost", 9000), "ws://localhost:9000");
+        assert_eq!(connection::ws_url("sidecar.lan", 9000), "wss://sidecar.lan:9000");
+        assert_eq!(connection::ws_url("fd00::7", 9000), "wss://[fd00::7]:9000");
+
+        // A plain listener fails the TLS handshake instead of being talked to in the clear
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("wss://127.0.0.1:{}", listener.local_addr().unwrap().port());
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let _ = tokio::io::AsyncWriteExt::write_all(&mut socket, b"HTTP/1.1 400 Bad Request\r\n\r\n").await;
+        });
+        assert!(tokio_tungstenite::connect_async(url).await.is_err());
+    }
+
+    #[test]
+    fn test_sidecar_python_path_puts_root_first() {
+        let root = Path::new("/opt/tailor");
+        assert_eq!(sidecar_python_path(root, None).unwrap(), OsString::from("/opt/tailor"));
+
+        let inherited = std::env::join_paths(["/a", "/b"]).unwrap();
+        let joined = sidecar_python_path(root, Some(&inherited)).unwrap();
+        let paths: Vec<PathBuf> = std::env::split_paths(&joined).collect();
+        assert_eq!(paths, [PathBuf::from("/opt/tailor"), PathBuf::from("/a"), PathBuf::from("/b")]);
+    }
+
+    #[tokio::test]
+    async fn test_fan_out_limits_concurrency_and_keeps_order() {
+        let running = AtomicUsize::new(0);
+        let peak = AtomicUsize::new(0);
+        let results = fan_out((0..10u64).collect(), 3, |n| {
+            let (running, peak) = (&running, &peak);
+            async move {
+                let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                peak.fetch_max(now, Ordering::SeqCst);
+                // Later items finish first
+                tokio::time::sleep(Duration::from_millis(30 - n * 2)).await;
+                running.fetch_sub(1, Ordering::SeqCst);
+                n
+            }
+        })
+        .await;
+
+        assert_eq!(results, (0..10).collect::<Vec<_>>());
+        assert_eq!(peak.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn test_ring_buffer_drops_oldest() {
+        let mut buffer = RingBuffer::new(2);
+        buffer.push(1);
+        buffer.push(2);
+        buffer.push(3);
+        assert_eq!(buffer.snapshot(), vec![2, 3]);
+    }
+
+    #[tokio::test]
+    async fn test_wait_until_ready() {
+        let processes = Mutex::new(HashMap::new());
+        let ready_file = std::env::temp_dir().join(format!("tailor-test-{}.ready", uuid::Uuid::new_v4()));
+
+        // No file and no tracked process: gives up without waiting for the timeout
+        assert!(SidecarManager::wait_until_ready(&processes, "w", &ready_file, 9000, Duration::from_secs(5))
+            .await
+            .is_err());
+
+        std::fs::write(&ready_file, "9000").unwrap();
+        assert_eq!(SidecarManager::wait_until_ready(&processes, "w", &ready_file, 9000, Duration::from_secs(1))
+            .await
+            .unwrap(), 9000);
+
+        // A sidecar that bound a different port is reached there
+        assert_eq!(SidecarManager::wait_until_ready(&processes, "w", &ready_file, 9001, Duration::from_secs(1))
+            .await
+            .unwrap(), 9000);
+        std::fs::write(&ready_file, "ready").unwrap();
+        assert!(SidecarManager::wait_until_ready(&processes, "w", &ready_file, 9000, Duration::from_secs(1))
+            .await
+            .is_err());
+        remove_ready_file(&ready_file);
+    }
+
+    #[tokio::test]
+    #[ignore = "spawns Python; run with --include-ignored"]
+    async fn test_commands_follow_a_fallback_port() {
+        // Listens wherever the OS puts it instead of on `--ws-port`, and says so in the ready file
+        let launcher = fake_ws_launcher(r#"
+server.close()
+server = socket.socket()
+server.bind(("127.0.0.1", 0))
+server.listen()
+port = server.getsockname()[1]
+def serve(conn):
+    handshake(conn)
+    request = recv_json(conn)
+    send_json(conn, {"jsonrpc": "2.0", "id": request["id"], "result": "pong"})
+"#);
+        let events = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = events.clone();
+        let manager = SidecarManager::new().with_event_emitter(Arc::new(move |event: &str, payload| {
+            match event {
+                "sidecar://ready" => sink.lock().unwrap().push(("ready", payload["ws_port"].clone(), None)),
+                "sidecar://port-changed" => {
+                    sink.lock().unwrap().push(("port-changed", payload["old_port"].clone(), Some(payload["new_port"].clone())));
+                }
+                _ => {}
+            }
+        }));
+        let options = SpawnOptions { launcher: Some(launcher.argv()), ..Default::default() };
+        let requested = manager.spawn_sidecar_with_options("w".into(), temp_vault(), options).await.unwrap();
+        let mut state = manager.watch_state("w");
+        tokio::time::timeout(Duration::from_secs(10), state.wait_for(|s| *s == SidecarState::Ready)).await
+            .unwrap()
+            .unwrap();
+
+        let bound = manager.get_ws_port("w").await.unwrap();
+        assert_ne!(bound, requested);
+        assert_eq!(*events.lock().unwrap(), [
+            ("port-changed", requested.into(), Some(bound.into())),
+            ("ready", bound.into(), None),
+        ]);
+        let response = manager.send_command("w", "ping", serde_json::json!({})).await.unwrap();
+        assert_eq!(response["result"], "pong");
+
+        // The restarted sidecar moves again, away from the port it is restarted on
+        events.lock().unwrap().clear();
+        let restarted = manager.restart_sidecar("w").await.unwrap();
+        tokio::time::timeout(Duration::from_secs(10), state.wait_for(|s| *s == SidecarState::Ready)).await
+            .unwrap()
+            .unwrap();
+        let moved = manager.get_ws_port("w").await.unwrap();
+        assert_ne!(moved, restarted);
+        let events = events.lock().unwrap().clone();
+        assert!(events.contains(&("port-changed", restarted.into(), Some(moved.into()))), "{:?}", events);
+        assert_eq!(events.last(), Some(&("ready", moved.into(), None)));
+        manager.shutdown().await;
+    }
+
+    #[tokio::test]
+    #[ignore = "spawns Python; run with --include-ignored"]
+    async fn test_terminate_closes_the_persistent_connection_cleanly() {
+        // Answers requests, and records a Close frame before echoing it
+        let marker = std::env::temp_dir().join(format!("tailor-close-{}", uuid::Uuid::new_v4()));
+        let launcher = fake_ws_launcher(&format!(r#"
+MARKER = {:?}
+def serve(conn):
+    handshake(conn)
+    while True:
+        opcode, payload = recv_frame(conn)
+        if opcode == 8:
+            with open(MARKER, "w") as marker:
+                marker.write("closed")
+            conn.sendall(bytes([0x88, 0]))
+            conn.close()
+            return
+        if opcode == 1:
+            request = json.loads(payload)
+            send_json(conn, {{"jsonrpc": "2.0", "id": request["id"], "result": "ok"}})
+"#, marker.to_string_lossy()));
+        let manager = SidecarManager::with_config(SidecarManagerConfig {
+            persistent_connections: true,
+            ..Default::default()
+        });
+        let options = SpawnOptions { launcher: Some(launcher.argv()), ..Default::default() };
+        let port = manager.spawn_sidecar_with_options("w".into(), temp_vault(), options).await.unwrap();
+        wait_ready(&manager, "w", port).await;
+        manager.send_command("w", "echo", serde_json::json!({})).await.unwrap();
+
+        manager.terminate_sidecar("w").await.unwrap();
+        assert_eq!(std::fs::read_to_string(&marker).unwrap(), "closed");
+        let _ = std::fs::remove_file(&marker);
+        manager.shutdown().await;
+    }
+
+    #[tokio::test]
+    #[ignore = "spawns Python; run with --include-ignored"]
+    async fn test_startup_logs_stream_until_ready_or_failed() {
+        async fn collect(mut logs: mpsc::UnboundedReceiver<LogLine>) -> Vec<(LogStream, String)> {
+            let mut lines = Vec::new();
+            while let Some(line) = tokio::time::timeout(Duration::from_secs(10), logs.recv()).await.unwrap() {
+                lines.push((line.stream, line.text));
+            }
+            lines
+        }
+        let manager = SidecarManager::new();
+        let boot = "print('loading plugins', flush=True)\nprint('downloading model', file=sys.stderr, flush=True)\n";
+        let launcher = fake_ws_launcher(&format!("{}def serve(conn):\n    handshake(conn)\n", boot));
+        let options = SpawnOptions { launcher: Some(launcher.argv()), ..Default::default() };
+        let (tx, rx) = mpsc::unbounded_channel();
+        manager.spawn_sidecar_with_startup_logs("w".into(), temp_vault(), options, SpawnCancel::new(), tx).await.unwrap();
+        let lines = collect(rx).await;
+        assert!(lines.contains(&(LogStream::Stdout, "loading plugins".to_string())), "{:?}", lines);
+        assert!(lines.contains(&(LogStream::Stderr, "downloading model".to_string())), "{:?}", lines);
+        assert_eq!(*manager.watch_state("w").borrow(), SidecarState::Ready);
+
+        // A sidecar that dies while booting closes the stream with its last words
+        let launcher = fake_launcher("import sys\nprint('ImportError: no module named torch', file=sys.stderr, flush=True)\nsys.exit(1)\n");
+        let options = SpawnOptions { launcher: Some(launcher.argv()), ..Default::default() };
+        let (tx, rx) = mpsc::unbounded_channel();
+        let err = manager.spawn_sidecar_with_startup_logs("x".into(), temp_vault(), options, SpawnCancel::new(), tx).await.unwrap_err();
+        assert!(matches!(err, SidecarError::SpawnFailed(_)), "{:?}", err);
+        let lines = collect(rx).await;
+        assert!(lines.contains(&(LogStream::Stderr, "ImportError: no module named torch".to_string())), "{:?}", lines);
+        assert!(lines.iter().all(|(_, text)| !text.contains("loading plugins")));
+        assert_eq!(*manager.watch_state("x").borrow(), SidecarState::Failed);
+        manager.shutdown().await;
+    }
+
+    #[tokio::test]
+    #[ignore = "spawns Python; run with --include-ignored"]
+    async fn test_duplicate_spawn_never_leaks_the_first_process() {
+        let launcher = fake_ws_launcher("def serve(conn):\n    handshake(conn)\n");
+        let options = || SpawnOptions {
+            launcher: Some(launcher.argv()),
+            ..Default::default()
+        };
+        #[cfg(unix)]
+        let alive = |pid: u32| unsafe { libc::kill(pid as libc::pid_t, 0) } == 0;
+        for policy in [DuplicateSpawn::Reject, DuplicateSpawn::Reuse, DuplicateSpawn::Replace] {
+            let manager = SidecarManager::with_config(SidecarManagerConfig { duplicate_spawn: policy, ..Default::default() });
+            let first = manager.spawn_sidecar_with_options("w".into(), temp_vault(), options()).await.unwrap();
+            let first_pid = manager.processes.lock().await["w"].child.id();
+
+            let second = manager.spawn_sidecar_with_options("w".into(), temp_vault(), options()).await;
+            let pid = manager.processes.lock().await["w"].child.id();
+            match policy {
+                DuplicateSpawn::Reject => {
+                    assert!(matches!(second, Err(SidecarError::AlreadyRunning { ws_port, .. }) if ws_port == first), "{:?}", second);
+                    assert_eq!(pid, first_pid);
+                }
+                DuplicateSpawn::Reuse => {
+                    assert_eq!(second.unwrap(), first);
+                    assert_eq!(pid, first_pid);
+                    // Another vault can't be served by the running sidecar
+                    let other = manager.spawn_sidecar_with_options("w".into(), "/".into(), options()).await;
+                    assert!(matches!(other, Err(SidecarError::AlreadyRunning { .. })), "{:?}", other);
+                }
+                DuplicateSpawn::Replace => {
+                    second.unwrap();
+                    assert_ne!(pid, first_pid);
+                    #[cfg(unix)]
+                    assert!(!alive(first_pid), "replaced sidecar {} still running", first_pid);
+                }
+            }
+            #[cfg(unix)]
+            assert!(alive(pid));
+            manager.shutdown().await;
+        }
+    }
+
+    #[tokio::test]
+    #[ignore = "spawns Python; run with --include-ignored"]
+    async fn test_spawn_command_preview_matches_the_launch() {
+        let vault = std::env::temp_dir().join(format!("tailor-vault-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&vault).unwrap();
+        std::fs::write(vault.join("tailor.toml"), "extra_args = [\"--verbose\"]\n[env]\nGREETING = \"hello world\"\n").unwrap();
+        let vault_path = vault.to_string_lossy().to_string();
+        let manager = SidecarManager::new();
+        // Away from the ports other tests' managers start at
+        *manager.next_port.lock().await = 18500;
+
+        let preview = manager.spawn_command_preview("w", &vault_path).await.unwrap();
+        let previewed_port = preview.argv.windows(2).find(|pair| pair[0] == "--ws-port").unwrap()[1].clone();
+        let again = manager.spawn_command_preview("w", &vault_path).await.unwrap();
+        assert!(again.argv.windows(2).any(|pair| pair == ["--ws-port", previewed_port.as_str()]));
+        assert_eq!(preview.argv[0], preview.python.path);
+        assert!(preview.argv.windows(2).any(|pair| pair == ["--vault", vault_path.as_str()]), "{:?}", preview.argv);
+        assert_eq!(preview.argv.last().unwrap(), "--verbose");
+        assert_eq!(preview.env["GREETING"], "hello world");
+        assert!(preview.env.contains_key("PYTHONPATH"));
+        assert!(preview.shell.contains("GREETING='hello world'"), "{}", preview.shell);
+        assert!(manager.get_ws_port("w").await.is_none());
+
+        // A running window is previewed with its own options and port
+        let launcher = fake_ws_launcher("def serve(conn):\n    handshake(conn)\n");
+        let options = SpawnOptions { launcher: Some(launcher.argv()), ..Default::default() };
+        let port = manager.spawn_sidecar_with_options("w".into(), vault_path.clone(), options).await.unwrap();
+        // Previews leave the ports they showed to the next spawn
+        assert_eq!(port.to_string(), previewed_port);
+        let preview = manager.spawn_command_preview("w", &vault_path).await.unwrap();
+        assert_eq!(preview.argv[..launcher.argv.len()], launcher.argv[..]);
+        assert!(preview.argv.windows(2).any(|pair| pair == ["--ws-port", port.to_string().as_str()]));
+        manager.shutdown().await;
+        let _ = std::fs::remove_dir_all(&vault);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    #[ignore = "spawns Python; run with --include-ignored"]
+    async fn test_spawn_can_install_dependencies_first() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let vault = std::env::temp_dir().join(format!("tailor-vault-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(vault.join("plugins")).unwrap();
+        std::fs::write(vault.join("plugins").join("requirements.txt"), "requests>=2\n").unwrap();
+        // A venv whose "pip" installs `requests` offline: `-m pip install --upgrade --target <lib> ...`
+        let venv_bin = vault.join(".venv").join("bin");
+        std::fs::create_dir_all(&venv_bin).unwrap();
+        std::fs::write(venv_bin.join("python"), "#!/bin/sh\nmkdir -p \"$6/requests-2.31.0.dist-info\"\n").unwrap();
+        std::fs::set_permissions(venv_bin.join("python"), std::fs::Permissions::from_mode(0o755)).unwrap();
+        let events = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = events.clone();
+        // pixi would take precedence over the vault's venv and reach the network
+        let manager = SidecarManager::with_config(SidecarManagerConfig {
+            pixi_executable: "/nonexistent/pixi".to_string(),
+            ..Default::default()
+        }).with_event_emitter(Arc::new(move |event: &str, payload| {
+            if event == "deps://progress" {
+                sink.lock().unwrap().push((payload["stage"].as_str().unwrap().to_string(), payload));
+            }
+        }));
+        let launcher = fake_ws_launcher("def serve(conn):\n    handshake(conn)\n");
+        let options = |install_dependencies| SpawnOptions {
+            launcher: Some(launcher.argv()),
+            install_dependencies,
+            ..Default::default()
+        };
+
+        manager.spawn_sidecar_with_options("w".into(), vault.to_string_lossy().into(), options(true)).await.unwrap();
+        {
+            let events = events.lock().unwrap();
+            let stages: Vec<&str> = events.iter().map(|(stage, _)| stage.as_str()).collect();
+            assert_eq!(stages, ["checking", "installing", "ready"]);
+            assert_eq!(events[1].1["missing"], serde_json::json!(["requests"]));
+            assert_eq!(events[2].1["backend"], "pip");
+        }
+        assert_eq!(DependencyChecker::needs_update_detailed(&vault.to_string_lossy()).await, DependencyStatus::UpToDate);
+
+        // Opting out skips the check, even for a vault that can't be inspected
+        events.lock().unwrap().clear();
+        manager.spawn_sidecar_with_options("x".into(), "/nonexistent/vault".into(), options(false)).await.unwrap();
+        assert!(events.lock().unwrap().is_empty());
+
+        let err = manager.spawn_sidecar_with_options("y".into(), "/nonexistent/vault".into(), options(true)).await.unwrap_err();
+        assert!(matches!(err, SidecarError::Dependencies(_)), "unexpected error: {:?}", err);
+        assert_eq!(events.lock().unwrap().last().unwrap().0, "failed");
+        assert!(manager.get_ws_port("y").await.is_none());
+
+        // Spawning the vault again within the TTL reuses the passed check, until invalidated
+        let stages = |events: &std::sync::Mutex<Vec<(String, serde_json::Value)>>| {
+            std::mem::take(&mut *events.lock().unwrap()).into_iter().map(|(stage, _)| stage).collect::<Vec<_>>()
+        };
+        events.lock().unwrap().clear();
+        manager.spawn_sidecar_with_options("z".into(), vault.to_string_lossy().into(), options(true)).await.unwrap();
+        assert_eq!(stages(&events), ["ready"]);
+        assert!(manager.invalidate_dependency_cache(&vault.to_string_lossy()));
+        manager.terminate_sidecar("z").await.unwrap();
+        manager.spawn_sidecar_with_options("z".into(), vault.to_string_lossy().into(), options(true)).await.unwrap();
+        assert_eq!(stages(&events), ["checking", "ready"]);
+        manager.shutdown().await;
+        let _ = std::fs::remove_dir_all(&vault);
+    }
+
+    #[tokio::test]
+    #[ignore = "spawns Python; run with --include-ignored"]
+    async fn test_coalesced_commands_only_send_the_latest() {
+        let launcher = fake_ws_launcher(r#"
+searches = []
+
+def serve(conn):
+    handshake(conn)
+    request = recv_json(conn)
+    if request["method"] == "search":
+        searches.append(request["params"]["q"])
+        result = request["params"]["q"]
+    else:
+        result = searches
+    send_json(conn, {"jsonrpc": "2.0", "id": request["id"], "result": result})
+"#);
+        let manager = SidecarManager::new();
+        let options = SpawnOptions { launcher: Some(launcher.argv()), ..Default::default() };
+        let port = manager.spawn_sidecar_with_options("w".into(), temp_vault(), options).await.unwrap();
+        wait_ready(&manager, "w", port).await;
+
+        let debounce = Duration::from_millis(100);
+        let typed = ["s", "se", "sea", "sear"].map(|q| {
+            manager.send_command_coalesced("w", "search", serde_json::json!({"q": q}), "box", debounce)
+        });
+        let results: Vec<Coalesced> = futures::future::join_all(typed).await
+            .into_iter()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(results[..3], [Coalesced::Superseded, Coalesced::Superseded, Coalesced::Superseded]);
+        assert!(matches!(&results[3], Coalesced::Sent(response) if response["result"] == "sear"), "{:?}", results[3]);
+
+        // Other keys and plain commands are left alone
+        let other = manager.send_command_coalesced("w", "search", serde_json::json!({"q": "x"}), "sidebar", debounce).await.unwrap();
+        assert!(matches!(other, Coalesced::Sent(_)));
+        let seen = manager.send_command("w", "seen", serde_json::json!({})).await.unwrap();
+        assert_eq!(seen["result"], serde_json::json!(["sear", "x"]));
+        manager.shutdown().await;
+    }
+
+    #[tokio::test]
+    #[ignore = "spawns Python; run with --include-ignored"]
+    async fn test_try_send_command_fails_fast_when_busy() {
+        // Slow to start; `slow` takes a while to answer
+        let launcher = fake_ws_launcher(r#"
+time.sleep(0.5)
+def serve(conn):
+    handshake(conn)
+    request = recv_json(conn)
+    if request["method"] == "slow":
+        time.sleep(0.5)
+    send_json(conn, {"jsonrpc": "2.0", "id": request["id"], "result": request["method"]})
+"#);
+        let manager = Arc::new(SidecarManager::with_config(SidecarManagerConfig {
+            max_in_flight: Some(1),
+            ..Default::default()
+        }));
+        let options = SpawnOptions { launcher: Some(launcher.argv()), ..Default::default() };
+        manager.spawn_sidecar_with_options("w".into(), temp_vault(), options).await.unwrap();
+        let err = manager.try_send_command("w", "fast", serde_json::json!({})).await.unwrap_err();
+        assert!(matches!(err, SidecarError::Busy { state: SidecarState::Starting, .. }), "unexpected error: {:?}", err);
+
+        let mut state = manager.watch_state("w");
+        tokio::time::timeout(Duration::from_secs(10), state.wait_for(|s| *s == SidecarState::Ready)).await
+            .unwrap()
+            .unwrap();
+        let slow = tokio::spawn({
+            let manager = manager.clone();
+            async move { manager.send_command("w", "slow", serde_json::json!({})).await }
+        });
+        state.wait_for(|s| *s == SidecarState::Busy).await.unwrap();
+
+        let err = manager.try_send_command("w", "fast", serde_json::json!({})).await.unwrap_err();
+        assert!(matches!(err, SidecarError::Busy { state: SidecarState::Busy, .. }), "unexpected error: {:?}", err);
+        // A queued command waits for the slot instead
+        let queued = manager.send_command("w", "fast", serde_json::json!({})).await.unwrap();
+        assert_eq!(queued["result"], "fast");
+        assert_eq!(slow.await.unwrap().unwrap()["result"], "slow");
+
+        let response = manager.try_send_command("w", "fast", serde_json::json!({})).await.unwrap();
+        assert_eq!(response["result"], "fast");
+        assert!(matches!(manager.try_send_command("x", "fast", serde_json::json!({})).await, Err(SidecarError::NotFound { .. })));
+        manager.shutdown().await;
+    }
+
+    #[tokio::test]
+    #[ignore = "spawns Python; run with --include-ignored"]
+    async fn test_send_all_keeps_order_past_failures() {
+        // `first` is answered last and `bad` with an error
+        let launcher = fake_ws_launcher(r#"
+def serve(conn):
+    handshake(conn)
+    request = recv_json(conn)
+    time.sleep(0.1 if request["method"] == "first" else 0)
+    if request["method"] == "bad":
+        send_json(conn, {"jsonrpc": "2.0", "id": request["id"], "error": {"code": -32000, "message": "bad"}})
+    else:
+        send_json(conn, {"jsonrpc": "2.0", "id": request["id"], "result": request["method"]})
+"#);
+        let manager = SidecarManager::with_config(SidecarManagerConfig {
+            max_in_flight: Some(2),
+            ..Default::default()
+        });
+        let options = SpawnOptions { launcher: Some(launcher.argv()), ..Default::default() };
+        let port = manager.spawn_sidecar_with_options("w".into(), temp_vault(), options).await.unwrap();
+        wait_ready(&manager, "w", port).await;
+
+        let commands = ["first", "bad", "third"].map(|method| (method.to_string(), serde_json::json!({})));
+        let results = manager.send_all("w", commands.to_vec()).await;
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].as_ref().unwrap()["result"], "first");
+        assert!(matches!(results[1], Err(SidecarError::Rpc { .. })), "{:?}", results[1]);
+        assert_eq!(results[2].as_ref().unwrap()["result"], "third");
+        assert!(manager.send_all("w", Vec::new()).await.is_empty());
+        manager.shutdown().await;
+    }
+
+    #[tokio::test]
+    #[ignore = "spawns Python; run with --include-ignored"]
+    async fn test_rate_limit_paces_commands() {
+        let launcher = fake_ws_launcher(r#"
+def serve(conn):
+    handshake(conn)
+    request = recv_json(conn)
+    send_json(conn, {"jsonrpc": "2.0", "id": request["id"], "result": request["method"]})
+"#);
+        let manager = SidecarManager::with_config(SidecarManagerConfig {
+            rate_limit: Some(RateLimitConfig { per_second: 100.0, burst: 100 }),
+            ..Default::default()
+        });
+        // The spawn option wins over the manager-wide limit
+        let rate_limit = Some(RateLimitConfig { per_second: 10.0, burst: 1 });
+        let options = SpawnOptions { launcher: Some(launcher.argv()), rate_limit, ..Default::default() };
+        let port = manager.spawn_sidecar_with_options("w".into(), temp_vault(), options).await.unwrap();
+        wait_ready(&manager, "w", port).await;
+
+        let started = Instant::now();
+        for _ in 0..3 {
+            manager.send_command("w", "ping", serde_json::json!({})).await.unwrap();
+        }
+        assert!(started.elapsed() >= Duration::from_millis(190));
+        let err = manager.try_send_command("w", "ping", serde_json::json!({})).await.unwrap_err();
+        assert!(matches!(err, SidecarError::RateLimited { .. }), "unexpected error: {:?}", err);
+
+        let stats = manager.rate_limit_stats("w").await.unwrap();
+        assert_eq!((stats.burst, stats.throttled, stats.rejected), (1, 2, 1));
+        assert!(manager.rate_limit_stats("x").await.is_none());
+        manager.shutdown().await;
+    }
+
+    #[tokio::test]
+    #[ignore = "spawns Python; run with --include-ignored"]
+    async fn test_commands_are_audited() {
+        let launcher = fake_ws_launcher(r#"
+def serve(conn):
+    handshake(conn)
+    request = recv_json(conn)
+    if request["method"] == "fail":
+        send_json(conn, {"jsonrpc": "2.0", "id": request["id"], "error": {"code": -1, "message": "nope"}})
+    else:
+        send_json(conn, {"jsonrpc": "2.0", "id": request["id"], "result": "found"})
+"#);
+        let dir = std::env::temp_dir().join(format!("tailor-audit-{}", uuid::Uuid::new_v4()));
+        let manager = SidecarManager::with_config(SidecarManagerConfig {
+            audit_log_dir: Some(dir.clone()),
+            ..Default::default()
+        });
+        let options = SpawnOptions { launcher: Some(launcher.argv()), ..Default::default() };
+        let port = manager.spawn_sidecar_with_options("w".into(), temp_vault(), options).await.unwrap();
+        wait_ready(&manager, "w", port).await;
+
+        let params = serde_json::json!({"query": "notes", "api_key": "hunter2"});
+        manager.send_command("w", "search", params.clone()).await.unwrap();
+        assert!(manager.send_command("w", "fail", serde_json::json!({})).await.is_err());
+        manager.shutdown().await;
+
+        let records: Vec<serde_json::Value> = std::fs::read_to_string(dir.join("w.jsonl")).unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(records.len(), 4);
+        assert_eq!(records[0]["type"], "request");
+        assert_eq!(records[0]["params"], serde_json::json!({"query": "notes", "api_key": "[redacted]"}));
+        assert_eq!(records[0]["params_bytes"], params.to_string().len());
+        assert_eq!(records[1]["type"], "response");
+        assert_eq!(records[1]["seq"], 0);
+        assert_eq!(records[1]["status"], "ok");
+        assert!(records[1]["result_bytes"].as_u64().unwrap() > 0);
+        assert!(records[1]["latency_ms"].as_f64().is_some());
+        assert_eq!(records[3]["seq"], 1);
+        assert_eq!(records[3]["error"], "Sidecar returned error -1: nope");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore = "spawns Python; run with --include-ignored"]
+    async fn test_moved_vault_is_detected_and_rebound() {
+        let vault = std::env::temp_dir().join(format!("tailor-vault-{}", uuid::Uuid::new_v4()));
+        let moved = vault.with_extension("moved");
+        std::fs::create_dir_all(&vault).unwrap();
+        let launcher = fake_ws_launcher(r#"
+def serve(conn):
+    handshake(conn)
+    request = recv_json(conn)
+    send_json(conn, {"jsonrpc": "2.0", "id": request["id"], "result": request.get("params")})
+"#);
+        let events = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let recorded = events.clone();
+        let manager = Arc::new(SidecarManager::with_config(SidecarManagerConfig {
+            vault_check_interval: Duration::from_millis(50),
+            ..Default::default()
+        }).with_event_emitter(Arc::new(move |event: &str, payload| {
+            if event.starts_with("vault://") {
+                recorded.lock().unwrap().push((event.to_string(), payload));
+            }
+        })));
+        let monitor = tokio::spawn({
+            let manager = manager.clone();
+            async move { manager.run_vault_monitor().await }
+        });
+        let options = SpawnOptions { launcher: Some(launcher.argv()), ..Default::default() };
+        let vault_path = vault.to_string_lossy().to_string();
+        let port = manager.spawn_sidecar_with_options("w".into(), vault_path.clone(), options).await.unwrap();
+        wait_ready(&manager, "w", port).await;
+
+        std::fs::rename(&vault, &moved).unwrap();
+        let mut state = manager.watch_state("w");
+        tokio::time::timeout(Duration::from_secs(5), state.wait_for(|s| *s == SidecarState::PathLost)).await
+            .unwrap()
+            .unwrap();
+        let err = manager.send_command("w", "search", serde_json::json!({})).await.unwrap_err();
+        assert!(matches!(&err, SidecarError::VaultPathLost { vault_path: lost, .. } if *lost == vault_path), "{:?}", err);
+        assert_eq!(events.lock().unwrap()[0], ("vault://path-invalid".to_string(),
+                                               serde_json::json!({"window_label": "w", "vault_path": vault_path})));
+
+        let moved_path = moved.to_string_lossy().to_string();
+        assert!(manager.rebind_vault("w", &vault_path).await.is_err());
+        assert_eq!(manager.rebind_vault("w", &moved_path).await.unwrap(), Rebind::HotSwitched);
+        assert_eq!(*manager.watch_state("w").borrow(), SidecarState::Ready);
+        assert_eq!(manager.processes.lock().await["w"].vault_path, moved_path);
+        // Stays ready through later checks
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        assert!(manager.send_command("w", "search", serde_json::json!({})).await.is_ok());
+
+        manager.shutdown().await;
+        monitor.await.unwrap();
+        std::fs::remove_dir_all(&moved).unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore = "spawns Python; run with --include-ignored"]
+    async fn test_find_by_vault_compares_canonical_paths() {
+        let other = std::env::temp_dir().join(format!("tailor-vault-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&other).unwrap();
+        let manager = SidecarManager::new();
+        let launcher = fake_ws_launcher("def serve(conn):\n    handshake(conn)\n");
+        let temp = temp_vault();
+        // The same directory spelled differently
+        let roundabout = format!("{}/../{}/", temp, Path::new(&temp).file_name().unwrap().to_string_lossy());
+        for (label, vault) in [("a", temp.as_str()), ("b", roundabout.as_str()), ("c", other.to_str().unwrap())] {
+            let options = SpawnOptions { launcher: Some(launcher.argv()), ..Default::default() };
+            manager.spawn_sidecar_with_options(label.into(), vault.into(), options).await.unwrap();
+        }
+
+        assert_eq!(manager.find_by_vault(&temp).await, ["a", "b"]);
+        assert_eq!(manager.find_by_vault(&format!("{}/", other.display())).await, ["c"]);
+        assert!(manager.find_by_vault("/nonexistent/vault").await.is_empty());
+        manager.terminate_sidecar("a").await.unwrap();
+        assert_eq!(manager.find_by_vault(&format!("{}/", temp)).await, ["b"]);
+        manager.shutdown().await;
+        let _ = std::fs::remove_dir(&other);
+    }
+
+    #[tokio::test]
+    #[ignore = "spawns Python; run with --include-ignored"]
+    async fn test_labels_select_sidecars_for_broadcasts() {
+        // Answers with its own `--vault`
+        let launcher = fake_ws_launcher(r#"
+vault = sys.argv[sys.argv.index("--vault") + 1]
+def serve(conn):
+    handshake(conn)
+    request = recv_json(conn)
+    send_json(conn, {"jsonrpc": "2.0", "id": request["id"], "result": vault})
+"#);
+        let manager = SidecarManager::new();
+        let vault = |label: &str| std::env::temp_dir().join(label).to_string_lossy().to_string();
+        for (label, workspace) in [("a", "notes"), ("b", "code"), ("c", "notes")] {
+            let options = SpawnOptions {
+                launcher: Some(launcher.argv()),
+                labels: HashMap::from([("workspace".to_string(), workspace.to_string())]),
+                ..Default::default()
+            };
+            let port = manager.spawn_sidecar_with_options(label.into(), vault(label), options).await.unwrap();
+            wait_ready(&manager, label, port).await;
+        }
+
+        assert_eq!(manager.list_sidecars_by_label("workspace", "notes").await, ["a", "c"]);
+        assert!(manager.list_sidecars_by_label("workspace", "music").await.is_empty());
+        assert!(manager.list_sidecars_by_label("project", "notes").await.is_empty());
+
+        let results = manager.broadcast_to_label("workspace", "notes", "reload", serde_json::json!({})).await;
+        let answers: Vec<_> = results.into_iter().map(|(label, result)| (label, result.unwrap()["result"].clone())).collect();
+        assert_eq!(answers, [("a".to_string(), vault("a").into()), ("c".to_string(), vault("c").into())]);
+        manager.shutdown().await;
+    }
+
+    #[tokio::test]
+    #[ignore = "spawns Python; run with --include-ignored"]
+    async fn test_restart_reuses_port() {
+        let manager = SidecarManager::new();
+        let launcher = fake_sidecar_launcher();
+        let options = SpawnOptions {
+            launcher: Some(launcher.argv()),
+            ..Default::default()
+        };
+        let port = manager
+            .spawn_sidecar_with_options("w".into(), temp_vault(), options)
+            .await
+            .unwrap();
+        wait_ready(&manager, "w", port).await;
+
+        // Terminate and immediately respawn on the same port
+        let restarted = manager.restart_sidecar("w").await.unwrap();
+        assert_eq!(restarted, port);
+        wait_ready(&manager, "w", restarted).await;
+        assert_eq!(manager.processes.lock().await["w"].restarts.len(), 1);
+
+        let ready_file = manager.processes.lock().await["w"].ready_file.clone();
+        manager.terminate_sidecar("w").await.unwrap();
+        assert!(manager.wait_for_port_release(port).await);
+        assert!(!ready_file.exists());
+    }
+
+    #[tokio::test]
+    #[ignore = "spawns Python; run with --include-ignored"]
+    async fn test_reserved_ports_are_held_until_spawned_on() {
+        let manager = SidecarManager::new();
+        let ports = manager.reserve_ports(3).await.unwrap();
+        assert_eq!(ports.iter().collect::<HashSet<_>>().len(), 3);
+        assert!(ports.iter().all(|port| !manager.is_port_available(*port)));
+        assert!(!ports.contains(&manager.allocate_port("127.0.0.1").await.unwrap()));
+
+        let launcher = fake_sidecar_launcher();
+        let options = SpawnOptions { launcher: Some(launcher.argv()), port: Some(ports[1]), ..Default::default() };
+        let port = manager.spawn_sidecar_with_options("w".into(), temp_vault(), options.clone()).await.unwrap();
+        assert_eq!(port, ports[1]);
+        wait_ready(&manager, "w", port).await;
+        // Neither reserved nor free any more
+        assert!(!manager.release_reservation(port));
+        let err = manager.spawn_sidecar_with_options("x".into(), temp_vault(), options).await.unwrap_err();
+        assert!(matches!(err, SidecarError::InvalidOptions(_)), "{:?}", err);
+
+        assert!(manager.release_reservation(ports[0]));
+        assert!(!manager.release_reservation(ports[0]));
+        manager.shutdown().await;
+        assert!(manager.is_port_available(ports[2]));
+    }
+
+    #[tokio::test]
+    #[ignore = "spawns Python; run with --include-ignored"]
+    async fn test_migrate_all_restarts_in_spawn_order() {
+        let events = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = events.clone();
+        let manager = SidecarManager::new().with_event_emitter(Arc::new(move |event: &str, payload: serde_json::Value| {
+            if event == "python://migration" {
+                sink.lock().unwrap().push(format!("{} {}", payload["window_label"], payload["status"]));
+            }
+        }));
+        let launcher = fake_sidecar_launcher();
+        for label in ["b", "a"] {
+            let options = SpawnOptions { launcher: Some(launcher.argv()), ..Default::default() };
+            let port = manager.spawn_sidecar_with_options(label.into(), temp_vault(), options).await.unwrap();
+            wait_ready(&manager, label, port).await;
+        }
+
+        assert!(matches!(manager.migrate_all("/nonexistent/python").await, Err(SidecarError::PythonNotFound(_))));
+        let python = manager.get_python_executable().await.unwrap().path;
+        let results = manager.migrate_all(&python).await.unwrap();
+        assert_eq!(results.iter().map(|(label, result)| (label.as_str(), result.is_ok())).collect::<Vec<_>>(),
+                   [("b", true), ("a", true)]);
+        assert_eq!(*events.lock().unwrap(), [r#""b" "restarting""#, r#""b" "migrated""#, r#""a" "restarting""#, r#""a" "migrated""#]);
+        for label in ["a", "b"] {
+            let processes = manager.processes.lock().await;
+            assert_eq!((processes[label].python.path.as_str(), processes[label].restarts.len()), (python.as_str(), 1));
+        }
+        manager.shutdown().await;
+    }
+
+    #[tokio::test]
+    #[ignore = "spawns Python; run with --include-ignored"]
+    async fn test_output_matches_reach_callbacks() {
+        let manager = SidecarManager::new();
+        assert!(matches!(manager.on_output_match("(", Arc::new(|_: &str, _: &str| {})), Err(SidecarError::InvalidOptions(_))));
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        manager.on_output_match(r"^Traceback \(most recent call last\)", Arc::new(move |label: &str, line: &str| {
+            let _ = tx.send(format!("{}: {}", label, line));
+        })).unwrap();
+
+        let launcher = fake_launcher("print('starting')\nraise RuntimeError('boom')");
+        let options = SpawnOptions { launcher: Some(launcher.argv()), ..Default::default() };
+        manager.spawn_sidecar_with_options("w".into(), temp_vault(), options).await.unwrap();
+        let matched = tokio::time::timeout(Duration::from_secs(10), rx.recv()).await.unwrap().unwrap();
+        assert_eq!(matched, "w: Traceback (most recent call last):");
+        manager.terminate_sidecar("w").await.unwrap();
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_log_lines_are_broadcast() {
+        let manager = SidecarManager::new();
+        let mut logs = manager.subscribe_logs();
+        let telemetry = SidecarTelemetry::new("w", manager.log_tx.clone(), OutputMatchers::default(),
+                                              watch::channel(SidecarState::Ready).0, None, None, None);
+
+        telemetry.record_log(LogStream::Stderr, "boom".to_string(), None, None);
+
+        let line = logs.recv().await.unwrap();
+        assert_eq!(line.window_label, "w");
+        assert_eq!(line.stream, LogStream::Stderr);
+        assert_eq!(line.text, "boom");
+        assert_eq!(telemetry.logs.lock().unwrap().snapshot().len(), 1);
+    }
+
+    #[tokio::test]
+    #[ignore = "spawns Python; run with --include-ignored"]
+    async fn test_log_parser_is_applied_to_output() {
+        let manager = SidecarManager::new().with_log_parser(Arc::new(log_parser::parse_json_line));
+        let mut logs = manager.subscribe_logs();
+        let python = manager.get_python_executable().await.unwrap().path;
+        let options = SpawnOptions {
+            launcher: Some(vec![
+                python,
+                "-c".to_string(),
+                r#"print('{"level": "info", "message": "hello", "plugin": "rag"}'); print('plain')"#.to_string(),
+            ]),
+            ..Default::default()
+        };
+        manager.spawn_sidecar_with_options("w".into(), temp_vault(), options).await.unwrap();
+
+        let structured = tokio::time::timeout(Duration::from_secs(10), logs.recv()).await.unwrap().unwrap();
+        let parsed = structured.parsed.expect("JSON line should be parsed");
+        assert_eq!((parsed.level.as_str(), parsed.message.as_str()), ("INFO", "hello"));
+        assert_eq!(parsed.fields["plugin"], "rag");
+
+        let raw = tokio::time::timeout(Duration::from_secs(10), logs.recv()).await.unwrap().unwrap();
+        assert_eq!(raw.text, "plain");
+        assert!(raw.parsed.is_none());
+        manager.terminate_sidecar("w").await.unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore = "spawns Python; run with --include-ignored"]
+    async fn test_sidecar_runs_in_vault_with_sidecar_root_on_path() {
+        let vault = std::env::temp_dir().join(format!("tailor-vault-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&vault).unwrap();
+        let manager = SidecarManager::with_config(SidecarManagerConfig {
+            sidecar_root: Some(PathBuf::from("/opt/tailor")),
+            working_dir: WorkingDir::Vault,
+            ..Default::default()
+        });
+        let mut logs = manager.subscribe_logs();
+        let python = manager.get_python_executable().await.unwrap().path;
+        let options = SpawnOptions {
+            launcher: Some(vec![
+                python,
+                "-c".to_string(),
+                "import os; print(os.getcwd()); print(os.environ['PYTHONPATH'])".to_string(),
+            ]),
+            ..Default::default()
+        };
+        manager.spawn_sidecar_with_options("w".into(), vault.to_string_lossy().into(), options).await.unwrap();
+
+        let cwd = tokio::time::timeout(Duration::from_secs(10), logs.recv()).await.unwrap().unwrap();
+        assert_eq!(Path::new(&cwd.text).canonicalize().unwrap(), vault.canonicalize().unwrap());
+        let python_path = tokio::time::timeout(Duration::from_secs(10), logs.recv()).await.unwrap().unwrap();
+        assert_eq!(std::env::split_paths(&python_path.text).next().unwrap(), PathBuf::from("/opt/tailor"));
+        manager.terminate_sidecar("w").await.unwrap();
+
+        // A per-sidecar working directory overrides the manager's
+        let other = vault.join("data");
+        std::fs::create_dir_all(&other).unwrap();
+        let python = manager.get_python_executable().await.unwrap().path;
+        let options = SpawnOptions {
+            launcher: Some(vec![python, "-c".to_string(), "import os; print(os.getcwd())".to_string()]),
+            working_dir: Some(WorkingDir::Path(other.clone())),
+            ..Default::default()
+        };
+        manager.spawn_sidecar_with_options("w".into(), vault.to_string_lossy().into(), options).await.unwrap();
+        let cwd = tokio::time::timeout(Duration::from_secs(10), logs.recv()).await.unwrap().unwrap();
+        assert_eq!(Path::new(&cwd.text).canonicalize().unwrap(), other.canonicalize().unwrap());
+
+        manager.terminate_sidecar("w").await.unwrap();
+        let _ = std::fs::remove_dir_all(&vault);
+    }
+
+    async fn next_notification(
+        events: &mut broadcast::Receiver<SidecarNotification>,
+    ) -> Result<SidecarNotification, broadcast::error::RecvError> {
+        tokio::time::timeout(Duration::from_secs(10), events.recv()).await.unwrap()
+    }
+
+    #[tokio::test]
+    #[ignore = "spawns Python; run with --include-ignored"]
+    async fn test_notifications_survive_restart() {
+        let manager = SidecarManager::with_config(SidecarManagerConfig {
+            persistent_connections: true,
+            ..Default::default()
+        });
+        let launcher = fake_ws_sidecar_launcher();
+        let options = SpawnOptions {
+            launcher: Some(launcher.argv()),
+            ..Default::default()
+        };
+        let port = manager.spawn_sidecar_with_options("w".into(), temp_vault(), options).await.unwrap();
+        wait_ready(&manager, "w", port).await;
+
+        let mut events = manager.subscribe_notifications("w").await.unwrap();
+        assert!(matches!(next_notification(&mut events).await, Ok(SidecarNotification::Event { .. })));
+
+        let restarted = manager.restart_sidecar("w").await.unwrap();
+        wait_ready(&manager, "w", restarted).await;
+        // Ticks from before the restart may still be queued ahead of the marker
+        loop {
+            match next_notification(&mut events).await {
+                Ok(SidecarNotification::Reconnected { ws_port }) => break assert_eq!(ws_port, restarted),
+                Ok(SidecarNotification::Event { .. }) => continue,
+                other => panic!("expected Reconnected, got {:?}", other),
+            }
+        }
+        assert!(matches!(next_notification(&mut events).await, Ok(SidecarNotification::Event { .. })));
+
+        // Terminating closes the stream once the last connection winds down
+        manager.terminate_sidecar("w").await.unwrap();
+        while let Ok(notification) = next_notification(&mut events).await {
+            assert!(matches!(notification, SidecarNotification::Event { .. }));
+        }
+    }
+
+    #[tokio::test]
+    #[ignore = "spawns Python; run with --include-ignored"]
+    async fn test_soft_shutdown_waits_while_progressing() {
+        let events = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = events.clone();
+        let manager = SidecarManager::with_config(SidecarManagerConfig {
+            soft_shutdown: Some(SoftShutdownConfig {
+                stall_timeout: Duration::from_millis(300),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }).with_event_emitter(Arc::new(move |event: &str, payload: serde_json::Value| {
+            if event == "shutdown://progress" {
+                sink.lock().unwrap().push(payload["progress"]["step"].clone());
+            }
+        }));
+
+        // Six reports over ~600ms: longer than the stall timeout in total, never between reports
+        let launcher = fake_flushing_sidecar_launcher(6, false);
+        let options = SpawnOptions { launcher: Some(launcher.argv()), ..Default::default() };
+        let port = manager.spawn_sidecar_with_options("w".into(), temp_vault(), options).await.unwrap();
+        wait_ready(&manager, "w", port).await;
+        manager.terminate_sidecar("w").await.unwrap();
+        assert_eq!(std::mem::take(&mut *events.lock().unwrap()), (0..6).map(serde_json::Value::from).collect::<Vec<_>>());
+        assert_eq!(manager.connection_stats("w").await, None);
+
+        // A sidecar that goes quiet is escalated after the stall timeout
+        let launcher = fake_flushing_sidecar_launcher(1, true);
+        let options = SpawnOptions { launcher: Some(launcher.argv()), ..Default::default() };
+        let port = manager.spawn_sidecar_with_options("w".into(), temp_vault(), options).await.unwrap();
+        wait_ready(&manager, "w", port).await;
+        let started = Instant::now();
+        manager.terminate_sidecar("w").await.unwrap();
+        assert!(started.elapsed() < Duration::from_secs(3), "took {:?}", started.elapsed());
+        assert_eq!(events.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    #[ignore = "spawns Python; run with --include-ignored"]
+    async fn test_degraded_alarm_fires_once_per_episode() {
+        let events = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = events.clone();
+        let manager = SidecarManager::with_config(SidecarManagerConfig {
+            degraded: Some(DegradedConfig { fraction: 0.6, ..Default::default() }),
+            ..Default::default()
+        }).with_event_emitter(Arc::new(move |event, payload| {
+            if event.starts_with("sidecars://") {
+                sink.lock().unwrap().push((event.to_string(), payload));
+            }
+        }));
+        // The plain TCP fakes drop the WebSocket handshake, so their pings fail
+        let launchers = [("a", fake_pong_sidecar_launcher()), ("b", fake_sidecar_launcher()), ("c", fake_sidecar_launcher())];
+        for &(label, ref launcher) in &launchers {
+            let options = SpawnOptions { launcher: Some(launcher.argv()), ..Default::default() };
+            let port = manager.spawn_sidecar_with_options(label.into(), temp_vault(), options).await.unwrap();
+            wait_ready(&manager, label, port).await;
+        }
+
+        let summary = manager.health_summary().await;
+        assert_eq!((summary.total, summary.healthy, summary.unresponsive, summary.failed, summary.degraded),
+                   (3, 1, 2, 0, true));
+        manager.health_report().await;
+        manager.terminate_sidecar("b").await.unwrap();
+        assert!(!manager.health_summary().await.degraded);
+
+        manager.shutdown().await;
+        let events = events.lock().unwrap();
+        let names: Vec<_> = events.iter().map(|(event, _)| event.as_str()).collect();
+        assert_eq!(names, ["sidecars://degraded", "sidecars://recovered"]);
+        assert_eq!(events[0].1["unresponsive"], 2);
+    }
+
+    #[tokio::test]
+    #[ignore = "spawns Python; run with --include-ignored"]
+    async fn test_health_report_does_not_wait_on_hung_sidecars() {
+        let manager = SidecarManager::new();
+        let launchers = [("a-ok", fake_pong_sidecar_launcher()), ("b-hung", fake_ws_sidecar_launcher())];
+        for &(label, ref launcher) in &launchers {
+            let options = SpawnOptions { launcher: Some(launcher.argv()), ..Default::default() };
+            let port = manager.spawn_sidecar_with_options(label.into(), temp_vault(), options).await.unwrap();
+            wait_ready(&manager, label, port).await;
+        }
+
+        let started = Instant::now();
+        let report = manager.health_report().await;
+        assert!(started.elapsed() < health::HEALTH_PING_TIMEOUT + Duration::from_secs(1));
+
+        let labels: Vec<_> = report.iter().map(|h| h.window_label.as_str()).collect();
+        assert_eq!(labels, ["a-ok", "b-hung"]);
+        assert!(report.iter().all(|h| h.alive && h.restart_count == 0 && h.last_error.is_none()));
+        assert!(report[0].responsive && report[0].ping_ms.is_some());
+        // The hung fake never reads its socket, so the ping goes unanswered
+        assert!(!report[1].responsive && report[1].ping_ms.is_none());
+        #[cfg(target_os = "linux")]
+        assert!(report[0].memory_bytes.is_some_and(|bytes| bytes > 0));
+
+        manager.shutdown().await;
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    #[ignore = "spawns Python; run with --include-ignored"]
+    async fn test_signal_reaches_sidecar_handler() {
+        let marker = std::env::temp_dir().join(format!("tailor-signal-{}", uuid::Uuid::new_v4()));
+        let manager = SidecarManager::new();
+        let launcher = fake_launcher(&format!(r#"
+import signal, socket, sys, time
+signal.signal(signal.SIGUSR1, lambda *_: open({:?}, "w").close())
+port = int(sys.argv[sys.argv.index("--ws-port") + 1])
+server = socket.socket()
+server.setsockopt(socket.SOL_SOCKET, socket.SO_REUSEADDR, 1)
+server.bind(("127.0.0.1", port))
+server.listen()
+with open(sys.argv[sys.argv.index("--ready-file") + 1], "w") as ready:
+    ready.write(str(port))
+while True:
+    time.sleep(1)
+"#, marker.to_string_lossy()));
+        let options = SpawnOptions { launcher: Some(launcher.argv()), ..Default::default() };
+        let port = manager.spawn_sidecar_with_options("w".into(), temp_vault(), options).await.unwrap();
+        wait_ready(&manager, "w", port).await;
+
+        manager.signal_sidecar("w", SidecarSignal::Usr1).await.unwrap();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !marker.exists() {
+            assert!(Instant::now() < deadline, "SIGUSR1 handler never ran");
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        let _ = std::fs::remove_file(&marker);
+
+        assert!(matches!(manager.signal_sidecar("x", SidecarSignal::Usr1).await, Err(SidecarError::NotFound { .. })));
+        manager.terminate_sidecar("w").await.unwrap();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    #[ignore = "spawns Python; run with --include-ignored"]
+    async fn test_watch_state_follows_lifecycle() {
+        let manager = SidecarManager::new();
+        let mut state = manager.watch_state("w");
+        // The current state is delivered straight away, even before the window has a sidecar
+        tokio::time::timeout(Duration::from_millis(100), state.changed()).await.unwrap().unwrap();
+        assert_eq!(*state.borrow_and_update(), SidecarState::Terminated);
+
+        let launcher = fake_sidecar_launcher();
+        let options = SpawnOptions { launcher: Some(launcher.argv()), ..Default::default() };
+        manager.spawn_sidecar_with_options("w".into(), temp_vault(), options).await.unwrap();
+        assert_eq!(*state.borrow(), SidecarState::Starting);
+        let wait_for = |state: &mut watch::Receiver<SidecarState>, expected| {
+            let mut state = state.clone();
+            async move {
+                tokio::time::timeout(Duration::from_secs(10), state.wait_for(|s| *s == expected)).await
+                    .unwrap_or_else(|_| panic!("never reached {:?}", expected))
+                    .unwrap();
+            }
+        };
+        wait_for(&mut state, SidecarState::Ready).await;
+
+        manager.signal_sidecar("w", SidecarSignal::Other(libc::SIGSTOP)).await.unwrap();
+        assert_eq!(*manager.watch_state("w").borrow(), SidecarState::Suspended);
+        manager.signal_sidecar("w", SidecarSignal::Other(libc::SIGCONT)).await.unwrap();
+        assert_eq!(*manager.watch_state("w").borrow(), SidecarState::Ready);
+
+        manager.terminate_sidecar("w").await.unwrap();
+        wait_for(&mut state, SidecarState::Terminated).await;
+    }
+
+    #[tokio::test]
+    #[ignore = "spawns Python; run with --include-ignored"]
+    async fn test_reload_falls_back_to_restart() {
+        let manager = SidecarManager::new();
+        let reload_result = r#"{"result": {"plugins_added": ["rag"], "plugins_removed": [], "errors": []}}"#;
+        let not_found = r#"{"error": {"code": -32601, "message": "Method not found"}}"#;
+        let mut launchers = Vec::new();
+        for (label, reply) in [("supported", reload_result), ("legacy", not_found)] {
+            let script = format!("REPLY = {:?}\n", reply) + r#"
+def serve(conn):
+    handshake(conn)
+    request = recv_json(conn)
+    send_json(conn, dict(json.loads(REPLY), jsonrpc="2.0", id=request["id"]))
+"#;
+            launchers.push(fake_ws_launcher(&script));
+            let launcher = launchers.last().unwrap();
+            let options = SpawnOptions { launcher: Some(launcher.argv()), ..Default::default() };
+            let port = manager.spawn_sidecar_with_options(label.into(), temp_vault(), options).await.unwrap();
+            wait_ready(&manager, label, port).await;
+        }
+
+        let report = manager.reload("supported").await.unwrap();
+        assert_eq!(report.plugins_added, ["rag"]);
+        assert!(!report.restarted);
+        assert!(manager.processes.lock().await["supported"].restarts.is_empty());
+
+        let report = manager.reload("legacy").await.unwrap();
+        assert!(report.restarted);
+        assert_eq!(manager.processes.lock().await["legacy"].restarts.len(), 1);
+
+        manager.shutdown().await;
+    }
+
+    #[tokio::test]
+    #[ignore = "spawns Python; run with --include-ignored"]
+    async fn test_crash_fails_in_flight_commands_or_restarts() {
+        for policy in [CrashPolicy::FailFast, CrashPolicy::RestartAndRetry] {
+            // The first launch dies mid-request; later ones (the marker exists) answer
+            let marker = std::env::temp_dir().join(format!("tailor-crash-{}", uuid::Uuid::new_v4()));
+            let script = format!("MARKER = {:?}\n", marker.to_string_lossy()) + r#"
+import os
+def serve(conn):
+    handshake(conn)
+    request = recv_json(conn)
+    if not os.path.exists(MARKER):
+        open(MARKER, "w").close()
+        sys.stderr.write("Traceback: index corrupted\n")
+        sys.stderr.flush()
+        os._exit(3)
+    send_json(conn, {"jsonrpc": "2.0", "id": request["id"], "result": "done"})
+"#;
+            let events = Arc::new(std::sync::Mutex::new(Vec::new()));
+            let recorded = events.clone();
+            let manager = Arc::new(SidecarManager::with_config(SidecarManagerConfig {
+                crash_policy: policy,
+                replay: ReplayConfig { methods: HashSet::from(["search".to_string()]), ..Default::default() },
+                ..Default::default()
+            }).with_event_emitter(Arc::new(move |event: &str, payload| {
+                if event == "sidecar://crashed" {
+                    recorded.lock().unwrap().push(payload);
+                }
+            })));
+            let handler = tokio::spawn({
+                let manager = manager.clone();
+                async move { manager.run_crash_handler().await }
+            });
+            let launcher = fake_ws_launcher(&script);
+            let options = SpawnOptions { launcher: Some(launcher.argv()), ..Default::default() };
+            let port = manager.spawn_sidecar_with_options("w".into(), temp_vault(), options).await.unwrap();
+            wait_ready(&manager, "w", port).await;
+
+            let started = Instant::now();
+            let result = manager.send_command("w", "search", serde_json::json!({})).await;
+            assert!(started.elapsed() < Duration::from_secs(15));
+            match policy {
+                CrashPolicy::FailFast => {
+                    let Err(SidecarError::Crashed(report)) = result else { panic!("expected a crash, got {:?}", result) };
+                    assert_eq!(report.exit, "exit status: 3");
+                    assert_eq!(report.stderr_tail.last().unwrap(), "Traceback: index corrupted");
+                    assert_eq!(*manager.watch_state("w").borrow(), SidecarState::Failed);
+                    // Later commands don't wait on the dead sidecar either
+                    let again = manager.send_command("w", "search", serde_json::json!({})).await;
+                    assert!(matches!(again, Err(SidecarError::Crashed(_))), "{:?}", again);
+                    assert!(manager.processes.lock().await["w"].restarts.is_empty());
+                }
+                CrashPolicy::RestartAndRetry => {
+                    assert_eq!(result.unwrap()["result"], "done");
+                    assert_eq!(manager.processes.lock().await["w"].restarts.len(), 1);
+                }
+            }
+            assert_eq!(events.lock().unwrap()[0]["exit"], "exit status: 3");
+
+            manager.shutdown().await;
+            handler.await.unwrap();
+            let _ = std::fs::remove_file(&marker);
+        }
+    }
+
+    #[tokio::test]
+    #[ignore = "spawns Python; run with --include-ignored"]
+    async fn test_crash_restarts_stop_at_the_limit_or_quarantine() {
+        // Dies on its own a second after starting, i.e. after becoming ready
+        let script = "import os\nthreading.Timer(1.0, lambda: os._exit(3)).start()\ndef serve(conn):\n    handshake(conn)\n";
+        let limit = RestartLimit { max_restarts: 2, backoff: Duration::from_millis(10), ..Default::default() };
+        for (quarantine, expected_restarts) in [(0, 2), (2, 1)] {
+            let manager = Arc::new(SidecarManager::with_config(SidecarManagerConfig {
+                crash_policy: CrashPolicy::RestartAndRetry,
+                restart_limit: limit.clone(),
+                quarantine: QuarantineConfig { max_crashes: quarantine, ..Default::default() },
+                ..Default::default()
+            }));
+            let handler = tokio::spawn({
+                let manager = manager.clone();
+                async move { manager.run_crash_handler().await }
+            });
+            let launcher = fake_ws_launcher(script);
+            let options = SpawnOptions { launcher: Some(launcher.argv()), ..Default::default() };
+            manager.spawn_sidecar_with_options("w".into(), temp_vault(), options).await.unwrap();
+
+            // Untracked for a moment while a restart resolves the interpreter
+            let given_up = || async {
+                let restarts = manager.processes.lock().await.get("w").map(|process| process.restarts.len());
+                (restarts, *manager.watch_state("w").borrow())
+            };
+            let deadline = Instant::now() + Duration::from_secs(20);
+            while given_up().await != (Some(expected_restarts), SidecarState::Failed) {
+                assert!(Instant::now() < deadline, "stuck at {:?}", given_up().await);
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+            tokio::time::sleep(Duration::from_millis(1500)).await;
+            assert_eq!(given_up().await, (Some(expected_restarts), SidecarState::Failed));
+            assert_eq!(manager.quarantined_vaults().len(), usize::from(quarantine > 0));
+
+            manager.shutdown().await;
+            handler.await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    #[ignore = "spawns Python; run with --include-ignored"]
+    async fn test_control_operations_use_control_socket() {
+        // Each socket reports which one answered
+        let script = r#"
+control = socket.socket()
+control.setsockopt(socket.SOL_SOCKET, socket.SO_REUSEADDR, 1)
+control.bind(("127.0.0.1", int(sys.argv[sys.argv.index("--control-port") + 1])))
+control.listen()
+def answer(conn, name):
+    handshake(conn)
+    request = recv_json(conn)
+    send_json(conn, {"jsonrpc": "2.0", "id": request["id"], "result": {"plugins_added": [name]}})
+def accept_control():
+    while True:
+        conn, _ = control.accept()
+        threading.Thread(target=answer, args=(conn, "control"), daemon=True).start()
+threading.Thread(target=accept_control, daemon=True).start()
+def serve(conn):
+    answer(conn, "rpc")
+"#;
+        let manager = SidecarManager::with_config(SidecarManagerConfig { control_socket: true, ..Default::default() });
+        let launcher = fake_ws_launcher(script);
+        let options = SpawnOptions { launcher: Some(launcher.argv()), ..Default::default() };
+        let port = manager.spawn_sidecar_with_options("w".into(), temp_vault(), options).await.unwrap();
+        wait_ready(&manager, "w", port).await;
+
+        assert_eq!(manager.reload("w").await.unwrap().plugins_added, ["control"]);
+        let result = manager.send_command("w", reload::RELOAD_METHOD, serde_json::json!({})).await.unwrap();
+        assert_eq!(result["result"]["plugins_added"], serde_json::json!(["rpc"]));
+        let health = manager.health_report().await;
+        assert!(health[0].control_port.is_some_and(|control| control != port), "{:?}", health[0]);
+        manager.shutdown().await;
+    }
+
+    #[tokio::test]
+    #[ignore = "spawns Python; run with --include-ignored"]
+    async fn test_set_env_applies_live_or_on_restart() {
+        let manager = SidecarManager::new();
+        let mut launchers = Vec::new();
+        for (label, supported) in [("live", true), ("legacy", false)] {
+            let script = format!("SUPPORTED = {}\n", if supported { "True" } else { "False" }) + r#"
+import os
+def serve(conn):
+    handshake(conn)
+    request = recv_json(conn)
+    if request["method"] == "set_env" and not SUPPORTED:
+        reply = {"error": {"code": -32601, "message": "Method not found"}}
+    else:
+        reply = {"result": os.environ.get("TAILOR_FLAG")}
+    send_json(conn, dict(reply, jsonrpc="2.0", id=request["id"]))
+"#;
+            launchers.push(fake_ws_launcher(&script));
+            let launcher = launchers.last().unwrap();
+            let options = SpawnOptions { launcher: Some(launcher.argv()), ..Default::default() };
+            let port = manager.spawn_sidecar_with_options(label.into(), temp_vault(), options).await.unwrap();
+            wait_ready(&manager, label, port).await;
+        }
+
+        assert_eq!(manager.set_env("live", "TAILOR_FLAG", "on").await.unwrap(), EnvUpdate::Applied);
+        assert!(!manager.processes.lock().await["live"].needs_restart);
+        assert_eq!(manager.set_env("legacy", "TAILOR_FLAG", "on").await.unwrap(), EnvUpdate::NeedsRestart);
+        assert!(manager.processes.lock().await["legacy"].needs_restart);
+        assert!(matches!(manager.set_env("legacy", "A=B", "x").await, Err(SidecarError::InvalidOptions(_))));
+
+        // The restarted process starts with the new value and a clean flag
+        let port = manager.restart_sidecar("legacy").await.unwrap();
+        wait_ready(&manager, "legacy", port).await;
+        assert!(!manager.processes.lock().await["legacy"].needs_restart);
+        let result = manager.send_command("legacy", "get", serde_json::json!({})).await.unwrap();
+        assert_eq!(result["result"], "on");
+        manager.shutdown().await;
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    #[ignore = "spawns Python; run with --include-ignored"]
+    async fn test_memory_budget_evicts_least_recently_used_sidecar() {
+        let events = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = events.clone();
+        let mut manager = SidecarManager::new().with_event_emitter(Arc::new(move |event, payload| {
+            sink.lock().unwrap().push((event.to_string(), payload));
+        }));
+        let script = r#"
+def serve(conn):
+    handshake(conn)
+    request = recv_json(conn)
+    send_json(conn, {"jsonrpc": "2.0", "id": request["id"], "result": "ok"})
+"#;
+        let launcher = fake_ws_launcher(script);
+        for label in ["a", "b"] {
+            let options = SpawnOptions { launcher: Some(launcher.argv()), ..Default::default() };
+            let port = manager.spawn_sidecar_with_options(label.into(), temp_vault(), options).await.unwrap();
+            wait_ready(&manager, label, port).await;
+        }
+        manager.send_command("b", "use", serde_json::json!({})).await.unwrap();
+        assert!(manager.enforce_memory_budget().await.is_empty(), "no budget configured");
+
+        // One byte under the current total: evicting the least recently used sidecar is enough
+        let usage = manager.memory_usage().await;
+        assert_eq!(usage.sidecars.len(), 2);
+        manager.config.total_memory_budget = Some(MemoryBudgetConfig {
+            total_bytes: usage.total_bytes - 1,
+            sample_interval: Duration::from_secs(10),
+            min_idle: Duration::ZERO,
+        });
+        assert_eq!(manager.enforce_memory_budget().await, ["a"]);
+        assert!(manager.get_ws_port("a").await.is_none());
+        let usage = manager.memory_usage().await;
+        assert_eq!(usage.sidecars.iter().map(|s| s.window_label.as_str()).collect::<Vec<_>>(), ["b"]);
+        assert!(events.lock().unwrap().iter()
+            .any(|(event, payload)| event == "sidecar://memory-evicted" && payload["window_label"] == "a"));
+        manager.shutdown().await;
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    #[ignore = "spawns Python; run with --include-ignored"]
+    async fn test_role_idle_timeout_terminates_idle_sidecars() {
+        let events = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = events.clone();
+        let manager = SidecarManager::with_config(SidecarManagerConfig {
+            roles: HashMap::from([
+                ("indexer".to_string(), RoleProfile { idle_timeout: Some(Duration::ZERO), ..Default::default() }),
+            ]),
+            ..Default::default()
+        }).with_event_emitter(Arc::new(move |event, payload| {
+            sink.lock().unwrap().push((event.to_string(), payload));
+        }));
+        manager.register_role("ui-helper", RoleProfile::default());
+
+        let unknown = SpawnOptions { role: Some("llm".to_string()), ..Default::default() };
+        let error = manager.spawn_sidecar_with_options("x".into(), temp_vault(), unknown).await.unwrap_err();
+        assert!(matches!(error, SidecarError::InvalidOptions(_)), "{}", error);
+
+        let launcher = fake_pong_sidecar_launcher();
+        for (label, role) in [("a", "indexer"), ("b", "ui-helper")] {
+            let options = SpawnOptions {
+                launcher: Some(launcher.argv()),
+                role: Some(role.to_string()),
+                ..Default::default()
+            };
+            let port = manager.spawn_sidecar_with_options(label.into(), temp_vault(), options).await.unwrap();
+            wait_ready(&manager, label, port).await;
+        }
+        assert_eq!(manager.role("b").await.unwrap().as_deref(), Some("ui-helper"));
+        assert!(matches!(manager.set_role("b", Some("llm".to_string())).await, Err(SidecarError::InvalidOptions(_))));
+
+        assert_eq!(manager.terminate_idle().await, ["a"]);
+        assert!(manager.get_ws_port("a").await.is_none());
+        assert!(events.lock().unwrap().iter()
+            .any(|(event, payload)| event == "sidecar://idle-terminated" && payload["role"] == "indexer"));
+
+        manager.set_role("b", Some("indexer".to_string())).await.unwrap();
+        let report = manager.health_report().await;
+        assert_eq!(report[0].role.as_deref(), Some("indexer"));
 
-impl Default for SidecarManager {
-    fn default() -> Self {
-        Self::new()
+        // A provider stays up while a sidecar depends on it
+        let launcher = fake_pong_sidecar_launcher();
+        let options = SpawnOptions {
+            launcher: Some(launcher.argv()),
+            depends_on: vec!["b".to_string()],
+            ..Default::default()
+        };
+        let port = manager.spawn_sidecar_with_options("c".into(), temp_vault(), options).await.unwrap();
+        wait_ready(&manager, "c", port).await;
+        assert!(manager.terminate_idle().await.is_empty());
+        manager.terminate_sidecar("c").await.unwrap();
+        assert_eq!(manager.terminate_idle().await, ["b"]);
+        manager.shutdown().await;
     }
-}
 
-impl SidecarManager {
-    pub fn new() -> Self {
-        Self {
-            processes: Arc::new(Mutex::new(HashMap::new())),
-            next_port: Arc::new(Mutex::new(9000)),
+    #[tokio::test]
+    async fn test_method_policy_refuses_before_sending() {
+        let manager = SidecarManager::with_config(SidecarManagerConfig {
+            method_policy: MethodPolicy { deny: vec!["fs.*".to_string()], ..Default::default() },
+            ..Default::default()
+        });
+        // Refused before the window is even looked up
+        let error = manager.send_command("w", "fs.delete", serde_json::json!({})).await.unwrap_err();
+        assert!(matches!(error, SidecarError::MethodForbidden { ref method } if method == "fs.delete"), "{}", error);
+        let error = manager.send_command_binary("w", "fs.read", serde_json::json!({})).await.unwrap_err();
+        assert!(matches!(error, SidecarError::MethodForbidden { .. }), "{}", error);
+        let error = manager.send_command("w", "search", serde_json::json!({})).await.unwrap_err();
+        assert!(matches!(error, SidecarError::NotFound { .. }), "{}", error);
+
+        // The manager's own RPCs pass an allowlist without them, the same methods from the UI don't
+        let manager = SidecarManager::with_config(SidecarManagerConfig {
+            method_policy: MethodPolicy { allow: vec!["search".to_string()], ..Default::default() },
+            ..Default::default()
+        });
+        for method in [snapshot::SNAPSHOT_METHOD, SET_ENV_METHOD, vault_path::REBIND_METHOD] {
+            let error = manager.send_command("w", method, serde_json::json!({})).await.unwrap_err();
+            assert!(matches!(error, SidecarError::MethodForbidden { .. }), "{}: {}", method, error);
         }
+        let error = manager.snapshot_state("w").await.unwrap_err();
+        assert!(matches!(error, SidecarError::NotFound { .. }), "{}", error);
     }
 
-    /// Spawn a Python sidecar process for a vault
-    pub async fn spawn_sidecar(
-        &self,
-        window_label: String,
-        vault_path: String,
-    ) -> Result<u16> {
-        // Allocate port
-        let ws_port = self.allocate_port().await;
+    #[tokio::test]
+    #[ignore = "spawns Python; run with --include-ignored"]
+    async fn test_method_not_found_refreshes_capabilities_once() {
+        let manager = SidecarManager::new();
+        // "search" is missing until the capability list has been fetched, imitating an upgrade
+        let script = r#"
+state = {"listed": False}
+def serve(conn):
+    handshake(conn)
+    request = recv_json(conn)
+    method = request["method"]
+    if method == "system.list_commands":
+        state["listed"] = True
+        reply = {"result": {"status": "success", "commands": ["system.list_commands", "search"]}}
+    elif method == "search" and state["listed"]:
+        reply = {"result": "found"}
+    else:
+        reply = {"error": {"code": -32601, "message": "Method not found"}}
+    send_json(conn, dict(reply, jsonrpc="2.0", id=request["id"]))
+"#;
+        let launcher = fake_ws_launcher(script);
+        let options = SpawnOptions { launcher: Some(launcher.argv()), ..Default::default() };
+        let port = manager.spawn_sidecar_with_options("w".into(), temp_vault(), options).await.unwrap();
+        wait_ready(&manager, "w", port).await;
 
-        // Get Python executable path
-        let python_exe = self.get_python_executable()?;
-        
-        // Get project root (parent of src-tauri) to set as CWD
-        let project_root = std::env::current_dir()?
-            .parent()
-            .context("Failed to get parent directory")?
-            .to_path_buf();
+        let response = manager.send_command_with_capability_refresh("w", "search", serde_json::json!({})).await.unwrap();
+        assert_eq!(response["result"], "found");
+        assert!(manager.capabilities("w").await.unwrap().contains("search"));
+        let err = manager.send_command_with_capability_refresh("w", "missing", serde_json::json!({})).await.unwrap_err();
+        assert!(matches!(&err, SidecarError::MethodNotSupported { method } if method == "missing"), "{:?}", err);
+        // Without opting in, the plain error comes back untouched
+        assert!(manager.send_command("w", "missing", serde_json::json!({})).await.unwrap_err().is_method_not_found());
+        manager.shutdown().await;
+    }
 
-        println!("Spawning sidecar for window '{}': vault={}, port={}", 
-                 window_label, vault_path, ws_port);
-        println!("Python executable: {}", python_exe);
-        println!("Project root: {}", project_root.display());
-
-        // Spawn Python process with unbuffered output
-        let mut child = Command::new(&python_exe)
-            .arg("-u")  // Unbuffered output
-            .arg("-m")
-            .arg("sidecar")
-            .arg("--vault")
-            .arg(&vault_path)
-            .arg("--ws-port")
-            .arg(ws_port.to_string())
-            .current_dir(&project_root)
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .spawn()
-            .context("Failed to spawn Python sidecar")?;
+    #[tokio::test]
+    #[ignore = "spawns Python; run with --include-ignored"]
+    async fn test_cancelled_spawn_stops_the_half_started_sidecar() {
+        let manager = Arc::new(SidecarManager::new());
+        let listening = std::env::temp_dir().join(format!("tailor-listening-{}", uuid::Uuid::new_v4()));
+        // Listens but never writes its ready file
+        let script = format!(r#"
+import socket, sys, time
+server = socket.socket()
+server.setsockopt(socket.SOL_SOCKET, socket.SO_REUSEADDR, 1)
+server.bind(("127.0.0.1", int(sys.argv[sys.argv.index("--ws-port") + 1])))
+server.listen()
+open({:?}, "w").close()
+time.sleep(60)
+"#, listening.display().to_string());
+        let launcher = fake_launcher(&script);
+        let options = SpawnOptions { launcher: Some(launcher.argv()), ..Default::default() };
+        let cancel = SpawnCancel::new();
+        let spawn = tokio::spawn({
+            let (manager, cancel) = (manager.clone(), cancel.clone());
+            async move { manager.spawn_sidecar_cancellable("w".into(), temp_vault(), options, cancel).await }
+        });
+        let mut state = manager.watch_state("w");
+        state.wait_for(|state| *state == SidecarState::Starting).await.unwrap();
+        // `Starting` is published before the interpreter is resolved, so the process may not be
+        // tracked yet; once it listens it is
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while !listening.exists() {
+            assert!(Instant::now() < deadline, "sidecar never listened");
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        let _ = std::fs::remove_file(&listening);
+        let (pid, port) = {
+            let processes = manager.processes.lock().await;
+            (processes["w"].child.id(), processes["w"].ws_port)
+        };
 
-        let pid = child.id();
-        println!("Sidecar spawned with PID: {}", pid);
+        assert!(manager.cancel_spawn("w"));
+        let result = tokio::time::timeout(Duration::from_secs(10), spawn).await.unwrap().unwrap();
+        assert!(matches!(result, Err(SidecarError::Cancelled)), "{:?}", result);
+        assert!(cancel.is_cancelled());
+        assert!(!manager.cancel_spawn("w"), "the finished launch is still cancellable");
+        assert_eq!(*state.borrow(), SidecarState::Terminated);
+        assert!(manager.processes.lock().await.is_empty());
+        #[cfg(unix)]
+        assert_ne!(unsafe { libc::kill(pid as libc::pid_t, 0) }, 0, "sidecar {} still running", pid);
+        #[cfg(not(unix))]
+        let _ = pid;
+        assert!(manager.is_port_available(port));
 
-        // Capture stdout for debugging
-        if let Some(stdout) = child.stdout.take() {
-            use std::io::BufRead;
-            std::thread::spawn(move || {
-                let reader = std::io::BufReader::new(stdout);
-                for line in reader.lines() {
-                    if let Ok(line) = line {
-                        println!("[Sidecar] {}", line);
-                    }
-                }
-            });
+        // Cancelled before anything started
+        let launcher = fake_launcher(&script);
+        let options = SpawnOptions { launcher: Some(launcher.argv()), install_dependencies: true, ..Default::default() };
+        let result = manager.spawn_sidecar_cancellable("v".into(), temp_vault(), options, cancel).await;
+        assert!(matches!(result, Err(SidecarError::Cancelled)), "{:?}", result);
+        assert!(manager.processes.lock().await.is_empty());
+    }
+
+    #[tokio::test]
+    #[ignore = "spawns Python; run with --include-ignored"]
+    async fn test_sidecar_version_is_cached_per_launch() {
+        let manager = SidecarManager::new();
+        // Sidecars for "/" predate the version RPC
+        let script = r#"
+asked = 0
+
+def serve(conn):
+    global asked
+    handshake(conn)
+    request = recv_json(conn)
+    if request["method"] == "system.version" and sys.argv[sys.argv.index("--vault") + 1] != "/":
+        asked += 1
+        reply = {"result": {"version": "1.2.%d" % asked, "git_commit": "abc", "python": "3.11.7"}}
+    else:
+        reply = {"error": {"code": -32601, "message": "Method not found"}}
+    send_json(conn, dict(reply, jsonrpc="2.0", id=request["id"]))
+"#;
+        let launcher = fake_ws_launcher(script);
+        let temp = temp_vault();
+        for (label, vault) in [("w", temp.as_str()), ("old", "/")] {
+            let options = SpawnOptions { launcher: Some(launcher.argv()), ..Default::default() };
+            let port = manager.spawn_sidecar_with_options(label.into(), vault.into(), options).await.unwrap();
+            wait_ready(&manager, label, port).await;
         }
 
-        // Capture stderr for debugging
-        if let Some(stderr) = child.stderr.take() {
-            use std::io::BufRead;
-            std::thread::spawn(move || {
-                let reader = std::io::BufReader::new(stderr);
-                for line in reader.lines() {
-                    if let Ok(line) = line {
-                        eprintln!("[Sidecar Error] {}", line);
+        let reported = VersionInfo::Reported {
+            version: "1.2.1".to_string(),
+            git_commit: Some("abc".to_string()),
+            python: Some("3.11.7".to_string()),
+        };
+        assert_eq!(manager.sidecar_version("w").await.unwrap(), reported);
+        assert_eq!(manager.sidecar_version("w").await.unwrap(), reported);
+        assert_eq!(manager.sidecar_version("old").await.unwrap(), VersionInfo::Unknown);
+        let diagnostics = manager.export_diagnostics("w").await.unwrap();
+        assert_eq!(diagnostics["sidecar_version"]["version"], "1.2.1");
+        assert_eq!(manager.export_diagnostics("old").await.unwrap()["sidecar_version"]["status"], "unknown");
+
+        // A restart may bring another version, so it asks again
+        let port = manager.restart_sidecar("w").await.unwrap();
+        wait_ready(&manager, "w", port).await;
+        assert_eq!(manager.processes.lock().await["w"].version, None);
+        assert!(matches!(manager.sidecar_version("w").await.unwrap(), VersionInfo::Reported { version, .. } if version == "1.2.1"));
+        assert!(matches!(manager.sidecar_version("missing").await, Err(SidecarError::NotFound { .. })));
+        manager.shutdown().await;
+    }
+
+    #[tokio::test]
+    #[ignore = "spawns Python; run with --include-ignored"]
+    async fn test_set_log_level_is_kept_across_restarts() {
+        let manager = SidecarManager::new();
+        let script = r#"
+def serve(conn):
+    handshake(conn)
+    request = recv_json(conn)
+    if request["method"] == "set_log_level":
+        reply = {"result": {"status": "success", "level": request["params"]["level"]}}
+    else:
+        reply = {"result": sys.argv}
+    send_json(conn, dict(reply, jsonrpc="2.0", id=request["id"]))
+"#;
+        let launcher = fake_ws_launcher(script);
+        let options = SpawnOptions { launcher: Some(launcher.argv()), ..Default::default() };
+        let port = manager.spawn_sidecar_with_options("w".into(), temp_vault(), options).await.unwrap();
+        wait_ready(&manager, "w", port).await;
+        manager.set_log_capture("w", false).await.unwrap();
+
+        assert!(matches!(manager.set_log_level("w", "verbose").await, Err(SidecarError::InvalidOptions(_))));
+        assert_eq!(manager.set_log_level("w", "debug").await.unwrap(), EnvUpdate::Applied);
+        assert!(manager.processes.lock().await["w"].telemetry.log_capture.load(Ordering::Relaxed));
+
+        let port = manager.restart_sidecar("w").await.unwrap();
+        wait_ready(&manager, "w", port).await;
+        let argv = manager.send_command("w", "argv", serde_json::json!({})).await.unwrap()["result"].clone();
+        let argv: Vec<String> = serde_json::from_value(argv).unwrap();
+        assert!(argv.windows(2).any(|pair| pair == ["--log-level", "DEBUG"]), "{:?}", argv);
+        manager.shutdown().await;
+    }
+
+    #[tokio::test]
+    #[ignore = "spawns Python; run with --include-ignored"]
+    async fn test_restart_restores_state_snapshot() {
+        let manager = SidecarManager::new();
+        let script = r#"
+state = {"count": 0}
+def serve(conn):
+    handshake(conn)
+    request = recv_json(conn)
+    if request["method"] == "bump":
+        state["count"] += 1
+    elif request["method"] == "restore":
+        state.update(request["params"]["state"])
+    send_json(conn, {"jsonrpc": "2.0", "id": request["id"], "result": dict(state)})
+"#;
+        let launcher = fake_ws_launcher(script);
+        let options = SpawnOptions { launcher: Some(launcher.argv()), restore_state: true, ..Default::default() };
+        let mut state = manager.watch_state("w");
+        let port = manager.spawn_sidecar_with_options("w".into(), temp_vault(), options).await.unwrap();
+        wait_ready(&manager, "w", port).await;
+        for _ in 0..2 {
+            manager.send_command("w", "bump", serde_json::json!({})).await.unwrap();
+        }
+
+        manager.restart_sidecar("w").await.unwrap();
+        // Restoring happens before the sidecar is reported ready
+        tokio::time::timeout(Duration::from_secs(10), state.wait_for(|s| *s == SidecarState::Ready)).await
+            .unwrap()
+            .unwrap();
+        let response = manager.send_command("w", "get", serde_json::json!({})).await.unwrap();
+        assert_eq!(response["result"], serde_json::json!({"count": 2}));
+
+        manager.shutdown().await;
+    }
+
+    #[tokio::test]
+    #[ignore = "spawns Python; run with --include-ignored"]
+    async fn test_middleware_rewrites_records_and_short_circuits() {
+        struct Redact;
+        impl RpcMiddleware for Redact {
+            fn on_request(&self, call: &RpcCall, params: &mut serde_json::Value) -> Option<serde_json::Value> {
+                match call.method {
+                    "fixture" => Some(serde_json::json!({"jsonrpc": "2.0", "id": "f", "result": "canned"})),
+                    _ => {
+                        params["token"] = "[redacted]".into();
+                        None
                     }
                 }
-            });
+            }
+        }
+        type Recorded = Vec<(String, Option<String>, serde_json::Value)>;
+        struct Record(Arc<std::sync::Mutex<Recorded>>);
+        impl RpcMiddleware for Record {
+            fn on_response(&self, call: &RpcCall, result: &mut Result<serde_json::Value, SidecarError>) {
+                let result = result.as_ref().map(|r| r["result"].clone()).unwrap_or_default();
+                self.0.lock().unwrap().push((call.method.to_string(), call.vault_path.map(String::from), result));
+            }
         }
 
-        // Store process
-        let process = SidecarProcess {
-            child,
-            vault_path: vault_path.clone(),
-            ws_port,
+        let script = r#"
+def serve(conn):
+    handshake(conn)
+    request = recv_json(conn)
+    send_json(conn, {"jsonrpc": "2.0", "id": request["id"], "result": request["params"]})
+"#;
+        let recorded = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let manager = SidecarManager::new();
+        manager.register_middleware(Box::new(Record(recorded.clone())));
+        manager.register_middleware(Box::new(Redact));
+
+        // Answered without a sidecar
+        let response = manager.send_command("w", "fixture", serde_json::json!({})).await.unwrap();
+        assert_eq!(response["result"], "canned");
+
+        let launcher = fake_ws_launcher(script);
+        let options = SpawnOptions { launcher: Some(launcher.argv()), ..Default::default() };
+        let port = manager.spawn_sidecar_with_options("w".into(), temp_vault(), options).await.unwrap();
+        wait_ready(&manager, "w", port).await;
+        let response = manager.send_command("w", "echo", serde_json::json!({"token": "abc"})).await.unwrap();
+        assert_eq!(response["result"], serde_json::json!({"token": "[redacted]"}));
+        assert_eq!(*recorded.lock().unwrap(), [
+            ("fixture".to_string(), None, serde_json::json!("canned")),
+            ("echo".to_string(), Some(temp_vault()), serde_json::json!({"token": "[redacted]"})),
+        ]);
+        manager.shutdown().await;
+    }
+
+    #[tokio::test]
+    #[ignore = "spawns Python; run with --include-ignored"]
+    async fn test_commands_wait_for_a_starting_sidecar() {
+        // Only starts listening half a second in, as a slow import would
+        let script = r#"
+server.close()
+time.sleep(0.5)
+server = socket.socket()
+server.setsockopt(socket.SOL_SOCKET, socket.SO_REUSEADDR, 1)
+server.bind(("127.0.0.1", port))
+server.listen()
+def serve(conn):
+    handshake(conn)
+    request = recv_json(conn)
+    send_json(conn, {"jsonrpc": "2.0", "id": request["id"], "result": "ok"})
+"#;
+        let manager = SidecarManager::new();
+        manager.set_method_timeout("quick", Duration::from_millis(100));
+        let launcher = fake_ws_launcher(script);
+        let spawn = |label: &'static str| {
+            let options = SpawnOptions { launcher: Some(launcher.argv()), ..Default::default() };
+            manager.spawn_sidecar_with_options(label.into(), temp_vault(), options)
         };
+        spawn("w").await.unwrap();
+        assert_eq!(*manager.watch_state("w").borrow(), SidecarState::Starting);
+        let response = manager.send_command("w", "echo", serde_json::json!({})).await.unwrap();
+        assert_eq!(response["result"], "ok");
 
-        self.processes.lock().await.insert(window_label.clone(), process);
+        // The wait counts against the method's timeout
+        spawn("x").await.unwrap();
+        let err = manager.send_command("x", "quick", serde_json::json!({})).await.unwrap_err();
+        assert!(matches!(err, SidecarError::ResponseTimeout { .. }), "unexpected error: {:?}", err);
+        let err = manager.try_send_command("x", "echo", serde_json::json!({})).await.unwrap_err();
+        assert!(matches!(err, SidecarError::Busy { state: SidecarState::Starting, .. }), "unexpected error: {:?}", err);
+        manager.shutdown().await;
+    }
 
-        Ok(ws_port)
+    #[tokio::test]
+    #[ignore = "spawns Python; run with --include-ignored"]
+    async fn test_init_method_runs_before_ready() {
+        // Refuses everything until initialized, LSP-style; `{"fail": true}` makes initialize fail
+        let script = r#"
+initialized = []
+def serve(conn):
+    handshake(conn)
+    request = recv_json(conn)
+    if request["method"] == "initialize" and not request["params"].get("fail"):
+        initialized.append(True)
+        result = {"capabilities": {"hover": True}, "roots": request["params"]["roots"]}
+        send_json(conn, {"jsonrpc": "2.0", "id": request["id"], "result": result})
+    elif request["method"] == "initialize":
+        send_json(conn, {"jsonrpc": "2.0", "id": request["id"], "error": {"code": -32002, "message": "bad roots"}})
+    elif initialized:
+        send_json(conn, {"jsonrpc": "2.0", "id": request["id"], "result": "ok"})
+    else:
+        send_json(conn, {"jsonrpc": "2.0", "id": request["id"], "error": {"code": -32002, "message": "not initialized"}})
+"#;
+        let manager = SidecarManager::new();
+        let launcher = fake_ws_launcher(script);
+        let options = |params| SpawnOptions {
+            launcher: Some(launcher.argv()),
+            init_method: Some("initialize".to_string()),
+            init_params: Some(params),
+            ..Default::default()
+        };
+        let params = serde_json::json!({"roots": ["/tmp"]});
+        manager.spawn_sidecar_cancellable("w".into(), temp_vault(), options(params), SpawnCancel::new()).await.unwrap();
+        assert_eq!(manager.init_result("w").await.unwrap(),
+                   Some(serde_json::json!({"capabilities": {"hover": true}, "roots": ["/tmp"]})));
+        assert_eq!(manager.send_command("w", "echo", serde_json::json!({})).await.unwrap()["result"], "ok");
+
+        let err = manager.spawn_sidecar_cancellable("x".into(), temp_vault(), options(serde_json::json!({"fail": true})),
+                                                    SpawnCancel::new()).await.unwrap_err();
+        assert!(err.to_string().contains("initialization ('initialize') failed") && err.to_string().contains("bad roots"),
+                "unexpected error: {}", err);
+        assert_eq!(*manager.watch_state("x").borrow(), SidecarState::Failed);
+        manager.shutdown().await;
     }
 
-    /// Terminate a sidecar process
-    pub async fn terminate_sidecar(&self, window_label: &str) -> Result<()> {
-        let mut processes = self.processes.lock().await;
-        
-        if let Some(mut process) = processes.remove(window_label) {
-            println!("Terminating sidecar for window '{}'", window_label);
-            
-            // Try graceful shutdown first
-            if let Err(e) = process.child.kill() {
-                eprintln!("Failed to kill sidecar process: {}", e);
+    #[tokio::test]
+    #[ignore = "spawns Python; run with --include-ignored"]
+    async fn test_preconnect_opens_the_connection_when_ready() {
+        let (connected_tx, mut connected) = mpsc::unbounded_channel();
+        let manager = SidecarManager::with_config(SidecarManagerConfig {
+            persistent_connections: true,
+            preconnect: true,
+            ..Default::default()
+        }).with_event_emitter(Arc::new(move |event: &str, payload| {
+            if event == "sidecar://connected" {
+                let _ = connected_tx.send(payload);
+            }
+        }));
+        let script = "def serve(conn):\n    handshake(conn)\n    while True:\n        request = recv_json(conn)\n        send_json(conn, {\"jsonrpc\": \"2.0\", \"id\": request[\"id\"], \"result\": \"ok\"})\n";
+        let launcher = fake_ws_launcher(script);
+        let options = SpawnOptions { launcher: Some(launcher.argv()), ..Default::default() };
+        let port = manager.spawn_sidecar_with_options("w".into(), temp_vault(), options).await.unwrap();
+
+        let event = tokio::time::timeout(Duration::from_secs(10), connected.recv()).await.unwrap().unwrap();
+        assert_eq!((event["window_label"].as_str(), event["ws_port"].as_u64()), (Some("w"), Some(port as u64)));
+        assert_eq!(manager.connection_stats("w").await.unwrap().connects, 1);
+        manager.send_command("w", "echo", serde_json::json!({})).await.unwrap();
+        assert_eq!(manager.connection_stats("w").await.unwrap().connects, 1);
+        manager.shutdown().await;
+    }
+
+    #[tokio::test]
+    #[ignore = "spawns Python; run with --include-ignored"]
+    async fn test_duplicate_responses() {
+        // Both copies go out in one write so the second is already there when the first is read
+        let script = r#"
+class Buffer:
+    data = b""
+    def sendall(self, data):
+        self.data += data
+def serve(conn):
+    handshake(conn)
+    while True:
+        request = recv_json(conn)
+        buffer = Buffer()
+        for _ in range(2):
+            send_json(buffer, {"jsonrpc": "2.0", "id": request["id"], "result": "ok"})
+        conn.sendall(buffer.data)
+"#;
+        let cases = [
+            (DuplicateResponses::Strict, false),
+            (DuplicateResponses::Strict, true),
+            (DuplicateResponses::Lenient, true),
+        ];
+        for (mode, persistent) in cases {
+            let manager = SidecarManager::with_config(SidecarManagerConfig {
+                duplicate_responses: mode,
+                persistent_connections: persistent,
+                ..Default::default()
+            });
+            let launcher = fake_ws_launcher(script);
+            let options = SpawnOptions { launcher: Some(launcher.argv()), ..Default::default() };
+            let port = manager.spawn_sidecar_with_options("w".into(), temp_vault(), options).await.unwrap();
+            wait_ready(&manager, "w", port).await;
+
+            let mut results = Vec::new();
+            for _ in 0..2 {
+                results.push(manager.send_command("w", "echo", serde_json::json!({})).await);
+                tokio::time::sleep(Duration::from_millis(50)).await;
             }
-            
-            // Wait for process to exit
-            if let Err(e) = process.child.wait() {
-                eprintln!("Failed to wait for sidecar exit: {}", e);
+            let reconnects = manager.connection_stats("w").await.unwrap().reconnects;
+            match (mode, persistent) {
+                (DuplicateResponses::Strict, false) => {
+                    assert!(results.iter().all(|r| matches!(r, Err(SidecarError::Protocol(_)))), "{:?}", results);
+                }
+                // The first response is delivered; only the duplicate is rejected
+                (DuplicateResponses::Strict, true) => {
+                    assert!(results.iter().all(Result::is_ok), "{:?}", results);
+                    let stats = manager.connection_stats("w").await.unwrap();
+                    assert_eq!((stats.reconnects, stats.rejected_duplicates), (0, 2));
+                }
+                _ => {
+                    assert!(results.iter().all(Result::is_ok), "{:?}", results);
+                    assert_eq!(reconnects, 0);
+                }
             }
-            
-            println!("Sidecar terminated for window '{}'", window_label);
+            manager.shutdown().await;
         }
+    }
 
-        Ok(())
+    #[tokio::test]
+    #[ignore = "spawns Python; run with --include-ignored"]
+    async fn test_spawn_stages_and_deadline() {
+        // Answers everything except `hang`, which stalls warmup
+        let script = r#"
+def serve(conn):
+    handshake(conn)
+    while True:
+        request = recv_json(conn)
+        if request["method"] != "hang":
+            send_json(conn, {"jsonrpc": "2.0", "id": request["id"], "result": None})
+"#;
+        let events = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = events.clone();
+        let manager = SidecarManager::with_config(SidecarManagerConfig {
+            spawn_deadline: Duration::from_secs(3),
+            ..Default::default()
+        }).with_event_emitter(Arc::new(move |event: &str, payload: serde_json::Value| {
+            if event.starts_with("spawn://") {
+                sink.lock().unwrap().push((event.to_string(), payload["stage"].clone()));
+            }
+        }));
+        let mut state = manager.watch_state("w");
+
+        let launcher = fake_ws_launcher(script);
+        for (warmup, last) in [("echo", "warmed-up"), ("hang", "warmed-up")] {
+            events.lock().unwrap().clear();
+            let options = SpawnOptions {
+                launcher: Some(launcher.argv()),
+                warmup_methods: vec![warmup.to_string()],
+                ..Default::default()
+            };
+            manager.spawn_sidecar_with_options("w".into(), temp_vault(), options).await.unwrap();
+            let settled = state.wait_for(|s| matches!(s, SidecarState::Ready | SidecarState::Failed));
+            let settled = *tokio::time::timeout(Duration::from_secs(10), settled).await.unwrap().unwrap();
+
+            let events = std::mem::take(&mut *events.lock().unwrap());
+            let mut expected: Vec<_> = ["python-resolved", "process-started", "port-listening"]
+                .into_iter()
+                .map(|stage| ("spawn://stage".to_string(), serde_json::json!(stage)))
+                .collect();
+            if warmup == "hang" {
+                assert_eq!(settled, SidecarState::Failed);
+                expected.push(("spawn://failed".to_string(), serde_json::json!(last)));
+                let errors = manager.export_diagnostics("w").await.unwrap()["recent_errors"].to_string();
+                assert!(errors.contains("stuck on 'warmed-up'"), "{}", errors);
+            } else {
+                assert_eq!(settled, SidecarState::Ready);
+                expected.push(("spawn://stage".to_string(), serde_json::json!(last)));
+            }
+            assert_eq!(events, expected);
+            manager.terminate_sidecar("w").await.unwrap();
+        }
     }
 
-    /// Terminate ALL sidecar processes (used for app shutdown)
-    pub fn shutdown_all(&self) {
-        println!("Shutting down all sidecars...");
-        // Use blocking lock for shutdown
-        if let Ok(mut processes) = self.processes.try_lock() {
-             for (label, mut process) in processes.drain() {
-                println!("Killing sidecar for window '{}' (PID: {})", label, process.child.id());
-                if let Err(e) = process.child.kill() {
-                    eprintln!("Failed to kill sidecar {}: {}", label, e);
-                } else {
-                     let _ = process.child.wait(); // Best effort wait
-                }
-             }
-        } else {
-            // Fallback: If we can't lock (unlikely in shutdown), we might leak. 
-            // Better to force lock if possible, but try_lock avoids deadlock potential in panic paths.
-            eprintln!("Failed to acquire lock for shutdown cleanup!");
+    #[tokio::test]
+    #[ignore = "spawns Python; run with --include-ignored"]
+    async fn test_crash_loop_quarantines_vault() {
+        let manager = SidecarManager::with_config(SidecarManagerConfig {
+            quarantine: QuarantineConfig { max_crashes: 2, ..Default::default() },
+            ..Default::default()
+        });
+        let mut state = manager.watch_state("w");
+        let launcher = fake_launcher("import sys\nsys.exit(3)");
+        let crashing = SpawnOptions { launcher: Some(launcher.argv()), ..Default::default() };
+        for _ in 0..2 {
+            manager.spawn_sidecar_with_options("w".into(), temp_vault(), crashing.clone()).await.unwrap();
+            let failed = state.wait_for(|s| *s == SidecarState::Failed);
+            tokio::time::timeout(Duration::from_secs(10), failed).await.unwrap().unwrap();
+        }
+
+        let quarantined = manager.quarantined_vaults();
+        assert_eq!(quarantined.len(), 1);
+        assert!(quarantined[0].reason.contains("exited during startup"), "{:?}", quarantined[0]);
+        let err = manager.spawn_sidecar_with_options("w".into(), temp_vault(), crashing).await.unwrap_err();
+        assert!(matches!(err, SidecarError::Quarantined(_)), "{:?}", err);
+
+        assert!(manager.clear_quarantine(&temp_vault()));
+        assert!(manager.quarantined_vaults().is_empty());
+        let launcher = fake_sidecar_launcher();
+        let options = SpawnOptions { launcher: Some(launcher.argv()), ..Default::default() };
+        let port = manager.spawn_sidecar_with_options("w".into(), temp_vault(), options).await.unwrap();
+        wait_ready(&manager, "w", port).await;
+        manager.terminate_sidecar("w").await.unwrap();
+
+        // Crashes after readiness count too
+        let script = r#"
+import os
+def serve(conn):
+    handshake(conn)
+    recv_json(conn)
+    os._exit(3)
+"#;
+        let launcher = fake_ws_launcher(script);
+        let crashing = SpawnOptions { launcher: Some(launcher.argv()), ..Default::default() };
+        for crash in 0..2 {
+            let port = manager.spawn_sidecar_with_options("w".into(), temp_vault(), crashing.clone()).await.unwrap();
+            wait_ready(&manager, "w", port).await;
+            assert_eq!(manager.quarantined_vaults().len(), 0, "quarantined after {} crashes", crash);
+            let _ = manager.send_command("w", "search", serde_json::json!({})).await;
+            let failed = state.wait_for(|s| *s == SidecarState::Failed);
+            tokio::time::timeout(Duration::from_secs(10), failed).await.unwrap().unwrap();
         }
+        let quarantined = manager.quarantined_vaults();
+        assert_eq!(quarantined.len(), 1);
+        assert!(quarantined[0].reason.contains("exit status: 3"), "{:?}", quarantined[0]);
     }
-    
-    /// Get WebSocket port for a sidecar
-    pub async fn get_ws_port(&self, window_label: &str) -> Option<u16> {
-        self.processes.lock().await
-            .get(window_label)
-            .map(|p| p.ws_port)
+
+    #[tokio::test]
+    #[ignore = "spawns Python; run with --include-ignored"]
+    async fn test_send_command_binary() {
+        let script = r#"
+def send_binary(conn, payload):
+    conn.sendall(bytes([0x82, 126]) + struct.pack(">H", len(payload)) + payload)
+def serve(conn):
+    handshake(conn)
+    while True:
+        request = recv_json(conn)
+        if request["method"] == "missing":
+            send_json(conn, {"jsonrpc": "2.0", "id": request["id"], "error": {"code": -32601, "message": "Method not found"}})
+            continue
+        assert request["binary_result"] is True
+        tag = request["id"].encode()
+        # A frame for some other request comes first and must be skipped
+        send_binary(conn, struct.pack(">H", 5) + b"other" + b"nope" * 40)
+        send_binary(conn, struct.pack(">H", len(tag)) + tag + bytes(range(256)))
+"#;
+        for persistent in [false, true] {
+            let manager = SidecarManager::with_config(SidecarManagerConfig {
+                persistent_connections: persistent,
+                ..Default::default()
+            });
+            let launcher = fake_ws_launcher(script);
+            let options = SpawnOptions { launcher: Some(launcher.argv()), ..Default::default() };
+            let port = manager.spawn_sidecar_with_options("w".into(), temp_vault(), options).await.unwrap();
+            wait_ready(&manager, "w", port).await;
+
+            let bytes = manager.send_command_binary("w", "render", serde_json::json!({})).await.unwrap();
+            assert_eq!(bytes, (0..=255).collect::<Vec<u8>>());
+            let err = manager.send_command_binary("w", "missing", serde_json::json!({})).await.unwrap_err();
+            assert!(matches!(err, SidecarError::Rpc { code: -32601, .. }), "{:?}", err);
+            manager.shutdown().await;
+        }
     }
 
-    /// Allocate next available port by actually checking port availability
-    async fn allocate_port(&self) -> u16 {
-        let mut port = self.next_port.lock().await;
-        
-        // Try to find an available port starting from current port
-        loop {
-            if Self::is_port_available(*port) {
-                let allocated = *port;
-                *port += 1;
-                return allocated;
+    #[tokio::test]
+    #[ignore = "spawns Python; run with --include-ignored"]
+    async fn test_injected_request_ids() {
+        // Answers with the id it was sent, after a reply to some other id that must be skipped
+        let script = r#"
+def serve(conn):
+    handshake(conn)
+    while True:
+        request = recv_json(conn)
+        send_json(conn, {"jsonrpc": "2.0", "id": -1, "result": None})
+        send_json(conn, {"jsonrpc": "2.0", "id": request["id"], "result": request["id"]})
+"#;
+        for persistent in [false, true] {
+            let next = Arc::new(AtomicU64::new(1));
+            let manager = SidecarManager::with_config(SidecarManagerConfig {
+                persistent_connections: persistent,
+                ..Default::default()
+            }).with_id_generator(Arc::new(move || RpcId::Num(next.fetch_add(1, Ordering::SeqCst) as i64)));
+            let launcher = fake_ws_launcher(script);
+            let options = SpawnOptions { launcher: Some(launcher.argv()), ..Default::default() };
+            let port = manager.spawn_sidecar_with_options("w".into(), temp_vault(), options).await.unwrap();
+            wait_ready(&manager, "w", port).await;
+
+            for expected in 1..=3 {
+                let response = manager.send_command("w", "m", serde_json::json!({})).await.unwrap();
+                assert_eq!(response["result"], expected, "persistent: {}", persistent);
             }
-            *port += 1;
-            
-            // Wrap around if we exceed reasonable ports
-            if *port > 19000 {
-                *port = 9000;
+            manager.shutdown().await;
+        }
+    }
+
+    #[tokio::test]
+    #[ignore = "spawns Python; run with --include-ignored"]
+    async fn test_stdio_transport() {
+        // Echoes `echo` params back (announcing them first), anything else is unknown
+        let script = r#"
+import json, sys
+def read():
+    length = None
+    while True:
+        line = sys.stdin.buffer.readline()
+        if not line:
+            sys.exit(0)
+        if not line.strip():
+            return json.loads(sys.stdin.buffer.read(length))
+        name, _, value = line.partition(b":")
+        if name.strip().lower() == b"content-length":
+            length = int(value)
+def write(message):
+    body = json.dumps(message).encode()
+    sys.stdout.buffer.write(b"Content-Length: %d\r\n\r\n" % len(body) + body)
+    sys.stdout.buffer.flush()
+assert "--stdio" in sys.argv and "--ws-port" not in sys.argv
+while True:
+    request = read()
+    if request["method"] == "echo":
+        write({"jsonrpc": "2.0", "method": "echoed", "params": request["params"]})
+        write({"jsonrpc": "2.0", "id": request["id"], "result": request["params"]})
+    else:
+        write({"jsonrpc": "2.0", "id": request["id"], "error": {"code": -32601, "message": "Method not found"}})
+"#;
+        let manager = SidecarManager::new();
+        let launcher = fake_launcher(script);
+        let options = SpawnOptions {
+            launcher: Some(launcher.argv()),
+            transport: Transport::Stdio,
+            ..Default::default()
+        };
+        let port = manager.spawn_sidecar_with_options("w".into(), temp_vault(), options).await.unwrap();
+        assert_eq!(port, 0);
+        let mut events = manager.subscribe_notifications("w").await.unwrap();
+
+        let (a, b) = tokio::join!(
+            manager.send_command("w", "echo", serde_json::json!({ "n": 1 })),
+            manager.send_command("w", "echo", serde_json::json!({ "n": 2 })),
+        );
+        assert_eq!((a.unwrap()["result"]["n"].clone(), b.unwrap()["result"]["n"].clone()), (1.into(), 2.into()));
+        assert!(matches!(next_notification(&mut events).await, Ok(SidecarNotification::Event { method, .. }) if method == "echoed"));
+        let err = manager.send_command("w", "missing", serde_json::json!({})).await.unwrap_err();
+        assert!(err.is_method_not_found(), "{:?}", err);
+        let err = manager.send_command_binary("w", "echo", serde_json::json!({})).await.unwrap_err();
+        assert!(matches!(err, SidecarError::InvalidOptions(_)), "{:?}", err);
+        assert!(manager.health_report().await[0].responsive);
+
+        manager.terminate_sidecar("w").await.unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore = "spawns Python; run with --include-ignored"]
+    async fn test_jobs_complete_by_notification_or_polling() {
+        // `index` and `fail` jobs report completion right after being accepted and answer
+        // their second status poll; `hang` jobs never finish
+        let script = r#"
+jobs = {}
+def serve(conn):
+    handshake(conn)
+    try:
+        while True:
+            request = recv_json(conn)
+            method, params = request["method"], request.get("params") or {}
+            result = {}
+            if method in ("index", "fail", "hang"):
+                job_id = "%s-%d" % (method, len(jobs))
+                jobs[job_id] = 0
+                result = {"job_id": job_id}
+            elif method == "job.status":
+                job_id = params["job_id"]
+                jobs[job_id] += 1
+                if job_id.startswith("hang") or jobs[job_id] < 2:
+                    result = {"status": "running"}
+                elif job_id.startswith("fail"):
+                    result = {"status": "failed", "error": {"code": 1, "message": "disk full"}}
+                else:
+                    result = {"status": "completed", "result": {"indexed": 3}}
+            send_json(conn, {"jsonrpc": "2.0", "id": request["id"], "result": result})
+            if method == "index":
+                send_json(conn, {"jsonrpc": "2.0", "method": "job.completed", "params": {"job_id": job_id, "result": {"indexed": 3}}})
+            elif method == "fail":
+                send_json(conn, {"jsonrpc": "2.0", "method": "job.completed", "params": {"job_id": job_id, "error": "disk full"}})
+    except OSError:
+        pass
+"#;
+        for persistent_connections in [true, false] {
+            let manager = SidecarManager::with_config(SidecarManagerConfig { persistent_connections, ..Default::default() });
+            let launcher = fake_ws_launcher(script);
+            let options = SpawnOptions { launcher: Some(launcher.argv()), ..Default::default() };
+            let port = manager.spawn_sidecar_with_options("w".into(), temp_vault(), options).await.unwrap();
+            wait_ready(&manager, "w", port).await;
+
+            let job = manager.submit_job("w", "index", serde_json::json!({})).await.unwrap();
+            let result = manager.await_job("w", &job, Duration::from_secs(5)).await.unwrap();
+            assert_eq!(result, serde_json::json!({"indexed": 3}), "persistent: {}", persistent_connections);
+            assert_eq!(manager.job_status(&job), Some(JobStatus::Completed { result }));
+            assert!(matches!(manager.await_job("v", &job, Duration::from_secs(1)).await, Err(SidecarError::JobNotFound { .. })));
+
+            let job = manager.submit_job("w", "fail", serde_json::json!({})).await.unwrap();
+            match manager.await_job("w", &job, Duration::from_secs(5)).await {
+                Err(SidecarError::JobFailed { message, .. }) => assert_eq!(message, "disk full"),
+                other => panic!("expected JobFailed, got {:?}", other),
             }
+
+            // Running out of time leaves the job running, until its sidecar goes away
+            let job = manager.submit_job("w", "hang", serde_json::json!({})).await.unwrap();
+            let err = manager.await_job("w", &job, Duration::from_millis(300)).await.unwrap_err();
+            assert!(matches!(err, SidecarError::JobTimeout { .. }), "{:?}", err);
+            assert_eq!(manager.job_status(&job), Some(JobStatus::Running));
+            manager.terminate_sidecar("w").await.unwrap();
+            assert!(matches!(manager.job_status(&job), Some(JobStatus::Failed { .. })));
+            manager.shutdown().await;
         }
     }
-    
-    /// Check if a port is available
-    fn is_port_available(port: u16) -> bool {
-        use std::net::TcpListener;
-        TcpListener::bind(("127.0.0.1", port)).is_ok()
+
+    #[tokio::test]
+    #[ignore = "spawns Python; run with --include-ignored"]
+    async fn test_method_latencies_are_tracked_per_method() {
+        // "slow" takes 100ms to answer, anything else answers right away
+        let script = r#"
+def serve(conn):
+    handshake(conn)
+    request = recv_json(conn)
+    if request["method"] == "slow":
+        time.sleep(0.1)
+    send_json(conn, {"jsonrpc": "2.0", "id": request["id"], "result": None})
+"#;
+        let manager = SidecarManager::new();
+        let launcher = fake_ws_launcher(script);
+        let options = SpawnOptions { launcher: Some(launcher.argv()), ..Default::default() };
+        let port = manager.spawn_sidecar_with_options("w".into(), temp_vault(), options).await.unwrap();
+        wait_ready(&manager, "w", port).await;
+        for method in ["fast", "fast", "slow"] {
+            manager.send_command("w", method, serde_json::json!({})).await.unwrap();
+        }
+
+        let latencies = manager.method_latencies("w").await.unwrap();
+        assert_eq!((latencies["fast"].count, latencies["slow"].count), (2, 1));
+        assert!(latencies["slow"].p50_ms >= 100.0, "{:?}", latencies);
+        assert!(latencies["fast"].p99_ms < latencies["slow"].p50_ms, "{:?}", latencies);
+        let diagnostics = manager.export_diagnostics("w").await.unwrap();
+        assert_eq!(diagnostics["method_latencies"]["slow"]["count"], 1);
+
+        manager.terminate_sidecar("w").await.unwrap();
+        assert_eq!(manager.method_latencies("w").await, None);
     }
 
-    /// Get Python executable path
-    fn get_python_executable(&self) -> Result<String> {
-        // Try to find Python in PATH
-        #[cfg(target_os = "windows")]
-        let python_candidates = vec!["python.exe", "python3.exe"];
-        
-        #[cfg(not(target_os = "windows"))]
-        let python_candidates = vec!["python3", "python"];
+    #[tokio::test]
+    #[ignore = "spawns Python; run with --include-ignored"]
+    async fn test_log_capture_pause_and_resume() {
+        let manager = SidecarManager::new();
+        let mut logs = manager.subscribe_logs();
+        let python = manager.get_python_executable().await.unwrap().path;
+        let options = SpawnOptions {
+            launcher: Some(vec![
+                python,
+                "-c".to_string(),
+                "import time\nwhile True:\n    print('tick', flush=True)\n    time.sleep(0.01)".to_string(),
+            ]),
+            ..Default::default()
+        };
+        manager.spawn_sidecar_with_options("w".into(), temp_vault(), options).await.unwrap();
+        tokio::time::timeout(Duration::from_secs(10), logs.recv()).await.unwrap().unwrap();
 
-        for candidate in python_candidates {
-            if let Ok(output) = Command::new(candidate)
-                .arg("--version")
-                .output()
-            {
-                if output.status.success() {
-                    return Ok(candidate.to_string());
-                }
+        manager.set_log_capture("w", false).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        // Drain whatever was read before the pause took effect
+        while logs.try_recv().is_ok() {}
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert!(logs.try_recv().is_err(), "no lines should be forwarded while paused");
+        let telemetry = manager.processes.lock().await["w"].telemetry.clone();
+        assert!(telemetry.dropped_lines.load(Ordering::Relaxed) > 0);
+
+        manager.set_log_capture("w", true).await.unwrap();
+        tokio::time::timeout(Duration::from_secs(10), logs.recv()).await.unwrap().unwrap();
+        assert!(matches!(manager.set_log_capture("missing", false).await, Err(SidecarError::NotFound { .. })));
+        manager.terminate_sidecar("w").await.unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore = "spawns Python; run with --include-ignored"]
+    async fn test_output_flood_does_not_block_the_sidecar() {
+        let manager = SidecarManager::new();
+        let python = manager.get_python_executable().await.unwrap().path;
+        let marker = std::env::temp_dir().join(format!("tailor-flood-{}", uuid::Uuid::new_v4()));
+        let script = format!(
+            "import sys, time\nline = 'x' * 99 + '\\n'\nfor _ in range(100000):\n    sys.stdout.write(line)\n\
+             sys.stdout.flush()\nopen({:?}, 'w').close()\ntime.sleep(60)",
+            marker.display().to_string(),
+        );
+        let options = SpawnOptions { launcher: Some(vec![python, "-c".to_string(), script]), ..Default::default() };
+        manager.spawn_sidecar_with_options("w".into(), temp_vault(), options).await.unwrap();
+        let telemetry = manager.processes.lock().await["w"].telemetry.clone();
+
+        // Stall logging entirely; the 10MB of output must still be drained
+        {
+            let _stalled = telemetry.logs.lock().unwrap();
+            let deadline = Instant::now() + Duration::from_secs(10);
+            while !marker.exists() && Instant::now() < deadline {
+                std::thread::sleep(Duration::from_millis(20));
             }
+            assert!(marker.exists(), "the sidecar blocked writing to its stdout");
         }
+        assert!(telemetry.dropped_lines.load(Ordering::Relaxed) > 0);
 
-        anyhow::bail!("Python not found in PATH")
+        manager.terminate_sidecar("w").await.unwrap();
+        let _ = std::fs::remove_file(&marker);
     }
 
-    /// Send a command to the sidecar via WebSocket
-    pub async fn send_command(
-        &self,
-        window_label: &str,
-        method: &str,
-        params: serde_json::Value,
-    ) -> Result<serde_json::Value> {
-        // 1. Get port
-        let port = self.get_ws_port(window_label)
-            .await
-            .ok_or_else(|| anyhow!("Sidecar not found for window: {}", window_label))?;
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    #[ignore = "spawns Python; run with --include-ignored"]
+    async fn test_reclaim_port_only_kills_sidecars() {
+        let manager = SidecarManager::new();
 
-        // 2. Connect
-        let url = Url::parse(&format!("ws://127.0.0.1:{}", port))
-            .context("Invalid WebSocket URL")?;
+        // A port held by something that isn't a sidecar (this test process) is left alone
+        let listener = std::net::TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        assert!(!manager.reclaim_port(port).await.unwrap());
+        drop(listener);
 
-        let (mut ws_stream, _) = connect_async(url.to_string())
-            .await
-            .context("Failed to connect to sidecar WebSocket")?;
+        // An untracked process with the sidecar signature is killed and its port freed
+        let python = manager.get_python_executable().await.unwrap().path;
+        let script = "import socket, sys, time\n\
+                      s = socket.socket(); s.bind(('127.0.0.1', int(sys.argv[-1]))); s.listen()\n\
+                      time.sleep(60)";
+        let mut orphan = Command::new(python)
+            .args(["-c", script, "-u", "-m", "sidecar", "--ws-port", &port.to_string()])
+            .spawn()
+            .unwrap();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while manager.is_port_available(port) {
+            assert!(Instant::now() < deadline, "orphan never bound its port");
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
 
+        assert!(manager.reclaim_port(port).await.unwrap());
+        assert!(!orphan.wait().unwrap().success());
+    }
 
-        // 3. Construct JSON-RPC Request
-        let request_id = uuid::Uuid::new_v4().to_string();
-        let request = serde_json::json!({
-            "jsonrpc": "2.0",
-            "method": method,
-            "params": params,
-            "id": request_id
-        });
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    #[ignore = "spawns Python; run with --include-ignored"]
+    async fn test_orphans_exclude_tracked_sidecars() {
+        let manager = SidecarManager::new();
+        let launcher = fake_sidecar_launcher();
+        let options = SpawnOptions { launcher: Some(launcher.argv()), ..Default::default() };
+        let port = manager.spawn_sidecar_with_options("w".into(), temp_vault(), options).await.unwrap();
+        wait_ready(&manager, "w", port).await;
+        let tracked = manager.processes.lock().await["w"].child.id();
 
-        // 4. Send Request
-        let request_text = serde_json::to_string(&request)?;
-        ws_stream.send(Message::Text(request_text)).await
-            .context("Failed to send WebSocket message")?;
-
-        // 5. Await Response
-        // We expect a single response for the request
-        while let Some(msg) = ws_stream.next().await {
-            let msg = msg.context("WebSocket stream error")?;
-            match msg {
-                Message::Text(text) => {
-                    let response: serde_json::Value = serde_json::from_str(&text)
-                        .context("Failed to parse sidecar response")?;
-                    
-                    if response.get("id").and_then(|id| id.as_str()) == Some(&request_id) {
-                         return Ok(response);
-                    }
-                }
-                Message::Close(_) => break,
-                _ => {}
+        let python = manager.get_python_executable().await.unwrap().path;
+        let mut orphan = Command::new(&python)
+            .args(["-c", "import time; time.sleep(60)", "-u", "-m", "sidecar", "--vault", "/v", "--ws-port", "1"])
+            .spawn()
+            .unwrap();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let found = loop {
+            // Wait for the interpreter to replace its pre-exec command line
+            let orphans = manager.find_orphaned_sidecars().await;
+            if let Some(found) = orphans.iter().find(|o| o.pid == orphan.id()) {
+                assert!(orphans.iter().all(|o| o.pid != tracked));
+                break found.clone();
             }
+            assert!(Instant::now() < deadline, "orphan never showed up");
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        };
+        assert_eq!((found.ws_port, found.vault_path.as_deref()), (1, Some("/v")));
+
+        // Other tests' sidecars are orphans to this manager, so only kill ours
+        assert!(manager.kill_orphan(&found).await.unwrap());
+        assert!(!orphan.wait().unwrap().success());
+        manager.terminate_sidecar("w").await.unwrap();
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    #[ignore = "spawns Python; run with --include-ignored"]
+    async fn test_reattach_adopts_sidecars_of_a_lost_manager() {
+        let file = std::env::temp_dir().join(format!("tailor-reattach-{}.json", uuid::Uuid::new_v4()));
+        let config = || SidecarManagerConfig { reattach_file: Some(file.clone()), ..Default::default() };
+        let first = SidecarManager::with_config(config());
+        let launcher = fake_pong_sidecar_launcher();
+        let options = SpawnOptions { launcher: Some(launcher.argv()), ..Default::default() };
+        let port = first.spawn_sidecar_with_options("w".into(), temp_vault(), options).await.unwrap();
+        wait_ready(&first, "w", port).await;
+        let pid = first.processes.lock().await["w"].child.id();
+        // As a reload would: the manager is gone without shutting its sidecars down
+        drop(first);
+
+        let second = SidecarManager::with_config(config());
+        let results = second.reattach_all().await.unwrap();
+        assert!(matches!(&results[..], [(label, Ok(p))] if label == "w" && *p == port), "{:?}", results);
+        assert_eq!(*second.watch_state("w").borrow(), SidecarState::Ready);
+        let health = second.health_report().await;
+        assert_eq!((health[0].pid, health[0].alive, health[0].responsive), (pid, true, true));
+        let again = second.reattach_all().await.unwrap();
+        assert!(matches!(again[0].1, Err(SidecarError::AlreadyRunning { .. })), "{:?}", again);
+
+        second.terminate_sidecar("w").await.unwrap();
+        assert!(process::pid_has_exited(pid));
+        assert!(reattach::load(&file).is_empty());
+        // Nothing left to adopt
+        let third = SidecarManager::with_config(config());
+        assert!(third.reattach_all().await.unwrap().is_empty());
+        let _ = std::fs::remove_file(file);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    #[ignore = "spawns Python; run with --include-ignored"]
+    async fn test_grace_period_per_terminate_call_and_shutdown() {
+        // Only SIGKILL stops this one, so each stop takes its grace period
+        let launcher = fake_launcher(r#"
+import signal, sys
+signal.signal(signal.SIGTERM, signal.SIG_IGN)
+open(sys.argv[sys.argv.index("--ready-file") + 1], "w").close()
+signal.pause()
+"#);
+        let manager = SidecarManager::with_config(SidecarManagerConfig {
+            terminate_grace: Duration::from_secs(30),
+            shutdown_grace: Duration::from_millis(200),
+            ..Default::default()
+        });
+        for label in ["a", "b"] {
+            let options = SpawnOptions { launcher: Some(launcher.argv()), ..Default::default() };
+            let port = manager.spawn_sidecar_with_options(label.into(), temp_vault(), options).await.unwrap();
+            wait_ready(&manager, label, port).await;
         }
 
-        Err(anyhow!("Connection closed without valid response"))
+        let started = Instant::now();
+        manager.terminate_sidecar_with_grace("a", Duration::from_millis(200)).await.unwrap();
+        assert!(started.elapsed() < Duration::from_secs(5), "took {:?}", started.elapsed());
+        let started = Instant::now();
+        manager.shutdown().await;
+        assert!(started.elapsed() < Duration::from_secs(5), "took {:?}", started.elapsed());
+        assert_eq!(*manager.watch_state("b").borrow(), SidecarState::Terminated);
     }
-}
 
-impl Drop for SidecarManager {
-    fn drop(&mut self) {
-        // Ensure all processes are terminated when manager is dropped
-        // Note: This is a blocking operation in async context
-        // In production, consider using a shutdown signal
-        println!("SidecarManager dropping - cleaning up processes");
+    #[cfg(unix)]
+    #[tokio::test]
+    #[ignore = "spawns Python; run with --include-ignored"]
+    async fn test_shutdown_follows_order_then_spawn_order() {
+        let log = std::env::temp_dir().join(format!("tailor-shutdown-{}", uuid::Uuid::new_v4()));
+        let manager = SidecarManager::with_config(SidecarManagerConfig {
+            sequential_shutdown: true,
+            ..Default::default()
+        });
+        // (label, shutdown_order): "b" goes first, then "c" and "a" in spawn order
+        let mut launchers = Vec::new();
+        for (label, shutdown_order) in [("c", 0), ("a", 0), ("b", -1)] {
+            launchers.push(fake_launcher(&format!(r#"
+import signal, socket, sys
+def stop(*_):
+    with open({:?}, "a") as log:
+        log.write({:?} + "\n")
+    sys.exit(0)
+signal.signal(signal.SIGTERM, stop)
+port = int(sys.argv[sys.argv.index("--ws-port") + 1])
+server = socket.socket()
+server.setsockopt(socket.SOL_SOCKET, socket.SO_REUSEADDR, 1)
+server.bind(("127.0.0.1", port))
+server.listen()
+with open(sys.argv[sys.argv.index("--ready-file") + 1], "w") as ready:
+    ready.write(str(port))
+signal.pause()
+"#, log.to_string_lossy(), label)));
+            let options = SpawnOptions {
+                launcher: Some(launchers.last().unwrap().argv()),
+                shutdown_order,
+                ..Default::default()
+            };
+            let port = manager.spawn_sidecar_with_options(label.into(), temp_vault(), options).await.unwrap();
+            wait_ready(&manager, label, port).await;
+        }
+        // Restarting keeps a window's place in spawn order
+        let port = manager.restart_sidecar("c").await.unwrap();
+        wait_ready(&manager, "c", port).await;
+
+        manager.shutdown().await;
+        let stopped = std::fs::read_to_string(&log).unwrap();
+        let _ = std::fs::remove_file(&log);
+        assert_eq!(stopped.lines().collect::<Vec<_>>(), ["c", "b", "c", "a"]);
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[tokio::test]
+    #[ignore = "spawns Python; run with --include-ignored"]
+    async fn test_dependency_cycles_are_refused_at_spawn() {
+        let manager = SidecarManager::new();
+        let launcher = fake_pong_sidecar_launcher();
+        let spawn = |label: &'static str, depends_on: &[&str]| {
+            let options = SpawnOptions {
+                launcher: Some(launcher.argv()),
+                depends_on: depends_on.iter().map(|label| label.to_string()).collect(),
+                ..Default::default()
+            };
+            manager.spawn_sidecar_with_options(label.into(), temp_vault(), options)
+        };
+        // Providers needn't be running yet
+        let port = spawn("llm", &["embeddings"]).await.unwrap();
+        wait_ready(&manager, "llm", port).await;
+        let error = spawn("embeddings", &["llm"]).await.unwrap_err();
+        assert!(matches!(&error, SidecarError::InvalidOptions(reason) if reason.contains("embeddings -> llm -> embeddings")),
+                "{}", error);
+        assert!(manager.get_ws_port("embeddings").await.is_none());
+        assert!(matches!(spawn("self", &["self"]).await, Err(SidecarError::InvalidOptions(_))));
+
+        let port = spawn("embeddings", &[]).await.unwrap();
+        wait_ready(&manager, "embeddings", port).await;
+        manager.shutdown().await;
+        assert!(manager.processes.lock().await.is_empty());
+    }
+
+    #[tokio::test]
+    #[ignore = "spawns Python; run with --include-ignored"]
+    async fn test_shutdown_stops_tasks_and_sidecars() {
+        let manager = SidecarManager::new();
+        let launcher = fake_sidecar_launcher();
+        let options = SpawnOptions {
+            launcher: Some(launcher.argv()),
+            ..Default::default()
+        };
+        let port = manager
+            .spawn_sidecar_with_options("w".into(), temp_vault(), options.clone())
+            .await
+            .unwrap();
+        let ready_file = manager.processes.lock().await["w"].ready_file.clone();
+
+        manager.shutdown().await;
+        assert!(manager.processes.lock().await.is_empty());
+        assert!(manager.tasks.lock().unwrap().is_empty());
+        assert!(!ready_file.exists());
+        assert!(manager.wait_for_port_release(port).await);
+
+        let refused = manager.spawn_sidecar_with_options("w".into(), temp_vault(), options).await;
+        assert!(matches!(refused, Err(SidecarError::ShuttingDown)));
+    }
 
     #[test]
-    fn test_is_port_available() {
-        // Find an open port by binding 0
-        let listener = std::net::TcpListener::bind(("127.0.0.1", 0)).unwrap();
-        let port = listener.local_addr().unwrap().port();
-        
-        // Port should be unavailable because we bound to it
-        assert!(!SidecarManager::is_port_available(port));
-        
-        // Drop the listener to free the port
-        drop(listener);
-        
-        // Now it should be available
-        assert!(SidecarManager::is_port_available(port));
+    fn test_method_timeout_overrides() {
+        let manager = SidecarManager::new();
+        assert_eq!(manager.method_timeout("reindex"), None);
+
+        manager.set_method_timeout("ping", Duration::from_millis(500));
+        assert_eq!(manager.method_timeout("ping"), Some(Duration::from_millis(500)));
+        assert_eq!(manager.method_timeout("reindex"), None);
+
+        let manager = SidecarManager::with_config(SidecarManagerConfig {
+            request_timeout: Some(Duration::from_secs(30)),
+            ..Default::default()
+        });
+        manager.set_method_timeout("reindex", Duration::from_secs(600));
+        assert_eq!(manager.method_timeout("reindex"), Some(Duration::from_secs(600)));
+        assert_eq!(manager.method_timeout("ping"), Some(Duration::from_secs(30)));
+    }
+
+    #[test]
+    fn test_project_root_falls_back_to_executable_directory() {
+        let gone = || Err(std::io::Error::new(std::io::ErrorKind::NotFound, "deleted"));
+        let exe = || Ok(PathBuf::from("/opt/tailor/bin/tailor"));
+        assert_eq!(resolve_project_root(Ok(PathBuf::from("/repo/src-tauri")), exe).unwrap(), Path::new("/repo"));
+        assert_eq!(resolve_project_root(gone(), exe).unwrap(), Path::new("/opt/tailor/bin"));
+        let err = resolve_project_root(gone(), gone).unwrap_err();
+        assert!(matches!(err, SidecarError::NoProjectRoot { .. }), "{:?}", err);
+        assert!(err.to_string().contains("sidecar_root"), "unexpected error: {}", err);
     }
 
     #[tokio::test]
@@ -304,4 +7173,84 @@ mod tests {
         assert_eq!(*manager.next_port.lock().await, 9000);
         assert!(manager.processes.lock().await.is_empty());
     }
+
+    /// Minimal JSON-RPC sidecar implementing `ping` and `echo`, serving any number of requests
+    /// per connection and answering WebSocket pings and close frames like a real server
+    const ECHO_SIDECAR: &str = r#"
+def serve(conn):
+    handshake(conn)
+    try:
+        while True:
+            opcode, payload = recv_frame(conn)
+            if opcode == 0x8:
+                conn.sendall(bytes([0x88, 0]))
+                return
+            if opcode == 0x9:
+                conn.sendall(bytes([0x8A, len(payload)]) + payload)
+                continue
+            request = json.loads(payload)
+            if request["method"] == "ping":
+                reply = {"result": "pong"}
+            elif request["method"] == "echo":
+                reply = {"result": request["params"]}
+            else:
+                reply = {"error": {"code": -32601, "message": "Method not found"}}
+            send_json(conn, dict(reply, jsonrpc="2.0", id=request["id"]))
+    except OSError:
+        pass
+    finally:
+        conn.close()
+"#;
+
+    /// Spawn → connect → command → terminate against a separate sidecar process, over both
+    /// one-shot and persistent connections. Slower than the unit tests, so opt-in:
+    /// `cargo test -- --ignored end_to_end`
+    #[tokio::test]
+    #[ignore = "spawns Python; run with --include-ignored"]
+    async fn test_end_to_end_sidecar_lifecycle() {
+        for persistent_connections in [false, true] {
+            let manager = SidecarManager::with_config(SidecarManagerConfig {
+                persistent_connections,
+                ..Default::default()
+            });
+            let mut state = manager.watch_state("e2e");
+            let launcher = fake_ws_launcher(ECHO_SIDECAR);
+            let options = SpawnOptions { launcher: Some(launcher.argv()), ..Default::default() };
+            let port = manager.spawn_sidecar_with_options("e2e".into(), temp_vault(), options).await.unwrap();
+            tokio::time::timeout(Duration::from_secs(10), state.wait_for(|s| *s == SidecarState::Ready)).await
+                .unwrap()
+                .unwrap();
+            assert_eq!(manager.get_ws_port("e2e").await, Some(port));
+            assert!(!manager.is_port_available(port));
+
+            let response = manager.send_command("e2e", "ping", serde_json::json!({})).await.unwrap();
+            assert_eq!(response["result"], "pong");
+            for i in 0..5 {
+                let params = serde_json::json!({"n": i, "text": "x".repeat(i * 100)});
+                let response = manager.send_command("e2e", "echo", params.clone()).await.unwrap();
+                assert_eq!(response["result"], params);
+            }
+            let err = manager.send_command("e2e", "missing", serde_json::json!({})).await.unwrap_err();
+            assert!(err.is_method_not_found(), "{:?}", err);
+
+            let pid = manager.processes.lock().await["e2e"].child.id();
+            manager.terminate_sidecar("e2e").await.unwrap();
+            assert_eq!(*state.borrow(), SidecarState::Terminated);
+            assert!(manager.processes.lock().await.is_empty());
+            assert!(matches!(
+                manager.send_command("e2e", "ping", serde_json::json!({})).await,
+                Err(SidecarError::NotFound { .. })
+            ));
+            #[cfg(unix)]
+            assert_ne!(unsafe { libc::kill(pid as libc::pid_t, 0) }, 0, "sidecar {} still running", pid);
+            #[cfg(not(unix))]
+            let _ = pid;
+            let deadline = Instant::now() + Duration::from_secs(5);
+            while !manager.is_port_available(port) {
+                assert!(Instant::now() < deadline, "port {} was not released", port);
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+            manager.shutdown().await;
+        }
+    }
 }
@@ -0,0 +1,229 @@
+//! The sidecar's command line after the interpreter, built from an [`ArgTemplate`] so forks
+//! whose sidecar takes different flags can be driven without code changes.
+//!
+//! Placeholders are written `{name}` and may sit inside a longer argument (`--port={port}`);
+//! `{{` and `}}` are literal braces. A placeholder without a value for a launch, e.g.
+//! `{control_port}` without a control socket or `{port}` for a stdio sidecar, leaves its
+//! argument out, along with an option flag (`-x`/`--xyz`, no placeholders) right before it.
+
+use std::ffi::{OsStr, OsString};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+/// Placeholders an [`ArgTemplate`] may use
+pub const PLACEHOLDERS: &[&str] = &["vault", "port", "ready_file", "control_port", "log_level"];
+
+/// Arguments passed to the interpreter after `SpawnOptions::python_flags` and before
+/// `extra_args`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct ArgTemplate(pub Vec<String>);
+
+impl Default for ArgTemplate {
+    fn default() -> Self {
+        Self([
+            "-u", "-m", "sidecar",
+            "--vault", "{vault}",
+            "--ws-port", "{port}",
+            "--ready-file", "{ready_file}",
+            "--control-port", "{control_port}",
+            "--log-level", "{log_level}",
+        ].map(str::to_string).to_vec())
+    }
+}
+
+/// What the placeholders stand for in one launch
+#[derive(Debug, Default)]
+pub struct ArgValues<'a> {
+    pub vault: &'a str,
+    pub port: Option<u16>,
+    pub ready_file: Option<&'a Path>,
+    pub control_port: Option<u16>,
+    pub log_level: Option<&'a str>,
+}
+
+impl ArgValues<'_> {
+    fn get(&self, name: &str) -> Option<OsString> {
+        match name {
+            "vault" => Some(self.vault.into()),
+            "port" => self.port.map(|port| port.to_string().into()),
+            "ready_file" => self.ready_file.map(|path| path.as_os_str().to_owned()),
+            "control_port" => self.control_port.map(|port| port.to_string().into()),
+            "log_level" => self.log_level.map(OsString::from),
+            _ => None,
+        }
+    }
+}
+
+enum Segment<'a> {
+    Text(&'a str),
+    Placeholder(&'a str),
+}
+
+/// Split an argument into literal text and placeholders
+fn parse(arg: &str) -> Result<Vec<Segment<'_>>, String> {
+    let mut segments = Vec::new();
+    let mut rest = arg;
+    while let Some(i) = rest.find(['{', '}']) {
+        if i > 0 {
+            segments.push(Segment::Text(&rest[..i]));
+        }
+        let brace = &rest[i..i + 1];
+        rest = &rest[i + 1..];
+        if let Some(after) = rest.strip_prefix(brace) {
+            segments.push(Segment::Text(brace));
+            rest = after;
+            continue;
+        }
+        if brace == "}" {
+            return Err(format!("Unmatched '}}' in argument '{}'", arg));
+        }
+        let end = rest.find('}').ok_or_else(|| format!("Unclosed '{{' in argument '{}'", arg))?;
+        let name = &rest[..end];
+        if !PLACEHOLDERS.contains(&name) {
+            return Err(format!("Unknown placeholder '{{{}}}' in argument '{}' (expected one of {})",
+                               name, arg, PLACEHOLDERS.join(", ")));
+        }
+        segments.push(Segment::Placeholder(name));
+        rest = &rest[end + 1..];
+    }
+    if !rest.is_empty() {
+        segments.push(Segment::Text(rest));
+    }
+    Ok(segments)
+}
+
+impl ArgTemplate {
+    /// Check every argument parses and only uses known placeholders
+    pub fn validate(&self) -> Result<(), String> {
+        self.0.iter().try_for_each(|arg| parse(arg).map(drop))
+    }
+
+    /// The arguments for one launch; call [`Self::validate`] first, invalid arguments are skipped
+    pub fn render(&self, values: &ArgValues) -> Vec<OsString> {
+        let mut args = Vec::new();
+        let mut last_was_flag = false;
+        for arg in &self.0 {
+            let Ok(segments) = parse(arg) else { continue };
+            let is_flag = arg.starts_with('-')
+                && segments.iter().all(|segment| matches!(segment, Segment::Text(_)));
+            let rendered = segments.iter().try_fold(OsString::new(), |mut out, segment| {
+                match segment {
+                    Segment::Text(text) => out.push(text),
+                    Segment::Placeholder(name) => out.push(values.get(name)?),
+                }
+                Some(out)
+            });
+            match rendered {
+                Some(rendered) => args.push(rendered),
+                None if last_was_flag => {
+                    args.pop();
+                }
+                None => {}
+            }
+            last_was_flag = is_flag;
+        }
+        args
+    }
+
+    /// What `placeholder` stood for in `cmdline` (a whole command line, interpreter included)
+    /// if it was launched from this template: the template's fixed arguments appear in it in
+    /// order, and so does the placeholder's argument. None for any other command line, or if the
+    /// placeholder shares its argument with another one.
+    pub fn value_in(&self, cmdline: &[String], placeholder: &str) -> Option<String> {
+        let parsed = self.0.iter().map(|arg| parse(arg)).collect::<Result<Vec<_>, _>>().ok()?;
+        let literal = |segments: &[Segment]| -> Option<String> {
+            segments.iter()
+                .map(|segment| match segment {
+                    Segment::Text(text) => Some(*text),
+                    Segment::Placeholder(_) => None,
+                })
+                .collect()
+        };
+        let flag = |i: usize| literal(&parsed[i]).filter(|_| self.0[i].starts_with('-'));
+
+        // Option flags right before a placeholder can be left out along with its value
+        let mut rest = cmdline;
+        for (i, segments) in parsed.iter().enumerate() {
+            let Some(text) = literal(segments) else { continue };
+            let takes_value = flag(i).is_some() && parsed.get(i + 1).is_some_and(|next| literal(next).is_none());
+            if !takes_value {
+                let found = rest.iter().position(|arg| *arg == text)?;
+                rest = &rest[found + 1..];
+            }
+        }
+
+        let is_wanted = |segment: &Segment| matches!(segment, Segment::Placeholder(name) if *name == placeholder);
+        let i = parsed.iter().position(|segments| segments.iter().any(is_wanted))?;
+        let at = parsed[i].iter().position(is_wanted)?;
+        let prefix = literal(&parsed[i][..at])?;
+        let suffix = literal(&parsed[i][at + 1..])?;
+        let extract = |arg: &String| arg.strip_prefix(&prefix)?.strip_suffix(&suffix).map(str::to_string);
+        match i.checked_sub(1).and_then(flag) {
+            Some(flag) => cmdline.windows(2).find(|pair| pair[0] == flag).and_then(|pair| extract(&pair[1])),
+            // A bare `{port}` could be any argument
+            None if !prefix.is_empty() || !suffix.is_empty() => cmdline.iter().find_map(extract),
+            None => None,
+        }
+    }
+}
+
+/// Render `args` for logging, one space between arguments
+pub fn display(args: &[OsString]) -> String {
+    args.iter().map(|arg| OsStr::to_string_lossy(arg)).collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(args: Vec<OsString>) -> Vec<String> {
+        args.into_iter().map(|arg| arg.into_string().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_default_template_builds_the_sidecar_command_line() {
+        let ready = Path::new("/tmp/x.ready");
+        let values = ArgValues { vault: "/v", port: Some(9000), ready_file: Some(ready), ..Default::default() };
+        assert_eq!(strings(ArgTemplate::default().render(&values)),
+                   ["-u", "-m", "sidecar", "--vault", "/v", "--ws-port", "9000", "--ready-file", "/tmp/x.ready"]);
+
+        // A stdio launch has no port or ready file; optional values bring their flags along
+        let values = ArgValues { vault: "/v", control_port: Some(9001), log_level: Some("DEBUG"), ..Default::default() };
+        assert_eq!(strings(ArgTemplate::default().render(&values)),
+                   ["-u", "-m", "sidecar", "--vault", "/v", "--control-port", "9001", "--log-level", "DEBUG"]);
+    }
+
+    #[test]
+    fn test_custom_templates() {
+        let template = ArgTemplate(["-m", "fork.sidecar", "--port={port}", "{{literal}}", "--debug", "{vault}"]
+            .map(str::to_string).to_vec());
+        template.validate().unwrap();
+        let values = ArgValues { vault: "/v", port: Some(7), ..Default::default() };
+        assert_eq!(strings(template.render(&values)), ["-m", "fork.sidecar", "--port=7", "{literal}", "--debug", "/v"]);
+
+        for bad in ["{token}", "--port={port", "x}"] {
+            assert!(ArgTemplate(vec![bad.to_string()]).validate().is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_values_are_read_back_from_command_lines() {
+        let cmdline = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+        let default = ArgTemplate::default();
+        let launched = cmdline(&["python3", "-O", "-u", "-m", "sidecar", "--vault", "/v", "--ws-port", "9001", "--extra"]);
+        assert_eq!(default.value_in(&launched, "port").as_deref(), Some("9001"));
+        assert_eq!(default.value_in(&launched, "vault").as_deref(), Some("/v"));
+        assert_eq!(default.value_in(&launched, "control_port"), None);
+        assert_eq!(default.value_in(&cmdline(&["python3", "-m", "sidecar", "--ws-port", "9001"]), "port"), None);
+        assert_eq!(default.value_in(&cmdline(&["python3", "-u", "-m", "other", "--ws-port", "9001"]), "port"), None);
+
+        let fork = ArgTemplate(["-m", "fork.sidecar", "--port={port}", "{{literal}}", "--debug", "{vault}"]
+            .map(str::to_string).to_vec());
+        let launched = cmdline(&["python3", "-m", "fork.sidecar", "--port=7", "{literal}", "--debug", "/v"]);
+        assert_eq!(fork.value_in(&launched, "port").as_deref(), Some("7"));
+        assert_eq!(fork.value_in(&launched, "vault").as_deref(), Some("/v"));
+        assert_eq!(default.value_in(&launched, "port"), None);
+    }
+}
@@ -0,0 +1,164 @@
+//! Optional per-sidecar audit trail of RPC calls, for turning "it worked yesterday" reports into
+//! a timeline of what the UI asked the sidecar to do.
+//!
+//! With `SidecarManagerConfig::audit_log_dir` set, every command appends two JSON lines to
+//! `<dir>/<window_label>.jsonl`: a `request` record (method, params size, the params with
+//! sensitive keys redacted and long strings elided) when it is sent, and a `response` record
+//! (status, latency, result size) when it finishes; both share a `seq` number. The file is kept
+//! across restarts.
+
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use chrono::Utc;
+
+use super::SidecarError;
+
+/// Param keys redacted unless configured otherwise
+pub const DEFAULT_REDACT: &[&str] = &["password", "token", "secret", "api_key", "authorization"];
+
+/// Longest param string kept verbatim; longer ones are recorded as their size
+const MAX_STRING_LEN: usize = 256;
+
+/// What is recorded about a request, computed before it is sent
+pub struct Request {
+    pub method: String,
+    pub params_bytes: usize,
+    pub params: serde_json::Value,
+}
+
+impl Request {
+    /// Summarize a request, replacing the values of object keys in `redact` (matched
+    /// case-insensitively, at any depth) with `"[redacted]"`
+    pub fn new(method: &str, params: &serde_json::Value, redact: &[String]) -> Self {
+        Self {
+            method: method.to_string(),
+            params_bytes: serialized_len(params),
+            params: compact(params, redact),
+        }
+    }
+}
+
+fn serialized_len(value: &serde_json::Value) -> usize {
+    serde_json::to_vec(value).map(|bytes| bytes.len()).unwrap_or_default()
+}
+
+fn compact(value: &serde_json::Value, redact: &[String]) -> serde_json::Value {
+    match value {
+        serde_json::Value::Object(map) => map.iter()
+            .map(|(key, value)| match redact.iter().any(|name| name.eq_ignore_ascii_case(key)) {
+                true => (key.clone(), "[redacted]".into()),
+                false => (key.clone(), compact(value, redact)),
+            })
+            .collect(),
+        serde_json::Value::Array(items) => items.iter().map(|item| compact(item, redact)).collect(),
+        serde_json::Value::String(text) if text.len() > MAX_STRING_LEN => format!("[{} bytes]", text.len()).into(),
+        value => value.clone(),
+    }
+}
+
+/// Size of what a command returned, for its response record
+pub trait ResultSize {
+    fn result_bytes(&self) -> usize;
+}
+
+impl ResultSize for serde_json::Value {
+    fn result_bytes(&self) -> usize {
+        serialized_len(self)
+    }
+}
+
+impl ResultSize for Vec<u8> {
+    fn result_bytes(&self) -> usize {
+        self.len()
+    }
+}
+
+/// One sidecar's audit file
+pub struct AuditLog {
+    file: std::sync::Mutex<File>,
+    next_seq: AtomicU64,
+}
+
+impl AuditLog {
+    /// Open (or create) the window's audit file in `dir` for appending
+    pub fn open(dir: &Path, window_label: &str) -> std::io::Result<Self> {
+        std::fs::create_dir_all(dir)?;
+        let name: String = window_label.chars()
+            .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+            .collect();
+        let file = OpenOptions::new().create(true).append(true).open(dir.join(format!("{}.jsonl", name)))?;
+        Ok(Self { file: std::sync::Mutex::new(file), next_seq: AtomicU64::new(0) })
+    }
+
+    /// Record a request being sent; returns the `seq` for its response record
+    pub fn request(&self, request: &Request) -> u64 {
+        let seq = self.next_seq.fetch_add(1, Ordering::Relaxed);
+        self.append(serde_json::json!({
+            "type": "request",
+            "seq": seq,
+            "timestamp": Utc::now(),
+            "method": request.method,
+            "params_bytes": request.params_bytes,
+            "params": request.params,
+        }));
+        seq
+    }
+
+    /// Record how request `seq` ended
+    pub fn response<T: ResultSize>(&self, seq: u64, method: &str, result: &Result<T, SidecarError>, latency: Duration) {
+        let mut record = serde_json::json!({
+            "type": "response",
+            "seq": seq,
+            "timestamp": Utc::now(),
+            "method": method,
+            "latency_ms": latency.as_secs_f64() * 1000.0,
+        });
+        match result {
+            Ok(value) => {
+                record["status"] = "ok".into();
+                record["result_bytes"] = value.result_bytes().into();
+            }
+            Err(e) => {
+                record["status"] = "error".into();
+                record["error"] = e.to_string().into();
+            }
+        }
+        self.append(record);
+    }
+
+    fn append(&self, record: serde_json::Value) {
+        let mut line = record.to_string();
+        line.push('\n');
+        let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        if let Err(e) = file.write_all(line.as_bytes()) {
+            eprintln!("Failed to write audit record: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_params_are_redacted_and_compacted() {
+        let params = serde_json::json!({
+            "query": "notes",
+            "auth": {"Token": "abc", "user": "me"},
+            "items": [{"password": "hunter2"}],
+            "body": "x".repeat(300),
+        });
+        let request = Request::new("search", &params, &["token".to_string(), "password".to_string()]);
+        assert_eq!(request.params, serde_json::json!({
+            "query": "notes",
+            "auth": {"Token": "[redacted]", "user": "me"},
+            "items": [{"password": "[redacted]"}],
+            "body": "[300 bytes]",
+        }));
+        assert_eq!(request.params_bytes, params.to_string().len());
+    }
+}
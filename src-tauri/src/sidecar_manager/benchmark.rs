@@ -0,0 +1,79 @@
+//! Startup timings gathered by `SidecarManager::benchmark_startup`.
+
+use std::time::Duration;
+
+use serde::Serialize;
+
+/// Spread of one startup milestone across benchmark iterations, in milliseconds
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct PhaseStats {
+    pub min_ms: f64,
+    pub max_ms: f64,
+    pub mean_ms: f64,
+}
+
+impl PhaseStats {
+    fn from_samples(samples: impl Iterator<Item = Duration>) -> Self {
+        let millis: Vec<f64> = samples.map(|d| d.as_secs_f64() * 1000.0).collect();
+        if millis.is_empty() {
+            return Self::default();
+        }
+        Self {
+            min_ms: millis.iter().copied().fold(f64::INFINITY, f64::min),
+            max_ms: millis.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+            mean_ms: millis.iter().sum::<f64>() / millis.len() as f64,
+        }
+    }
+}
+
+/// Milestones of a single spawn, each measured from the spawn request
+#[derive(Debug, Clone, Copy)]
+pub struct StartupSample {
+    pub process_start: Duration,
+    pub port_listen: Duration,
+    pub first_ping: Duration,
+}
+
+/// Result of a startup benchmark
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct StartupStats {
+    pub iterations: usize,
+    /// Until the OS process exists
+    pub process_start: PhaseStats,
+    /// Until the sidecar's WebSocket accepts a connection
+    pub port_listen: PhaseStats,
+    /// Until a WebSocket ping on that connection is answered
+    pub first_ping: PhaseStats,
+}
+
+impl StartupStats {
+    pub fn from_samples(samples: &[StartupSample]) -> Self {
+        Self {
+            iterations: samples.len(),
+            process_start: PhaseStats::from_samples(samples.iter().map(|s| s.process_start)),
+            port_listen: PhaseStats::from_samples(samples.iter().map(|s| s.port_listen)),
+            first_ping: PhaseStats::from_samples(samples.iter().map(|s| s.first_ping)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stats_from_samples() {
+        let sample = |start, listen, ping| StartupSample {
+            process_start: Duration::from_millis(start),
+            port_listen: Duration::from_millis(listen),
+            first_ping: Duration::from_millis(ping),
+        };
+        let stats = StartupStats::from_samples(&[sample(10, 100, 110), sample(30, 300, 330)]);
+
+        assert_eq!(stats.iterations, 2);
+        assert_eq!(stats.process_start, PhaseStats { min_ms: 10.0, max_ms: 30.0, mean_ms: 20.0 });
+        assert_eq!(stats.port_listen.mean_ms, 200.0);
+        assert_eq!(stats.first_ping.max_ms, 330.0);
+        assert_eq!(StartupStats::from_samples(&[]).first_ping, PhaseStats::default());
+    }
+}
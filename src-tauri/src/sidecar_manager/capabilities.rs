@@ -0,0 +1,32 @@
+//! The commands a sidecar implements, as listed by its [`CAPABILITIES_METHOD`].
+//!
+//! The manager caches the list per window. A sidecar restarted with a different version can
+//! make it stale, so `send_command_with_capability_refresh` re-fetches it once when a command
+//! comes back "method not found" before deciding the method really isn't supported.
+
+use std::collections::HashSet;
+
+use super::error::SidecarError;
+
+/// RPC listing the sidecar's registered commands (`{"commands": [...]}`)
+pub const CAPABILITIES_METHOD: &str = "system.list_commands";
+
+/// The command names in a [`CAPABILITIES_METHOD`] response
+pub fn from_response(response: &serde_json::Value) -> Result<HashSet<String>, SidecarError> {
+    response.pointer("/result/commands")
+        .and_then(|commands| commands.as_array())
+        .map(|commands| commands.iter().filter_map(|c| c.as_str().map(str::to_string)).collect())
+        .ok_or_else(|| SidecarError::Protocol("Capability list is missing 'commands'".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_commands_are_read_from_the_result() {
+        let response = serde_json::json!({"result": {"status": "success", "commands": ["chat.send", "system.info"]}});
+        assert_eq!(from_response(&response).unwrap(), HashSet::from(["chat.send".to_string(), "system.info".to_string()]));
+        assert!(from_response(&serde_json::json!({"result": {"status": "success"}})).is_err());
+    }
+}
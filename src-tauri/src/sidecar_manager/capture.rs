@@ -0,0 +1,190 @@
+//! Splitting sidecar stdout/stderr into log records.
+
+use std::io::{BufRead, BufReader, ErrorKind, Read};
+
+use serde::Deserialize;
+
+/// Longest record kept in memory unless `SidecarManagerConfig::max_log_record` says otherwise
+pub const DEFAULT_MAX_RECORD_LEN: usize = 64 * 1024;
+
+/// Longest line kept in [`CaptureMode::Lines`] unless `SidecarManagerConfig::max_log_line` says otherwise
+pub const DEFAULT_MAX_LINE_LEN: usize = 8 * 1024;
+
+/// Most bytes handed over per record in [`CaptureMode::Raw`]
+const RAW_CHUNK_LEN: usize = 8 * 1024;
+
+/// How a sidecar's output pipes are cut into records, set per vault with `capture = "raw"`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CaptureMode {
+    /// Newline-terminated text. A line longer than the cap is cut there and marked with a
+    /// `...[truncated N bytes]` suffix; the rest of it is read and dropped.
+    #[default]
+    Lines,
+    /// Bytes as they arrive, for binary output
+    Raw,
+    /// Frames of a 4-byte big-endian length followed by that many bytes
+    LengthPrefixed,
+}
+
+/// Read `reader` to EOF, passing each record to `sink`. No record exceeds `max_len` bytes, so
+/// a sidecar flooding output without newlines can't make us buffer without bound.
+pub fn read_records(reader: impl Read, mode: CaptureMode, max_len: usize, sink: impl FnMut(Vec<u8>)) {
+    let max_len = max_len.max(1);
+    match mode {
+        CaptureMode::Lines => read_lines(BufReader::new(reader), max_len, sink),
+        CaptureMode::Raw => read_raw(reader, max_len.min(RAW_CHUNK_LEN), sink),
+        CaptureMode::LengthPrefixed => read_frames(reader, max_len, sink),
+    }
+}
+
+fn read_lines(mut reader: impl BufRead, max_len: usize, mut sink: impl FnMut(Vec<u8>)) {
+    let mut line = Line::default();
+    loop {
+        let buf = match reader.fill_buf() {
+            Ok([]) => break,
+            Ok(buf) => buf,
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(_) => break,
+        };
+        let (taken, complete) = match buf.iter().position(|&b| b == b'\n') {
+            Some(newline) => (newline + 1, true),
+            None => (buf.len(), false),
+        };
+        line.push(&buf[..taken - complete as usize], max_len);
+        reader.consume(taken);
+        if complete {
+            sink(std::mem::take(&mut line).finish());
+        }
+    }
+    if !line.bytes.is_empty() || line.dropped > 0 {
+        sink(line.finish());
+    }
+}
+
+/// A line being read: the bytes kept so far and how many were dropped past the cap
+#[derive(Default)]
+struct Line {
+    bytes: Vec<u8>,
+    dropped: usize,
+    /// The last byte read, kept or not, was `\r`
+    ends_with_cr: bool,
+}
+
+impl Line {
+    fn push(&mut self, data: &[u8], max_len: usize) {
+        let kept = data.len().min(max_len.saturating_sub(self.bytes.len()));
+        self.bytes.extend_from_slice(&data[..kept]);
+        self.dropped += data.len() - kept;
+        if let Some(&last) = data.last() {
+            self.ends_with_cr = last == b'\r';
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.ends_with_cr && self.dropped > 0 {
+            self.dropped -= 1;
+        } else if self.ends_with_cr {
+            self.bytes.pop();
+        }
+        if self.dropped == 0 {
+            return self.bytes;
+        }
+        // Don't leave half a UTF-8 character before the marker
+        if let Err(e) = std::str::from_utf8(&self.bytes) {
+            if e.error_len().is_none() {
+                self.dropped += self.bytes.len() - e.valid_up_to();
+                self.bytes.truncate(e.valid_up_to());
+            }
+        }
+        self.bytes.extend_from_slice(format!("...[truncated {} bytes]", self.dropped).as_bytes());
+        self.bytes
+    }
+}
+
+fn read_raw(mut reader: impl Read, chunk_len: usize, mut sink: impl FnMut(Vec<u8>)) {
+    let mut buf = vec![0; chunk_len];
+    loop {
+        match reader.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => sink(buf[..n].to_vec()),
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(_) => break,
+        }
+    }
+}
+
+fn read_frames(mut reader: impl Read, max_len: usize, mut sink: impl FnMut(Vec<u8>)) {
+    let mut header = [0u8; 4];
+    while reader.read_exact(&mut header).is_ok() {
+        let len = u32::from_be_bytes(header) as usize;
+        if len > max_len {
+            // There's no resynchronising after a bad length; keep draining so the child never blocks
+            eprintln!("Sidecar frame of {} bytes exceeds the {} byte limit, discarding the rest of the stream", len, max_len);
+            let _ = std::io::copy(&mut reader, &mut std::io::sink());
+            return;
+        }
+        let mut frame = vec![0; len];
+        if reader.read_exact(&mut frame).is_err() {
+            break;
+        }
+        sink(frame);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn records(input: &[u8], mode: CaptureMode, max_len: usize) -> Vec<Vec<u8>> {
+        let mut out = Vec::new();
+        read_records(input, mode, max_len, |record| out.push(record));
+        out
+    }
+
+    #[test]
+    fn test_lines_strip_endings() {
+        assert_eq!(records(b"one\r\ntwo\n\nthree", CaptureMode::Lines, 64), [&b"one"[..], b"two", b"", b"three"]);
+
+        // A line exactly at the cap is whole: no truncation marker, no empty record after it
+        assert_eq!(records(b"abcd\nef\r\nabcd\r\nabcd", CaptureMode::Lines, 4), [&b"abcd"[..], b"ef", b"abcd", b"abcd"]);
+    }
+
+    #[test]
+    fn test_long_lines_are_truncated_at_the_cap() {
+        let flood = vec![b'x'; 1_000_000];
+        let out = records(&flood, CaptureMode::Lines, 4096);
+        let mut expected = vec![b'x'; 4096];
+        expected.extend_from_slice(format!("...[truncated {} bytes]", 1_000_000 - 4096).as_bytes());
+        assert_eq!(out, [expected]);
+
+        // The rest of a long line is drained across reads: the next line comes out whole
+        let mut input = vec![b'y'; 5000];
+        input.extend_from_slice(b"\r\nshort\n");
+        let out = records(&input, CaptureMode::Lines, 4096);
+        assert_eq!(String::from_utf8_lossy(&out[0][4096..]), "...[truncated 904 bytes]");
+        assert_eq!(out[1], b"short");
+
+        // A multi-byte character straddling the cap is dropped whole
+        let out = records("aé€b\n".as_bytes(), CaptureMode::Lines, 4);
+        assert_eq!(String::from_utf8(out[0].clone()).unwrap(), "aé...[truncated 4 bytes]");
+    }
+
+    #[test]
+    fn test_raw_and_length_prefixed() {
+        let binary: Vec<u8> = (0..=255).collect();
+        assert_eq!(records(&binary, CaptureMode::Raw, 100).concat(), binary);
+
+        let mut framed = Vec::new();
+        for frame in [&b"\x00\x01"[..], b"", b"hello"] {
+            framed.extend_from_slice(&(frame.len() as u32).to_be_bytes());
+            framed.extend_from_slice(frame);
+        }
+        assert_eq!(records(&framed, CaptureMode::LengthPrefixed, 64), [&b"\x00\x01"[..], b"", b"hello"]);
+
+        // An oversized frame ends framing instead of allocating it
+        let mut oversized = 1_000_000u32.to_be_bytes().to_vec();
+        oversized.extend_from_slice(b"junk");
+        assert!(records(&oversized, CaptureMode::LengthPrefixed, 64).is_empty());
+    }
+}
@@ -0,0 +1,93 @@
+//! Debouncing rapid-fire commands, e.g. a `search` per keystroke, for
+//! `SidecarManager::send_command_coalesced`.
+//!
+//! A coalesced command waits out its debounce before it is sent. Another one for the same
+//! window, method and caller-chosen key arriving meanwhile takes its place, and the waiting one
+//! resolves as [`Coalesced::Superseded`] straight away. Once sent, a command is never superseded.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use serde::Serialize;
+use tokio::sync::watch;
+
+/// Outcome of a coalesced command
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "status", content = "response", rename_all = "snake_case")]
+pub enum Coalesced {
+    /// Sent; the sidecar's response
+    Sent(serde_json::Value),
+    /// A newer command with the same key replaced this one before it was sent
+    Superseded,
+}
+
+/// Debounce for callers without an opinion, e.g. the IPC command
+pub const DEFAULT_DEBOUNCE: Duration = Duration::from_millis(150);
+
+type Key = (String, String, String);
+
+/// The newest command waiting per key
+#[derive(Default)]
+pub struct Coalescer {
+    latest: std::sync::Mutex<HashMap<Key, watch::Sender<u64>>>,
+    next_ticket: AtomicU64,
+}
+
+impl Coalescer {
+    /// Wait `debounce` for the command, returning whether it should still be sent (no newer
+    /// one with the same key came in)
+    pub async fn debounce(&self, window_label: &str, method: &str, key: &str, debounce: Duration) -> bool {
+        let key = (window_label.to_string(), method.to_string(), key.to_string());
+        let ticket = self.next_ticket.fetch_add(1, Ordering::Relaxed);
+        let mut newest = {
+            let mut latest = self.latest.lock().unwrap_or_else(|e| e.into_inner());
+            let sender = latest.entry(key.clone()).or_insert_with(|| watch::channel(ticket).0);
+            sender.send_replace(ticket);
+            sender.subscribe()
+        };
+        let superseded = tokio::select! {
+            _ = tokio::time::sleep(debounce) => false,
+            _ = newest.wait_for(|newest| *newest != ticket) => true,
+        };
+        let current = !superseded && *newest.borrow() == ticket;
+        if current {
+            // Later commands with this key start a fresh debounce
+            let mut latest = self.latest.lock().unwrap_or_else(|e| e.into_inner());
+            if latest.get(&key).is_some_and(|sender| *sender.borrow() == ticket) {
+                latest.remove(&key);
+            }
+        }
+        current
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_newer_commands_supersede_waiting_ones() {
+        let coalescer = Arc::new(Coalescer::default());
+        let debounce = Duration::from_millis(100);
+        let first = tokio::spawn({
+            let coalescer = coalescer.clone();
+            async move { coalescer.debounce("w", "search", "box", debounce).await }
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        let other_key = tokio::spawn({
+            let coalescer = coalescer.clone();
+            async move { coalescer.debounce("w", "search", "sidebar", debounce).await }
+        });
+        let started = std::time::Instant::now();
+        let second = coalescer.debounce("w", "search", "box", debounce).await;
+
+        assert!(!first.await.unwrap());
+        assert!(second);
+        assert!(other_key.await.unwrap());
+        assert!(started.elapsed() >= debounce);
+        assert!(coalescer.latest.lock().unwrap().is_empty());
+    }
+}
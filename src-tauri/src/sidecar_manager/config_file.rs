@@ -0,0 +1,216 @@
+//! Reading and writing [`SidecarManagerConfig`] as a file, so settings tuned by the user survive
+//! restarts. A `.json` file is JSON, anything else TOML.
+//!
+//! Durations are written in milliseconds under `<field>_ms` keys, e.g. `request_timeout_ms = 30000`.
+//! Missing keys keep the value they have in the defaults the file is loaded over, so a file only
+//! needs the settings that differ. `handshake_headers` usually carry auth tokens and are never
+//! written; ones added to the file by hand are kept when it is saved over.
+
+use std::path::Path;
+
+use anyhow::{Context, Result};
+
+use super::SidecarManagerConfig;
+
+/// `serde(with)` helpers for a `Duration` stored as whole milliseconds
+pub mod duration_ms {
+    use std::time::Duration;
+
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(duration.as_millis().try_into().unwrap_or(u64::MAX))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        u64::deserialize(deserializer).map(Duration::from_millis)
+    }
+}
+
+/// `serde(with)` helpers for an `Option<Duration>` stored as whole milliseconds, None as null
+pub mod option_duration_ms {
+    use std::time::Duration;
+
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(duration: &Option<Duration>, serializer: S) -> Result<S::Ok, S::Error> {
+        match duration {
+            Some(duration) => super::duration_ms::serialize(duration, serializer),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Duration>, D::Error> {
+        Option::<u64>::deserialize(deserializer).map(|ms| ms.map(Duration::from_millis))
+    }
+}
+
+/// Where the app keeps the settings file, in its data directory
+pub const CONFIG_FILE_NAME: &str = "sidecar_config.toml";
+
+/// Settings [`save_config`] leaves out of the file, since they usually hold secrets
+const SECRET_KEYS: [&str; 1] = ["handshake_headers"];
+
+fn is_secret(key: &str) -> bool {
+    SECRET_KEYS.contains(&key)
+}
+
+fn is_json(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "json")
+}
+
+/// The settings table in `path`
+fn read_table(path: &Path) -> Result<serde_json::Map<String, serde_json::Value>> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    let value: serde_json::Value = if is_json(path) {
+        serde_json::from_str(&contents)
+            .with_context(|| format!("Invalid JSON in {}", path.display()))?
+    } else {
+        toml::from_str(&contents)
+            .with_context(|| format!("Invalid TOML in {}", path.display()))?
+    };
+    match value {
+        serde_json::Value::Object(table) => Ok(table),
+        _ => anyhow::bail!("{} must contain a table of settings", path.display()),
+    }
+}
+
+/// `base` with `changes` laid over it: tables are merged key by key, anything else is replaced
+pub fn merge_config(base: &SidecarManagerConfig, changes: serde_json::Value) -> Result<SidecarManagerConfig> {
+    fn overlay(base: &mut serde_json::Value, changes: serde_json::Value) {
+        match (base, changes) {
+            (serde_json::Value::Object(base), serde_json::Value::Object(changes)) => {
+                for (key, value) in changes {
+                    overlay(base.entry(key).or_insert(serde_json::Value::Null), value);
+                }
+            }
+            (base, changes) => *base = changes,
+        }
+    }
+
+    let mut merged = serde_json::to_value(base)?;
+    overlay(&mut merged, changes);
+    Ok(serde_json::from_value(merged)?)
+}
+
+/// Load a config written by [`save_config`] (or by hand) over `defaults`
+pub fn load_config(path: &Path, defaults: &SidecarManagerConfig) -> Result<SidecarManagerConfig> {
+    let table = read_table(path)?;
+    let known = serde_json::to_value(SidecarManagerConfig::default())?;
+    for key in table.keys().filter(|key| known.get(key.as_str()).is_none()) {
+        // Unknown keys may belong to a newer version; don't fail on them
+        eprintln!("Warning: ignoring unknown key '{}' in {}", key, path.display());
+    }
+
+    merge_config(defaults, serde_json::Value::Object(table))
+        .with_context(|| format!("Invalid sidecar manager settings in {}", path.display()))
+}
+
+/// Write `config` to `path`, replacing the file. [`SECRET_KEYS`] are not written from `config`;
+/// whatever the file already had for them is kept.
+pub fn save_config(config: &SidecarManagerConfig, path: &Path) -> Result<()> {
+    let mut kept = match path.exists() {
+        true => read_table(path)?,
+        false => Default::default(),
+    };
+    kept.retain(|key, _| is_secret(key));
+
+    let contents = if is_json(path) {
+        let mut table = serde_json::to_value(config)?;
+        if let Some(table) = table.as_object_mut() {
+            table.retain(|key, _| !is_secret(key));
+            table.extend(kept);
+        }
+        serde_json::to_string_pretty(&table)?
+    } else {
+        let mut table = toml::Table::try_from(config)?;
+        table.retain(|key, _| !is_secret(key));
+        for (key, value) in kept {
+            table.insert(key, toml::Value::try_from(value)?);
+        }
+        toml::to_string_pretty(&table)?
+    };
+    std::fs::write(path, contents).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::sidecar_manager::quarantine::QuarantineConfig;
+    use crate::sidecar_manager::process::ProcessPriority;
+    use crate::sidecar_manager::{DegradedConfig, MemoryBudgetConfig, RequestFormat, RoleProfile, WorkingDir};
+
+    #[test]
+    fn test_config_round_trips_through_toml_and_json() {
+        let base = std::env::temp_dir().join(format!("tailor-config-{}", uuid::Uuid::new_v4()));
+        let mut config = SidecarManagerConfig {
+            request_timeout: Some(Duration::from_millis(2500)),
+            bundled_python: Some("/opt/python/bin/python3".to_string()),
+            working_dir: WorkingDir::Path("/tmp/sidecars".into()),
+            handshake_headers: vec![("Authorization".to_string(), "Bearer x".to_string())],
+            request_format: RequestFormat::Indented("\t".to_string()),
+            degraded: Some(DegradedConfig::default()),
+            total_memory_budget: Some(MemoryBudgetConfig {
+                total_bytes: 1 << 30,
+                sample_interval: Duration::from_secs(10),
+                min_idle: Duration::from_secs(60),
+            }),
+            ..Default::default()
+        };
+        config.replay.methods.insert("search".to_string());
+        config.keepalive.interval = Duration::from_secs(5);
+        config.roles.insert("indexer".to_string(), RoleProfile {
+            priority: ProcessPriority::Nice(5),
+            idle_timeout: Some(Duration::from_secs(30)),
+            max_restarts: None,
+        });
+
+        for path in [base.with_extension("toml"), base.with_extension("json")] {
+            save_config(&config, &path).unwrap();
+            let contents = std::fs::read_to_string(&path).unwrap();
+            assert!(!contents.contains("Bearer"), "secrets were written: {}", contents);
+            let loaded = load_config(&path, &SidecarManagerConfig::default()).unwrap();
+            assert!(loaded.handshake_headers.is_empty());
+            let loaded = SidecarManagerConfig { handshake_headers: config.handshake_headers.clone(), ..loaded };
+            assert_eq!(format!("{:?}", loaded), format!("{:?}", config), "{}", path.display());
+
+            // Headers added by hand survive saving over the file
+            let by_hand = match is_json(&path) {
+                true => r#"{"handshake_headers": [["Authorization", "Bearer y"]]}"#,
+                false => "handshake_headers = [[\"Authorization\", \"Bearer y\"]]\n",
+            };
+            std::fs::write(&path, by_hand).unwrap();
+            save_config(&config, &path).unwrap();
+            let loaded = load_config(&path, &SidecarManagerConfig::default()).unwrap();
+            assert_eq!(loaded.handshake_headers, [("Authorization".to_string(), "Bearer y".to_string())]);
+            assert_eq!(loaded.request_format, config.request_format);
+            let _ = std::fs::remove_file(&path);
+        }
+    }
+
+    #[test]
+    fn test_missing_keys_take_defaults() {
+        let path = std::env::temp_dir().join(format!("tailor-config-{}.toml", uuid::Uuid::new_v4()));
+        std::fs::write(&path, "kill_timeout_ms = 750\nfuture_setting = true\n\n[quarantine]\nmax_crashes = 5\n\n[degraded]\nmin_sidecars = 4\n").unwrap();
+        let defaults = SidecarManagerConfig {
+            degraded: Some(DegradedConfig { fraction: 0.25, ..Default::default() }),
+            ..Default::default()
+        };
+
+        let config = load_config(&path, &defaults).unwrap();
+        assert_eq!(config.kill_timeout, Duration::from_millis(750));
+        assert_eq!(config.quarantine.max_crashes, 5);
+        assert_eq!(config.quarantine.window, QuarantineConfig::default().window);
+        assert_eq!(config.request_timeout, SidecarManagerConfig::default().request_timeout);
+        // Nested tables merge over the defaults instead of replacing them
+        let degraded = config.degraded.unwrap();
+        assert_eq!((degraded.fraction, degraded.min_sidecars), (0.25, 4));
+
+        std::fs::write(&path, "kill_timeout_ms = \"soon\"\n").unwrap();
+        assert!(load_config(&path, &defaults).is_err());
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,1365 @@
+//! Long-lived WebSocket connection to a sidecar.
+//!
+//! A single background task owns the socket: it writes queued requests, routes responses back
+//! to their callers by JSON-RPC id, and keeps the link alive with WebSocket pings. A pong that
+//! does not arrive within [`KeepaliveConfig::timeout`] marks the connection dead; every pending
+//! request fails and the next command opens a fresh connection. Messages that aren't responses
+//! (a `method` and no `id`) are published as [`SidecarNotification::Event`]s.
+//!
+//! A request with a binary attachment is sent as two adjacent frames: the JSON-RPC text frame
+//! carrying an extra top-level `"attachment": {"size": N}` member, then one binary frame with
+//! the raw bytes. The sidecar hands the bytes to the handler as its `blob` param and answers
+//! with an ordinary JSON-RPC response.
+//!
+//! A request sent with a top-level `"binary_result": true` member is answered with one binary
+//! frame instead of a JSON-RPC result: a 2-byte big-endian id length, the request id in UTF-8,
+//! then the raw result bytes (see [`decode_binary_result`]). The id tag lets shared connections
+//! correlate binary results like text ones. Failures still come back as JSON-RPC errors.
+//!
+//! A response whose id was already answered is a sidecar bug; [`DuplicateResponses`] decides
+//! whether it is dropped with a warning or rejected as a protocol violation. Either way only the
+//! duplicate is affected: the connection and the other requests in flight carry on. An error response
+//! with `"id": null`, which JSON-RPC sends when it couldn't read the request's id, can only be
+//! matched by elimination; [`NullIdErrors`] decides whether the only request in flight takes it.
+//!
+//! The task ends a connection with a Close frame and waits (up to [`CLOSE_HANDSHAKE_TIMEOUT`])
+//! for the sidecar's answering one, so the sidecar sees a clean disconnect rather than a reset.
+//! [`PersistentConnection::close`] does this on demand, e.g. before its sidecar is terminated.
+//!
+//! Every connection's upgrade request carries `X-Tailor-Client-Version` plus any configured
+//! handshake headers (auth tokens, trace ids), see [`handshake_request`].
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::FutureExt;
+use serde::{Deserialize, Serialize};
+use tokio::net::TcpStream;
+use tokio::sync::{broadcast, mpsc, oneshot, watch};
+use tokio::task::JoinHandle;
+use tokio::time::{interval_at, Instant, MissedTickBehavior};
+use tokio_tungstenite::tungstenite::error::{Error as WsError, ProtocolError};
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::handshake::client::Request;
+use tokio_tungstenite::tungstenite::http::{HeaderName, HeaderValue};
+use tokio_tungstenite::{connect_async, tungstenite::protocol::Message, MaybeTlsStream, WebSocketStream};
+
+use super::notifications::SidecarNotification;
+use super::transport::RpcTransport;
+use super::error;
+use super::SidecarError;
+
+/// Requests queued for the connection task before callers start waiting
+const REQUEST_QUEUE_CAPACITY: usize = 64;
+
+/// How long the WebSocket handshake with a sidecar may take
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long a closing connection waits for the sidecar to answer its Close frame
+pub const CLOSE_HANDSHAKE_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Answered request ids a persistent connection remembers to recognise duplicate responses
+const ANSWERED_ID_MEMORY: usize = 256;
+
+/// Sent on every handshake so the sidecar can tell which app build is talking to it
+pub const CLIENT_VERSION_HEADER: &str = "x-tailor-client-version";
+
+/// Headers the WebSocket handshake itself depends on; configured headers may not replace them
+const RESERVED_HEADERS: [&str; 6] =
+    ["host", "connection", "upgrade", "sec-websocket-key", "sec-websocket-version", CLIENT_VERSION_HEADER];
+
+pub type WsStream = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// What to do with a second response carrying an already answered request id
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DuplicateResponses {
+    /// Keep the first response, log and discard the rest
+    #[default]
+    Lenient,
+    /// Treat it as a protocol violation: a one-shot request fails with [`SidecarError::Protocol`];
+    /// on a persistent connection, whose request was already answered, the duplicate is
+    /// rejected and counted in [`ConnStats::rejected_duplicates`]
+    Strict,
+}
+
+/// What to do with an error response whose `id` is null
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum NullIdErrors {
+    /// With exactly one request in flight the error must be its answer, so that request fails
+    /// with it; with several it is treated as under `Reject`
+    #[default]
+    AttributeToSole,
+    /// Treat it as a protocol violation: the requests in flight fail with
+    /// [`SidecarError::Protocol`] and a persistent connection is dropped
+    Reject,
+}
+
+impl NullIdErrors {
+    /// The request in flight that the error answers, given the ids in flight
+    pub fn owner<'a>(self, mut in_flight: impl ExactSizeIterator<Item = &'a String>) -> Option<&'a String> {
+        match (self, in_flight.len()) {
+            (Self::AttributeToSole, 1) => in_flight.next(),
+            _ => None,
+        }
+    }
+}
+
+/// Whether `message` is an error response with `"id": null`
+pub fn is_null_id_error(message: &serde_json::Value) -> bool {
+    message.get("id").is_some_and(serde_json::Value::is_null) && message.get("error").is_some()
+}
+
+/// The error for requests in flight when an error response without an id answers none of them
+pub fn unattributed_error(message: &serde_json::Value, in_flight: usize) -> SidecarError {
+    SidecarError::Protocol(format!("Error response with a null id while {} requests were in flight: {}",
+                                   in_flight, message["error"]))
+}
+
+/// How requests are serialized before they are sent
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RequestFormat {
+    #[default]
+    Compact,
+    /// Indented, for reading requests in sidecar logs or a packet capture
+    Pretty,
+    /// Like `Pretty` with this indent, e.g. `"\t"` for sidecar parsers or tools expecting tabs
+    Indented(String),
+}
+
+impl RequestFormat {
+    pub fn encode(&self, request: &serde_json::Value) -> Result<String, SidecarError> {
+        let protocol = |e: serde_json::Error| SidecarError::Protocol(e.to_string());
+        match self {
+            Self::Compact => serde_json::to_string(request).map_err(protocol),
+            Self::Pretty => serde_json::to_string_pretty(request).map_err(protocol),
+            Self::Indented(indent) => {
+                let mut buf = Vec::new();
+                let formatter = serde_json::ser::PrettyFormatter::with_indent(indent.as_bytes());
+                request.serialize(&mut serde_json::Serializer::with_formatter(&mut buf, formatter)).map_err(protocol)?;
+                String::from_utf8(buf)
+                    .map_err(|_| SidecarError::Protocol("Request indent is not valid UTF-8".to_string()))
+            }
+        }
+    }
+}
+
+/// A JSON-RPC request id; the spec allows strings and integers
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+#[serde(untagged)]
+pub enum RpcId {
+    Str(String),
+    Num(i64),
+}
+
+impl RpcId {
+    /// The id as JSON text, which is how responses are matched to requests: the string `"7"` and
+    /// the number `7` are different ids
+    pub fn key(&self) -> String {
+        match self {
+            Self::Str(id) => serde_json::Value::from(id.as_str()).to_string(),
+            Self::Num(id) => id.to_string(),
+        }
+    }
+
+    /// [`Self::key`] of a response's `id` member
+    pub fn key_of(message: &serde_json::Value) -> Option<String> {
+        match message.get("id")? {
+            serde_json::Value::String(id) => Some(Self::Str(id.clone()).key()),
+            serde_json::Value::Number(id) => id.as_i64().map(|id| Self::Num(id).key()),
+            _ => None,
+        }
+    }
+
+    /// Keys of the ids a binary result's tag may stand for. The frame carries the id as bare
+    /// text, so a numeric tag answers either a string or an integer id.
+    pub fn tag_keys(tag: &str) -> Vec<String> {
+        let mut keys = vec![Self::Str(tag.to_string()).key()];
+        keys.extend(tag.parse().ok().map(|id| Self::Num(id).key()));
+        keys
+    }
+}
+
+impl From<String> for RpcId {
+    fn from(id: String) -> Self {
+        Self::Str(id)
+    }
+}
+
+impl From<i64> for RpcId {
+    fn from(id: i64) -> Self {
+        Self::Num(id)
+    }
+}
+
+/// Produces the id of each request, e.g. a counter for deterministic tests or ids taken from an
+/// external tracing system. Ids must be unique among a sidecar's outstanding requests.
+pub type IdGenerator = Arc<dyn Fn() -> RpcId + Send + Sync>;
+
+/// Where request ids come from
+#[derive(Clone, Default)]
+pub enum RequestIds {
+    /// A random UUID v4 per request
+    #[default]
+    Uuid,
+    Custom(IdGenerator),
+}
+
+impl fmt::Debug for RequestIds {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Uuid => f.write_str("Uuid"),
+            Self::Custom(_) => f.write_str("Custom(..)"),
+        }
+    }
+}
+
+impl RequestIds {
+    pub fn next(&self) -> RpcId {
+        match self {
+            Self::Uuid => RpcId::Str(uuid::Uuid::new_v4().to_string()),
+            Self::Custom(generate) => generate(),
+        }
+    }
+}
+
+/// How the manager talks to sidecars, shared by one-shot and persistent connections
+#[derive(Debug, Clone, Default)]
+pub struct ClientOptions {
+    /// Added to every upgrade request, see [`handshake_request`]
+    pub headers: Vec<(String, String)>,
+    pub duplicate_responses: DuplicateResponses,
+    pub null_id_errors: NullIdErrors,
+    pub format: RequestFormat,
+    pub ids: RequestIds,
+}
+
+/// Ping schedule for a persistent connection
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KeepaliveConfig {
+    /// Time between pings
+    #[serde(with = "super::config_file::duration_ms", rename = "interval_ms")]
+    pub interval: Duration,
+    /// How long a ping may go unanswered before the connection is considered dead
+    #[serde(with = "super::config_file::duration_ms", rename = "timeout_ms")]
+    pub timeout: Duration,
+}
+
+impl Default for KeepaliveConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(15),
+            timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+/// Snapshot of a sidecar's wire traffic, counting data frames in both directions
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct ConnStats {
+    /// WebSockets opened, including reconnects
+    pub connects: u64,
+    /// Persistent connections reopened after the previous one died
+    pub reconnects: u64,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    pub messages_sent: u64,
+    pub messages_received: u64,
+    /// Duplicate responses rejected under [`DuplicateResponses::Strict`]
+    pub rejected_duplicates: u64,
+    /// Mean size of a data frame in either direction, in bytes
+    pub avg_message_size: f64,
+}
+
+/// Running totals behind [`ConnStats`], shared by every connection made to one sidecar
+#[derive(Debug, Default)]
+pub struct ConnectionCounters {
+    connects: AtomicU64,
+    reconnects: AtomicU64,
+    bytes_sent: AtomicU64,
+    bytes_received: AtomicU64,
+    messages_sent: AtomicU64,
+    messages_received: AtomicU64,
+    rejected_duplicates: AtomicU64,
+}
+
+impl ConnectionCounters {
+    pub fn record_connect(&self, reconnect: bool) {
+        self.connects.fetch_add(1, Ordering::Relaxed);
+        if reconnect {
+            self.reconnects.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn record_sent(&self, bytes: usize) {
+        self.messages_sent.fetch_add(1, Ordering::Relaxed);
+        self.bytes_sent.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub fn record_received(&self, bytes: usize) {
+        self.messages_received.fetch_add(1, Ordering::Relaxed);
+        self.bytes_received.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub fn record_rejected_duplicate(&self) {
+        self.rejected_duplicates.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> ConnStats {
+        let bytes_sent = self.bytes_sent.load(Ordering::Relaxed);
+        let bytes_received = self.bytes_received.load(Ordering::Relaxed);
+        let messages_sent = self.messages_sent.load(Ordering::Relaxed);
+        let messages_received = self.messages_received.load(Ordering::Relaxed);
+        let messages = messages_sent + messages_received;
+        ConnStats {
+            connects: self.connects.load(Ordering::Relaxed),
+            reconnects: self.reconnects.load(Ordering::Relaxed),
+            bytes_sent,
+            bytes_received,
+            messages_sent,
+            messages_received,
+            rejected_duplicates: self.rejected_duplicates.load(Ordering::Relaxed),
+            avg_message_size: if messages == 0 {
+                0.0
+            } else {
+                (bytes_sent + bytes_received) as f64 / messages as f64
+            },
+        }
+    }
+}
+
+/// Open a WebSocket to the sidecar listening on `host:port`, adding `headers` to the upgrade request
+pub async fn open(host: &str, port: u16, headers: &[(String, String)]) -> Result<WsStream, SidecarError> {
+    let request = handshake_request(host, port, headers)?;
+    match tokio::time::timeout(CONNECT_TIMEOUT, connect_async(request)).await {
+        Ok(Ok((ws_stream, _))) => Ok(ws_stream),
+        Ok(Err(e)) => Err(SidecarError::Connection(format!("Failed to connect to sidecar WebSocket: {}", e))),
+        Err(_) => Err(SidecarError::ConnectTimeout { port }),
+    }
+}
+
+/// Send a WebSocket ping and wait for its pong, skipping any other traffic
+pub async fn ping<T: RpcTransport>(transport: &mut T) -> Result<(), SidecarError> {
+    transport.send(Message::Ping(Vec::new())).await
+        .map_err(|e| map_ws_error("Failed to send ping", e))?;
+    while let Some(msg) = transport.next().await {
+        match msg {
+            Ok(Message::Pong(_)) => return Ok(()),
+            Ok(_) => {}
+            Err(e) => return Err(map_ws_error("WebSocket stream error", e)),
+        }
+    }
+    Err(SidecarError::ConnectionClosed("Connection closed before pong".to_string()))
+}
+
+/// The upgrade request for `host:port`: the client version header, then `headers` in order, a
+/// later one replacing an earlier one of the same name. Handshake headers can't be overridden.
+pub fn handshake_request(host: &str, port: u16, headers: &[(String, String)]) -> Result<Request, SidecarError> {
+    let mut request = ws_url(host, port)
+        .into_client_request()
+        .map_err(|e| SidecarError::Connection(format!("Invalid sidecar WebSocket URL: {}", e)))?;
+    request.headers_mut().insert(CLIENT_VERSION_HEADER, HeaderValue::from_static(env!("CARGO_PKG_VERSION")));
+    for (name, value) in headers {
+        let header = HeaderName::from_bytes(name.as_bytes())
+            .map_err(|_| SidecarError::InvalidOptions(format!("Invalid handshake header name '{}'", name)))?;
+        if RESERVED_HEADERS.contains(&header.as_str()) {
+            return Err(SidecarError::InvalidOptions(format!("Handshake header '{}' can't be overridden", name)));
+        }
+        // The value may be a secret, so it stays out of the error
+        let value = HeaderValue::from_str(value)
+            .map_err(|_| SidecarError::InvalidOptions(format!("Invalid value for handshake header '{}'", name)))?;
+        request.headers_mut().insert(header, value);
+    }
+    Ok(request)
+}
+
+/// After a one-shot request got its response: whether another response for `request_id` has
+/// already arrived. Only looks at what is buffered or readable right now, it never waits.
+pub fn has_buffered_duplicate<T: RpcTransport>(transport: &mut T, request_id: &str) -> bool {
+    while let Some(Some(Ok(msg))) = transport.next().now_or_never() {
+        let id = match msg {
+            Message::Text(text) => serde_json::from_str::<serde_json::Value>(&text).ok()
+                .and_then(|message| RpcId::key_of(&message)),
+            Message::Binary(frame) => decode_binary_result(&frame)
+                .and_then(|(tag, _)| RpcId::tag_keys(tag).into_iter().find(|key| key == request_id)),
+            _ => None,
+        };
+        if id.as_deref() == Some(request_id) {
+            return true;
+        }
+    }
+    false
+}
+
+/// Send an encoded `(id, text)` request over `transport` and wait for the text or binary frame
+/// answering it, skipping anything else. This is the one-shot path: the caller owns the
+/// transport and nothing else reads from it meanwhile.
+pub async fn exchange_once<T: RpcTransport>(
+    transport: &mut T,
+    counters: &ConnectionCounters,
+    client: &ClientOptions,
+    method: &str,
+    (request_id, request_text): (String, String),
+    blob: Option<Vec<u8>>,
+) -> Result<Reply, SidecarError> {
+    // Send the request, followed by its attachment frame
+    counters.record_sent(request_text.len());
+    transport.send(Message::Text(request_text)).await
+        .map_err(|e| map_ws_error("Failed to send WebSocket message", e))?;
+    if let Some(blob) = blob {
+        counters.record_sent(blob.len());
+        transport.send(Message::Binary(blob)).await
+            .map_err(|e| map_ws_error("Failed to send attachment", e))?;
+    }
+
+    // A single response is expected for the request
+    while let Some(msg) = transport.next().await {
+        let msg = msg.map_err(|e| map_ws_error("WebSocket stream error", e))?;
+        let reply = match msg {
+            Message::Text(text) => {
+                counters.record_received(text.len());
+                let response: serde_json::Value = serde_json::from_str(&text)
+                    .map_err(|e| SidecarError::Protocol(e.to_string()))?;
+                if is_null_id_error(&response) {
+                    // Ours is the only request on this transport
+                    return match client.null_id_errors {
+                        NullIdErrors::AttributeToSole => Ok(Reply::Json(response)),
+                        NullIdErrors::Reject => Err(unattributed_error(&response, 1)),
+                    };
+                }
+                if RpcId::key_of(&response).as_ref() != Some(&request_id) {
+                    continue;
+                }
+                Reply::Json(response)
+            }
+            Message::Binary(frame) => {
+                counters.record_received(frame.len());
+                match decode_binary_result(&frame) {
+                    Some((tag, payload)) if RpcId::tag_keys(tag).contains(&request_id) => Reply::Binary(payload.to_vec()),
+                    _ => continue,
+                }
+            }
+            Message::Close(_) => break,
+            _ => continue,
+        };
+        if client.duplicate_responses == DuplicateResponses::Strict
+            && has_buffered_duplicate(transport, &request_id)
+        {
+            return Err(SidecarError::Protocol(format!("Duplicate response for '{}'", method)));
+        }
+        return Ok(reply);
+    }
+
+    Err(SidecarError::ConnectionClosed("Connection closed without valid response".to_string()))
+}
+
+/// `ws://host:port` on loopback, else `wss://host:port`, bracketing IPv6 literals. Remote
+/// sidecars are only ever reached over TLS, with the certificate checked against the web PKI
+/// roots; plain WebSockets never leave the machine.
+pub fn ws_url(host: &str, port: u16) -> String {
+    let scheme = if is_loopback_host(host) { "ws" } else { "wss" };
+    if host.parse::<std::net::Ipv6Addr>().is_ok() {
+        format!("{}://[{}]:{}", scheme, host, port)
+    } else {
+        format!("{}://{}:{}", scheme, host, port)
+    }
+}
+
+/// Whether `host` names this machine: `localhost` or a loopback address
+pub fn is_loopback_host(host: &str) -> bool {
+    host.eq_ignore_ascii_case("localhost")
+        || host.parse::<std::net::IpAddr>().is_ok_and(|addr| addr.is_loopback())
+}
+
+/// Whether `e` means the peer has gone away (closed, reset, or a write into a dead socket)
+/// rather than something recoverable on this connection
+pub fn is_peer_closed(e: &WsError) -> bool {
+    use std::io::ErrorKind;
+    match e {
+        WsError::ConnectionClosed | WsError::AlreadyClosed => true,
+        WsError::Protocol(ProtocolError::ResetWithoutClosingHandshake) => true,
+        WsError::Io(e) => matches!(
+            e.kind(),
+            ErrorKind::BrokenPipe
+                | ErrorKind::ConnectionReset
+                | ErrorKind::ConnectionAborted
+                | ErrorKind::NotConnected
+                | ErrorKind::UnexpectedEof
+        ),
+        _ => false,
+    }
+}
+
+/// Map a WebSocket error to [`SidecarError::ConnectionClosed`] when the peer is gone, else
+/// [`SidecarError::Connection`]; `context` prefixes the message
+pub fn map_ws_error(context: &str, e: WsError) -> SidecarError {
+    if is_peer_closed(&e) {
+        SidecarError::ConnectionClosed(format!("{}: {}", context, e))
+    } else {
+        SidecarError::Connection(format!("{}: {}", context, e))
+    }
+}
+
+/// Build a JSON-RPC 2.0 request with an id from `client.ids`, returning the id's
+/// [`RpcId::key`] alongside the text encoded in `client.format`. `attachment_size` announces
+/// a binary frame that will follow.
+pub fn encode_request(
+    method: &str,
+    params: serde_json::Value,
+    attachment_size: Option<usize>,
+    client: &ClientOptions,
+) -> Result<(String, String), SidecarError> {
+    build_request(method, params, attachment_size, false, client)
+}
+
+/// Like [`encode_request`], asking for the result as a tagged binary frame
+pub fn encode_binary_request(
+    method: &str,
+    params: serde_json::Value,
+    client: &ClientOptions,
+) -> Result<(String, String), SidecarError> {
+    build_request(method, params, None, true, client)
+}
+
+fn build_request(
+    method: &str,
+    params: serde_json::Value,
+    attachment_size: Option<usize>,
+    binary_result: bool,
+    client: &ClientOptions,
+) -> Result<(String, String), SidecarError> {
+    let request_id = client.ids.next();
+    let mut request = serde_json::json!({
+        "jsonrpc": "2.0",
+        "method": method,
+        "params": params,
+        "id": request_id
+    });
+    if let Some(size) = attachment_size {
+        request["attachment"] = serde_json::json!({ "size": size });
+    }
+    if binary_result {
+        request["binary_result"] = serde_json::Value::Bool(true);
+    }
+    Ok((request_id.key(), client.format.encode(&request)?))
+}
+
+/// Split a binary result frame into the request id it answers and the result bytes
+pub fn decode_binary_result(frame: &[u8]) -> Option<(&str, &[u8])> {
+    let len = u16::from_be_bytes([*frame.first()?, *frame.get(1)?]) as usize;
+    let rest = frame.get(2..)?;
+    if rest.len() < len {
+        return None;
+    }
+    let (id, payload) = rest.split_at(len);
+    Some((std::str::from_utf8(id).ok()?, payload))
+}
+
+/// What a request got back: a JSON-RPC envelope, or the bytes of a binary result frame
+#[derive(Debug)]
+pub enum Reply {
+    Json(serde_json::Value),
+    Binary(Vec<u8>),
+}
+
+impl Reply {
+    /// The JSON-RPC envelope, with error responses turned into [`SidecarError::Rpc`]
+    pub fn into_json(self) -> Result<serde_json::Value, SidecarError> {
+        match self {
+            Self::Json(response) => error::check_response(response),
+            Self::Binary(_) => Err(SidecarError::Protocol("Expected a JSON-RPC response, got a binary result".to_string())),
+        }
+    }
+
+    /// The bytes of a binary result; an error response becomes [`SidecarError::Rpc`]
+    pub fn into_binary(self) -> Result<Vec<u8>, SidecarError> {
+        match self {
+            Self::Binary(bytes) => Ok(bytes),
+            Self::Json(response) => {
+                error::check_response(response)?;
+                Err(SidecarError::Protocol("Expected a binary result, got a JSON-RPC result".to_string()))
+            }
+        }
+    }
+}
+
+type Waiter = oneshot::Sender<Result<Reply, SidecarError>>;
+
+struct Outgoing {
+    id: String,
+    text: String,
+    blob: Option<Vec<u8>>,
+    reply: Waiter,
+}
+
+/// Handle to a connection task. Cheap to clone; the task exits once every handle is dropped.
+#[derive(Clone)]
+pub struct PersistentConnection {
+    requests: mpsc::Sender<Outgoing>,
+    /// Asks the task to close the socket; answered with whether the sidecar completed the handshake
+    closes: mpsc::Sender<oneshot::Sender<bool>>,
+    alive: Arc<AtomicBool>,
+    client: ClientOptions,
+}
+
+impl PersistentConnection {
+    /// Connect to the sidecar on `host:port` and start the connection task, which closes the socket
+    /// and exits once `shutdown` turns true. Traffic is tallied in `counters`; notifications
+    /// from the sidecar go to `notifications`.
+    pub async fn connect(
+        host: &str,
+        port: u16,
+        client: &ClientOptions,
+        keepalive: KeepaliveConfig,
+        shutdown: watch::Receiver<bool>,
+        counters: Arc<ConnectionCounters>,
+        notifications: broadcast::Sender<SidecarNotification>,
+    ) -> Result<(Self, JoinHandle<()>), SidecarError> {
+        let ws_stream = open(host, port, &client.headers).await?;
+        Ok(Self::with_transport(ws_stream, port, client, keepalive, shutdown, counters, notifications))
+    }
+
+    /// Start the connection task over an already open `transport`; `port` only labels log lines
+    pub fn with_transport<T: RpcTransport + 'static>(
+        transport: T,
+        port: u16,
+        client: &ClientOptions,
+        keepalive: KeepaliveConfig,
+        shutdown: watch::Receiver<bool>,
+        counters: Arc<ConnectionCounters>,
+        notifications: broadcast::Sender<SidecarNotification>,
+    ) -> (Self, JoinHandle<()>) {
+        let (tx, rx) = mpsc::channel(REQUEST_QUEUE_CAPACITY);
+        let (closes, close_rx) = mpsc::channel(1);
+        let alive = Arc::new(AtomicBool::new(true));
+        let task = tokio::spawn(run(
+            transport, rx, close_rx, keepalive, client.duplicate_responses, client.null_id_errors, alive.clone(), shutdown,
+            counters, notifications, port,
+        ));
+
+        (Self { requests: tx, closes, alive, client: client.clone() }, task)
+    }
+
+    /// Close the socket with a Close frame and wait for the sidecar's answer, at most
+    /// [`CLOSE_HANDSHAKE_TIMEOUT`]. Requests still pending fail with `ConnectionClosed`. Returns
+    /// whether the handshake completed; false as well if the connection was already gone.
+    pub async fn close(&self) -> bool {
+        let (done, clean) = oneshot::channel();
+        if self.closes.send(done).await.is_err() {
+            return false;
+        }
+        clean.await.unwrap_or(false)
+    }
+
+    /// False once the socket closed, errored or missed a pong deadline
+    pub fn is_alive(&self) -> bool {
+        self.alive.load(Ordering::SeqCst) && !self.requests.is_closed()
+    }
+
+    /// Send a JSON-RPC request, plus its attachment frame if any, and wait for the response
+    /// with the matching id
+    pub async fn request(
+        &self,
+        method: &str,
+        params: serde_json::Value,
+        blob: Option<Vec<u8>>,
+    ) -> Result<serde_json::Value, SidecarError> {
+        let (id, text) = encode_request(method, params, blob.as_ref().map(Vec::len), &self.client)?;
+        match self.exchange(id, text, blob).await? {
+            Reply::Json(response) => Ok(response),
+            reply => reply.into_json(),
+        }
+    }
+
+    /// Send a request asking for a binary result and wait for the frame tagged with its id
+    pub async fn request_binary(&self, method: &str, params: serde_json::Value) -> Result<Vec<u8>, SidecarError> {
+        let (id, text) = encode_binary_request(method, params, &self.client)?;
+        self.exchange(id, text, None).await?.into_binary()
+    }
+
+    async fn exchange(&self, id: String, text: String, blob: Option<Vec<u8>>) -> Result<Reply, SidecarError> {
+        let (reply, response) = oneshot::channel();
+        self.requests
+            .send(Outgoing { id, text, blob, reply })
+            .await
+            .map_err(|_| SidecarError::ConnectionClosed("Sidecar connection closed".to_string()))?;
+        response.await.map_err(|_| {
+            SidecarError::ConnectionClosed("Sidecar connection closed without valid response".to_string())
+        })?
+    }
+}
+
+/// Why a connection task stopped
+struct Dropped {
+    reason: String,
+    /// The sidecar closed or reset the socket, as opposed to a keepalive or local failure
+    peer_closed: bool,
+}
+
+impl Dropped {
+    fn local(reason: String) -> Self {
+        Self { reason, peer_closed: false }
+    }
+
+    fn from_ws(context: &str, e: &WsError) -> Self {
+        Self { reason: format!("{}: {}", context, e), peer_closed: is_peer_closed(e) }
+    }
+
+    fn error(&self) -> SidecarError {
+        let reason = format!("dropped: {}", self.reason);
+        if self.peer_closed {
+            SidecarError::ConnectionClosed(reason)
+        } else {
+            SidecarError::Connection(reason)
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn run<T: RpcTransport>(
+    mut transport: T,
+    mut requests: mpsc::Receiver<Outgoing>,
+    mut closes: mpsc::Receiver<oneshot::Sender<bool>>,
+    keepalive: KeepaliveConfig,
+    duplicates: DuplicateResponses,
+    null_ids: NullIdErrors,
+    alive: Arc<AtomicBool>,
+    mut shutdown: watch::Receiver<bool>,
+    counters: Arc<ConnectionCounters>,
+    notifications: broadcast::Sender<SidecarNotification>,
+    port: u16,
+) {
+    let mut pending: HashMap<String, Waiter> = HashMap::new();
+    // Most recently answered ids, newest last
+    let mut answered: VecDeque<String> = VecDeque::with_capacity(ANSWERED_ID_MEMORY);
+    let mut ticker = interval_at(Instant::now() + keepalive.interval, keepalive.interval);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    // When the oldest unanswered ping was sent
+    let mut awaiting_pong: Option<Instant> = None;
+    // Set when `PersistentConnection::close` asked for this
+    let mut closed: Option<(oneshot::Sender<bool>, bool)> = None;
+
+    let reason = loop {
+        let pong_deadline = async {
+            match awaiting_pong {
+                Some(sent) => tokio::time::sleep_until(sent + keepalive.timeout).await,
+                None => std::future::pending().await,
+            }
+        };
+
+        tokio::select! {
+            outgoing = requests.recv() => {
+                let Some(outgoing) = outgoing else {
+                    // Every handle dropped
+                    close_handshake(&mut transport).await;
+                    break None;
+                };
+                // Both frames go out back to back so nothing can be interleaved between them
+                counters.record_sent(outgoing.text.len());
+                let mut sent = transport.send(Message::Text(outgoing.text)).await;
+                if let (Ok(()), Some(blob)) = (&sent, outgoing.blob) {
+                    counters.record_sent(blob.len());
+                    sent = transport.send(Message::Binary(blob)).await;
+                }
+                // A write into a socket the sidecar already closed fails here, so the caller
+                // hears about it now instead of at its response timeout
+                if let Err(e) = sent {
+                    let dropped = Dropped::from_ws("send failed", &e);
+                    let _ = outgoing.reply.send(Err(map_ws_error("Failed to send WebSocket message", e)));
+                    break Some(dropped);
+                }
+                pending.insert(outgoing.id, outgoing.reply);
+            }
+            incoming = transport.next() => match incoming {
+                Some(Ok(Message::Text(text))) => {
+                    counters.record_received(text.len());
+                    let Ok(response) = serde_json::from_str::<serde_json::Value>(&text) else {
+                        eprintln!("Ignoring unparseable message from sidecar on port {}", port);
+                        continue;
+                    };
+                    if is_null_id_error(&response) {
+                        match null_ids.owner(pending.keys()).cloned() {
+                            Some(id) => {
+                                deliver(&mut pending, &mut answered, duplicates, &counters, port, id, Reply::Json(response));
+                            }
+                            None if pending.is_empty() => {
+                                eprintln!("Ignoring error response with a null id from sidecar on port {}: {}", port, response);
+                            }
+                            None => {
+                                let in_flight = pending.len();
+                                for (_, reply) in pending.drain() {
+                                    let _ = reply.send(Err(unattributed_error(&response, in_flight)));
+                                }
+                                break Some(Dropped::local("error response with a null id".to_string()));
+                            }
+                        }
+                    } else if let Some(id) = RpcId::key_of(&response).filter(|id| is_expected(&pending, &answered, id)) {
+                        deliver(&mut pending, &mut answered, duplicates, &counters, port, id, Reply::Json(response));
+                    } else if let Some(notification) = SidecarNotification::from_message(&response) {
+                        // No subscribers is not an error
+                        let _ = notifications.send(notification);
+                    }
+                }
+                Some(Ok(Message::Ping(_))) => {
+                    // tungstenite queues the pong itself; flush so it goes out during quiet periods
+                    if let Err(e) = transport.flush().await {
+                        break Some(Dropped::from_ws("pong failed", &e));
+                    }
+                }
+                Some(Ok(Message::Pong(_))) => awaiting_pong = None,
+                Some(Ok(Message::Close(_))) | None => break Some(Dropped {
+                    reason: "closed by sidecar".to_string(),
+                    peer_closed: true,
+                }),
+                Some(Ok(Message::Binary(data))) => {
+                    counters.record_received(data.len());
+                    let decoded = decode_binary_result(&data).and_then(|(tag, payload)| {
+                        // A pending request before an answered one, should a tag fit both
+                        let keys = RpcId::tag_keys(tag);
+                        let key = keys.iter().find(|key| pending.contains_key(*key))
+                            .or_else(|| keys.iter().find(|key| is_expected(&pending, &answered, key)))?;
+                        Some((key.clone(), payload))
+                    });
+                    match decoded {
+                        Some((id, payload)) => {
+                            let reply = Reply::Binary(payload.to_vec());
+                            deliver(&mut pending, &mut answered, duplicates, &counters, port, id, reply);
+                        }
+                        _ => eprintln!("Ignoring binary frame from sidecar on port {} that answers no request", port),
+                    }
+                }
+                Some(Ok(_)) => {}
+                Some(Err(e)) => break Some(Dropped::from_ws("stream error", &e)),
+            },
+            _ = ticker.tick() => {
+                if let Err(e) = transport.send(Message::Ping(Vec::new())).await {
+                    break Some(Dropped::from_ws("ping failed", &e));
+                }
+                awaiting_pong.get_or_insert_with(Instant::now);
+            }
+            _ = pong_deadline => {
+                break Some(Dropped::local(format!("no pong within {:?}", keepalive.timeout)));
+            }
+            // The returned guard isn't Send; drop it before awaiting anything else
+            _ = async { let _ = shutdown.wait_for(|stop| *stop).await; } => {
+                close_handshake(&mut transport).await;
+                break Some(Dropped::local("manager shutting down".to_string()));
+            }
+            Some(done) = closes.recv() => {
+                let clean = close_handshake(&mut transport).await;
+                for (_, reply) in pending.drain() {
+                    let _ = reply.send(Err(SidecarError::ConnectionClosed("Sidecar connection closed".to_string())));
+                }
+                closed = Some((done, clean));
+                break None;
+            }
+        }
+    };
+
+    alive.store(false, Ordering::SeqCst);
+    if let Some(dropped) = reason {
+        eprintln!("Sidecar connection on port {} dropped: {}", port, dropped.reason);
+        for (_, reply) in pending.drain() {
+            let _ = reply.send(Err(dropped.error()));
+        }
+    }
+    if let Some((done, clean)) = closed {
+        let _ = done.send(clean);
+    }
+}
+
+/// Send a Close frame and wait up to [`CLOSE_HANDSHAKE_TIMEOUT`] for the sidecar's reply,
+/// discarding anything that arrives first. Returns whether the reply came.
+async fn close_handshake<T: RpcTransport>(transport: &mut T) -> bool {
+    if transport.send(Message::Close(None)).await.is_err() {
+        return false;
+    }
+    let reply = async {
+        loop {
+            match transport.next().await {
+                // tungstenite reports a completed handshake as `ConnectionClosed`
+                Some(Ok(Message::Close(_))) | Some(Err(WsError::ConnectionClosed)) => return true,
+                Some(Ok(_)) => {}
+                Some(Err(_)) | None => return false,
+            }
+        }
+    };
+    tokio::time::timeout(CLOSE_HANDSHAKE_TIMEOUT, reply).await.unwrap_or(false)
+}
+
+/// Whether `id` belongs to a request that is waiting or was recently answered
+fn is_expected(pending: &HashMap<String, Waiter>, answered: &VecDeque<String>, id: &str) -> bool {
+    pending.contains_key(id) || answered.iter().any(|answered| answered == id)
+}
+
+/// Hand `reply` to the request waiting on `id`, or discard a duplicate for an answered one
+fn deliver(
+    pending: &mut HashMap<String, Waiter>,
+    answered: &mut VecDeque<String>,
+    duplicates: DuplicateResponses,
+    counters: &ConnectionCounters,
+    port: u16,
+    id: String,
+    reply: Reply,
+) {
+    let Some(waiter) = pending.remove(&id) else {
+        match duplicates {
+            DuplicateResponses::Lenient => {
+                eprintln!("Discarding duplicate response for request {} from sidecar on port {}", id, port);
+            }
+            DuplicateResponses::Strict => {
+                eprintln!("Protocol violation: rejected duplicate response for request {} from sidecar on port {}", id, port);
+                counters.record_rejected_duplicate();
+            }
+        }
+        return;
+    };
+    let _ = waiter.send(Ok(reply));
+    if answered.len() == ANSWERED_ID_MEMORY {
+        answered.pop_front();
+    }
+    answered.push_back(id);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sidecar_manager::transport::MemoryTransport;
+    use futures::{SinkExt, StreamExt};
+    use tokio::net::TcpListener;
+    use tokio_tungstenite::accept_async;
+
+    /// Notification sender for tests that don't look at notifications
+    fn discard() -> broadcast::Sender<SidecarNotification> {
+        broadcast::channel(8).0
+    }
+
+    #[tokio::test]
+    async fn test_request_round_trip_and_keepalive() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut ws = accept_async(stream).await.unwrap();
+            // Reading answers pings automatically; echo each request back as a result
+            while let Some(Ok(msg)) = ws.next().await {
+                if let Message::Text(text) = msg {
+                    let request: serde_json::Value = serde_json::from_str(&text).unwrap();
+                    let response = serde_json::json!({
+                        "jsonrpc": "2.0",
+                        "result": request["params"],
+                        "id": request["id"],
+                    });
+                    ws.send(Message::Text(response.to_string())).await.unwrap();
+                }
+            }
+        });
+
+        let keepalive = KeepaliveConfig {
+            interval: Duration::from_millis(20),
+            timeout: Duration::from_millis(200),
+        };
+        let (_shutdown_tx, shutdown) = watch::channel(false);
+        let (conn, _) = PersistentConnection::connect("127.0.0.1", port, &ClientOptions::default(), keepalive, shutdown, Arc::default(), discard())
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert!(conn.is_alive(), "answered pings should keep the connection open");
+
+        let response = conn.request("echo", serde_json::json!({"n": 1}), None).await.unwrap();
+        assert_eq!(response["result"]["n"], 1);
+    }
+
+    #[tokio::test]
+    async fn test_blob_follows_its_request_frame() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut ws = accept_async(stream).await.unwrap();
+            let Some(Ok(Message::Text(text))) = ws.next().await else { panic!("expected request frame") };
+            let Some(Ok(Message::Binary(blob))) = ws.next().await else { panic!("expected blob frame") };
+            let request: serde_json::Value = serde_json::from_str(&text).unwrap();
+            let response = serde_json::json!({
+                "jsonrpc": "2.0",
+                "result": { "announced": request["attachment"]["size"], "received": blob.len() },
+                "id": request["id"],
+            });
+            ws.send(Message::Text(response.to_string())).await.unwrap();
+        });
+
+        let (_shutdown_tx, shutdown) = watch::channel(false);
+        let counters = Arc::new(ConnectionCounters::default());
+        let keepalive = KeepaliveConfig::default();
+        let (conn, _) = PersistentConnection::connect("127.0.0.1", port, &ClientOptions::default(), keepalive, shutdown, counters.clone(), discard())
+            .await
+            .unwrap();
+        let response = conn.request("upload", serde_json::json!({}), Some(vec![0u8; 1024])).await.unwrap();
+        assert_eq!(response["result"]["announced"], 1024);
+        assert_eq!(response["result"]["received"], 1024);
+
+        let stats = counters.snapshot();
+        assert_eq!((stats.messages_sent, stats.messages_received), (2, 1));
+        assert!(stats.bytes_sent > 1024);
+        assert!(stats.avg_message_size > 0.0);
+    }
+
+    #[tokio::test]
+    async fn test_notifications_are_published() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut ws = accept_async(stream).await.unwrap();
+            let event = serde_json::json!({"jsonrpc": "2.0", "method": "chat.token", "params": {"t": "hi"}});
+            ws.send(Message::Text(event.to_string())).await.unwrap();
+            while ws.next().await.is_some() {}
+        });
+
+        let (_shutdown_tx, shutdown) = watch::channel(false);
+        let (notifications, mut received) = broadcast::channel(8);
+        let keepalive = KeepaliveConfig::default();
+        let (_conn, _) =
+            PersistentConnection::connect("127.0.0.1", port, &ClientOptions::default(), keepalive, shutdown, Arc::default(), notifications)
+                .await
+                .unwrap();
+        let notification = tokio::time::timeout(Duration::from_secs(2), received.recv()).await.unwrap().unwrap();
+        assert_eq!(notification, SidecarNotification::Event {
+            method: "chat.token".to_string(),
+            params: serde_json::json!({"t": "hi"}),
+        });
+    }
+
+    #[tokio::test]
+    async fn test_peer_close_fails_request_promptly() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut ws = accept_async(stream).await.unwrap();
+            // Take the request, then drop the socket without answering or a close handshake
+            let _ = ws.next().await;
+        });
+
+        let (_shutdown_tx, shutdown) = watch::channel(false);
+        let keepalive = KeepaliveConfig::default();
+        let (conn, _) = PersistentConnection::connect("127.0.0.1", port, &ClientOptions::default(), keepalive, shutdown, Arc::default(), discard())
+            .await
+            .unwrap();
+        let result = tokio::time::timeout(Duration::from_secs(2), conn.request("x", serde_json::json!({}), None))
+            .await
+            .expect("a closed peer should fail the request before any response timeout");
+        assert!(matches!(result, Err(SidecarError::ConnectionClosed(_))), "unexpected result: {:?}", result);
+        assert!(!conn.is_alive());
+
+        assert!(is_peer_closed(&WsError::Io(std::io::ErrorKind::BrokenPipe.into())));
+        assert!(!is_peer_closed(&WsError::Utf8));
+    }
+
+    #[tokio::test]
+    async fn test_missing_pong_marks_connection_dead() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let (done_tx, done_rx) = oneshot::channel::<()>();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            // Complete the handshake, then never read so pings go unanswered
+            let _ws = accept_async(stream).await.unwrap();
+            let _ = done_rx.await;
+        });
+
+        let keepalive = KeepaliveConfig {
+            interval: Duration::from_millis(20),
+            timeout: Duration::from_millis(50),
+        };
+        let (_shutdown_tx, shutdown) = watch::channel(false);
+        let (conn, _) = PersistentConnection::connect("127.0.0.1", port, &ClientOptions::default(), keepalive, shutdown, Arc::default(), discard())
+            .await
+            .unwrap();
+        let err = conn.request("hang", serde_json::json!({}), None).await.unwrap_err();
+        assert!(err.to_string().contains("no pong"), "unexpected error: {}", err);
+        assert!(!conn.is_alive());
+        let _ = done_tx.send(());
+    }
+    #[tokio::test]
+    #[allow(clippy::result_large_err)] // The callback's signature is tungstenite's
+    async fn test_handshake_carries_headers() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut seen = None;
+            let _ws = tokio_tungstenite::accept_hdr_async(stream, |request: &Request, response| {
+                seen = Some(request.headers().clone());
+                Ok(response)
+            }).await.unwrap();
+            seen.unwrap()
+        });
+
+        let headers = [
+            ("Authorization".to_string(), "Bearer first".to_string()),
+            ("X-Trace-Id".to_string(), "abc123".to_string()),
+            ("authorization".to_string(), "Bearer second".to_string()),
+        ];
+        let _ws = open("127.0.0.1", port, &headers).await.unwrap();
+        let seen = server.await.unwrap();
+        assert_eq!(seen[CLIENT_VERSION_HEADER], env!("CARGO_PKG_VERSION"));
+        assert_eq!(seen["x-trace-id"], "abc123");
+        // Later entries replace earlier ones, so per-call headers win over the manager's
+        assert_eq!(seen.get_all("authorization").iter().collect::<Vec<_>>(), ["Bearer second"]);
+
+        for bad in [("Upgrade", "h2c"), ("X-Tailor-Client-Version", "9"), ("bad header", "x"), ("X-Token", "secret\n")] {
+            let headers = [(bad.0.to_string(), bad.1.to_string())];
+            let err = handshake_request("127.0.0.1", port, &headers).unwrap_err();
+            assert!(matches!(err, SidecarError::InvalidOptions(_)), "{:?} accepted", bad);
+            assert!(!err.to_string().contains("secret"), "value leaked into '{}'", err);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_scripted_transport_routes_responses_by_id() {
+        let (transport, mut peer) = MemoryTransport::pair();
+        let (_shutdown_tx, shutdown) = watch::channel(false);
+        let (notifications, mut received) = broadcast::channel(8);
+        let (conn, task) = PersistentConnection::with_transport(
+            transport, 0, &ClientOptions::default(), KeepaliveConfig::default(), shutdown, Arc::default(), notifications,
+        );
+
+        let first = tokio::spawn({
+            let conn = conn.clone();
+            async move { conn.request("first", serde_json::json!({}), None).await }
+        });
+        let first_id = peer.recv_json().await["id"].clone();
+        let second = tokio::spawn({
+            let conn = conn.clone();
+            async move { conn.request("second", serde_json::json!({}), None).await }
+        });
+        let second_id = peer.recv_json().await["id"].clone();
+
+        // Answered out of order, around a notification and a response nobody asked for
+        peer.send_json(serde_json::json!({"jsonrpc": "2.0", "method": "progress", "params": {"done": 1}}));
+        peer.send_json(serde_json::json!({"jsonrpc": "2.0", "id": "stranger", "result": 0}));
+        peer.send_json(serde_json::json!({"jsonrpc": "2.0", "id": second_id, "result": 2}));
+        peer.send_json(serde_json::json!({"jsonrpc": "2.0", "id": first_id, "error": {"code": -32000, "message": "nope"}}));
+        // Lenient by default: a repeat is dropped and the connection survives
+        peer.send_json(serde_json::json!({"jsonrpc": "2.0", "id": second_id, "result": 3}));
+        assert_eq!(second.await.unwrap().unwrap()["result"], 2);
+        let err = Reply::Json(first.await.unwrap().unwrap()).into_json().unwrap_err();
+        assert!(matches!(err, SidecarError::Rpc { .. }), "unexpected error: {:?}", err);
+        assert_eq!(received.recv().await.unwrap(), SidecarNotification::Event {
+            method: "progress".to_string(),
+            params: serde_json::json!({"done": 1}),
+        });
+
+        // The sidecar goes away with a request outstanding
+        let pending = tokio::spawn({
+            let conn = conn.clone();
+            async move { conn.request("third", serde_json::json!({}), None).await }
+        });
+        peer.recv_json().await;
+        peer.close();
+        let result = pending.await.unwrap();
+        assert!(matches!(result, Err(SidecarError::ConnectionClosed(_))), "unexpected result: {:?}", result);
+        task.await.unwrap();
+        assert!(!conn.is_alive());
+    }
+
+    #[tokio::test]
+    async fn test_null_id_errors_go_to_the_sole_request_or_fail_all() {
+        let (transport, mut peer) = MemoryTransport::pair();
+        let (_shutdown_tx, shutdown) = watch::channel(false);
+        let (conn, task) = PersistentConnection::with_transport(
+            transport, 0, &ClientOptions::default(), KeepaliveConfig::default(), shutdown, Arc::default(), discard(),
+        );
+        let null_id_error = serde_json::json!({"jsonrpc": "2.0", "id": null, "error": {"code": -32700, "message": "Parse error"}});
+        let request = |method: &'static str| tokio::spawn({
+            let conn = conn.clone();
+            async move { conn.request(method, serde_json::json!({}), None).await }
+        });
+
+        // Only one request can have caused it
+        let only = request("only");
+        peer.recv_json().await;
+        peer.send_json(null_id_error.clone());
+        let err = Reply::Json(only.await.unwrap().unwrap()).into_json().unwrap_err();
+        assert!(matches!(err, SidecarError::Rpc { .. }), "unexpected error: {:?}", err);
+
+        // With two in flight neither is hung waiting for it
+        let (first, second) = (request("first"), request("second"));
+        peer.recv_json().await;
+        peer.recv_json().await;
+        peer.send_json(null_id_error.clone());
+        for result in [first.await.unwrap(), second.await.unwrap()] {
+            assert!(matches!(result, Err(SidecarError::Protocol(_))), "unexpected result: {:?}", result);
+        }
+        task.await.unwrap();
+        assert!(!conn.is_alive());
+
+        // One-shot exchanges have a single request in flight by construction
+        let reject = ClientOptions { null_id_errors: NullIdErrors::Reject, ..Default::default() };
+        for client in [ClientOptions::default(), reject] {
+            let (mut transport, peer) = MemoryTransport::pair();
+            let request = encode_request("lookup", serde_json::json!({}), None, &client).unwrap();
+            peer.send_json(null_id_error.clone());
+            let result = exchange_once(&mut transport, &ConnectionCounters::default(), &client, "lookup", request, None).await;
+            match client.null_id_errors {
+                NullIdErrors::AttributeToSole => assert!(result.unwrap().into_json().is_err()),
+                NullIdErrors::Reject => assert!(matches!(result, Err(SidecarError::Protocol(_))), "unexpected result: {:?}", result),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_scripted_transport_duplicates_and_one_shot_exchanges() {
+        let strict = ClientOptions { duplicate_responses: DuplicateResponses::Strict, ..Default::default() };
+        let counters = ConnectionCounters::default();
+
+        // Persistent: a strict client rejects the repeated id alone; the request in flight survives
+        let (transport, mut peer) = MemoryTransport::pair();
+        let (_shutdown_tx, shutdown) = watch::channel(false);
+        let persistent_counters = Arc::new(ConnectionCounters::default());
+        let (conn, task) = PersistentConnection::with_transport(
+            transport, 0, &strict, KeepaliveConfig::default(), shutdown, persistent_counters.clone(), discard(),
+        );
+        let first = tokio::spawn({
+            let conn = conn.clone();
+            async move { conn.request("first", serde_json::json!({}), None).await }
+        });
+        let first_id = peer.recv_json().await["id"].clone();
+        let second = tokio::spawn({
+            let conn = conn.clone();
+            async move { conn.request("second", serde_json::json!({}), None).await }
+        });
+        let second_id = peer.recv_json().await["id"].clone();
+        peer.send_json(serde_json::json!({"jsonrpc": "2.0", "id": first_id, "result": 1}));
+        peer.send_json(serde_json::json!({"jsonrpc": "2.0", "id": first_id, "result": 1}));
+        peer.send_json(serde_json::json!({"jsonrpc": "2.0", "id": second_id, "result": 2}));
+        assert_eq!(first.await.unwrap().unwrap()["result"], 1);
+        assert_eq!(second.await.unwrap().unwrap()["result"], 2);
+        assert!(conn.is_alive());
+        assert_eq!(persistent_counters.snapshot().rejected_duplicates, 1);
+        drop(peer);
+        task.await.unwrap();
+
+        // One-shot: the duplicate is only noticed if it is already buffered with the response
+        for (client, duplicated) in [(&strict, true), (&strict, false), (&ClientOptions::default(), true)] {
+            let (mut transport, mut peer) = MemoryTransport::pair();
+            let request = encode_request("lookup", serde_json::json!({}), None, client).unwrap();
+            let key = request.0.clone();
+            let exchange = tokio::spawn({
+                let client = client.clone();
+                let counters = ConnectionCounters::default();
+                async move { exchange_once(&mut transport, &counters, &client, "lookup", request, None).await }
+            });
+            let id = peer.recv_json().await["id"].clone();
+            assert_eq!(RpcId::key_of(&serde_json::json!({"id": id})), Some(key));
+            peer.send(Message::Ping(Vec::new()));
+            peer.send_json(serde_json::json!({"jsonrpc": "2.0", "id": "other", "result": 0}));
+            let response = serde_json::json!({"jsonrpc": "2.0", "id": id, "result": 7});
+            if duplicated {
+                // Both land before the exchange reads either
+                peer.send_json(response.clone());
+            }
+            peer.send_json(response);
+            let result = exchange.await.unwrap();
+            if client.duplicate_responses == DuplicateResponses::Strict && duplicated {
+                assert!(matches!(result, Err(SidecarError::Protocol(_))), "unexpected result: {:?}", result);
+            } else {
+                assert_eq!(result.unwrap().into_json().unwrap()["result"], 7);
+            }
+        }
+
+        let (mut transport, mut peer) = MemoryTransport::pair();
+        peer.close();
+        let request = encode_request("lookup", serde_json::json!({}), None, &strict).unwrap();
+        let result = exchange_once(&mut transport, &counters, &strict, "lookup", request, None).await;
+        assert!(matches!(result, Err(SidecarError::ConnectionClosed(_))), "unexpected result: {:?}", result);
+        assert!(peer.recv().await.is_some(), "the request should still have been written");
+    }
+
+    #[tokio::test]
+    async fn test_close_waits_for_the_close_handshake() {
+        for answers in [true, false] {
+            let (transport, mut peer) = MemoryTransport::pair();
+            let (_shutdown_tx, shutdown) = watch::channel(false);
+            let (conn, task) = PersistentConnection::with_transport(
+                transport, 0, &ClientOptions::default(), KeepaliveConfig::default(), shutdown, Arc::default(), discard(),
+            );
+            let pending = tokio::spawn({
+                let conn = conn.clone();
+                async move { conn.request("slow", serde_json::json!({}), None).await }
+            });
+            peer.recv_json().await;
+
+            let closing = tokio::spawn({
+                let conn = conn.clone();
+                async move { conn.close().await }
+            });
+            assert!(matches!(peer.recv().await, Some(Message::Close(None))));
+            if answers {
+                peer.send(Message::Close(None));
+            }
+            let started = Instant::now();
+            assert_eq!(closing.await.unwrap(), answers);
+            if !answers {
+                assert!(started.elapsed() >= CLOSE_HANDSHAKE_TIMEOUT - Duration::from_millis(50));
+            }
+            let result = pending.await.unwrap();
+            assert!(matches!(result, Err(SidecarError::ConnectionClosed(_))), "unexpected result: {:?}", result);
+            task.await.unwrap();
+            assert!(!conn.is_alive());
+            assert!(!conn.close().await, "an already closed connection has no handshake to do");
+        }
+    }
+
+    #[test]
+    fn test_request_formats() {
+        let params = serde_json::json!({"ratio": 0.5});
+        let client = |format| ClientOptions { format, ..Default::default() };
+        let (_, compact) = encode_request("m", params.clone(), None, &client(RequestFormat::Compact)).unwrap();
+        assert!(!compact.contains('\n'));
+        let (_, pretty) = encode_request("m", params.clone(), None, &client(RequestFormat::Pretty)).unwrap();
+        assert!(pretty.contains("\n  \"params\""), "{}", pretty);
+
+        let tabs = RequestFormat::Indented("\t".to_string());
+        let (id, indented) = encode_request("m", params, None, &client(tabs)).unwrap();
+        assert!(indented.contains("\n\t\"params\""), "{}", indented);
+        let decoded: serde_json::Value = serde_json::from_str(&indented).unwrap();
+        assert_eq!((RpcId::key_of(&decoded), decoded["params"]["ratio"].as_f64()), (Some(id), Some(0.5)));
+    }
+
+    #[test]
+    fn test_custom_request_ids() {
+        let next = Arc::new(AtomicU64::new(7));
+        let ids = RequestIds::Custom(Arc::new(move || RpcId::Num(next.fetch_add(1, Ordering::SeqCst) as i64)));
+        let client = ClientOptions { ids, ..Default::default() };
+        let (key, text) = encode_request("m", serde_json::json!({}), None, &client).unwrap();
+        let request: serde_json::Value = serde_json::from_str(&text).unwrap();
+        assert_eq!((key.as_str(), &request["id"]), ("7", &serde_json::json!(7)));
+        assert_eq!(encode_binary_request("m", serde_json::json!({}), &client).unwrap().0, "8");
+
+        // Responses are matched by key whichever form the id takes
+        assert_eq!(RpcId::key_of(&serde_json::json!({"id": 7})), Some(key));
+        assert_eq!(RpcId::key_of(&serde_json::json!({"id": "trace-1"})), Some(RpcId::from("trace-1".to_string()).key()));
+        assert_eq!(RpcId::key_of(&serde_json::json!({"id": null})), None);
+        assert_ne!(RpcId::key_of(&serde_json::json!({"id": "7"})), RpcId::key_of(&serde_json::json!({"id": 7})));
+        assert_eq!(RpcId::tag_keys("7"), [RpcId::from("7".to_string()).key(), RpcId::from(7).key()]);
+        assert_eq!(RpcId::tag_keys("trace-1"), [RpcId::from("trace-1".to_string()).key()]);
+    }
+
+    #[test]
+    fn test_decode_binary_result() {
+        let mut frame = 3u16.to_be_bytes().to_vec();
+        frame.extend_from_slice(b"abc\x00\xff");
+        assert_eq!(decode_binary_result(&frame), Some(("abc", &b"\x00\xff"[..])));
+        assert_eq!(decode_binary_result(&frame[..4]), None);
+        assert_eq!(decode_binary_result(b"\x00"), None);
+        assert_eq!(decode_binary_result(b"\x00\x01\xff"), None);
+    }
+}
@@ -0,0 +1,133 @@
+//! Noticing a sidecar that died on its own, and what becomes of the commands it was running.
+//!
+//! Once a sidecar's output pipes close, the manager checks whether its process exited while it
+//! was still tracked, i.e. without the manager stopping it. Commands in flight then fail at once
+//! with [`SidecarError::Crashed`](super::SidecarError::Crashed), carrying the exit status and
+//! the tail of stderr, instead of each waiting out its timeout. With
+//! [`CrashPolicy::RestartAndRetry`] the sidecar is also restarted, and commands whose method is
+//! in `SidecarManagerConfig::replay` are parked and replayed once it is ready again.
+//!
+//! Restarts back off per [`RestartLimit`]: each one waits twice as long as the one before within
+//! its window, and a sidecar that has used up its restarts, or whose vault got quarantined for
+//! crashing, is left `Failed`.
+
+use std::fmt;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::{LogLine, LogStream};
+
+/// Lines of stderr kept in a [`CrashReport`]
+pub const STDERR_TAIL_LINES: usize = 20;
+
+/// How long a process whose pipes closed gets to be reaped before it is assumed to live on
+pub const EXIT_CONFIRM_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// How long a command whose connection was closed waits to learn whether the sidecar crashed;
+/// the socket usually closes just before the exit is confirmed
+pub const REPORT_GRACE: Duration = Duration::from_millis(500);
+
+/// What happens when a sidecar that was ready crashes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CrashPolicy {
+    /// Fail its in-flight commands and leave it `Failed`
+    #[default]
+    FailFast,
+    /// Restart it; in-flight commands for replayable methods wait for the new process, the
+    /// rest fail
+    RestartAndRetry,
+}
+
+/// How [`CrashPolicy::RestartAndRetry`] paces the restarts of a sidecar that keeps crashing
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RestartLimit {
+    /// Restarts of the window (including manual ones) within `window` after which a crash is
+    /// no longer restarted; a role's `max_restarts` takes precedence
+    pub max_restarts: usize,
+    #[serde(with = "super::config_file::duration_ms", rename = "window_ms")]
+    pub window: Duration,
+    /// Delay before restarting after a crash, doubled for every restart within `window`
+    #[serde(with = "super::config_file::duration_ms", rename = "backoff_ms")]
+    pub backoff: Duration,
+    #[serde(with = "super::config_file::duration_ms", rename = "max_backoff_ms")]
+    pub max_backoff: Duration,
+}
+
+impl Default for RestartLimit {
+    fn default() -> Self {
+        Self {
+            max_restarts: 5,
+            window: Duration::from_secs(10 * 60),
+            backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(30),
+        }
+    }
+}
+
+impl RestartLimit {
+    /// How many of `restarts` fall within the window before `now`
+    pub fn recent(&self, restarts: &[DateTime<Utc>], now: DateTime<Utc>) -> usize {
+        let window = chrono::Duration::from_std(self.window).unwrap_or(chrono::Duration::MAX);
+        restarts.iter().filter(|at| now.signed_duration_since(**at) < window).count()
+    }
+
+    /// The delay before the next restart after `recent` ones
+    pub fn backoff(&self, recent: usize) -> Duration {
+        let factor = 2u32.saturating_pow(recent.min(u32::MAX as usize) as u32);
+        self.backoff.saturating_mul(factor).min(self.max_backoff)
+    }
+}
+
+/// How a sidecar went down, also sent as `sidecar://crashed`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CrashReport {
+    pub window_label: String,
+    /// The exit status as the OS reports it, e.g. `exit status: 1` or `signal: 9 (SIGKILL)`
+    pub exit: String,
+    /// The last [`STDERR_TAIL_LINES`] lines the sidecar wrote to stderr
+    pub stderr_tail: Vec<String>,
+    pub crashed_at: DateTime<Utc>,
+}
+
+impl CrashReport {
+    pub fn new(window_label: &str, exit: std::process::ExitStatus, logs: &[LogLine]) -> Self {
+        let mut stderr_tail: Vec<String> = logs.iter()
+            .rev()
+            .filter(|line| line.stream == LogStream::Stderr)
+            .take(STDERR_TAIL_LINES)
+            .map(|line| line.text.clone())
+            .collect();
+        stderr_tail.reverse();
+        Self { window_label: window_label.to_string(), exit: exit.to_string(), stderr_tail, crashed_at: Utc::now() }
+    }
+}
+
+impl fmt::Display for CrashReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Sidecar for window '{}' crashed ({})", self.window_label, self.exit)?;
+        if !self.stderr_tail.is_empty() {
+            write!(f, "; stderr:\n{}", self.stderr_tail.join("\n"))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_restarts_back_off_within_the_window() {
+        let limit = RestartLimit::default();
+        let now = Utc::now();
+        let restarts = [now - chrono::Duration::hours(1), now - chrono::Duration::minutes(5), now];
+        assert_eq!(limit.recent(&restarts, now), 2);
+        let delays: Vec<_> = (0..7).map(|recent| limit.backoff(recent).as_secs()).collect();
+        assert_eq!(delays, [1, 2, 4, 8, 16, 30, 30]);
+        assert_eq!(limit.backoff(usize::MAX), limit.max_backoff);
+    }
+}
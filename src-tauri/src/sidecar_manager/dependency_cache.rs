@@ -0,0 +1,105 @@
+//! Remembering which vaults recently passed their dependency check, so spawning them again
+//! (switching back and forth between vaults) skips `DependencyChecker` while the result is fresh.
+//!
+//! A vault's entry is recorded once its requirements are satisfied, checked or freshly installed,
+//! and stays valid for `SidecarManagerConfig::dependency_cache_ttl`, as long as neither
+//! `plugins/requirements.txt` nor the `lib` directory it installs into has changed since (their
+//! modification times are compared). Failed checks are never cached.
+//! `SidecarManager::invalidate_dependency_cache` drops an entry by hand, e.g. after the user edited
+//! a plugin's dependencies in a way the timestamps don't show.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+/// How long a passed check is trusted unless configured otherwise
+pub const DEFAULT_TTL: Duration = Duration::from_secs(5 * 60);
+
+/// Modification times of what the check reads; `None` for what doesn't exist
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Fingerprint {
+    requirements: Option<SystemTime>,
+    lib: Option<SystemTime>,
+}
+
+impl Fingerprint {
+    fn of(vault: &Path) -> Self {
+        let modified = |path: PathBuf| std::fs::metadata(path).and_then(|meta| meta.modified()).ok();
+        Self {
+            requirements: modified(vault.join("plugins").join("requirements.txt")),
+            lib: modified(vault.join("lib")),
+        }
+    }
+}
+
+/// Vaults whose dependencies were last found satisfied, keyed by canonical path
+pub struct DependencyCache {
+    ttl: Duration,
+    entries: std::sync::Mutex<HashMap<PathBuf, (Instant, Fingerprint)>>,
+}
+
+impl DependencyCache {
+    /// A zero `ttl` disables the cache
+    pub fn new(ttl: Duration) -> Self {
+        Self { ttl, entries: Default::default() }
+    }
+
+    /// Whether `vault` passed a check within the TTL and is unchanged since
+    pub fn is_fresh(&self, vault: &Path) -> bool {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let Some((checked_at, fingerprint)) = entries.get(vault) else { return false };
+        if checked_at.elapsed() < self.ttl && *fingerprint == Fingerprint::of(vault) {
+            return true;
+        }
+        entries.remove(vault);
+        false
+    }
+
+    /// Remember that `vault`'s dependencies are satisfied as of now
+    pub fn record(&self, vault: &Path) {
+        if self.ttl.is_zero() {
+            return;
+        }
+        self.entries.lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(vault.to_path_buf(), (Instant::now(), Fingerprint::of(vault)));
+    }
+
+    /// Forget `vault`'s result; returns whether one was cached
+    pub fn invalidate(&self, vault: &Path) -> bool {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner()).remove(vault).is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entries_expire_and_follow_the_manifest() {
+        let vault = std::env::temp_dir().join(format!("tailor-deps-cache-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(vault.join("plugins")).unwrap();
+        let requirements = vault.join("plugins").join("requirements.txt");
+        std::fs::write(&requirements, "requests\n").unwrap();
+
+        let cache = DependencyCache::new(Duration::from_secs(60));
+        assert!(!cache.is_fresh(&vault));
+        cache.record(&vault);
+        assert!(cache.is_fresh(&vault));
+
+        // A touched manifest invalidates, whatever the TTL
+        let later = SystemTime::now() + Duration::from_secs(5);
+        std::fs::File::options().write(true).open(&requirements).unwrap().set_modified(later).unwrap();
+        assert!(!cache.is_fresh(&vault));
+
+        cache.record(&vault);
+        assert!(cache.invalidate(&vault));
+        assert!(!cache.invalidate(&vault));
+        assert!(!cache.is_fresh(&vault));
+
+        let expired = DependencyCache::new(Duration::ZERO);
+        expired.record(&vault);
+        assert!(!expired.is_fresh(&vault));
+        let _ = std::fs::remove_dir_all(vault);
+    }
+}
@@ -0,0 +1,218 @@
+//! Typed failures returned by the public `SidecarManager` API.
+
+use std::fmt;
+use std::time::Duration;
+
+use super::crash::CrashReport;
+use super::process::TerminationFailed;
+use super::quarantine::QuarantineEntry;
+use super::spawn_progress::SpawnStage;
+use super::state::SidecarState;
+
+/// JSON-RPC "method not found": the sidecar doesn't implement the method
+const METHOD_NOT_FOUND: i64 = -32601;
+
+/// Everything that can go wrong managing or talking to a sidecar.
+///
+/// Converts to and from `anyhow::Error`, so `?` keeps working at either boundary.
+#[derive(Debug)]
+pub enum SidecarError {
+    /// No sidecar is tracked for the window
+    NotFound { window_label: String },
+    /// Spawn options or the vault's sidecar config file were rejected
+    InvalidOptions(String),
+    /// No usable Python interpreter could be resolved
+    PythonNotFound(String),
+    /// The sidecar process could not be started
+    SpawnFailed(String),
+    /// `SpawnOptions::install_dependencies` was set and the vault's requirements couldn't be
+    /// checked or installed
+    Dependencies(String),
+    /// Neither the working directory nor the executable's path could be read to locate the
+    /// project root, and `SidecarManagerConfig::sidecar_root` isn't set
+    NoProjectRoot { current_dir: String, current_exe: String },
+    /// `SidecarManagerConfig::spawn_deadline` passed while the launch was working towards `stage`
+    SpawnTimeout { stage: SpawnStage, deadline: Duration },
+    /// The launch was aborted through its `SpawnCancel`; anything it had started was stopped
+    Cancelled,
+    /// The sidecar rejected or failed `SpawnOptions::init_method`, so the launch was abandoned
+    InitializeFailed { method: String, reason: String },
+    /// The vault's sidecar kept failing to start; cleared with `SidecarManager::clear_quarantine`
+    Quarantined(QuarantineEntry),
+    /// A sidecar is already running for the window (see `SidecarManagerConfig::duplicate_spawn`)
+    AlreadyRunning { window_label: String, ws_port: u16 },
+    /// Every port in the allocation range is taken
+    PortExhausted,
+    /// The sidecar's WebSocket did not accept a connection in time
+    ConnectTimeout { port: u16 },
+    /// The WebSocket failed mid-request
+    Connection(String),
+    /// The sidecar closed or reset its end of the WebSocket, so no response can arrive
+    ConnectionClosed(String),
+    /// The sidecar's process exited on its own while the command was in flight or queued
+    Crashed(CrashReport),
+    /// `try_send_command` found the sidecar not ready (`state`), or `Busy` with every command
+    /// slot taken
+    Busy { window_label: String, state: SidecarState },
+    /// `try_send_command` found the sidecar's rate limit exhausted; a token frees up after
+    /// `retry_after`
+    RateLimited { window_label: String, retry_after: Duration },
+    /// The sidecar's vault directory is gone (see `SidecarManager::rebind_vault`)
+    VaultPathLost { window_label: String, vault_path: String },
+    /// No response arrived before the method's deadline
+    ResponseTimeout { method: String, timeout: Duration },
+    /// The sidecar sent something that is not a JSON-RPC response
+    Protocol(String),
+    /// The method is missing from the sidecar's freshly fetched capability list
+    MethodNotSupported { method: String },
+    /// `SidecarManagerConfig::method_policy` doesn't let the method through; nothing was sent
+    MethodForbidden { method: String },
+    /// The sidecar answered with a JSON-RPC error object
+    Rpc { code: i64, message: String },
+    /// `await_job` was given a job that isn't tracked for the window
+    JobNotFound { job_id: String },
+    /// The sidecar reported the job failed, or it was lost with its sidecar
+    JobFailed { job_id: String, message: String },
+    /// The job was still running when `await_job` gave up
+    JobTimeout { job_id: String, timeout: Duration },
+    /// The sidecar survived SIGTERM and SIGKILL
+    TerminationFailed(TerminationFailed),
+    /// `SidecarManager::shutdown` has been called
+    ShuttingDown,
+    /// The operation isn't available on this platform
+    Unsupported(String),
+    /// Anything not covered above
+    Other(anyhow::Error),
+}
+
+impl fmt::Display for SidecarError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotFound { window_label } => write!(f, "Sidecar not found for window: {}", window_label),
+            Self::InvalidOptions(reason) => write!(f, "Invalid sidecar options: {}", reason),
+            Self::PythonNotFound(reason) => write!(f, "Python interpreter not found: {}", reason),
+            Self::SpawnFailed(reason) => write!(f, "Failed to spawn Python sidecar: {}", reason),
+            Self::Dependencies(reason) => write!(f, "Failed to install vault dependencies: {}", reason),
+            Self::NoProjectRoot { current_dir, current_exe } => write!(
+                f, "Can't locate the project root: current directory unavailable ({}), executable path \
+                    unavailable ({}); set SidecarManagerConfig::sidecar_root", current_dir, current_exe),
+            Self::SpawnTimeout { stage, deadline } => {
+                write!(f, "Sidecar did not start within {:?}: stuck on '{}'", deadline, stage)
+            }
+            Self::Cancelled => write!(f, "Sidecar launch was cancelled"),
+            Self::InitializeFailed { method, reason } => {
+                write!(f, "Sidecar initialization ('{}') failed: {}", method, reason)
+            }
+            Self::Quarantined(entry) => write!(
+                f, "Vault {} is quarantined after {} failed sidecar starts (last at {}): {}",
+                entry.vault_path, entry.crashes, entry.quarantined_at.to_rfc3339(), entry.reason),
+            Self::AlreadyRunning { window_label, ws_port } => {
+                write!(f, "A sidecar is already running for window '{}' on port {}", window_label, ws_port)
+            }
+            Self::PortExhausted => write!(f, "No free port available for a sidecar"),
+            Self::ConnectTimeout { port } => write!(f, "Timed out connecting to sidecar on port {}", port),
+            Self::Connection(reason) => write!(f, "Sidecar connection failed: {}", reason),
+            Self::ConnectionClosed(reason) => write!(f, "Sidecar closed the connection: {}", reason),
+            Self::Crashed(report) => report.fmt(f),
+            Self::Busy { window_label, state } => {
+                write!(f, "Sidecar for window '{}' can't take a command now: {:?}", window_label, state)
+            }
+            Self::RateLimited { window_label, retry_after } => {
+                write!(f, "Sidecar for window '{}' is rate limited, retry in {:?}", window_label, retry_after)
+            }
+            Self::VaultPathLost { window_label, vault_path } => write!(
+                f, "Vault {} of window '{}' was moved or renamed; reopen it or rebind the window to its new location",
+                vault_path, window_label),
+            Self::ResponseTimeout { method, timeout } => {
+                write!(f, "Timed out after {:?} waiting for a response to '{}'", timeout, method)
+            }
+            Self::Protocol(reason) => write!(f, "Invalid sidecar response: {}", reason),
+            Self::MethodNotSupported { method } => write!(f, "Sidecar does not support method '{}'", method),
+            Self::MethodForbidden { method } => write!(f, "Method '{}' is not allowed by the sidecar method policy", method),
+            Self::Rpc { code, message } => write!(f, "Sidecar returned error {}: {}", code, message),
+            Self::JobNotFound { job_id } => write!(f, "Unknown job {}", job_id),
+            Self::JobFailed { job_id, message } => write!(f, "Job {} failed: {}", job_id, message),
+            Self::JobTimeout { job_id, timeout } => write!(f, "Job {} still running after {:?}", job_id, timeout),
+            Self::TerminationFailed(e) => e.fmt(f),
+            Self::ShuttingDown => write!(f, "Sidecar manager is shutting down"),
+            Self::Unsupported(what) => write!(f, "Not supported on this platform: {}", what),
+            Self::Other(e) => write!(f, "{:#}", e),
+        }
+    }
+}
+
+impl SidecarError {
+    /// Failures that may clear up once the sidecar is back (crashed, restarting, not listening)
+    pub fn is_transient(&self) -> bool {
+        matches!(self, Self::Connection(_) | Self::ConnectionClosed(_) | Self::ConnectTimeout { .. })
+    }
+
+    /// The sidecar answered that it doesn't implement the method, e.g. an older sidecar
+    pub fn is_method_not_found(&self) -> bool {
+        matches!(self, Self::Rpc { code, .. } if *code == METHOD_NOT_FOUND)
+    }
+}
+
+impl std::error::Error for SidecarError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::TerminationFailed(e) => Some(e),
+            Self::Other(e) => Some(e.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<TerminationFailed> for SidecarError {
+    fn from(e: TerminationFailed) -> Self {
+        Self::TerminationFailed(e)
+    }
+}
+
+impl From<anyhow::Error> for SidecarError {
+    fn from(e: anyhow::Error) -> Self {
+        match e.downcast::<SidecarError>() {
+            Ok(e) => e,
+            Err(e) => Self::Other(e),
+        }
+    }
+}
+
+/// Turn a JSON-RPC error response into [`SidecarError::Rpc`], passing other responses through
+pub fn check_response(response: serde_json::Value) -> Result<serde_json::Value, SidecarError> {
+    match response.get("error").filter(|e| !e.is_null()) {
+        Some(error) => Err(SidecarError::Rpc {
+            code: error.get("code").and_then(|c| c.as_i64()).unwrap_or_default(),
+            message: error.get("message").and_then(|m| m.as_str()).unwrap_or_default().to_string(),
+        }),
+        None => Ok(response),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_response_and_anyhow_round_trip() {
+        let ok = serde_json::json!({"jsonrpc": "2.0", "result": 1, "id": "a"});
+        assert!(check_response(ok).is_ok());
+
+        let err = serde_json::json!({
+            "jsonrpc": "2.0",
+            "error": {"code": -32601, "message": "Method not found: x"},
+            "id": "a"
+        });
+        match check_response(err) {
+            Err(SidecarError::Rpc { code, message }) => {
+                assert_eq!(code, -32601);
+                assert_eq!(message, "Method not found: x");
+            }
+            other => panic!("expected Rpc error, got {:?}", other),
+        }
+
+        // Going through anyhow and back keeps the variant
+        let wrapped: anyhow::Error = SidecarError::PortExhausted.into();
+        assert!(matches!(SidecarError::from(wrapped), SidecarError::PortExhausted));
+    }
+}
@@ -0,0 +1,137 @@
+//! Per-sidecar health, as reported by `SidecarManager::health_report`, and the fleet-wide
+//! [`HealthSummary`] behind the `sidecars://degraded` alarm.
+
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use super::python::PythonInterpreter;
+use super::ErrorRecord;
+
+/// How long a sidecar gets to answer the health ping before it is reported unresponsive
+pub const HEALTH_PING_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Point-in-time health of one sidecar
+#[derive(Debug, Clone, Serialize)]
+pub struct SidecarHealth {
+    pub window_label: String,
+    pub vault_path: String,
+    pub pid: u32,
+    pub ws_port: u16,
+    /// Set when the sidecar has a control socket; the ping below goes there
+    pub control_port: Option<u16>,
+    /// The interpreter the sidecar was launched with
+    pub python: PythonInterpreter,
+    /// The process is still running
+    pub alive: bool,
+    /// A WebSocket ping was answered within [`HEALTH_PING_TIMEOUT`]
+    pub responsive: bool,
+    /// Round trip of that ping, including connecting
+    pub ping_ms: Option<f64>,
+    pub in_flight: usize,
+    /// Resident set size; None where sysinfo can't read it
+    pub memory_bytes: Option<u64>,
+    pub uptime_secs: f64,
+    pub restart_count: usize,
+    /// Spawn options changed since launch (see `SidecarManager::set_env`)
+    pub needs_restart: bool,
+    /// `SpawnOptions::role`
+    pub role: Option<String>,
+    pub last_error: Option<ErrorRecord>,
+}
+
+/// When enough sidecars are down at once to suggest a systemic cause (e.g. a Python environment
+/// broken by an OS update) rather than one misbehaving vault
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DegradedConfig {
+    /// Share of sidecars, unresponsive or dead, that counts as degraded
+    pub fraction: f64,
+    /// Fewer sidecars than this are never degraded, so one stuck vault isn't an alarm
+    pub min_sidecars: usize,
+    /// How often `SidecarManager::run_health_monitor` checks
+    #[serde(with = "super::config_file::duration_ms", rename = "interval_ms")]
+    pub interval: Duration,
+}
+
+impl Default for DegradedConfig {
+    fn default() -> Self {
+        Self { fraction: 0.5, min_sidecars: 2, interval: Duration::from_secs(30) }
+    }
+}
+
+/// Counts over a health report
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct HealthSummary {
+    pub total: usize,
+    /// Alive and answered the ping
+    pub healthy: usize,
+    /// Alive but didn't answer the ping
+    pub unresponsive: usize,
+    /// The process has exited
+    pub failed: usize,
+    /// Over the configured [`DegradedConfig`] threshold
+    pub degraded: bool,
+}
+
+impl HealthSummary {
+    pub fn from_report(report: &[SidecarHealth], config: Option<&DegradedConfig>) -> Self {
+        let mut summary = Self { total: report.len(), ..Default::default() };
+        for health in report {
+            match (health.alive, health.responsive) {
+                (false, _) => summary.failed += 1,
+                (true, false) => summary.unresponsive += 1,
+                (true, true) => summary.healthy += 1,
+            }
+        }
+        summary.degraded = config.is_some_and(|config| {
+            summary.total > 0
+                && summary.total >= config.min_sidecars
+                && (summary.unresponsive + summary.failed) as f64 / summary.total as f64 >= config.fraction
+        });
+        summary
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn health(alive: bool, responsive: bool) -> SidecarHealth {
+        SidecarHealth {
+            window_label: "w".to_string(),
+            vault_path: "/v".to_string(),
+            pid: 1,
+            ws_port: 9000,
+            control_port: None,
+            python: PythonInterpreter {
+                path: "python3".to_string(),
+                version: "Python 3.12.1".to_string(),
+                source: crate::sidecar_manager::python::PythonSource::Path,
+            },
+            alive,
+            responsive,
+            ping_ms: None,
+            in_flight: 0,
+            memory_bytes: None,
+            uptime_secs: 0.0,
+            restart_count: 0,
+            needs_restart: false,
+            role: None,
+            last_error: None,
+        }
+    }
+
+    #[test]
+    fn test_degraded_past_the_threshold_fraction() {
+        let config = DegradedConfig::default();
+        let report = [health(true, true), health(true, false), health(false, false)];
+        let summary = HealthSummary::from_report(&report, Some(&config));
+        assert_eq!((summary.healthy, summary.unresponsive, summary.failed), (1, 1, 1));
+        assert!(summary.degraded);
+        assert!(!HealthSummary::from_report(&report, None).degraded);
+        assert!(!HealthSummary::from_report(&report[..1], Some(&config)).degraded);
+        // A lone stuck sidecar is below `min_sidecars`
+        assert!(!HealthSummary::from_report(&report[1..2], Some(&config)).degraded);
+    }
+}
@@ -0,0 +1,253 @@
+//! Long-running sidecar operations that answer with a job id straight away and finish later,
+//! started with `SidecarManager::submit_job` and collected with `SidecarManager::await_job`.
+//!
+//! The sidecar's response to the submitting command carries `{"job_id": ...}` in its result.
+//! Completion is reported with a [`JOB_COMPLETED_METHOD`] notification, `{"job_id", "result"}`
+//! or `{"job_id", "error"}`, when the window has a notification stream (persistent connections
+//! or stdio); otherwise waiters poll [`JOB_STATUS_METHOD`] every [`JOB_POLL_INTERVAL`]. Jobs
+//! still running when their sidecar restarts or is terminated fail, since the process that ran
+//! them is gone.
+//!
+//! Job ids handed out by the manager are unique across windows; the sidecar's own id is only
+//! used on the wire.
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::time::Duration;
+
+use serde::Serialize;
+use tokio::sync::watch;
+
+use super::error::SidecarError;
+
+/// Notification a sidecar sends when a job finishes
+pub const JOB_COMPLETED_METHOD: &str = "job.completed";
+/// RPC returning `{"status": "running" | "completed" | "failed", "result", "error"}` for a job id
+pub const JOB_STATUS_METHOD: &str = "job.status";
+/// Time between [`JOB_STATUS_METHOD`] polls for a window without notifications
+pub const JOB_POLL_INTERVAL: Duration = Duration::from_millis(500);
+/// Finished jobs remembered for `job_status` and late `await_job` calls; the oldest are forgotten
+const FINISHED_JOBS_KEPT: usize = 256;
+/// Completions received before their job was registered, e.g. for a job done within its own
+/// submitting request
+const EARLY_COMPLETIONS_KEPT: usize = 64;
+
+/// Manager-issued id of a submitted job
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+#[serde(transparent)]
+pub struct JobId(String);
+
+impl JobId {
+    pub fn parse(id: &str) -> Self {
+        Self(id.to_string())
+    }
+}
+
+impl fmt::Display for JobId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Where a job stands
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum JobStatus {
+    Running,
+    Completed { result: serde_json::Value },
+    Failed { error: String },
+}
+
+struct Job {
+    window_label: String,
+    remote_id: String,
+    /// Completion arrives as a notification rather than by polling
+    notified: bool,
+    status: watch::Sender<JobStatus>,
+}
+
+/// What `await_job` needs to wait for a job
+pub struct JobWatch {
+    pub remote_id: String,
+    pub notified: bool,
+    pub status: watch::Receiver<JobStatus>,
+}
+
+/// Outstanding and recently finished jobs of every window
+#[derive(Default)]
+pub struct JobTracker {
+    jobs: std::sync::Mutex<Jobs>,
+}
+
+#[derive(Default)]
+struct Jobs {
+    by_id: HashMap<JobId, Job>,
+    finished: VecDeque<JobId>,
+    early: VecDeque<(String, String, JobStatus)>,
+}
+
+impl Jobs {
+    fn finish(&mut self, id: &JobId, status: JobStatus) {
+        let Some(job) = self.by_id.get(id) else { return };
+        if *job.status.borrow() != JobStatus::Running {
+            return;
+        }
+        job.status.send_replace(status);
+        self.finished.push_back(id.clone());
+        if self.finished.len() > FINISHED_JOBS_KEPT {
+            if let Some(oldest) = self.finished.pop_front() {
+                self.by_id.remove(&oldest);
+            }
+        }
+    }
+}
+
+impl JobTracker {
+    fn lock(&self) -> std::sync::MutexGuard<'_, Jobs> {
+        self.jobs.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Track a job the window's sidecar accepted as `remote_id`
+    pub fn insert(&self, window_label: &str, remote_id: String, notified: bool) -> JobId {
+        let id = JobId(uuid::Uuid::new_v4().to_string());
+        let mut jobs = self.lock();
+        let early = jobs.early.iter()
+            .position(|(label, remote, _)| label == window_label && *remote == remote_id)
+            .and_then(|i| jobs.early.remove(i));
+        jobs.by_id.insert(id.clone(), Job {
+            window_label: window_label.to_string(),
+            remote_id,
+            notified,
+            status: watch::channel(JobStatus::Running).0,
+        });
+        if let Some((_, _, status)) = early {
+            jobs.finish(&id, status);
+        }
+        id
+    }
+
+    pub fn status(&self, id: &JobId) -> Option<JobStatus> {
+        self.lock().by_id.get(id).map(|job| job.status.borrow().clone())
+    }
+
+    /// The job, if it belongs to `window_label`
+    pub fn watch(&self, window_label: &str, id: &JobId) -> Option<JobWatch> {
+        self.lock().by_id.get(id)
+            .filter(|job| job.window_label == window_label)
+            .map(|job| JobWatch {
+                remote_id: job.remote_id.clone(),
+                notified: job.notified,
+                status: job.status.subscribe(),
+            })
+    }
+
+    pub fn finish(&self, id: &JobId, status: JobStatus) {
+        self.lock().finish(id, status);
+    }
+
+    /// Settle the job the window's sidecar knows as `remote_id`
+    pub fn finish_remote(&self, window_label: &str, remote_id: &str, status: JobStatus) {
+        let mut jobs = self.lock();
+        let id = jobs.by_id.iter()
+            .find(|(_, job)| job.window_label == window_label && job.remote_id == remote_id)
+            .map(|(id, _)| id.clone());
+        match id {
+            Some(id) => jobs.finish(&id, status),
+            None => {
+                if jobs.early.len() == EARLY_COMPLETIONS_KEPT {
+                    jobs.early.pop_front();
+                }
+                jobs.early.push_back((window_label.to_string(), remote_id.to_string(), status));
+            }
+        }
+    }
+
+    /// Fail every running job of the window, e.g. because its sidecar went away
+    pub fn fail_window(&self, window_label: &str, reason: &str) {
+        let mut jobs = self.lock();
+        jobs.early.retain(|(label, _, _)| label != window_label);
+        let running: Vec<JobId> = jobs.by_id.iter()
+            .filter(|(_, job)| job.window_label == window_label)
+            .map(|(id, _)| id.clone())
+            .collect();
+        for id in running {
+            jobs.finish(&id, JobStatus::Failed { error: reason.to_string() });
+        }
+    }
+}
+
+/// The sidecar's id for a job, from the response to its submitting command
+pub fn remote_id_from_response(response: &serde_json::Value) -> Result<String, SidecarError> {
+    match response.pointer("/result/job_id") {
+        Some(serde_json::Value::String(id)) => Ok(id.clone()),
+        Some(serde_json::Value::Number(id)) => Ok(id.to_string()),
+        _ => Err(SidecarError::Protocol("Job submission response is missing 'job_id'".to_string())),
+    }
+}
+
+/// The job id and outcome in a [`JOB_COMPLETED_METHOD`] notification's params
+pub fn completion_from_params(params: &serde_json::Value) -> Option<(String, JobStatus)> {
+    let id = match params.get("job_id")? {
+        serde_json::Value::String(id) => id.clone(),
+        serde_json::Value::Number(id) => id.to_string(),
+        _ => return None,
+    };
+    Some((id, outcome(params)))
+}
+
+/// A [`JOB_STATUS_METHOD`] response as a status
+pub fn status_from_response(response: &serde_json::Value) -> Result<JobStatus, SidecarError> {
+    let result = response.get("result")
+        .ok_or_else(|| SidecarError::Protocol("Job status response has no result".to_string()))?;
+    match result.get("status").and_then(|s| s.as_str()) {
+        Some("running") => Ok(JobStatus::Running),
+        Some("completed" | "failed") => Ok(outcome(result)),
+        other => Err(SidecarError::Protocol(format!("Unknown job status {:?}", other))),
+    }
+}
+
+fn outcome(report: &serde_json::Value) -> JobStatus {
+    match report.get("error").filter(|e| !e.is_null()) {
+        Some(error) => JobStatus::Failed {
+            error: error.get("message")
+                .and_then(|m| m.as_str())
+                .or_else(|| error.as_str())
+                .map(str::to_string)
+                .unwrap_or_else(|| error.to_string()),
+        },
+        None => JobStatus::Completed { result: report.get("result").cloned().unwrap_or_default() },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_completions_settle_the_matching_job_once() {
+        let tracker = JobTracker::default();
+        let a = tracker.insert("w", "1".to_string(), true);
+        let b = tracker.insert("v", "1".to_string(), true);
+
+        let (remote, status) = completion_from_params(&serde_json::json!({"job_id": 1, "result": [3]})).unwrap();
+        tracker.finish_remote("w", &remote, status);
+        assert_eq!(tracker.status(&a), Some(JobStatus::Completed { result: serde_json::json!([3]) }));
+        assert_eq!(tracker.status(&b), Some(JobStatus::Running));
+        assert!(tracker.watch("v", &a).is_none());
+
+        // A finished job keeps its outcome
+        tracker.fail_window("w", "Sidecar terminated");
+        tracker.fail_window("v", "Sidecar terminated");
+        assert!(matches!(tracker.status(&a), Some(JobStatus::Completed { .. })));
+        assert_eq!(tracker.status(&b), Some(JobStatus::Failed { error: "Sidecar terminated".to_string() }));
+
+        // A completion may beat the registration of its job
+        tracker.finish_remote("w", "2", JobStatus::Failed { error: "disk full".to_string() });
+        let c = tracker.insert("w", "2".to_string(), true);
+        assert_eq!(tracker.status(&c), Some(JobStatus::Failed { error: "disk full".to_string() }));
+
+        let polled = serde_json::json!({"result": {"status": "failed", "error": {"code": 1, "message": "boom"}}});
+        assert_eq!(status_from_response(&polled).unwrap(), JobStatus::Failed { error: "boom".to_string() });
+        assert!(remote_id_from_response(&serde_json::json!({"result": {}})).is_err());
+    }
+}
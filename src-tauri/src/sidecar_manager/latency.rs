@@ -0,0 +1,116 @@
+//! Round-trip latency of a sidecar's commands, per method, to find the RPC handlers worth
+//! optimizing. Each method keeps its latest [`LATENCY_SAMPLES`] round trips; percentiles are
+//! taken over those, so they follow the sidecar's current behaviour rather than its whole life.
+
+use std::collections::{HashMap, VecDeque};
+use std::time::Duration;
+
+use serde::Serialize;
+
+/// Round trips kept per method
+pub const LATENCY_SAMPLES: usize = 512;
+
+/// Latency distribution of one method's recent round trips, in milliseconds
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LatencyStats {
+    /// Round trips since the sidecar started, including ones no longer sampled
+    pub count: u64,
+    /// Round trips the figures below are computed from
+    pub samples: usize,
+    pub min_ms: f64,
+    pub mean_ms: f64,
+    pub p50_ms: f64,
+    pub p95_ms: f64,
+    pub p99_ms: f64,
+    pub max_ms: f64,
+}
+
+#[derive(Debug, Default)]
+struct Samples {
+    recent: VecDeque<Duration>,
+    count: u64,
+}
+
+/// Recent round-trip times of every method called on one sidecar
+#[derive(Debug, Default)]
+pub struct MethodLatencies {
+    methods: HashMap<String, Samples>,
+}
+
+impl MethodLatencies {
+    /// Record one round trip of `method`, however it ended
+    pub fn record(&mut self, method: &str, elapsed: Duration) {
+        let samples = match self.methods.get_mut(method) {
+            Some(samples) => samples,
+            None => self.methods.entry(method.to_string()).or_default(),
+        };
+        if samples.recent.len() == LATENCY_SAMPLES {
+            samples.recent.pop_front();
+        }
+        samples.recent.push_back(elapsed);
+        samples.count += 1;
+    }
+
+    /// Stats of every method recorded so far
+    pub fn stats(&self) -> HashMap<String, LatencyStats> {
+        self.methods.iter()
+            .map(|(method, samples)| (method.clone(), samples.stats()))
+            .collect()
+    }
+}
+
+impl Samples {
+    fn stats(&self) -> LatencyStats {
+        let mut sorted: Vec<f64> = self.recent.iter().map(|d| d.as_secs_f64() * 1000.0).collect();
+        sorted.sort_by(f64::total_cmp);
+        LatencyStats {
+            count: self.count,
+            samples: sorted.len(),
+            min_ms: sorted.first().copied().unwrap_or_default(),
+            mean_ms: sorted.iter().sum::<f64>() / sorted.len().max(1) as f64,
+            p50_ms: percentile(&sorted, 50.0),
+            p95_ms: percentile(&sorted, 95.0),
+            p99_ms: percentile(&sorted, 99.0),
+            max_ms: sorted.last().copied().unwrap_or_default(),
+        }
+    }
+}
+
+/// Nearest-rank percentile of ascending `sorted`
+fn percentile(sorted: &[f64], p: f64) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    let rank = (p / 100.0 * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_percentiles_over_recent_samples() {
+        let mut latencies = MethodLatencies::default();
+        for ms in 1..=100 {
+            latencies.record("search", Duration::from_millis(ms));
+        }
+        latencies.record("ping", Duration::from_millis(3));
+
+        let stats = latencies.stats();
+        let search = &stats["search"];
+        assert_eq!((search.count, search.samples), (100, 100));
+        assert_eq!((search.min_ms, search.p50_ms, search.p95_ms, search.p99_ms, search.max_ms),
+                   (1.0, 50.0, 95.0, 99.0, 100.0));
+        assert_eq!(search.mean_ms, 50.5);
+        assert_eq!(stats["ping"].p99_ms, 3.0);
+
+        // Only the latest samples count once the window is full
+        for _ in 0..LATENCY_SAMPLES {
+            latencies.record("search", Duration::from_millis(7));
+        }
+        let search = &latencies.stats()["search"];
+        assert_eq!((search.count, search.samples), (100 + LATENCY_SAMPLES as u64, LATENCY_SAMPLES));
+        assert_eq!((search.p50_ms, search.max_ms), (7.0, 7.0));
+    }
+}
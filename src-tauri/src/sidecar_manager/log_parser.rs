@@ -0,0 +1,79 @@
+//! Structured parsing of sidecar stdout/stderr lines.
+
+use std::sync::Arc;
+
+use serde::Serialize;
+
+/// A log record recovered from one line of sidecar output
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ParsedLog {
+    /// Upper-cased level name, e.g. `INFO`
+    pub level: String,
+    pub message: String,
+    /// Everything else the record carried (logger name, extra context, ...)
+    pub fields: serde_json::Map<String, serde_json::Value>,
+}
+
+/// Turns a line into a [`ParsedLog`], or returns `None` to keep it as raw text.
+/// Runs on the reader threads, so it should be cheap.
+pub type LineParser = Arc<dyn Fn(&str) -> Option<ParsedLog> + Send + Sync>;
+
+/// Parser for JSON log lines. Accepts flat objects with `level` and `message` (or `msg`),
+/// keeping the remaining keys as fields, and loguru's `serialize=True` output, where the
+/// record sits under `record` and its context under `record.extra`.
+pub fn parse_json_line(line: &str) -> Option<ParsedLog> {
+    let line = line.trim();
+    if !line.starts_with('{') {
+        return None;
+    }
+    let serde_json::Value::Object(mut object) = serde_json::from_str(line).ok()? else {
+        return None;
+    };
+
+    if let Some(serde_json::Value::Object(record)) = object.get("record") {
+        let level = record.get("level")?.get("name")?.as_str()?;
+        let message = record.get("message")?.as_str()?;
+        let mut fields = match record.get("extra") {
+            Some(serde_json::Value::Object(extra)) => extra.clone(),
+            _ => serde_json::Map::new(),
+        };
+        for key in ["name", "function", "line"] {
+            if let Some(value) = record.get(key) {
+                fields.insert(key.to_string(), value.clone());
+            }
+        }
+        return Some(ParsedLog { level: level.to_uppercase(), message: message.to_string(), fields });
+    }
+
+    let level = object.remove("level")?.as_str()?.to_uppercase();
+    let message = match object.remove("message").or_else(|| object.remove("msg"))? {
+        serde_json::Value::String(message) => message,
+        other => other.to_string(),
+    };
+    Some(ParsedLog { level, message, fields: object })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_json_line() {
+        let flat = parse_json_line(r#"{"level": "warning", "msg": "slow plugin", "plugin": "rag"}"#).unwrap();
+        assert_eq!(flat.level, "WARNING");
+        assert_eq!(flat.message, "slow plugin");
+        assert_eq!(flat.fields["plugin"], "rag");
+
+        let loguru = parse_json_line(
+            r#"{"text": "...", "record": {"level": {"name": "INFO", "no": 20}, "message": "ready",
+               "extra": {"vault": "/v"}, "name": "sidecar.main", "line": 42}}"#,
+        ).unwrap();
+        assert_eq!(loguru.level, "INFO");
+        assert_eq!(loguru.message, "ready");
+        assert_eq!(loguru.fields["vault"], "/v");
+        assert_eq!(loguru.fields["line"], 42);
+
+        assert_eq!(parse_json_line("2024-01-01 12:00:00 | INFO | plain text"), None);
+        assert_eq!(parse_json_line(r#"{"message": "no level"}"#), None);
+    }
+}
@@ -0,0 +1,98 @@
+//! A memory budget shared by all sidecars, so opening many vaults can't exhaust the machine.
+//!
+//! [`SidecarManager::run_memory_budget`](super::SidecarManager::run_memory_budget) samples each
+//! sidecar's resident memory every `sample_interval`. While the total is over `total_bytes`, the
+//! least recently used idle sidecars are terminated (suspending would keep their memory) and
+//! announced with `sidecar://memory-evicted`. Busy, starting and recently used sidecars are left
+//! alone, so the budget can stay exceeded until one of them goes idle.
+
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+/// How the combined memory of all sidecars is capped
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MemoryBudgetConfig {
+    /// Combined resident memory allowed across all sidecars
+    pub total_bytes: u64,
+    #[serde(with = "super::config_file::duration_ms", rename = "sample_interval_ms", default = "default_sample_interval")]
+    pub sample_interval: Duration,
+    /// A sidecar used more recently than this is never evicted
+    #[serde(with = "super::config_file::duration_ms", rename = "min_idle_ms", default = "default_min_idle")]
+    pub min_idle: Duration,
+}
+
+fn default_sample_interval() -> Duration {
+    Duration::from_secs(10)
+}
+
+fn default_min_idle() -> Duration {
+    Duration::from_secs(60)
+}
+
+/// One sidecar's share of [`MemoryUsage`]
+#[derive(Debug, Clone, Serialize)]
+pub struct SidecarMemory {
+    pub window_label: String,
+    /// None where resident memory can't be read (platforms sysinfo doesn't support)
+    pub memory_bytes: Option<u64>,
+    /// Time since the sidecar last finished (or started) a command
+    pub idle_secs: f64,
+    /// Whether it may be evicted: ready, nothing in flight and idle for at least `min_idle`
+    pub evictable: bool,
+}
+
+/// Combined sidecar memory, for display and budget enforcement
+#[derive(Debug, Clone, Serialize)]
+pub struct MemoryUsage {
+    pub total_bytes: u64,
+    pub budget_bytes: Option<u64>,
+    /// Sorted by window label
+    pub sidecars: Vec<SidecarMemory>,
+}
+
+impl MemoryUsage {
+    /// Sidecars to terminate, least recently used first, to bring the total within `budget`
+    pub fn evictions(&self, budget: u64) -> Vec<&SidecarMemory> {
+        let mut candidates: Vec<_> = self.sidecars.iter()
+            .filter(|sidecar| sidecar.evictable && sidecar.memory_bytes.is_some())
+            .collect();
+        candidates.sort_by(|a, b| b.idle_secs.total_cmp(&a.idle_secs));
+        let mut total = self.total_bytes;
+        candidates.into_iter()
+            .take_while(|sidecar| {
+                let over = total > budget;
+                total = total.saturating_sub(sidecar.memory_bytes.unwrap_or_default());
+                over
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sidecar(label: &str, memory_bytes: u64, idle_secs: f64, evictable: bool) -> SidecarMemory {
+        SidecarMemory { window_label: label.to_string(), memory_bytes: Some(memory_bytes), idle_secs, evictable }
+    }
+
+    #[test]
+    fn test_evicts_least_recently_used_idle_sidecars_until_within_budget() {
+        let usage = MemoryUsage {
+            total_bytes: 600,
+            budget_bytes: Some(250),
+            sidecars: vec![
+                sidecar("busy", 300, 500.0, false),
+                sidecar("old", 100, 300.0, true),
+                sidecar("older", 100, 400.0, true),
+                sidecar("recent", 100, 100.0, true),
+            ],
+        };
+        let labels = |budget| usage.evictions(budget).iter().map(|s| s.window_label.clone()).collect::<Vec<_>>();
+        assert_eq!(labels(450), ["older", "old"]);
+        // The busy sidecar alone is over budget, so every idle one goes and it still doesn't fit
+        assert_eq!(labels(250), ["older", "old", "recent"]);
+        assert!(labels(600).is_empty());
+    }
+}
@@ -0,0 +1,87 @@
+//! Which RPC methods the manager forwards to sidecars, for locked-down deployments that keep the
+//! UI away from `exec`-like methods whatever the sidecar itself allows.
+//!
+//! Patterns are method names where `*` stands for any run of characters, so `fs.*` covers
+//! `fs.read` and `fs.tree.list`. A method must match the allowlist, if there is one, and nothing
+//! on the denylist; a refused command fails with [`SidecarError::MethodForbidden`] before any
+//! middleware runs or a connection is opened.
+//!
+//! The policy covers the commands sent through the manager's public send methods, i.e. by the
+//! UI over IPC, whatever the method. The RPCs the manager sends on its own behalf (snapshots,
+//! capability and version queries, live settings, reloads, rebinds, job polling) are not
+//! checked, so a policy can keep the UI from calling `set_env` directly while `set_env()` keeps
+//! working. The policy doesn't cover the vault window's own WebSocket connection to its sidecar,
+//! which doesn't go through the manager at all; the sidecar has to refuse what that socket
+//! mustn't do.
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use super::SidecarError;
+
+/// Method patterns to forward and to refuse; both empty forwards everything
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MethodPolicy {
+    /// When not empty, only methods matching one of these are forwarded
+    pub allow: Vec<String>,
+    /// Methods matching any of these are refused, even if allowed
+    pub deny: Vec<String>,
+}
+
+/// A [`MethodPolicy`] with its patterns compiled
+#[derive(Debug, Default)]
+pub struct MethodFilter {
+    allow: Vec<Regex>,
+    deny: Vec<Regex>,
+}
+
+impl MethodFilter {
+    pub fn new(policy: &MethodPolicy) -> Self {
+        Self { allow: compile(&policy.allow), deny: compile(&policy.deny) }
+    }
+
+    /// Fail with [`SidecarError::MethodForbidden`] unless `method` may be sent
+    pub fn check(&self, method: &str) -> Result<(), SidecarError> {
+        let matches = |patterns: &[Regex]| patterns.iter().any(|pattern| pattern.is_match(method));
+        let allowed = (self.allow.is_empty() || matches(&self.allow)) && !matches(&self.deny);
+        match allowed {
+            true => Ok(()),
+            false => Err(SidecarError::MethodForbidden { method: method.to_string() }),
+        }
+    }
+}
+
+/// Anchored regexes for `patterns`; everything but `*` is literal, so compiling can't fail
+fn compile(patterns: &[String]) -> Vec<Regex> {
+    patterns.iter()
+        .map(|pattern| {
+            let literal: Vec<String> = pattern.split('*').map(regex::escape).collect();
+            Regex::new(&format!("^{}$", literal.join(".*"))).expect("escaped pattern")
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wildcards_allow_and_deny() {
+        let filter = MethodFilter::new(&MethodPolicy {
+            allow: vec!["fs.*".to_string(), "search".to_string()],
+            deny: vec!["fs.exec*".to_string()],
+        });
+        for method in ["fs.read", "fs.tree.list", "search"] {
+            assert!(filter.check(method).is_ok(), "{} should be allowed", method);
+        }
+        for method in ["fs.exec", "fs.execute", "fs", "search.all", "shell.run", "fsXread", "snapshot", "set_env"] {
+            assert!(matches!(filter.check(method), Err(SidecarError::MethodForbidden { .. })), "{} should be refused", method);
+        }
+
+        let deny_only = MethodFilter::new(&MethodPolicy { deny: vec!["*exec*".to_string()], ..Default::default() });
+        assert!(deny_only.check("anything").is_ok());
+        assert!(deny_only.check("os.exec_command").is_err());
+        assert!(MethodFilter::default().check("os.exec_command").is_ok());
+    }
+}
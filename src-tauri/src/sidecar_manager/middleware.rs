@@ -0,0 +1,176 @@
+//! Hooks observing, and optionally rewriting, the JSON commands sent to sidecars, registered with
+//! `SidecarManager::register_middleware`, e.g. to redact params, validate responses against a
+//! schema or record fixtures.
+//!
+//! Every middleware sees each command's params before it is audited and sent, in registration
+//! order, and its outcome afterwards, in reverse order. One that answers the request itself stops
+//! it there: the synthetic response goes through the same JSON-RPC error check as a real one and
+//! back through the middleware before it, without the sidecar (which needn't even be running)
+//! being contacted. Binary results, control-socket calls and the startup RPCs bypass middleware.
+//!
+//! [`FixtureRecorder`] is the one the app ships: with `TAILOR_RECORD_FIXTURES` set to a file, it
+//! appends every command and its outcome there, to replay a session in tests.
+
+use std::io::Write;
+use std::path::Path;
+use std::sync::{Arc, Mutex, RwLock};
+
+use super::SidecarError;
+
+/// The command a hook is called for
+#[derive(Debug, Clone, Copy)]
+pub struct RpcCall<'a> {
+    pub window_label: &'a str,
+    /// The window's vault, if it has a sidecar
+    pub vault_path: Option<&'a str>,
+    pub method: &'a str,
+}
+
+pub trait RpcMiddleware: Send + Sync {
+    /// Called before the request is sent, with its params to inspect or rewrite. Returning a
+    /// response (a whole JSON-RPC envelope, as [`super::SidecarManager::send_command`] returns)
+    /// answers the command without sending it.
+    fn on_request(&self, call: &RpcCall, params: &mut serde_json::Value) -> Option<serde_json::Value> {
+        let _ = (call, params);
+        None
+    }
+
+    /// Called with the command's outcome, to inspect or replace before it reaches the caller
+    fn on_response(&self, call: &RpcCall, result: &mut Result<serde_json::Value, SidecarError>) {
+        let _ = (call, result);
+    }
+}
+
+/// Appends each command's params and then its outcome to a JSON Lines file, as separate
+/// `request` and `response` records tagged with the window and method
+pub struct FixtureRecorder(Mutex<std::fs::File>);
+
+impl FixtureRecorder {
+    pub fn new(path: &Path) -> std::io::Result<Self> {
+        let file = std::fs::OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self(Mutex::new(file)))
+    }
+
+    fn record(&self, call: &RpcCall, mut record: serde_json::Value) {
+        record["window_label"] = call.window_label.into();
+        record["vault_path"] = call.vault_path.into();
+        record["method"] = call.method.into();
+        let mut file = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        if let Err(e) = writeln!(file, "{}", record) {
+            eprintln!("Failed to record fixture for '{}': {}", call.method, e);
+        }
+    }
+}
+
+impl RpcMiddleware for FixtureRecorder {
+    fn on_request(&self, call: &RpcCall, params: &mut serde_json::Value) -> Option<serde_json::Value> {
+        self.record(call, serde_json::json!({"request": params}));
+        None
+    }
+
+    fn on_response(&self, call: &RpcCall, result: &mut Result<serde_json::Value, SidecarError>) {
+        let record = match result {
+            Ok(response) => serde_json::json!({"response": response}),
+            Err(e) => serde_json::json!({"error": e.to_string()}),
+        };
+        self.record(call, record);
+    }
+}
+
+/// Registered middleware, in registration order
+#[derive(Clone, Default)]
+pub struct Middlewares(Arc<RwLock<Vec<Arc<dyn RpcMiddleware>>>>);
+
+impl Middlewares {
+    pub fn add(&self, middleware: Box<dyn RpcMiddleware>) {
+        self.0.write().unwrap_or_else(|e| e.into_inner()).push(Arc::from(middleware));
+    }
+
+    /// The middleware to run a command through, so none is held locked while it is sent
+    pub fn snapshot(&self) -> Vec<Arc<dyn RpcMiddleware>> {
+        self.0.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+}
+
+/// Run `params` through the request hooks of `chain`. Returns how many of them ran, which are
+/// the ones whose response hooks [`response`] then runs, and the synthetic response if one of
+/// them answered.
+pub fn request(
+    chain: &[Arc<dyn RpcMiddleware>],
+    call: &RpcCall,
+    params: &mut serde_json::Value,
+) -> (usize, Option<serde_json::Value>) {
+    for (index, middleware) in chain.iter().enumerate() {
+        if let Some(response) = middleware.on_request(call, params) {
+            return (index + 1, Some(response));
+        }
+    }
+    (chain.len(), None)
+}
+
+/// Run `result` through the response hooks of the first `ran` middleware, last one first
+pub fn response(
+    chain: &[Arc<dyn RpcMiddleware>],
+    ran: usize,
+    call: &RpcCall,
+    result: &mut Result<serde_json::Value, SidecarError>,
+) {
+    for middleware in chain[..ran].iter().rev() {
+        middleware.on_response(call, result);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Tag(&'static str, Option<serde_json::Value>, Arc<std::sync::Mutex<Vec<String>>>);
+
+    impl RpcMiddleware for Tag {
+        fn on_request(&self, _: &RpcCall, _: &mut serde_json::Value) -> Option<serde_json::Value> {
+            self.2.lock().unwrap().push(format!("request {}", self.0));
+            self.1.clone()
+        }
+
+        fn on_response(&self, _: &RpcCall, _: &mut Result<serde_json::Value, SidecarError>) {
+            self.2.lock().unwrap().push(format!("response {}", self.0));
+        }
+    }
+
+    #[test]
+    fn test_short_circuit_unwinds_through_earlier_middleware() {
+        let seen = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let middlewares = Middlewares::default();
+        middlewares.add(Box::new(Tag("a", None, seen.clone())));
+        middlewares.add(Box::new(Tag("b", Some(serde_json::json!({"result": 1})), seen.clone())));
+        middlewares.add(Box::new(Tag("c", None, seen.clone())));
+
+        let chain = middlewares.snapshot();
+        let call = RpcCall { window_label: "w", vault_path: None, method: "m" };
+        let (ran, synthetic) = request(&chain, &call, &mut serde_json::json!({}));
+        assert_eq!((ran, synthetic), (2, Some(serde_json::json!({"result": 1}))));
+        response(&chain, ran, &call, &mut Ok(serde_json::Value::Null));
+        assert_eq!(*seen.lock().unwrap(), ["request a", "request b", "response b", "response a"]);
+    }
+
+    #[test]
+    fn test_fixture_recorder_appends_requests_and_outcomes() {
+        let path = std::env::temp_dir().join(format!("tailor-fixtures-{}.jsonl", uuid::Uuid::new_v4()));
+        let chain: Vec<Arc<dyn RpcMiddleware>> = vec![Arc::new(FixtureRecorder::new(&path).unwrap())];
+        let call = RpcCall { window_label: "w", vault_path: Some("/vault"), method: "search" };
+        let (ran, _) = request(&chain, &call, &mut serde_json::json!({"query": "rust"}));
+        response(&chain, ran, &call, &mut Ok(serde_json::json!({"result": []})));
+        response(&chain, ran, &call, &mut Err(SidecarError::Cancelled));
+
+        let records: Vec<serde_json::Value> = std::fs::read_to_string(&path).unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(records, [
+            serde_json::json!({"window_label": "w", "vault_path": "/vault", "method": "search", "request": {"query": "rust"}}),
+            serde_json::json!({"window_label": "w", "vault_path": "/vault", "method": "search", "response": {"result": []}}),
+            serde_json::json!({"window_label": "w", "vault_path": "/vault", "method": "search", "error": SidecarError::Cancelled.to_string()}),
+        ]);
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,72 @@
+//! Server-pushed JSON-RPC notifications, kept flowing across sidecar restarts.
+//!
+//! Each window has one hub that outlives its sidecar process: the persistent connection of
+//! whichever process is current feeds it, so receivers survive a restart. After a restart the
+//! manager reconnects, re-sends the subscription RPCs registered with
+//! `SidecarManager::add_subscription`, and then publishes [`SidecarNotification::Reconnected`]
+//! so subscribers know events may have been missed in between.
+
+use serde::Serialize;
+use tokio::sync::broadcast;
+
+/// Events buffered per notification subscriber before it starts lagging
+const NOTIFICATION_BUFFER_CAPACITY: usize = 256;
+
+/// What a notification subscriber receives
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SidecarNotification {
+    /// A JSON-RPC message from the sidecar that isn't a response to one of our requests
+    Event {
+        method: String,
+        params: serde_json::Value,
+    },
+    /// The sidecar restarted and the stream is live again; events in the gap were lost
+    Reconnected { ws_port: u16 },
+}
+
+impl SidecarNotification {
+    /// The notification carried by a sidecar message, if it is one (a `method`, no `id`)
+    pub fn from_message(message: &serde_json::Value) -> Option<Self> {
+        if message.get("id").is_some_and(|id| !id.is_null()) {
+            return None;
+        }
+        Some(Self::Event {
+            method: message.get("method")?.as_str()?.to_string(),
+            params: message.get("params").cloned().unwrap_or(serde_json::Value::Null),
+        })
+    }
+}
+
+/// A window's notification channel and the subscription RPCs to replay after a restart
+pub struct NotificationHub {
+    pub tx: broadcast::Sender<SidecarNotification>,
+    pub subscriptions: Vec<(String, serde_json::Value)>,
+}
+
+impl Default for NotificationHub {
+    fn default() -> Self {
+        Self {
+            tx: broadcast::channel(NOTIFICATION_BUFFER_CAPACITY).0,
+            subscriptions: Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_message_only_accepts_notifications() {
+        let event = serde_json::json!({"jsonrpc": "2.0", "method": "chat.token", "params": {"t": "hi"}});
+        assert_eq!(
+            SidecarNotification::from_message(&event),
+            Some(SidecarNotification::Event { method: "chat.token".into(), params: serde_json::json!({"t": "hi"}) })
+        );
+
+        let response = serde_json::json!({"jsonrpc": "2.0", "method": "x", "result": 1, "id": "a"});
+        assert_eq!(SidecarNotification::from_message(&response), None);
+        assert_eq!(SidecarNotification::from_message(&serde_json::json!({"result": 1})), None);
+    }
+}
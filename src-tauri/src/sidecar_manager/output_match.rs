@@ -0,0 +1,59 @@
+//! Callbacks for sidecar output lines matching a pattern, registered with
+//! `SidecarManager::on_output_match`. Watching for e.g. the sidecar's unhandled exception marker
+//! reports a crash the moment it is printed instead of when a command times out or the process
+//! exits.
+//!
+//! Patterns are applied on the reader threads to every captured stdout/stderr line (not to
+//! binary records, nor to lines dropped while capture is paused), before the line reaches
+//! `subscribe_logs`. Callbacks run there too and should hand heavy work off.
+
+use regex::Regex;
+
+/// Called with the window label and the matching line
+pub type OutputCallback = std::sync::Arc<dyn Fn(&str, &str) + Send + Sync>;
+
+struct Matcher {
+    pattern: Regex,
+    callback: OutputCallback,
+}
+
+/// Registered patterns, shared by the manager and every sidecar's reader threads
+#[derive(Clone, Default)]
+pub struct OutputMatchers(std::sync::Arc<std::sync::RwLock<Vec<Matcher>>>);
+
+impl OutputMatchers {
+    pub fn add(&self, pattern: Regex, callback: OutputCallback) {
+        self.0.write().unwrap_or_else(|e| e.into_inner()).push(Matcher { pattern, callback });
+    }
+
+    /// Run the callback of every pattern `line` matches
+    pub fn apply(&self, window_label: &str, line: &str) {
+        for matcher in self.0.read().unwrap_or_else(|e| e.into_inner()).iter() {
+            if matcher.pattern.is_match(line) {
+                (matcher.callback)(window_label, line);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_every_matching_pattern_is_called() {
+        let seen = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let matchers = OutputMatchers::default();
+        for (name, pattern) in [("traceback", "^Traceback"), ("memory", "MemoryError|OutOfMemory")] {
+            let seen = seen.clone();
+            matchers.add(Regex::new(pattern).unwrap(), std::sync::Arc::new(move |label: &str, line: &str| {
+                seen.lock().unwrap().push(format!("{} {}: {}", name, label, line));
+            }));
+        }
+
+        matchers.apply("w", "Traceback (most recent call last):");
+        matchers.apply("w", "  File \"x.py\", line 1, in <module>");
+        matchers.apply("v", "MemoryError");
+        assert_eq!(*seen.lock().unwrap(), ["traceback w: Traceback (most recent call last):", "memory v: MemoryError"]);
+    }
+}
@@ -0,0 +1,139 @@
+//! Reloading a sidecar when its vault's `plugins/` directory changes, for plugin development.
+//!
+//! A sidecar spawned with `SpawnOptions::watch_plugins` (or `watch_plugins = true` in the vault
+//! config) gets a [`PluginWatcher`]: a `notify` watcher on `<vault>/plugins` whose events are
+//! debounced, so the burst of writes from one save (or a `git checkout`) triggers one reload.
+//! Editor temp and backup files and bytecode are ignored. The watcher lives in the
+//! `SidecarProcess`, so it stops with the sidecar and a restart gets a fresh one.
+//! `SidecarManager::run_plugin_watcher` performs the reloads.
+
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use tokio::sync::{mpsc, watch};
+use tokio::task::JoinHandle;
+
+/// Quiet period after the last change before reloading unless
+/// `SidecarManagerConfig::plugin_watch_debounce` says otherwise
+pub const DEFAULT_DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Directory watched inside the vault
+pub const PLUGINS_DIR: &str = "plugins";
+
+/// Debounced changes under one sidecar's plugins directory
+#[derive(Debug, Clone)]
+pub struct PluginChange {
+    pub window_label: String,
+    pub paths: Vec<PathBuf>,
+}
+
+/// Watches one vault's plugins directory; dropping it stops the watch and its debounce task
+pub struct PluginWatcher {
+    _watcher: RecommendedWatcher,
+}
+
+impl PluginWatcher {
+    /// Watch `<vault_path>/plugins`, sending a [`PluginChange`] to `changes` once `debounce` has
+    /// passed without further events. The debounce task is returned for `shutdown` to await.
+    pub fn start(
+        window_label: &str,
+        vault_path: &Path,
+        debounce: Duration,
+        changes: mpsc::UnboundedSender<PluginChange>,
+        mut shutdown: watch::Receiver<bool>,
+    ) -> notify::Result<(Self, JoinHandle<()>)> {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+            let Ok(event) = event else { return };
+            if matches!(event.kind, EventKind::Access(_)) {
+                return;
+            }
+            for path in event.paths.into_iter().filter(|path| !is_ignored(path)) {
+                let _ = tx.send(path);
+            }
+        })?;
+        watcher.watch(&vault_path.join(PLUGINS_DIR), RecursiveMode::Recursive)?;
+
+        let window_label = window_label.to_string();
+        let task = tokio::spawn(async move {
+            loop {
+                let batch = tokio::select! {
+                    batch = collect(&mut rx, debounce) => batch,
+                    _ = shutdown.wait_for(|stop| *stop) => return,
+                };
+                // The watcher (and with it the sender) is gone
+                let Some(paths) = batch else { return };
+                let change = PluginChange { window_label: window_label.clone(), paths: paths.into_iter().collect() };
+                if changes.send(change).is_err() {
+                    return;
+                }
+            }
+        });
+        Ok((Self { _watcher: watcher }, task))
+    }
+}
+
+/// Wait for a change, then keep collecting until `quiet` passes without one. None once the
+/// sender is dropped.
+pub async fn collect(rx: &mut mpsc::UnboundedReceiver<PathBuf>, quiet: Duration) -> Option<BTreeSet<PathBuf>> {
+    let mut paths = BTreeSet::from([rx.recv().await?]);
+    loop {
+        match tokio::time::timeout(quiet, rx.recv()).await {
+            Ok(Some(path)) => {
+                paths.insert(path);
+            }
+            Ok(None) | Err(_) => return Some(paths),
+        }
+    }
+}
+
+/// Editor swap/backup files, hidden files and bytecode, which don't warrant a reload
+pub fn is_ignored(path: &Path) -> bool {
+    if path.components().any(|c| c.as_os_str() == "__pycache__") {
+        return true;
+    }
+    let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+        return true;
+    };
+    const SUFFIXES: [&str; 8] = ["~", ".swp", ".swx", ".tmp", ".bak", ".orig", ".pyc", ".pyo"];
+    // Vim probes whether it can write a directory with a file named 4913
+    name.starts_with('.') || name.starts_with('#') || name == "4913"
+        || SUFFIXES.iter().any(|suffix| name.ends_with(suffix))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_temp_and_backup_files_are_ignored() {
+        for ignored in ["plugins/a/.main.py.swp", "plugins/a/main.py~", "plugins/#main.py#", "plugins/a/4913",
+                        "plugins/a/__pycache__/main.cpython-312.pyc", "plugins/a/main.py.tmp", "plugins/.git"] {
+            assert!(is_ignored(Path::new(ignored)), "{} not ignored", ignored);
+        }
+        for watched in ["plugins/a/main.py", "plugins/a/plugin.toml", "plugins/a/data/prompt.md"] {
+            assert!(!is_ignored(Path::new(watched)), "{} ignored", watched);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rapid_changes_are_coalesced() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let writer = tokio::spawn(async move {
+            for name in ["a.py", "b.py", "a.py"] {
+                tx.send(PathBuf::from(name)).unwrap();
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+            tokio::time::sleep(Duration::from_millis(300)).await;
+            tx.send(PathBuf::from("c.py")).unwrap();
+        });
+        let quiet = Duration::from_millis(150);
+        let first = collect(&mut rx, quiet).await.unwrap();
+        assert_eq!(first, BTreeSet::from([PathBuf::from("a.py"), PathBuf::from("b.py")]));
+        assert_eq!(collect(&mut rx, quiet).await.unwrap(), BTreeSet::from([PathBuf::from("c.py")]));
+        writer.await.unwrap();
+        assert!(collect(&mut rx, quiet).await.is_none());
+    }
+}
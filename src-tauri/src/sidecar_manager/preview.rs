@@ -0,0 +1,64 @@
+//! A dry run of a sidecar launch, for reproducing "why won't my sidecar start" by hand.
+//!
+//! `SidecarManager::spawn_command_preview` resolves the interpreter, command line, working
+//! directory and environment the same way a spawn does, and returns them as a [`SpawnPreview`]
+//! instead of starting a process.
+
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::path::PathBuf;
+
+use serde::Serialize;
+
+use super::python::PythonInterpreter;
+
+/// What a spawn would run
+#[derive(Debug, Clone, Serialize)]
+pub struct SpawnPreview {
+    /// The interpreter and where it was resolved from
+    pub python: PythonInterpreter,
+    /// Program and arguments, the launcher first if there is one
+    pub argv: Vec<String>,
+    pub working_dir: PathBuf,
+    /// Variables set on top of the app's environment, `PYTHONPATH` included
+    pub env: BTreeMap<String, String>,
+    /// The same launch as one POSIX shell line, ready to paste
+    pub shell: String,
+}
+
+impl SpawnPreview {
+    pub fn new(python: PythonInterpreter, argv: &[OsString], working_dir: PathBuf, env: BTreeMap<String, String>) -> Self {
+        let argv: Vec<String> = argv.iter().map(|arg| arg.to_string_lossy().to_string()).collect();
+        let mut shell = format!("cd {} &&", quote(&working_dir.to_string_lossy()));
+        for (key, value) in &env {
+            shell.push_str(&format!(" {}={}", key, quote(value)));
+        }
+        for arg in &argv {
+            shell.push(' ');
+            shell.push_str(&quote(arg));
+        }
+        Self { python, argv, working_dir, env, shell }
+    }
+}
+
+/// `arg` as a single shell word: as is when that's safe, else single-quoted
+fn quote(arg: &str) -> String {
+    let safe = !arg.is_empty()
+        && arg.chars().all(|c| c.is_ascii_alphanumeric() || "-_./:=,+@%".contains(c));
+    match safe {
+        true => arg.to_string(),
+        false => format!("'{}'", arg.replace('\'', r"'\''")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shell_line_quotes_what_needs_it() {
+        assert_eq!(quote("--vault"), "--vault");
+        assert_eq!(quote("/my vault/it's"), r"'/my vault/it'\''s'");
+        assert_eq!(quote(""), "''");
+    }
+}
@@ -0,0 +1,725 @@
+//! OS-level process control for sidecar children.
+
+use std::collections::HashSet;
+use std::fmt;
+use std::process::{Child, Command, ExitStatus};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use sysinfo::{Pid, ProcessRefreshKind, ProcessStatus, ProcessesToUpdate, System, UpdateKind};
+
+use super::args::ArgTemplate;
+
+/// Delay between `try_wait` polls while waiting for a child to exit
+const EXIT_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// A signal for [`send_signal_to`]. Only `Kill` is available on Windows.
+#[cfg_attr(not(unix), allow(dead_code))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SidecarSignal {
+    /// SIGHUP, conventionally "reload configuration"
+    Hup,
+    Int,
+    Term,
+    Kill,
+    /// SIGUSR1, e.g. "dump state"
+    Usr1,
+    Usr2,
+    /// Any other signal number (Unix only)
+    Other(i32),
+}
+
+impl SidecarSignal {
+    #[cfg(unix)]
+    fn number(self) -> libc::c_int {
+        match self {
+            Self::Hup => libc::SIGHUP,
+            Self::Int => libc::SIGINT,
+            Self::Term => libc::SIGTERM,
+            Self::Kill => libc::SIGKILL,
+            Self::Usr1 => libc::SIGUSR1,
+            Self::Usr2 => libc::SIGUSR2,
+            Self::Other(signal) => signal,
+        }
+    }
+
+    /// `Some(true)` for signals that stop the process (SIGSTOP, SIGTSTP), `Some(false)` for SIGCONT
+    pub fn suspends(self) -> Option<bool> {
+        #[cfg(unix)]
+        {
+            match self.number() {
+                libc::SIGSTOP | libc::SIGTSTP => Some(true),
+                libc::SIGCONT => Some(false),
+                _ => None,
+            }
+        }
+        #[cfg(not(unix))]
+        {
+            None
+        }
+    }
+}
+
+/// Unix nice range; values outside it are clamped
+const NICE_RANGE: std::ops::RangeInclusive<i32> = -20..=19;
+
+/// Scheduling priority for a sidecar, applied right after spawn. In a vault config file it is
+/// written `priority = "low"` (`idle`, `low`, `normal`, `high`) or `priority = { nice = 5 }`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProcessPriority {
+    /// Only runs when nothing else wants the CPU (nice 19, `IDLE_PRIORITY_CLASS`)
+    Idle,
+    /// Background work such as indexing (nice 10, `BELOW_NORMAL_PRIORITY_CLASS`)
+    Low,
+    /// Left as inherited from the app
+    #[default]
+    Normal,
+    /// Interactive sidecars (nice -5, `ABOVE_NORMAL_PRIORITY_CLASS`); usually needs privileges on Unix
+    High,
+    /// Explicit nice value, clamped to -20..=19; on Windows mapped to the nearest priority class
+    Nice(i32),
+}
+
+impl ProcessPriority {
+    /// The nice value this priority maps to on Unix
+    pub fn nice(self) -> i32 {
+        match self {
+            Self::Idle => 19,
+            Self::Low => 10,
+            Self::Normal => 0,
+            Self::High => -5,
+            Self::Nice(nice) => nice.clamp(*NICE_RANGE.start(), *NICE_RANGE.end()),
+        }
+    }
+
+    /// The Windows priority class closest to [`Self::nice`]
+    #[cfg(windows)]
+    fn priority_class(self) -> u32 {
+        const IDLE: u32 = 0x40;
+        const BELOW_NORMAL: u32 = 0x4000;
+        const NORMAL: u32 = 0x20;
+        const ABOVE_NORMAL: u32 = 0x8000;
+        const HIGH: u32 = 0x80;
+        match self.nice() {
+            15.. => IDLE,
+            5..=14 => BELOW_NORMAL,
+            -4..=4 => NORMAL,
+            -14..=-5 => ABOVE_NORMAL,
+            _ => HIGH,
+        }
+    }
+}
+
+/// A sidecar that survived both SIGTERM and SIGKILL (e.g. stuck in uninterruptible sleep)
+#[derive(Debug, Clone)]
+pub struct TerminationFailed {
+    pub window_label: String,
+    pub pid: u32,
+}
+
+impl fmt::Display for TerminationFailed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Sidecar for window '{}' (PID {}) did not exit after being killed",
+            self.window_label, self.pid
+        )
+    }
+}
+
+impl std::error::Error for TerminationFailed {}
+
+/// A running process launched from our sidecar arguments that no manager in this app tracks
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct OrphanInfo {
+    pub pid: u32,
+    pub ws_port: u16,
+    /// The `{vault}` argument, if present
+    pub vault_path: Option<String>,
+    pub cmdline: Vec<String>,
+}
+
+/// A tracked sidecar's process: one this manager spawned, or one a previous manager left
+/// running and [`super::SidecarManager::reattach_all`] adopted by PID. An adopted process isn't
+/// our child, so it is watched through `/proc` and its exit status can't be collected.
+pub enum SidecarChild {
+    Spawned(Child),
+    Adopted(u32),
+}
+
+impl SidecarChild {
+    pub fn id(&self) -> u32 {
+        match self {
+            Self::Spawned(child) => child.id(),
+            Self::Adopted(pid) => *pid,
+        }
+    }
+
+    /// `Ok(None)` while the process runs. An adopted process that exited yields an error, as
+    /// only its real parent could collect the status.
+    pub fn try_wait(&mut self) -> std::io::Result<Option<ExitStatus>> {
+        match self {
+            Self::Spawned(child) => child.try_wait(),
+            Self::Adopted(pid) if adopted_exited(*pid) => Err(std::io::Error::new(
+                std::io::ErrorKind::Other,
+                format!("adopted PID {} exited; its status went to the process that spawned it", pid),
+            )),
+            Self::Adopted(_) => Ok(None),
+        }
+    }
+
+    /// [`send_signal_to`] for either kind of process
+    pub fn signal(&mut self, group: bool, signal: SidecarSignal) -> std::io::Result<()> {
+        match self {
+            Self::Spawned(child) => send_signal_to(child, group, signal),
+            #[cfg(unix)]
+            Self::Adopted(pid) => {
+                let target = if group { -(*pid as libc::pid_t) } else { *pid as libc::pid_t };
+                // SAFETY: kill(2) on a PID re-checked to carry our sidecar signature when adopted
+                if unsafe { libc::kill(target, signal.number()) } != 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+                Ok(())
+            }
+            #[cfg(not(unix))]
+            Self::Adopted(_) => Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "adopted sidecars can't be signalled on this platform",
+            )),
+        }
+    }
+
+    /// [`terminate_child`] for either kind of process
+    pub fn terminate(
+        &mut self,
+        window_label: &str,
+        group: bool,
+        grace: Duration,
+        kill_timeout: Duration,
+    ) -> Result<(), TerminationFailed> {
+        match self {
+            Self::Spawned(child) => terminate_child(window_label, child, group, grace, kill_timeout).map(drop),
+            Self::Adopted(pid) => terminate_adopted(window_label, *pid, group, grace, kill_timeout),
+        }
+    }
+}
+
+/// Whether an adopted process is gone; adoption only happens where `/proc` can tell
+fn adopted_exited(pid: u32) -> bool {
+    #[cfg(target_os = "linux")]
+    {
+        pid_has_exited(pid)
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = pid;
+        true
+    }
+}
+
+/// SIGTERM, wait `grace`, SIGKILL, wait `kill_timeout` for a process we didn't spawn
+fn terminate_adopted(
+    window_label: &str,
+    pid: u32,
+    group: bool,
+    grace: Duration,
+    kill_timeout: Duration,
+) -> Result<(), TerminationFailed> {
+    #[cfg(target_os = "linux")]
+    {
+        let target = if group { -(pid as libc::pid_t) } else { pid as libc::pid_t };
+        if pid_has_exited(pid) {
+            return Ok(());
+        }
+        // SAFETY: kill(2) on a PID re-checked to carry our sidecar signature when adopted
+        unsafe { libc::kill(target, libc::SIGTERM) };
+        if wait_for_exit(pid, grace) {
+            return Ok(());
+        }
+        println!("Sidecar '{}' ignored SIGTERM for {:?}, killing", window_label, grace);
+        // SAFETY: as above
+        unsafe { libc::kill(target, libc::SIGKILL) };
+        if wait_for_exit(pid, kill_timeout) {
+            return Ok(());
+        }
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = (group, grace, kill_timeout);
+    }
+    Err(TerminationFailed { window_label: window_label.to_string(), pid })
+}
+
+/// Wait up to `timeout` for the child to exit
+pub fn wait_with_timeout(child: &mut Child, timeout: Duration) -> std::io::Result<Option<ExitStatus>> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(Some(status));
+        }
+        if Instant::now() >= deadline {
+            return Ok(None);
+        }
+        std::thread::sleep(EXIT_POLL_INTERVAL);
+    }
+}
+
+/// Start the child as the leader of a new process group (Unix only) so that signals can reach
+/// everything it launches, e.g. the interpreter behind a sandbox wrapper
+pub fn set_new_process_group(command: &mut Command) {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        command.process_group(0);
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = command;
+    }
+}
+
+/// Restrict the child to the CPU cores in `cores` (zero-based). On Linux every thread it has
+/// started so far is pinned and later threads inherit the mask; on Windows the process affinity
+/// mask is set. Returns false where affinity isn't supported (e.g. macOS).
+pub fn set_cpu_affinity(child: &Child, cores: &[usize]) -> std::io::Result<bool> {
+    #[cfg(target_os = "linux")]
+    {
+        let limit = libc::CPU_SETSIZE as usize;
+        if let Some(core) = cores.iter().find(|&&core| core >= limit) {
+            return Err(invalid_core(*core, limit));
+        }
+        // SAFETY: cpu_set_t is plain bit storage; all-zero is the empty set
+        let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+        for &core in cores {
+            // SAFETY: `core` is below CPU_SETSIZE, checked above
+            unsafe { libc::CPU_SET(core, &mut set) };
+        }
+
+        for tid in threads_of(child.id()) {
+            // SAFETY: sched_setaffinity(2) reads `size_of::<cpu_set_t>()` bytes from `set`
+            if unsafe { libc::sched_setaffinity(tid, std::mem::size_of::<libc::cpu_set_t>(), &set) } != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+        }
+        Ok(true)
+    }
+    #[cfg(windows)]
+    {
+        use std::os::windows::io::AsRawHandle;
+
+        #[link(name = "kernel32")]
+        extern "system" {
+            fn SetProcessAffinityMask(process: *mut std::ffi::c_void, mask: usize) -> i32;
+        }
+
+        let limit = usize::BITS as usize;
+        if let Some(core) = cores.iter().find(|&&core| core >= limit) {
+            return Err(invalid_core(*core, limit));
+        }
+        let mask = cores.iter().fold(0usize, |mask, &core| mask | 1 << core);
+        // SAFETY: the handle belongs to a child we own and is open for as long as `child` lives
+        if unsafe { SetProcessAffinityMask(child.as_raw_handle(), mask) } == 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(true)
+    }
+    #[cfg(not(any(target_os = "linux", windows)))]
+    {
+        let _ = (child, cores);
+        Ok(false)
+    }
+}
+
+/// Apply `priority` to the child (every thread it has started so far on Linux, where niceness
+/// is per thread). Returns false where priorities aren't supported. Raising priority above
+/// normal typically needs privileges and fails with `PermissionDenied`.
+pub fn set_priority(child: &Child, priority: ProcessPriority) -> std::io::Result<bool> {
+    #[cfg(unix)]
+    {
+        let nice = priority.nice();
+        #[cfg(target_os = "linux")]
+        let targets = threads_of(child.id());
+        #[cfg(not(target_os = "linux"))]
+        let targets = [child.id() as libc::pid_t];
+        for target in targets {
+            // SAFETY: setpriority(2) on a process/thread we spawned; the `which` type differs by libc
+            if unsafe { libc::setpriority(libc::PRIO_PROCESS as _, target as libc::id_t, nice) } != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+        }
+        Ok(true)
+    }
+    #[cfg(windows)]
+    {
+        use std::os::windows::io::AsRawHandle;
+
+        #[link(name = "kernel32")]
+        extern "system" {
+            fn SetPriorityClass(process: *mut std::ffi::c_void, class: u32) -> i32;
+        }
+
+        // SAFETY: the handle belongs to a child we own and is open for as long as `child` lives
+        if unsafe { SetPriorityClass(child.as_raw_handle(), priority.priority_class()) } == 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(true)
+    }
+    #[cfg(not(any(unix, windows)))]
+    {
+        let _ = (child, priority);
+        Ok(false)
+    }
+}
+
+/// Thread IDs of `pid`, or just `pid` if they can't be listed
+#[cfg(target_os = "linux")]
+fn threads_of(pid: u32) -> Vec<libc::pid_t> {
+    let threads: Vec<libc::pid_t> = std::fs::read_dir(format!("/proc/{}/task", pid))
+        .map(|tasks| tasks.flatten().filter_map(|task| task.file_name().to_str()?.parse().ok()).collect())
+        .unwrap_or_default();
+    if threads.is_empty() {
+        vec![pid as libc::pid_t]
+    } else {
+        threads
+    }
+}
+
+#[cfg(any(target_os = "linux", windows))]
+fn invalid_core(core: usize, limit: usize) -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::InvalidInput,
+        format!("CPU core {} is out of range (at most {} cores can be addressed)", core, limit),
+    )
+}
+
+/// Deliver `signal` to the child, or to its whole process group when `group` is set. Fails
+/// with `ErrorKind::Unsupported` for signals the platform has no equivalent of.
+pub fn send_signal_to(child: &mut Child, group: bool, signal: SidecarSignal) -> std::io::Result<()> {
+    #[cfg(unix)]
+    {
+        let pid = child.id() as libc::pid_t;
+        let target = if group { -pid } else { pid };
+        // SAFETY: kill(2) with a PID (or group led by a PID) we spawned and still own
+        if unsafe { libc::kill(target, signal.number()) } != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(())
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = group;
+        match signal {
+            SidecarSignal::Kill => child.kill(),
+            _ => Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                format!("{:?} has no equivalent on this platform", signal),
+            )),
+        }
+    }
+}
+
+/// Send `signal` to the child, or to its whole process group when `group` is set
+#[cfg(unix)]
+fn send_signal(child: &Child, group: bool, signal: libc::c_int) -> bool {
+    let pid = child.id() as libc::pid_t;
+    let target = if group { -pid } else { pid };
+    // SAFETY: kill(2) with a PID (or group led by a PID) we spawned and still own
+    unsafe { libc::kill(target, signal) == 0 }
+}
+
+/// Ask the child to exit (SIGTERM on Unix). Returns false where no graceful signal exists.
+fn request_exit(child: &Child, group: bool) -> bool {
+    #[cfg(unix)]
+    {
+        send_signal(child, group, libc::SIGTERM)
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = (child, group);
+        false
+    }
+}
+
+/// Forcefully kill the child (and its process group when `group` is set)
+fn force_kill(child: &mut Child, group: bool) -> std::io::Result<()> {
+    #[cfg(unix)]
+    {
+        if group && send_signal(child, true, libc::SIGKILL) {
+            return Ok(());
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = group;
+    }
+    child.kill()
+}
+
+/// Stop a child with bounded escalation: SIGTERM, wait `grace`, SIGKILL, wait `kill_timeout`.
+/// With `group` set, signals go to the child's whole process group (see [`set_new_process_group`]).
+/// Blocks the calling thread; async callers should use `spawn_blocking`.
+pub fn terminate_child(
+    window_label: &str,
+    child: &mut Child,
+    group: bool,
+    grace: Duration,
+    kill_timeout: Duration,
+) -> Result<ExitStatus, TerminationFailed> {
+    let pid = child.id();
+
+    if let Ok(Some(status)) = child.try_wait() {
+        return Ok(status);
+    }
+
+    if request_exit(child, group) {
+        if let Ok(Some(status)) = wait_with_timeout(child, grace) {
+            return Ok(status);
+        }
+        println!("Sidecar '{}' ignored SIGTERM for {:?}, killing", window_label, grace);
+    }
+
+    if let Err(e) = force_kill(child, group) {
+        eprintln!("Failed to kill sidecar '{}': {}", window_label, e);
+    }
+
+    match wait_with_timeout(child, kill_timeout) {
+        Ok(Some(status)) => Ok(status),
+        _ => Err(TerminationFailed {
+            window_label: window_label.to_string(),
+            pid,
+        }),
+    }
+}
+
+/// The process table, or just `pids`, with command lines if `cmd` is set
+fn scan_processes(pids: ProcessesToUpdate<'_>, cmd: bool) -> System {
+    let mut refresh = ProcessRefreshKind::new();
+    if cmd {
+        refresh = refresh.with_cmd(UpdateKind::Always);
+    }
+    let mut system = System::new();
+    system.refresh_processes_specifics(pids, true, refresh);
+    system
+}
+
+fn cmdline(process: &sysinfo::Process) -> Vec<String> {
+    process.cmd().iter().map(|arg| arg.to_string_lossy().into_owned()).collect()
+}
+
+/// True once `pid` has exited (gone, or a zombie awaiting its parent)
+pub fn pid_has_exited(pid: u32) -> bool {
+    let pid = Pid::from_u32(pid);
+    scan_processes(ProcessesToUpdate::Some(&[pid]), false)
+        .process(pid)
+        .map_or(true, |process| process.status() == ProcessStatus::Zombie)
+}
+
+/// Command line of `pid` as separate arguments
+pub fn read_cmdline(pid: u32) -> Option<Vec<String>> {
+    let pid = Pid::from_u32(pid);
+    scan_processes(ProcessesToUpdate::Some(&[pid]), true).process(pid).map(cmdline)
+}
+
+/// Whether a command line looks like one of our sidecars launched from `template` (the
+/// configured `SidecarManagerConfig::sidecar_args`) serving `port`
+pub fn is_sidecar_cmdline(args: &[String], template: &ArgTemplate, port: u16) -> bool {
+    sidecar_port(args, template) == Some(port)
+}
+
+/// The `{port}` of a command line launched from `template`, e.g. `--ws-port <port>` after
+/// `-u -m sidecar` with the default one
+pub fn sidecar_port(args: &[String], template: &ArgTemplate) -> Option<u16> {
+    template.value_in(args, "port")?.parse().ok()
+}
+
+/// Every visible process whose command line was launched from `template`, except `tracked`
+/// PIDs and their descendants (the interpreter behind a tracked launcher)
+pub fn find_sidecar_processes(tracked: &HashSet<u32>, template: &ArgTemplate) -> Vec<OrphanInfo> {
+    let system = scan_processes(ProcessesToUpdate::All, true);
+    let is_tracked = |mut pid: Pid| loop {
+        if tracked.contains(&pid.as_u32()) {
+            return true;
+        }
+        match system.process(pid).and_then(sysinfo::Process::parent) {
+            Some(parent) if parent != pid => pid = parent,
+            _ => return false,
+        }
+    };
+    system.processes()
+        .iter()
+        .filter(|(pid, _)| !is_tracked(**pid))
+        .filter_map(|(pid, process)| {
+            let cmdline = cmdline(process);
+            let ws_port = sidecar_port(&cmdline, template)?;
+            let vault_path = template.value_in(&cmdline, "vault");
+            Some(OrphanInfo { pid: pid.as_u32(), ws_port, vault_path, cmdline })
+        })
+        .collect()
+}
+
+/// Resident set size of `pid` in bytes; None if it is gone or sysinfo doesn't support the platform
+pub fn resident_memory(pid: u32) -> Option<u64> {
+    let pid = Pid::from_u32(pid);
+    let mut system = System::new();
+    system.refresh_processes_specifics(ProcessesToUpdate::Some(&[pid]), true, ProcessRefreshKind::new().with_memory());
+    system.process(pid).map(sysinfo::Process::memory)
+}
+
+/// PID of the process with a listening TCP socket on `port`, found via `/proc/net/tcp{,6}`
+#[cfg(target_os = "linux")]
+pub fn find_port_owner(port: u16) -> Option<u32> {
+    const TCP_LISTEN: &str = "0A";
+
+    let mut inodes = Vec::new();
+    for table in ["/proc/net/tcp", "/proc/net/tcp6"] {
+        let Ok(contents) = std::fs::read_to_string(table) else { continue };
+        for line in contents.lines().skip(1) {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() < 10 || fields[3] != TCP_LISTEN {
+                continue;
+            }
+            let local_port = fields[1].rsplit(':').next()
+                .and_then(|hex| u16::from_str_radix(hex, 16).ok());
+            if local_port == Some(port) {
+                inodes.push(format!("socket:[{}]", fields[9]));
+            }
+        }
+    }
+    if inodes.is_empty() {
+        return None;
+    }
+
+    for entry in std::fs::read_dir("/proc").ok()?.flatten() {
+        let Some(pid) = entry.file_name().to_str().and_then(|name| name.parse::<u32>().ok()) else {
+            continue;
+        };
+        // Processes owned by other users are unreadable; skipping them is exactly what we want
+        let Ok(fds) = std::fs::read_dir(entry.path().join("fd")) else { continue };
+        for fd in fds.flatten() {
+            if let Ok(target) = std::fs::read_link(fd.path()) {
+                if inodes.iter().any(|inode| target.as_os_str() == inode.as_str()) {
+                    return Some(pid);
+                }
+            }
+        }
+    }
+    None
+}
+
+/// Signal an untracked process by PID: SIGTERM, wait `grace`, SIGKILL, wait `kill_timeout`.
+/// Where there's no SIGTERM (Windows) it is killed outright. Returns whether it exited.
+pub fn kill_pid(pid: u32, grace: Duration, kill_timeout: Duration) -> bool {
+    let sysinfo_pid = Pid::from_u32(pid);
+    let system = scan_processes(ProcessesToUpdate::Some(&[sysinfo_pid]), false);
+    let Some(process) = system.process(sysinfo_pid) else { return true };
+    if process.kill_with(sysinfo::Signal::Term).is_some() && wait_for_exit(pid, grace) {
+        return true;
+    }
+    process.kill();
+    wait_for_exit(pid, kill_timeout)
+}
+
+/// Poll up to `timeout` for an untracked `pid` to exit
+fn wait_for_exit(pid: u32, timeout: Duration) -> bool {
+    let deadline = Instant::now() + timeout;
+    while !pid_has_exited(pid) {
+        if Instant::now() >= deadline {
+            return false;
+        }
+        std::thread::sleep(EXIT_POLL_INTERVAL);
+    }
+    true
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::process::Command;
+
+    #[test]
+    fn test_terminate_escalates_to_kill() {
+        // Ignored signals stay ignored across exec, so this child shrugs off SIGTERM
+        let mut child = Command::new("sh")
+            .args(["-c", "trap '' TERM; exec sleep 30"])
+            .spawn()
+            .unwrap();
+        std::thread::sleep(Duration::from_millis(100));
+
+        let started = Instant::now();
+        let status = terminate_child("w", &mut child, false, Duration::from_millis(200), Duration::from_secs(2))
+            .unwrap();
+        assert!(!status.success());
+        assert!(started.elapsed() < Duration::from_secs(2));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_terminate_reaches_process_group() {
+        // A wrapper that does not exec: the grandchild only dies if the whole group is signalled
+        let pid_file = std::env::temp_dir().join(format!("tailor-pgid-{}", uuid::Uuid::new_v4()));
+        let mut command = Command::new("sh");
+        command.args(["-c", &format!("sleep 30 & echo $! > {}; wait", pid_file.display())]);
+        set_new_process_group(&mut command);
+        let mut child = command.spawn().unwrap();
+
+        let deadline = Instant::now() + Duration::from_secs(2);
+        let grandchild: u32 = loop {
+            if let Some(pid) = std::fs::read_to_string(&pid_file).ok().and_then(|s| s.trim().parse().ok()) {
+                break pid;
+            }
+            assert!(Instant::now() < deadline, "wrapper never reported its child");
+            std::thread::sleep(Duration::from_millis(20));
+        };
+
+        terminate_child("w", &mut child, true, Duration::from_secs(1), Duration::from_secs(1)).unwrap();
+        std::thread::sleep(Duration::from_millis(100));
+        assert!(pid_has_exited(grandchild), "grandchild should be gone");
+        let _ = std::fs::remove_file(pid_file);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_set_cpu_affinity() {
+        let mut child = Command::new("sleep").arg("5").spawn().unwrap();
+        assert!(set_cpu_affinity(&child, &[0]).unwrap());
+        let status = std::fs::read_to_string(format!("/proc/{}/status", child.id())).unwrap();
+        assert!(status.lines().any(|line| line.split_whitespace().eq(["Cpus_allowed_list:", "0"])), "{}", status);
+
+        let err = set_cpu_affinity(&child, &[libc::CPU_SETSIZE as usize]).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+        let _ = child.kill();
+        let _ = child.wait();
+    }
+
+    #[test]
+    fn test_set_priority_lowers_niceness() {
+        assert_eq!(ProcessPriority::Nice(42).nice(), 19);
+        assert_eq!(ProcessPriority::Nice(-99).nice(), -20);
+
+        let mut child = Command::new("sleep").arg("5").spawn().unwrap();
+        assert!(set_priority(&child, ProcessPriority::Low).unwrap());
+        // SAFETY: getpriority(2) on our own child
+        let nice = unsafe { libc::getpriority(libc::PRIO_PROCESS as _, child.id() as libc::id_t) };
+        assert_eq!(nice, ProcessPriority::Low.nice());
+        let _ = child.kill();
+        let _ = child.wait();
+    }
+
+    #[test]
+    fn test_is_sidecar_cmdline() {
+        let args: Vec<String> = ["python3", "-u", "-m", "sidecar", "--vault", "/v", "--ws-port", "9001"]
+            .iter().map(|s| s.to_string()).collect();
+        let template = ArgTemplate::default();
+        assert!(is_sidecar_cmdline(&args, &template, 9001));
+        assert!(!is_sidecar_cmdline(&args, &template, 9002));
+        assert!(!is_sidecar_cmdline(&args[..3], &template, 9001));
+
+        // Sidecars launched from a custom template are recognised by it
+        let custom = ArgTemplate(["-m", "fork.sidecar", "--port={port}"].map(str::to_string).to_vec());
+        let args: Vec<String> = ["python3", "-m", "fork.sidecar", "--port=9001"].iter().map(|s| s.to_string()).collect();
+        assert!(is_sidecar_cmdline(&args, &custom, 9001));
+        assert!(!is_sidecar_cmdline(&args, &template, 9001));
+    }
+}
@@ -0,0 +1,532 @@
+//! Python interpreter resolution for sidecars.
+//!
+//! Precedence, highest first:
+//! 1. An explicit path set with `SidecarManager::set_python_executable`
+//! 2. The `TAILOR_PYTHON` environment variable
+//! 3. The project's pixi environment, when the project root has a `pixi.toml`
+//!    (`.pixi/envs/default`, or whatever `pixi run` reports if it isn't installed yet)
+//! 4. An interpreter bundled with the app (`SidecarManagerConfig::bundled_python`)
+//! 5. `python3`/`python` (`python.exe`/`python3.exe` on Windows) found on `PATH`
+//!
+//! Every candidate except the explicit one (validated when it is set) must answer
+//! `--version`; an invalid candidate is logged and resolution falls through to the next one.
+//!
+//! A sidecar spawned with a [`VersionConstraint`] (`SpawnOptions::python_version`) keeps that
+//! interpreter if it satisfies the constraint. Otherwise the newest matching interpreter under the
+//! search locations is used: `SidecarManagerConfig::python_search_paths`, then pyenv's versions
+//! and the project's pixi environments (see [`default_search_paths`]).
+//!
+//! Resolution probes interpreters and may run pixi, which can install an environment first, so
+//! async callers run it on the blocking pool. A [`PixiLocator`] runs `pixi run` at most once
+//! per project.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+/// Environment variable operators can use to pin the interpreter
+pub const PYTHON_ENV_VAR: &str = "TAILOR_PYTHON";
+
+/// Single-letter interpreter options accepted in `SpawnOptions::python_flags`. Left out: `-u`
+/// (always passed), `-m`/`-c` (the manager picks what runs), and options that print and exit or
+/// read from the terminal (`-h`, `-V`, `-i`, `-x`, ...).
+const ALLOWED_FLAGS: &str = "bBdEIOPqRsSv";
+
+/// Options taking a value, attached (`-Xdev`) or as the next element (`-X`, `dev`)
+const VALUE_FLAGS: &str = "XW";
+
+/// Where a resolved interpreter came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PythonSource {
+    Explicit,
+    Environment,
+    Pixi,
+    Bundled,
+    Path,
+    /// Found under a search location to satisfy a version constraint
+    Search,
+}
+
+/// Resolved Python interpreter used to launch a sidecar
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PythonInterpreter {
+    pub path: String,
+    pub version: String,
+    pub source: PythonSource,
+}
+
+/// Run `<path> --version`, returning the reported version if the interpreter works
+pub fn probe(path: &str) -> Option<String> {
+    let output = Command::new(path).arg("--version").output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    // Older interpreters print the version to stderr
+    let version = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if version.is_empty() {
+        Some(String::from_utf8_lossy(&output.stderr).trim().to_string())
+    } else {
+        Some(version)
+    }
+}
+
+/// Validate `path` and wrap it as an interpreter from `source`
+pub fn validate(path: &str, source: PythonSource) -> Result<PythonInterpreter> {
+    let version = probe(path)
+        .ok_or_else(|| anyhow::anyhow!("'{}' is not a working Python interpreter", path))?;
+    Ok(PythonInterpreter {
+        path: path.to_string(),
+        version,
+        source,
+    })
+}
+
+/// Resolve the interpreter following the precedence documented on this module
+pub fn resolve(
+    explicit: Option<PythonInterpreter>,
+    env_python: Option<String>,
+    pixi: Option<(&PixiLocator, &Path)>,
+    bundled: Option<&str>,
+) -> Result<PythonInterpreter> {
+    let resolved = explicit
+        .or_else(|| env_python.filter(|p| !p.is_empty()).and_then(|p| {
+            validate(&p, PythonSource::Environment)
+                .map_err(|e| eprintln!("Ignoring {}: {}", PYTHON_ENV_VAR, e))
+                .ok()
+        }))
+        .or_else(|| pixi.and_then(|(pixi, root)| pixi.find(root)).and_then(|p| {
+            validate(&p, PythonSource::Pixi)
+                .map_err(|e| eprintln!("Ignoring pixi environment: {}", e))
+                .ok()
+        }))
+        .or_else(|| bundled.and_then(|p| {
+            validate(p, PythonSource::Bundled)
+                .map_err(|e| eprintln!("Ignoring bundled Python: {}", e))
+                .ok()
+        }))
+        .or_else(find_on_path)
+        .ok_or_else(|| anyhow::anyhow!("Python not found in PATH"))?;
+
+    println!("Using Python '{}' ({}) from {:?} source", resolved.path, resolved.version, resolved.source);
+    Ok(resolved)
+}
+
+/// Finds the interpreter of a project's pixi environment with the configured pixi program
+/// (`SidecarManagerConfig::pixi_executable`), remembering what `pixi run` reported per project
+#[derive(Debug, Default)]
+pub struct PixiLocator {
+    program: String,
+    reported: std::sync::Mutex<HashMap<PathBuf, Option<String>>>,
+}
+
+impl PixiLocator {
+    pub fn new(program: &str) -> Self {
+        Self { program: program.to_string(), reported: Default::default() }
+    }
+
+    /// The interpreter of the pixi environment for the project at `project_root`
+    pub fn find(&self, project_root: &Path) -> Option<String> {
+        let manifest = project_root.join("pixi.toml");
+        if !manifest.is_file() {
+            return None;
+        }
+
+        #[cfg(target_os = "windows")]
+        let installed = project_root.join(".pixi").join("envs").join("default").join("python.exe");
+
+        #[cfg(not(target_os = "windows"))]
+        let installed = project_root.join(".pixi").join("envs").join("default").join("bin").join("python");
+
+        if installed.is_file() {
+            return Some(installed.to_string_lossy().to_string());
+        }
+
+        // Not installed yet (or a non-default layout): let pixi resolve, installing on first use.
+        // Holding the lock keeps concurrent spawns from installing the same environment twice.
+        let mut reported = self.reported.lock().unwrap_or_else(|e| e.into_inner());
+        reported.entry(manifest.clone())
+            .or_insert_with(|| self.run(&manifest))
+            .clone()
+    }
+
+    fn run(&self, manifest: &Path) -> Option<String> {
+        let output = Command::new(&self.program)
+            .arg("run")
+            .arg("--manifest-path")
+            .arg(manifest)
+            .args(["python", "-c", "import sys; print(sys.executable)"])
+            .output()
+            .map_err(|e| eprintln!("Failed to run pixi '{}': {}", self.program, e))
+            .ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .last()
+            .map(|line| line.trim().to_string())
+            .filter(|path| !path.is_empty())
+    }
+}
+
+/// The numeric components of a `--version` report, e.g. `[3, 12, 1]` for `Python 3.12.1`; a
+/// pre-release suffix (`3.13.0rc1`) is dropped
+pub fn parse_version(reported: &str) -> Option<Vec<u32>> {
+    let version = reported.trim().strip_prefix("Python").unwrap_or(reported).trim();
+    let parts: Vec<u32> = version.split('.')
+        .map_while(|part| {
+            let digits: String = part.chars().take_while(char::is_ascii_digit).collect();
+            digits.parse().ok()
+        })
+        .collect();
+    (!parts.is_empty()).then_some(parts)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Comparison {
+    Eq,
+    Ne,
+    Ge,
+    Gt,
+    Le,
+    Lt,
+}
+
+/// Required Python version: comma-separated clauses that must all hold, each a version with an
+/// optional `==`, `!=`, `>=`, `>`, `<=` or `<`. Versions compare on as many components as the
+/// clause gives, so `3.12` (same as `==3.12`) accepts any 3.12.x and `<3.13` rejects 3.13.0.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VersionConstraint {
+    text: String,
+    clauses: Vec<(Comparison, Vec<u32>)>,
+}
+
+impl VersionConstraint {
+    pub fn parse(text: &str) -> std::result::Result<Self, String> {
+        let mut clauses = Vec::new();
+        for clause in text.split(',').map(str::trim) {
+            let (comparison, version) = [
+                ("==", Comparison::Eq), ("!=", Comparison::Ne), (">=", Comparison::Ge),
+                ("<=", Comparison::Le), (">", Comparison::Gt), ("<", Comparison::Lt),
+            ]
+            .into_iter()
+            .find_map(|(op, comparison)| clause.strip_prefix(op).map(|rest| (comparison, rest.trim())))
+            .unwrap_or((Comparison::Eq, clause));
+            let parts = version.split('.')
+                .map(|part| part.parse::<u32>().ok())
+                .collect::<Option<Vec<u32>>>()
+                .filter(|parts| !parts.is_empty())
+                .ok_or_else(|| format!("Invalid Python version constraint '{}'", text))?;
+            clauses.push((comparison, parts));
+        }
+        Ok(Self { text: text.trim().to_string(), clauses })
+    }
+
+    pub fn matches(&self, version: &[u32]) -> bool {
+        self.clauses.iter().all(|(comparison, wanted)| {
+            let have: Vec<u32> = (0..wanted.len()).map(|i| version.get(i).copied().unwrap_or(0)).collect();
+            let ordering = have.cmp(wanted);
+            match comparison {
+                Comparison::Eq => ordering.is_eq(),
+                Comparison::Ne => ordering.is_ne(),
+                Comparison::Ge => ordering.is_ge(),
+                Comparison::Gt => ordering.is_gt(),
+                Comparison::Le => ordering.is_le(),
+                Comparison::Lt => ordering.is_lt(),
+            }
+        })
+    }
+
+    /// Whether `interpreter`'s reported version satisfies the constraint
+    pub fn accepts(&self, interpreter: &PythonInterpreter) -> bool {
+        parse_version(&interpreter.version).is_some_and(|version| self.matches(&version))
+    }
+}
+
+impl fmt::Display for VersionConstraint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
+/// Locations searched after the configured ones: pyenv's installed versions (`$PYENV_ROOT`,
+/// else `~/.pyenv`) and every pixi environment of the project
+pub fn default_search_paths(project_root: Option<&Path>) -> Vec<PathBuf> {
+    let pyenv_root = std::env::var_os("PYENV_ROOT").map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE"))
+            .map(|home| PathBuf::from(home).join(".pyenv")));
+    pyenv_root.map(|root| root.join("versions"))
+        .into_iter()
+        .chain(project_root.map(|root| root.join(".pixi").join("envs")))
+        .collect()
+}
+
+/// The interpreter of the environment rooted at `dir`, if it has one
+fn environment_python(dir: &Path) -> Option<PathBuf> {
+    #[cfg(target_os = "windows")]
+    let candidates = [dir.join("python.exe"), dir.join("Scripts").join("python.exe")];
+
+    #[cfg(not(target_os = "windows"))]
+    let candidates = [dir.join("bin").join("python3"), dir.join("bin").join("python")];
+
+    candidates.into_iter().find(|path| path.is_file())
+}
+
+/// Working interpreters under `locations`. Each location is an interpreter, an environment
+/// (`<dir>/bin/python3`), or a directory of environments such as `~/.pyenv/versions`.
+/// Missing locations are skipped.
+pub fn find_interpreters(locations: &[PathBuf]) -> Vec<PythonInterpreter> {
+    let mut paths = Vec::new();
+    for location in locations {
+        if location.is_file() {
+            paths.push(location.clone());
+        } else if let Some(python) = environment_python(location) {
+            paths.push(python);
+        } else if let Ok(entries) = std::fs::read_dir(location) {
+            let mut environments: Vec<PathBuf> = entries.flatten().map(|entry| entry.path()).collect();
+            environments.sort();
+            paths.extend(environments.iter().filter_map(|dir| environment_python(dir)));
+        }
+    }
+    paths.iter()
+        .filter_map(|path| validate(&path.to_string_lossy(), PythonSource::Search).ok())
+        .collect()
+}
+
+/// `preferred` if it satisfies `constraint`, else the newest interpreter under `locations` that does
+pub fn resolve_matching(
+    preferred: Option<PythonInterpreter>,
+    constraint: &VersionConstraint,
+    locations: &[PathBuf],
+) -> Result<PythonInterpreter> {
+    if let Some(preferred) = preferred.as_ref().filter(|python| constraint.accepts(python)) {
+        return Ok(preferred.clone());
+    }
+    let found = find_interpreters(locations);
+    let chosen = found.iter()
+        .filter(|python| constraint.accepts(python))
+        .max_by_key(|python| parse_version(&python.version));
+    match chosen {
+        Some(chosen) => {
+            println!("Using Python '{}' ({}) to satisfy '{}'", chosen.path, chosen.version, constraint);
+            Ok(chosen.clone())
+        }
+        None => {
+            let mut seen: Vec<String> = Vec::new();
+            for python in preferred.iter().chain(&found) {
+                let entry = format!("{} ({})", python.version, python.path);
+                if !seen.contains(&entry) {
+                    seen.push(entry);
+                }
+            }
+            let seen = if seen.is_empty() { "none".to_string() } else { seen.join(", ") };
+            anyhow::bail!("No Python interpreter satisfies '{}'; found: {}", constraint, seen)
+        }
+    }
+}
+
+/// Check interpreter options meant to go between the interpreter and `-m sidecar`. Useful ones:
+///
+/// - `-O`/`-OO`: strip asserts (and docstrings)
+/// - `-B`: don't write `.pyc` files, e.g. for a read-only install
+/// - `-s`, `-P`: ignore the user site directory, don't put the working directory on `sys.path`
+/// - `-I`/`-E`: isolated mode / ignore `PYTHON*` variables. These also ignore the `PYTHONPATH`
+///   the manager sets, so the `sidecar` package must be installed in the interpreter's environment.
+/// - `-X importtime`, `-X dev`, `-X frozen_modules=off`, `-W error`
+pub fn validate_flags(flags: &[String]) -> std::result::Result<(), String> {
+    let mut flags = flags.iter();
+    while let Some(flag) = flags.next() {
+        let letters = flag.strip_prefix('-').filter(|letters| !letters.is_empty() && !letters.starts_with('-'))
+            .ok_or_else(|| format!("'{}' is not a single-letter interpreter option", flag))?;
+        for (i, letter) in letters.char_indices() {
+            if VALUE_FLAGS.contains(letter) {
+                // The rest of the element, or else the next one, is the value
+                if i + 1 == letters.len() && flags.next().is_none() {
+                    return Err(format!("'-{}' needs a value", letter));
+                }
+                break;
+            }
+            if !ALLOWED_FLAGS.contains(letter) {
+                return Err(match letter {
+                    'u' => "'-u' is always passed by the manager".to_string(),
+                    'm' | 'c' => format!("'-{}' would replace the sidecar module", letter),
+                    _ => format!("Unsupported interpreter option '-{}' in '{}'", letter, flag),
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Whether `flags` (already validated) make the interpreter ignore `PYTHONPATH`
+pub fn ignores_pythonpath(flags: &[String]) -> bool {
+    let mut flags = flags.iter();
+    while let Some(flag) = flags.next() {
+        for letter in flag.trim_start_matches('-').chars() {
+            if letter == 'I' || letter == 'E' {
+                return true;
+            }
+            if VALUE_FLAGS.contains(letter) {
+                if flag.ends_with(letter) {
+                    flags.next();
+                }
+                break;
+            }
+        }
+    }
+    false
+}
+
+/// Search `PATH` for a working interpreter
+fn find_on_path() -> Option<PythonInterpreter> {
+    #[cfg(target_os = "windows")]
+    let python_candidates = ["python.exe", "python3.exe"];
+
+    #[cfg(not(target_os = "windows"))]
+    let python_candidates = ["python3", "python"];
+
+    python_candidates.iter()
+        .find_map(|candidate| validate(candidate, PythonSource::Path).ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[ignore = "spawns Python; run with --include-ignored"]
+    fn test_resolution_precedence() {
+        let on_path = resolve(None, None, None, None).unwrap();
+        assert_eq!(on_path.source, PythonSource::Path);
+
+        let from_env = resolve(None, Some(on_path.path.clone()), None, None).unwrap();
+        assert_eq!(from_env.source, PythonSource::Environment);
+
+        // A broken env override falls through instead of failing the spawn
+        let fallback = resolve(None, Some("/nonexistent/python".into()), None, Some(&on_path.path)).unwrap();
+        assert_eq!(fallback.source, PythonSource::Bundled);
+
+        let explicit = validate(&on_path.path, PythonSource::Explicit).unwrap();
+        let chosen = resolve(Some(explicit), Some(on_path.path.clone()), None, None).unwrap();
+        assert_eq!(chosen.source, PythonSource::Explicit);
+    }
+
+    #[test]
+    fn test_validate_flags() {
+        let flags = |flags: &[&str]| flags.iter().map(|f| f.to_string()).collect::<Vec<_>>();
+        assert!(validate_flags(&flags(&["-OO", "-B", "-X", "importtime", "-Xdev", "-W", "error", "-sP"])).is_ok());
+        // A value that looks like a flag still belongs to -X
+        assert!(validate_flags(&flags(&["-X", "-u"])).is_ok());
+        assert!(!ignores_pythonpath(&flags(&["-O", "-X", "I"])));
+        assert!(ignores_pythonpath(&flags(&["-BI"])));
+
+        for bad in [&["-u"][..], &["-Bu"], &["-m", "other"], &["-c", "print()"], &["-X"], &["--version"], &["-"], &["O"], &["-i"]] {
+            assert!(validate_flags(&flags(bad)).is_err(), "{:?} accepted", bad);
+        }
+    }
+
+    #[test]
+    fn test_version_constraints() {
+        assert_eq!(parse_version("Python 3.12.1"), Some(vec![3, 12, 1]));
+        assert_eq!(parse_version("Python 3.13.0rc1\n"), Some(vec![3, 13, 0]));
+        assert_eq!(parse_version("not python"), None);
+
+        let matches = |constraint: &str, version: &[u32]| VersionConstraint::parse(constraint).unwrap().matches(version);
+        assert!(matches("3.12", &[3, 12, 4]));
+        assert!(!matches("==3.12", &[3, 13, 0]));
+        assert!(matches(">=3.10, <3.13", &[3, 12, 0]));
+        assert!(!matches(">=3.10, <3.13", &[3, 13, 0]));
+        assert!(matches("<=3.12", &[3, 12, 9]));
+        assert!(!matches(">3.10", &[3, 10, 7]));
+        assert!(matches("!=3.11", &[3, 10, 2]));
+        for bad in ["", "3.x", "~=3.10", ">=3.10,"] {
+            assert!(VersionConstraint::parse(bad).is_err(), "{:?} accepted", bad);
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_constraint_searches_environments_for_the_newest_match() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let root = std::env::temp_dir().join(format!("tailor-pythons-{}", uuid::Uuid::new_v4()));
+        let fake = |env: &str, version: &str| {
+            let bin = root.join(env).join("bin");
+            std::fs::create_dir_all(&bin).unwrap();
+            let path = bin.join("python3");
+            std::fs::write(&path, format!("#!/bin/sh\necho 'Python {}'\n", version)).unwrap();
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+            validate(&path.to_string_lossy(), PythonSource::Path).unwrap()
+        };
+        fake("3.9", "3.9.18");
+        fake("3.11", "3.11.4");
+        let preferred = fake("preferred", "3.13.0");
+        let locations = [root.join("missing"), root.clone()];
+
+        let constraint = VersionConstraint::parse("3.13").unwrap();
+        let chosen = resolve_matching(Some(preferred.clone()), &constraint, &locations).unwrap();
+        assert_eq!((chosen.path, chosen.source), (preferred.path.clone(), PythonSource::Path));
+
+        let constraint = VersionConstraint::parse(">=3.9, <3.13").unwrap();
+        let chosen = resolve_matching(Some(preferred.clone()), &constraint, &locations).unwrap();
+        assert_eq!((chosen.version.as_str(), chosen.source), ("Python 3.11.4", PythonSource::Search));
+
+        let constraint = VersionConstraint::parse("3.10").unwrap();
+        let err = resolve_matching(Some(preferred), &constraint, &locations).unwrap_err().to_string();
+        assert!(err.contains("'3.10'") && err.contains("Python 3.9.18"), "unexpected error: {}", err);
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    #[ignore = "spawns Python; run with --include-ignored"]
+    fn test_pixi_environment_beats_bundled() {
+        let on_path = resolve(None, None, None, None).unwrap();
+        let project = std::env::temp_dir().join(format!("tailor-pixi-{}", uuid::Uuid::new_v4()));
+        let bin = project.join(".pixi/envs/default/bin");
+        std::fs::create_dir_all(&bin).unwrap();
+        let real = Command::new(&on_path.path)
+            .args(["-c", "import sys; print(sys.executable)"])
+            .output()
+            .unwrap();
+        std::os::unix::fs::symlink(String::from_utf8_lossy(&real.stdout).trim(), bin.join("python")).unwrap();
+
+        // Without a manifest the directory is not a pixi project
+        let pixi = PixiLocator::new("pixi");
+        assert_eq!(pixi.find(&project), None);
+
+        std::fs::write(project.join("pixi.toml"), "").unwrap();
+        let chosen = resolve(None, None, Some((&pixi, &project)), Some(&on_path.path)).unwrap();
+        assert_eq!(chosen.source, PythonSource::Pixi);
+        assert!(chosen.path.ends_with(".pixi/envs/default/bin/python"));
+
+        std::fs::remove_dir_all(&project).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_pixi_locator_runs_the_configured_pixi_once() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let project = std::env::temp_dir().join(format!("tailor-pixi-run-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&project).unwrap();
+        std::fs::write(project.join("pixi.toml"), "").unwrap();
+        let calls = project.join("calls");
+        let program = project.join("fake-pixi");
+        std::fs::write(&program, format!("#!/bin/sh\necho \"$1\" >> '{}'\necho /opt/env/bin/python\n", calls.display()))
+            .unwrap();
+        std::fs::set_permissions(&program, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let pixi = PixiLocator::new(&program.to_string_lossy());
+        assert_eq!(pixi.find(&project).as_deref(), Some("/opt/env/bin/python"));
+        assert_eq!(pixi.find(&project).as_deref(), Some("/opt/env/bin/python"));
+        assert_eq!(std::fs::read_to_string(&calls).unwrap(), "run\n");
+
+        std::fs::remove_dir_all(&project).unwrap();
+    }
+}
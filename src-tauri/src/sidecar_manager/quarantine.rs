@@ -0,0 +1,172 @@
+//! Quarantine of vaults whose sidecar keeps failing to start or keeps crashing.
+//!
+//! Every launch that never becomes ready (the process exits during startup, the spawn deadline
+//! passes) counts as a crash of its vault, and so does a sidecar exiting on its own after it was
+//! ready. `max_crashes` of them within `window` trip flap
+//! detection: the vault is quarantined and `spawn_sidecar` refuses it with
+//! [`SidecarError::Quarantined`] until [`Quarantine::clear`]. Quarantined vaults are written
+//! to `file`, so reopening the app doesn't restart the crash loop.
+
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::error::SidecarError;
+
+/// When a vault is quarantined, and where it is remembered
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct QuarantineConfig {
+    /// Failed launches and crashes within `window` that quarantine a vault. Zero disables quarantine.
+    pub max_crashes: usize,
+    #[serde(with = "super::config_file::duration_ms", rename = "window_ms")]
+    pub window: Duration,
+    /// JSON file holding the quarantined vaults. Without one, quarantine lasts until the app exits.
+    pub file: Option<PathBuf>,
+}
+
+impl Default for QuarantineConfig {
+    fn default() -> Self {
+        Self {
+            max_crashes: 3,
+            window: Duration::from_secs(10 * 60),
+            file: None,
+        }
+    }
+}
+
+/// A vault refused until its quarantine is cleared
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QuarantineEntry {
+    pub vault_path: String,
+    /// Why the last launch failed
+    pub reason: String,
+    pub quarantined_at: DateTime<Utc>,
+    pub crashes: usize,
+}
+
+pub struct Quarantine {
+    config: QuarantineConfig,
+    entries: std::sync::Mutex<HashMap<String, QuarantineEntry>>,
+    crashes: std::sync::Mutex<HashMap<String, VecDeque<Instant>>>,
+}
+
+impl Quarantine {
+    /// Load the vaults quarantined in `config.file`, if any
+    pub fn new(config: QuarantineConfig) -> Self {
+        let entries = config.file.as_deref().map(load).unwrap_or_default();
+        Self { config, entries: std::sync::Mutex::new(entries), crashes: Default::default() }
+    }
+
+    /// `Err(SidecarError::Quarantined)` if `vault_path` may not be spawned
+    pub fn check(&self, vault_path: &str) -> Result<(), SidecarError> {
+        match self.entries.lock().unwrap_or_else(|e| e.into_inner()).get(vault_path) {
+            Some(entry) => Err(SidecarError::Quarantined(entry.clone())),
+            None => Ok(()),
+        }
+    }
+
+    /// Count a failed launch or crash of `vault_path`; returns the entry if this one tripped quarantine
+    pub fn record_crash(&self, vault_path: &str, reason: &str) -> Option<QuarantineEntry> {
+        if self.config.max_crashes == 0 {
+            return None;
+        }
+        let count = {
+            let mut crashes = self.crashes.lock().unwrap_or_else(|e| e.into_inner());
+            let recent = crashes.entry(vault_path.to_string()).or_default();
+            let now = Instant::now();
+            recent.retain(|at| now.duration_since(*at) < self.config.window);
+            recent.push_back(now);
+            if recent.len() < self.config.max_crashes {
+                return None;
+            }
+            crashes.remove(vault_path).map(|recent| recent.len()).unwrap_or_default()
+        };
+        let entry = QuarantineEntry {
+            vault_path: vault_path.to_string(),
+            reason: reason.to_string(),
+            quarantined_at: Utc::now(),
+            crashes: count,
+        };
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.insert(vault_path.to_string(), entry.clone());
+        self.save(&entries);
+        Some(entry)
+    }
+
+    /// Lift the quarantine on `vault_path`; returns whether it was quarantined
+    pub fn clear(&self, vault_path: &str) -> bool {
+        self.crashes.lock().unwrap_or_else(|e| e.into_inner()).remove(vault_path);
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let cleared = entries.remove(vault_path).is_some();
+        if cleared {
+            self.save(&entries);
+        }
+        cleared
+    }
+
+    /// Quarantined vaults, oldest first
+    pub fn list(&self) -> Vec<QuarantineEntry> {
+        let mut entries: Vec<_> = self.entries.lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .values()
+            .cloned()
+            .collect();
+        entries.sort_by_key(|entry| entry.quarantined_at);
+        entries
+    }
+
+    fn save(&self, entries: &HashMap<String, QuarantineEntry>) {
+        let Some(path) = &self.config.file else { return };
+        let mut list: Vec<_> = entries.values().collect();
+        list.sort_by_key(|entry| entry.quarantined_at);
+        let result = serde_json::to_vec_pretty(&list)
+            .map_err(std::io::Error::from)
+            .and_then(|json| std::fs::write(path, json));
+        if let Err(e) = result {
+            eprintln!("Failed to save sidecar quarantine to {}: {}", path.display(), e);
+        }
+    }
+}
+
+fn load(path: &Path) -> HashMap<String, QuarantineEntry> {
+    let list: Vec<QuarantineEntry> = match std::fs::read(path) {
+        Ok(json) => serde_json::from_slice(&json).unwrap_or_else(|e| {
+            eprintln!("Ignoring unreadable sidecar quarantine file {}: {}", path.display(), e);
+            Vec::new()
+        }),
+        Err(_) => Vec::new(),
+    };
+    list.into_iter().map(|entry| (entry.vault_path.clone(), entry)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_repeated_crashes_quarantine_until_cleared() {
+        let file = std::env::temp_dir().join(format!("tailor-quarantine-{}.json", uuid::Uuid::new_v4()));
+        let config = QuarantineConfig { max_crashes: 2, file: Some(file.clone()), ..Default::default() };
+        let quarantine = Quarantine::new(config.clone());
+
+        assert!(quarantine.record_crash("/vault", "exited").is_none());
+        assert!(quarantine.check("/vault").is_ok());
+        let entry = quarantine.record_crash("/vault", "exited again").unwrap();
+        assert_eq!((entry.reason.as_str(), entry.crashes), ("exited again", 2));
+        let err = quarantine.check("/vault").unwrap_err();
+        assert!(err.to_string().contains("exited again"), "unexpected error: {}", err);
+        assert!(quarantine.check("/other").is_ok());
+
+        // The marker survives a new manager
+        let reloaded = Quarantine::new(config.clone());
+        assert_eq!(reloaded.list(), vec![entry]);
+        assert!(reloaded.clear("/vault"));
+        assert!(!reloaded.clear("/vault"));
+        assert!(Quarantine::new(config).list().is_empty());
+        let _ = std::fs::remove_file(&file);
+    }
+}
@@ -0,0 +1,170 @@
+//! Optional cap on the rate of commands sent to each sidecar, for sidecars that are fragile or
+//! proxy a rate-limited external service.
+//!
+//! Each sidecar gets its own token bucket holding up to `burst` tokens, refilled at `per_second`.
+//! A command takes a token before it is sent. When the bucket is empty, queued commands reserve
+//! the next tokens in arrival order and wait for them. `try_send_command` fails with
+//! `SidecarError::RateLimited` instead. Control-socket calls are not limited.
+
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+/// Rate and burst of one sidecar's token bucket
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct RateLimitConfig {
+    /// Commands per second in the long run
+    pub per_second: f64,
+    /// Commands that may go out at once after a quiet period
+    pub burst: u32,
+}
+
+impl RateLimitConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.per_second.is_finite() || self.per_second <= 0.0 {
+            return Err(format!("rate must be a positive number of commands per second, got {}", self.per_second));
+        }
+        if self.burst == 0 {
+            return Err("burst must be at least 1".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Point-in-time view of a sidecar's limiter
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RateLimitStats {
+    pub per_second: f64,
+    pub burst: u32,
+    /// Tokens in the bucket now; zero while commands wait
+    pub available: f64,
+    /// Commands waiting for a token
+    pub waiting: usize,
+    /// Commands that had to wait, since launch
+    pub throttled: u64,
+    /// `try_send_command` calls refused for want of a token, since launch
+    pub rejected: u64,
+}
+
+struct Bucket {
+    /// Negative once waiting commands have reserved tokens not yet refilled
+    tokens: f64,
+    refilled: Instant,
+}
+
+pub struct RateLimiter {
+    config: RateLimitConfig,
+    bucket: std::sync::Mutex<Bucket>,
+    waiting: AtomicUsize,
+    throttled: AtomicU64,
+    rejected: AtomicU64,
+}
+
+impl RateLimiter {
+    /// A limiter starting with a full bucket
+    pub fn new(config: RateLimitConfig) -> Self {
+        Self {
+            config,
+            bucket: std::sync::Mutex::new(Bucket { tokens: config.burst as f64, refilled: Instant::now() }),
+            waiting: AtomicUsize::new(0),
+            throttled: AtomicU64::new(0),
+            rejected: AtomicU64::new(0),
+        }
+    }
+
+    /// The bucket, topped up for the time since it was last looked at
+    fn refill(&self) -> std::sync::MutexGuard<'_, Bucket> {
+        let mut bucket = self.bucket.lock().unwrap_or_else(|e| e.into_inner());
+        let now = Instant::now();
+        let refilled = now.duration_since(bucket.refilled).as_secs_f64() * self.config.per_second;
+        bucket.tokens = (bucket.tokens + refilled).min(self.config.burst as f64);
+        bucket.refilled = now;
+        bucket
+    }
+
+    /// Take a token, waiting for one if the bucket is empty
+    pub async fn acquire(&self) {
+        let wait = {
+            let mut bucket = self.refill();
+            bucket.tokens -= 1.0;
+            match bucket.tokens < 0.0 {
+                true => Duration::from_secs_f64(-bucket.tokens / self.config.per_second),
+                false => return,
+            }
+        };
+        self.throttled.fetch_add(1, Ordering::Relaxed);
+        let mut waiter = Waiter::new(self);
+        tokio::time::sleep(wait).await;
+        waiter.served = true;
+    }
+
+    /// Take a token if one is free now; otherwise how long until one would be
+    pub fn try_acquire(&self) -> Result<(), Duration> {
+        let mut bucket = self.refill();
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            return Ok(());
+        }
+        self.rejected.fetch_add(1, Ordering::Relaxed);
+        Err(Duration::from_secs_f64((1.0 - bucket.tokens) / self.config.per_second))
+    }
+
+    pub fn stats(&self) -> RateLimitStats {
+        RateLimitStats {
+            per_second: self.config.per_second,
+            burst: self.config.burst,
+            available: self.refill().tokens.max(0.0),
+            waiting: self.waiting.load(Ordering::Relaxed),
+            throttled: self.throttled.load(Ordering::Relaxed),
+            rejected: self.rejected.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Counts a command as waiting; one dropped before its token came hands the reservation back
+struct Waiter<'a> {
+    limiter: &'a RateLimiter,
+    served: bool,
+}
+
+impl<'a> Waiter<'a> {
+    fn new(limiter: &'a RateLimiter) -> Self {
+        limiter.waiting.fetch_add(1, Ordering::Relaxed);
+        Self { limiter, served: false }
+    }
+}
+
+impl Drop for Waiter<'_> {
+    fn drop(&mut self) {
+        self.limiter.waiting.fetch_sub(1, Ordering::Relaxed);
+        if !self.served {
+            self.limiter.refill().tokens += 1.0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_bucket_allows_a_burst_then_the_rate() {
+        let limiter = RateLimiter::new(RateLimitConfig { per_second: 10.0, burst: 3 });
+        let started = Instant::now();
+        for _ in 0..3 {
+            limiter.acquire().await;
+        }
+        assert!(started.elapsed() < Duration::from_millis(50));
+        assert!(limiter.try_acquire().is_err());
+
+        limiter.acquire().await;
+        limiter.acquire().await;
+        assert!(started.elapsed() >= Duration::from_millis(190));
+        let stats = limiter.stats();
+        assert_eq!((stats.waiting, stats.throttled, stats.rejected), (0, 2, 1));
+
+        assert!(RateLimitConfig { per_second: 0.0, burst: 1 }.validate().is_err());
+        assert!(RateLimitConfig { per_second: 1.0, burst: 0 }.validate().is_err());
+    }
+}
@@ -0,0 +1,115 @@
+//! Adopting sidecars a previous manager left running, for the development hot-reload loop.
+//!
+//! `cargo tauri dev` kills and restarts the Rust process on every rebuild, losing its
+//! `SidecarManager` while the Python sidecars live on. With `SidecarManagerConfig::reattach_file`
+//! set, the manager keeps a [`ReattachRecord`] per running WebSocket sidecar in that file,
+//! rewritten whenever one is spawned, moves port or is terminated (and emptied by `shutdown`).
+//! A fresh manager's `reattach_all` reads it back and adopts every sidecar whose PID still
+//! carries the sidecar signature for its port and that answers a ping, instead of respawning it.
+//!
+//! An adopted sidecar is tracked like any other, with two gaps: its stdout/stderr went to the old
+//! process, so none of its output is captured, and it keeps running under the old options apart
+//! from those recorded here, so a restart launches it with the vault's config file and defaults
+//! for the rest. Stdio sidecars die with their pipes and are never recorded.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::python::PythonInterpreter;
+
+/// What a later manager needs to adopt one running sidecar
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReattachRecord {
+    pub window_label: String,
+    pub vault_path: String,
+    pub pid: u32,
+    pub host: String,
+    pub ws_port: u16,
+    pub control_port: Option<u16>,
+    pub python: PythonInterpreter,
+    pub started_at: DateTime<Utc>,
+    /// Removed once the adopted sidecar is terminated
+    pub ready_file: PathBuf,
+    /// Kept so an adopted sidecar behind a launcher is still signalled as a process group
+    pub launcher: Option<Vec<String>>,
+    #[serde(default)]
+    pub labels: HashMap<String, String>,
+    #[serde(default)]
+    pub shutdown_order: i32,
+    #[serde(default)]
+    pub depends_on: Vec<String>,
+    #[serde(default)]
+    pub role: Option<String>,
+}
+
+/// Overwrite `path` with `records`; a failure is logged, as it only costs the next reattach
+pub fn save(path: &Path, records: &[ReattachRecord]) {
+    let result = serde_json::to_vec_pretty(records)
+        .map_err(std::io::Error::from)
+        .and_then(|json| std::fs::write(path, json));
+    if let Err(e) = result {
+        eprintln!("Failed to save sidecar reattach state to {}: {}", path.display(), e);
+    }
+}
+
+/// The records in `path`; none if it is missing or unreadable
+pub fn load(path: &Path) -> Vec<ReattachRecord> {
+    match std::fs::read(path) {
+        Ok(json) => serde_json::from_slice(&json).unwrap_or_else(|e| {
+            eprintln!("Ignoring unreadable sidecar reattach state {}: {}", path.display(), e);
+            Vec::new()
+        }),
+        Err(_) => Vec::new(),
+    }
+}
+
+/// Whether the recorded PID is still our sidecar (launched from `template`) on its port, and
+/// not a reused PID
+#[cfg(target_os = "linux")]
+pub fn still_running(record: &ReattachRecord, template: &super::args::ArgTemplate) -> bool {
+    !super::process::pid_has_exited(record.pid)
+        && super::process::read_cmdline(record.pid)
+            .is_some_and(|args| super::process::is_sidecar_cmdline(&args, template, record.ws_port))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sidecar_manager::python::PythonSource;
+
+    #[test]
+    fn test_records_round_trip() {
+        let path = std::env::temp_dir().join(format!("tailor-reattach-{}.json", uuid::Uuid::new_v4()));
+        assert!(load(&path).is_empty());
+
+        let record = ReattachRecord {
+            window_label: "w".to_string(),
+            vault_path: "/vault".to_string(),
+            pid: 4242,
+            host: "127.0.0.1".to_string(),
+            ws_port: 9001,
+            control_port: Some(9002),
+            python: PythonInterpreter {
+                path: "python3".to_string(),
+                version: "3.12.1".to_string(),
+                source: PythonSource::Path,
+            },
+            started_at: Utc::now(),
+            ready_file: PathBuf::from("/tmp/ready"),
+            launcher: None,
+            labels: HashMap::from([("workspace".to_string(), "notes".to_string())]),
+            shutdown_order: 1,
+            depends_on: vec!["embeddings".to_string()],
+            role: Some("indexer".to_string()),
+        };
+        save(&path, std::slice::from_ref(&record));
+        assert_eq!(load(&path), [record]);
+
+        std::fs::write(&path, "not json").unwrap();
+        assert!(load(&path).is_empty());
+        let _ = std::fs::remove_file(path);
+    }
+}
@@ -0,0 +1,60 @@
+//! In-process reloads: the sidecar re-reads its config and plugins without restarting Python.
+
+use serde::{Deserialize, Serialize};
+
+use super::error::SidecarError;
+
+/// RPC asking the sidecar to reload itself
+pub const RELOAD_METHOD: &str = "reload";
+
+/// What a reload changed
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReloadReport {
+    pub plugins_added: Vec<String>,
+    pub plugins_removed: Vec<String>,
+    /// Plugins or config that failed to load; the reload still went ahead for the rest
+    pub errors: Vec<String>,
+    /// The sidecar couldn't reload in-process and was restarted instead, so nothing above is known
+    #[serde(skip_deserializing)]
+    pub restarted: bool,
+}
+
+impl ReloadReport {
+    /// Read the sidecar's response to [`RELOAD_METHOD`]; missing lists are taken as empty
+    pub fn from_response(response: serde_json::Value) -> Result<Self, SidecarError> {
+        let result = response.get("result").cloned().unwrap_or_default();
+        if result.is_null() {
+            return Ok(Self::default());
+        }
+        serde_json::from_value(result).map_err(|e| SidecarError::Protocol(format!("Invalid reload report: {}", e)))
+    }
+}
+
+/// Whether `error` means the sidecar doesn't implement [`RELOAD_METHOD`]
+pub fn is_unsupported(error: &SidecarError) -> bool {
+    error.is_method_not_found()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report_from_response() {
+        let report = ReloadReport::from_response(serde_json::json!({"jsonrpc": "2.0", "id": "1", "result": {
+            "plugins_added": ["rag"],
+            "errors": ["memory: ImportError"],
+            "restarted": true,
+        }})).unwrap();
+        assert_eq!(report.plugins_added, ["rag"]);
+        assert!(report.plugins_removed.is_empty());
+        assert_eq!(report.errors, ["memory: ImportError"]);
+        assert!(!report.restarted, "only the manager decides this");
+
+        assert_eq!(ReloadReport::from_response(serde_json::json!({"result": null})).unwrap(), ReloadReport::default());
+        assert!(ReloadReport::from_response(serde_json::json!({"result": {"plugins_added": "rag"}})).is_err());
+        assert!(is_unsupported(&SidecarError::Rpc { code: -32601, message: "Method not found".into() }));
+        assert!(!is_unsupported(&SidecarError::Rpc { code: -32000, message: "boom".into() }));
+    }
+}
@@ -0,0 +1,238 @@
+//! Parking of idempotent commands while a sidecar is down or restarting.
+//!
+//! A command whose method is on the allowlist and that fails with a transient error is queued
+//! instead of failing; its caller keeps waiting on a oneshot. When the window's sidecar next
+//! becomes ready the queue is drained in order and each result is delivered to its caller; a
+//! launch that fails before becoming ready fails them with its error instead.
+//! The queue is bounded and entries expire, so callers never wait longer than `ttl`.
+//!
+//! Every launch gets a generation number, and the queue records which generation of each
+//! window is ready under the same lock it parks commands with. A command that failed against
+//! an older generation than the one now ready is retried at once rather than parked, so a
+//! sidecar becoming ready between the failure and the park can't strand it.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::oneshot;
+
+use super::SidecarError;
+
+/// Which commands may be replayed, and for how long
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReplayConfig {
+    /// Methods safe to run again after a failed attempt. Empty disables replay.
+    pub methods: HashSet<String>,
+    /// Most commands parked at once across all windows
+    pub capacity: usize,
+    /// How long a parked command waits for its sidecar before failing with the original error
+    #[serde(with = "super::config_file::duration_ms", rename = "ttl_ms")]
+    pub ttl: Duration,
+}
+
+impl Default for ReplayConfig {
+    fn default() -> Self {
+        Self {
+            methods: HashSet::new(),
+            capacity: 64,
+            ttl: Duration::from_secs(60),
+        }
+    }
+}
+
+/// A parked command and the caller waiting on it
+pub struct PendingCommand {
+    pub window_label: String,
+    pub method: String,
+    pub params: serde_json::Value,
+    pub blob: Option<Vec<u8>>,
+    pub reply: oneshot::Sender<Result<serde_json::Value, SidecarError>>,
+    deadline: Instant,
+}
+
+/// Outcome of [`ReplayQueue::park`]
+pub enum Parked {
+    /// Queued; the result arrives on the receiver
+    Waiting(oneshot::Receiver<Result<serde_json::Value, SidecarError>>),
+    /// A newer sidecar is already ready; send again instead of waiting
+    RetryNow,
+    /// The queue is at capacity
+    Full,
+}
+
+#[derive(Default)]
+struct State {
+    entries: VecDeque<PendingCommand>,
+    /// Ready generation per window
+    ready: HashMap<String, u64>,
+}
+
+pub struct ReplayQueue {
+    config: ReplayConfig,
+    state: std::sync::Mutex<State>,
+}
+
+impl ReplayQueue {
+    pub fn new(config: ReplayConfig) -> Self {
+        Self {
+            config,
+            state: std::sync::Mutex::new(State::default()),
+        }
+    }
+
+    pub fn accepts(&self, method: &str) -> bool {
+        self.config.methods.contains(method)
+    }
+
+    pub fn ttl(&self) -> Duration {
+        self.config.ttl
+    }
+
+    /// Park a command that failed against `attempted` (`None` when no sidecar was tracked).
+    ///
+    /// A failure against the generation that is currently ready means that sidecar went
+    /// down, so the window stops counting as ready until its next launch reports in.
+    pub fn park(
+        &self,
+        window_label: &str,
+        attempted: Option<u64>,
+        method: &str,
+        params: serde_json::Value,
+        blob: Option<Vec<u8>>,
+    ) -> Parked {
+        let mut state = self.lock();
+        Self::expire(&mut state.entries);
+        if let Some(&ready) = state.ready.get(window_label) {
+            if attempted.map_or(true, |attempted| ready > attempted) {
+                return Parked::RetryNow;
+            }
+            state.ready.remove(window_label);
+        }
+        if state.entries.len() >= self.config.capacity {
+            return Parked::Full;
+        }
+
+        let (reply, response) = oneshot::channel();
+        state.entries.push_back(PendingCommand {
+            window_label: window_label.to_string(),
+            method: method.to_string(),
+            params,
+            blob,
+            reply,
+            deadline: Instant::now() + self.config.ttl,
+        });
+        Parked::Waiting(response)
+    }
+
+    /// Record that `generation` of the window's sidecar is ready and hand back the live
+    /// commands parked for it, oldest first
+    pub fn mark_ready(&self, window_label: &str, generation: u64) -> Vec<PendingCommand> {
+        let mut state = self.lock();
+        state.ready.insert(window_label.to_string(), generation);
+        Self::take(&mut state, window_label)
+    }
+
+    /// Hand back the live commands parked for the window without marking it ready, for a
+    /// launch that never got there
+    pub fn take_parked(&self, window_label: &str) -> Vec<PendingCommand> {
+        Self::take(&mut self.lock(), window_label)
+    }
+
+    fn take(state: &mut State, window_label: &str) -> Vec<PendingCommand> {
+        Self::expire(&mut state.entries);
+        let (taken, kept) = state.entries.drain(..).partition(|e| e.window_label == window_label);
+        state.entries = kept;
+        taken.into()
+    }
+
+    /// The window's sidecar was stopped
+    pub fn mark_down(&self, window_label: &str) {
+        self.lock().ready.remove(window_label);
+    }
+
+    /// Drop every parked command; their callers fail with the original error
+    pub fn clear(&self) {
+        let mut state = self.lock();
+        state.entries.clear();
+        state.ready.clear();
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Drop expired entries and entries whose caller has stopped waiting
+    fn expire(entries: &mut VecDeque<PendingCommand>) {
+        let now = Instant::now();
+        entries.retain(|e| e.deadline > now && !e.reply.is_closed());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn queue(capacity: usize, ttl: Duration) -> ReplayQueue {
+        ReplayQueue::new(ReplayConfig {
+            methods: HashSet::from(["index.add".to_string()]),
+            capacity,
+            ttl,
+        })
+    }
+
+    fn waiting(parked: Parked) -> oneshot::Receiver<Result<serde_json::Value, SidecarError>> {
+        match parked {
+            Parked::Waiting(response) => response,
+            Parked::RetryNow => panic!("expected Waiting, got RetryNow"),
+            Parked::Full => panic!("expected Waiting, got Full"),
+        }
+    }
+
+    #[test]
+    fn test_queue_is_bounded_and_per_window() {
+        let replay = queue(2, Duration::from_secs(60));
+        assert!(replay.accepts("index.add"));
+        assert!(!replay.accepts("chat.send"));
+
+        let _a = waiting(replay.park("a", Some(1), "index.add", serde_json::json!({"n": 1}), None));
+        let _b = waiting(replay.park("b", Some(1), "index.add", serde_json::json!({"n": 2}), None));
+        assert!(matches!(replay.park("a", Some(1), "index.add", serde_json::json!({"n": 3}), None), Parked::Full));
+
+        let taken = replay.mark_ready("a", 2);
+        assert_eq!(taken.len(), 1);
+        assert_eq!(taken[0].params["n"], 1);
+        assert_eq!(replay.mark_ready("b", 2).len(), 1);
+    }
+
+    #[test]
+    fn test_generations_decide_between_retry_and_park() {
+        let replay = queue(8, Duration::from_secs(60));
+        replay.mark_ready("a", 2);
+
+        // Failed against a sidecar older than the one now ready
+        assert!(matches!(replay.park("a", Some(1), "index.add", serde_json::json!({}), None), Parked::RetryNow));
+
+        // The ready sidecar itself failed: it is down, so park until the next launch
+        let _waiting = waiting(replay.park("a", Some(2), "index.add", serde_json::json!({}), None));
+        assert_eq!(replay.mark_ready("a", 3).len(), 1);
+
+        // A launch that never became ready hands its commands back without counting as ready
+        let _waiting = waiting(replay.park("a", Some(3), "index.add", serde_json::json!({}), None));
+        assert_eq!(replay.take_parked("a").len(), 1);
+        assert!(matches!(replay.park("a", Some(3), "index.add", serde_json::json!({}), None), Parked::Waiting(_)));
+    }
+
+    #[test]
+    fn test_expired_and_abandoned_entries_are_dropped() {
+        let replay = queue(8, Duration::from_millis(20));
+        let _waiting = waiting(replay.park("a", Some(1), "index.add", serde_json::json!({}), None));
+        std::thread::sleep(Duration::from_millis(40));
+        assert!(replay.mark_ready("a", 2).is_empty());
+
+        let replay = queue(8, Duration::from_secs(60));
+        drop(waiting(replay.park("a", Some(1), "index.add", serde_json::json!({}), None)));
+        assert!(replay.mark_ready("a", 2).is_empty());
+    }
+}
@@ -0,0 +1,99 @@
+//! Roles such as `indexer`, `llm` or `ui-helper`, naming a profile of defaults configured in
+//! `SidecarManagerConfig::roles` (e.g. `[roles.indexer]` with `priority = "low"` and
+//! `idle_timeout_ms`) or registered with `SidecarManager::register_role`, and picked by
+//! `SpawnOptions::role`.
+//!
+//! A profile's priority applies to sidecars whose spawn options leave it `Normal`, so heavy
+//! background roles can be deprioritized without every spawn saying so. Its idle timeout has
+//! `SidecarManager::run_idle_monitor` terminate the sidecar once it has gone that long without a
+//! command (unless a running sidecar depends on it), and its restart cap stops `run_crash_handler` from restarting a sidecar that keeps
+//! crashing. Spawning with an unregistered role is refused.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use super::process::ProcessPriority;
+use super::SpawnOptions;
+
+/// How often `run_idle_monitor` looks for sidecars past their role's idle timeout
+pub const IDLE_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Defaults for the sidecars of one role
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RoleProfile {
+    /// Used when the spawn options leave `priority` at `Normal`
+    pub priority: ProcessPriority,
+    /// Terminate the sidecar once it is ready with nothing in flight and no command for this
+    /// long; None keeps it running
+    #[serde(with = "super::config_file::option_duration_ms", rename = "idle_timeout_ms")]
+    pub idle_timeout: Option<Duration>,
+    /// Restarts of the window (including manual ones) after which a crash is no longer
+    /// restarted automatically; None leaves it to `SidecarManagerConfig::crash_policy`
+    pub max_restarts: Option<usize>,
+}
+
+/// Registered profiles by role name
+#[derive(Default)]
+pub struct Roles(std::sync::RwLock<HashMap<String, RoleProfile>>);
+
+impl Roles {
+    pub fn register(&self, role: &str, profile: RoleProfile) {
+        self.0.write().unwrap_or_else(|e| e.into_inner()).insert(role.to_string(), profile);
+    }
+
+    /// The profile of `role`; None for sidecars without one
+    pub fn get(&self, role: Option<&str>) -> Option<RoleProfile> {
+        self.0.read().unwrap_or_else(|e| e.into_inner()).get(role?).cloned()
+    }
+
+    /// Fail unless `role` is unset or registered
+    pub fn check(&self, role: Option<&str>) -> Result<(), String> {
+        match role {
+            Some(role) if !self.0.read().unwrap_or_else(|e| e.into_inner()).contains_key(role) => {
+                Err(format!("Unknown sidecar role '{}'", role))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Fill in what `options` leaves to its role's profile
+    pub fn apply_to(&self, options: &mut SpawnOptions) -> Result<(), String> {
+        self.check(options.role.as_deref())?;
+        if let Some(profile) = self.get(options.role.as_deref()) {
+            if options.priority == ProcessPriority::Normal {
+                options.priority = profile.priority;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profile_fills_in_unset_priority() {
+        let roles = Roles::default();
+        roles.register("indexer", RoleProfile { priority: ProcessPriority::Low, ..Default::default() });
+
+        let mut options = SpawnOptions { role: Some("indexer".to_string()), ..Default::default() };
+        roles.apply_to(&mut options).unwrap();
+        assert_eq!(options.priority, ProcessPriority::Low);
+
+        let mut explicit = SpawnOptions {
+            role: Some("indexer".to_string()),
+            priority: ProcessPriority::High,
+            ..Default::default()
+        };
+        roles.apply_to(&mut explicit).unwrap();
+        assert_eq!(explicit.priority, ProcessPriority::High);
+
+        let mut unknown = SpawnOptions { role: Some("llm".to_string()), ..Default::default() };
+        assert_eq!(roles.apply_to(&mut unknown).unwrap_err(), "Unknown sidecar role 'llm'");
+        roles.apply_to(&mut SpawnOptions::default()).unwrap();
+    }
+}
@@ -0,0 +1,155 @@
+//! POSIX shared-memory regions for handing large payloads to a sidecar
+//! without serializing them over the WebSocket.
+//!
+//! The Rust side creates a named region, fills it, and passes the small
+//! [`SharedMemoryRegion::descriptor`] in the command params. The sidecar opens
+//! it by name with `sidecar.utils.read_shared_memory()` (a thin wrapper over
+//! `multiprocessing.shared_memory`). The region is unlinked when the Rust
+//! handle is dropped, so keep it alive until the command has returned;
+//! `SidecarManager::send_command_shared` does both.
+//!
+//! POSIX names carry exactly one leading slash. The descriptor's name leaves
+//! it out, matching Python's public `SharedMemory.name`, and both sides add it
+//! back wherever the OS wants the full name.
+
+use std::ffi::CString;
+use anyhow::{Result, bail};
+
+pub struct SharedMemoryRegion {
+    name: String,
+    ptr: *mut u8,
+    len: usize,
+}
+
+// The mapping is owned exclusively by this handle and only freed in Drop
+unsafe impl Send for SharedMemoryRegion {}
+unsafe impl Sync for SharedMemoryRegion {}
+
+impl SharedMemoryRegion {
+    /// Create and map a new zero-filled region of `len` bytes
+    pub fn create(len: usize) -> Result<Self> {
+        if len == 0 {
+            bail!("Shared memory region must not be empty");
+        }
+
+        // macOS caps shm names at 31 bytes, so keep them short
+        let uuid = uuid::Uuid::new_v4().simple().to_string();
+        let name = format!("/tailor-{}", &uuid[..16]);
+        let c_name = CString::new(name.clone())?;
+
+        #[cfg(target_os = "macos")]
+        let mode = (libc::S_IRUSR | libc::S_IWUSR) as libc::c_uint;
+        #[cfg(not(target_os = "macos"))]
+        let mode = libc::S_IRUSR | libc::S_IWUSR;
+
+        // SAFETY: c_name is a valid NUL-terminated string
+        let fd = unsafe {
+            libc::shm_open(c_name.as_ptr(), libc::O_CREAT | libc::O_EXCL | libc::O_RDWR, mode)
+        };
+        if fd < 0 {
+            bail!("shm_open failed: {}", std::io::Error::last_os_error());
+        }
+
+        let cleanup = |err: std::io::Error| {
+            // SAFETY: fd was returned by shm_open above and c_name is still valid
+            unsafe {
+                libc::close(fd);
+                libc::shm_unlink(c_name.as_ptr());
+            }
+            err
+        };
+
+        // SAFETY: fd is a valid shared memory descriptor
+        if unsafe { libc::ftruncate(fd, len as libc::off_t) } != 0 {
+            let err = cleanup(std::io::Error::last_os_error());
+            bail!("Failed to size shared memory region: {}", err);
+        }
+
+        // SAFETY: mapping a freshly sized descriptor; the result is checked below
+        let ptr = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED,
+                fd,
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            let err = cleanup(std::io::Error::last_os_error());
+            bail!("Failed to map shared memory region: {}", err);
+        }
+
+        // The mapping keeps the region alive; the descriptor is no longer needed
+        // SAFETY: fd is valid and not used after this point
+        unsafe { libc::close(fd) };
+
+        Ok(Self {
+            name,
+            ptr: ptr as *mut u8,
+            len,
+        })
+    }
+
+    /// Create a region holding a copy of `data`
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        let mut region = Self::create(data.len())?;
+        region.as_mut_slice().copy_from_slice(data);
+        Ok(region)
+    }
+
+    /// Region name as the sidecar should open it (without the leading slash)
+    pub fn name(&self) -> &str {
+        self.name.trim_start_matches('/')
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [u8] {
+        // SAFETY: ptr is a live mapping of exactly len bytes, borrowed mutably
+        unsafe { std::slice::from_raw_parts_mut(self.ptr, self.len) }
+    }
+
+    /// JSON descriptor to embed in command params in place of the payload
+    pub fn descriptor(&self) -> serde_json::Value {
+        serde_json::json!({
+            "$shm": {
+                "name": self.name(),
+                "size": self.len,
+            }
+        })
+    }
+}
+
+impl Drop for SharedMemoryRegion {
+    fn drop(&mut self) {
+        // SAFETY: ptr/len describe the mapping created in `create`
+        unsafe {
+            libc::munmap(self.ptr as *mut libc::c_void, self.len);
+        }
+        if let Ok(c_name) = CString::new(self.name.clone()) {
+            // The sidecar may already have unlinked it; ignore the result
+            // SAFETY: c_name is a valid NUL-terminated string
+            unsafe { libc::shm_unlink(c_name.as_ptr()) };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_region_roundtrip_and_unlink() {
+        let mut region = SharedMemoryRegion::from_bytes(b"hello sidecar").unwrap();
+        assert_eq!(region.as_mut_slice(), b"hello sidecar");
+        assert!(!region.name().starts_with('/'));
+        assert_eq!(region.descriptor()["$shm"]["size"], 13);
+
+        let c_name = CString::new(format!("/{}", region.name())).unwrap();
+        drop(region);
+
+        // SAFETY: c_name is a valid NUL-terminated string
+        let fd = unsafe { libc::shm_open(c_name.as_ptr(), libc::O_RDONLY, 0) };
+        assert!(fd < 0, "region should be unlinked on drop");
+    }
+}
@@ -0,0 +1,110 @@
+//! Teardown order for sidecars that depend on each other (`SpawnOptions::depends_on`), e.g. an
+//! LLM sidecar talking to a shared embedding sidecar: a dependent must get its clean shutdown
+//! while its provider is still there.
+//!
+//! `SidecarManager::shutdown` stops a sidecar only once every sidecar depending on it has
+//! stopped; among those free to go, `SpawnOptions::shutdown_order` and then spawn order decide.
+//! Stopping sidecars concurrently (`sequential_shutdown = false`) proceeds in waves of everything
+//! free at once. Dependencies on windows that aren't running are ignored, and a spawn that would
+//! close a cycle is refused, so the order always exists.
+
+use std::collections::{HashMap, HashSet};
+
+/// The cycle `window_label` would close by depending on `depends_on`, as the labels along it
+/// from `window_label` back to itself. `providers` maps every other running sidecar to its
+/// `depends_on`.
+pub fn find_cycle(
+    providers: &HashMap<String, Vec<String>>,
+    window_label: &str,
+    depends_on: &[String],
+) -> Option<Vec<String>> {
+    fn visit(
+        providers: &HashMap<String, Vec<String>>,
+        target: &str,
+        path: &mut Vec<String>,
+        visited: &mut HashSet<String>,
+    ) -> bool {
+        let last = path.last().expect("path starts non-empty").clone();
+        if last == target {
+            return true;
+        }
+        if !visited.insert(last.clone()) {
+            return false;
+        }
+        for provider in providers.get(&last).into_iter().flatten() {
+            path.push(provider.clone());
+            if visit(providers, target, path, visited) {
+                return true;
+            }
+            path.pop();
+        }
+        false
+    }
+
+    let mut visited = HashSet::new();
+    depends_on.iter().find_map(|provider| {
+        let mut path = vec![window_label.to_string(), provider.clone()];
+        visit(providers, window_label, &mut path, &mut visited).then_some(path)
+    })
+}
+
+/// Groups of window labels to stop one after the other, from `(label, depends_on, rank)` per
+/// sidecar: each group holds the lowest ranked sidecar nothing left depends on, or with
+/// `concurrent` all of them
+pub fn waves<R: Ord + Copy>(sidecars: &[(String, Vec<String>, R)], concurrent: bool) -> Vec<Vec<String>> {
+    let mut remaining: Vec<_> = sidecars.iter().collect();
+    remaining.sort_by_key(|(_, _, rank)| *rank);
+    let mut waves = Vec::new();
+    while !remaining.is_empty() {
+        let free: Vec<usize> = (0..remaining.len())
+            .filter(|&i| !remaining.iter().any(|(_, depends_on, _)| depends_on.contains(&remaining[i].0)))
+            .collect();
+        // Empty only for a cycle, which spawning refuses; fall back to rank order
+        let wave = match (free.first(), concurrent) {
+            (None, _) => vec![0],
+            (Some(&first), false) => vec![first],
+            (Some(_), true) => free,
+        };
+        waves.push(wave.iter().map(|&i| remaining[i].0.clone()).collect());
+        let mut index = 0;
+        remaining.retain(|_| {
+            index += 1;
+            !wave.contains(&(index - 1))
+        });
+    }
+    waves
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn labels(labels: &[&str]) -> Vec<String> {
+        labels.iter().map(|label| label.to_string()).collect()
+    }
+
+    #[test]
+    fn test_cycles_are_found() {
+        let providers = HashMap::from([
+            ("llm".to_string(), labels(&["embeddings"])),
+            ("embeddings".to_string(), labels(&["store"])),
+        ]);
+        assert_eq!(find_cycle(&providers, "store", &labels(&["cache", "llm"])),
+                   Some(labels(&["store", "llm", "embeddings", "store"])));
+        assert_eq!(find_cycle(&providers, "self", &labels(&["self"])), Some(labels(&["self", "self"])));
+        assert_eq!(find_cycle(&providers, "ui", &labels(&["llm", "embeddings"])), None);
+    }
+
+    #[test]
+    fn test_dependents_stop_before_their_providers() {
+        // (label, depends_on, rank): "embeddings" ranks first but both others need it
+        let sidecars = [
+            ("embeddings".to_string(), labels(&[]), (-1, 0)),
+            ("llm".to_string(), labels(&["embeddings", "missing"]), (0, 1)),
+            ("search".to_string(), labels(&["embeddings"]), (0, 2)),
+            ("notes".to_string(), labels(&[]), (0, 3)),
+        ];
+        assert_eq!(waves(&sidecars, false), [["llm"], ["search"], ["embeddings"], ["notes"]]);
+        assert_eq!(waves(&sidecars, true), [labels(&["llm", "search", "notes"]), labels(&["embeddings"])]);
+    }
+}
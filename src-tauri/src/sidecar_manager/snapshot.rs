@@ -0,0 +1,40 @@
+//! Carrying a sidecar's in-memory state across restarts.
+//!
+//! The manager asks the old process for its state with [`SNAPSHOT_METHOD`] and hands it to the
+//! new one with [`RESTORE_METHOD`] (`{"state": ...}`) once it is listening, before warmup and
+//! `sidecar://ready`. Both are ordinary RPCs, so sidecars without them just start fresh.
+
+use super::error::SidecarError;
+
+/// RPC returning the sidecar's serialized state as its result
+pub const SNAPSHOT_METHOD: &str = "snapshot";
+
+/// RPC feeding a snapshot to a freshly started sidecar
+pub const RESTORE_METHOD: &str = "restore";
+
+/// Largest snapshot kept unless `SidecarManagerConfig::max_snapshot_bytes` says otherwise
+pub const DEFAULT_MAX_SNAPSHOT_BYTES: usize = 8 * 1024 * 1024;
+
+/// The state in a [`SNAPSHOT_METHOD`] response, refused if its JSON exceeds `max_bytes`
+pub fn from_response(response: serde_json::Value, max_bytes: usize) -> Result<serde_json::Value, SidecarError> {
+    let state = response.get("result").cloned().unwrap_or_default();
+    let size = serde_json::to_vec(&state).map_err(|e| SidecarError::Protocol(e.to_string()))?.len();
+    if size > max_bytes {
+        return Err(SidecarError::Protocol(format!(
+            "State snapshot of {} bytes exceeds the {} byte limit", size, max_bytes)));
+    }
+    Ok(state)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot_size_is_bounded() {
+        let response = serde_json::json!({"jsonrpc": "2.0", "id": "1", "result": {"open_files": ["a.md"]}});
+        assert_eq!(from_response(response.clone(), 1024).unwrap(), serde_json::json!({"open_files": ["a.md"]}));
+        let err = from_response(response, 8).unwrap_err();
+        assert!(err.to_string().contains("exceeds the 8 byte limit"), "unexpected error: {}", err);
+    }
+}
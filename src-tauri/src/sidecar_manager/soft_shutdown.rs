@@ -0,0 +1,138 @@
+//! Cooperative shutdown: ask the sidecar to flush over RPC before it is signalled.
+//!
+//! A sidecar saving a large index may need far longer than `terminate_grace`, so instead of a
+//! fixed deadline it reports progress with `progress_method` notifications while the RPC runs.
+//! Each one is forwarded as a `shutdown://progress` event and resets the stall timer; only a
+//! sidecar that goes quiet for `stall_timeout` is handed to the SIGTERM/SIGKILL escalation.
+
+use std::time::Duration;
+
+use futures::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use tokio_tungstenite::tungstenite::protocol::Message;
+
+use super::connection::{self, ClientOptions, ConnectionCounters};
+use super::error::{self, SidecarError};
+use super::EventEmitter;
+
+/// The flush RPC sent before a sidecar is terminated
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SoftShutdownConfig {
+    pub method: String,
+    pub params: serde_json::Value,
+    /// Notification method the sidecar uses to report flush progress
+    pub progress_method: String,
+    /// Longest gap between progress reports before the sidecar is considered stuck
+    #[serde(with = "super::config_file::duration_ms", rename = "stall_timeout_ms")]
+    pub stall_timeout: Duration,
+}
+
+impl Default for SoftShutdownConfig {
+    fn default() -> Self {
+        Self {
+            method: "shutdown".to_string(),
+            params: serde_json::json!({}),
+            progress_method: "shutdown.progress".to_string(),
+            stall_timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+/// How a soft shutdown ended
+#[derive(Debug)]
+pub enum SoftShutdownOutcome {
+    /// The sidecar answered the RPC (or closed the connection once done)
+    Completed,
+    /// No progress for `stall_timeout`
+    Stalled,
+    Failed(SidecarError),
+}
+
+/// Send the flush RPC to the sidecar on `host:port` and wait for it to finish, emitting
+/// `shutdown://progress` for every progress report along the way
+pub async fn run(
+    window_label: &str,
+    host: &str,
+    port: u16,
+    client: &ClientOptions,
+    counters: &ConnectionCounters,
+    config: &SoftShutdownConfig,
+    emitter: Option<&EventEmitter>,
+) -> SoftShutdownOutcome {
+    flush(window_label, host, port, client, counters, config, emitter).await.unwrap_or_else(SoftShutdownOutcome::Failed)
+}
+
+async fn flush(
+    window_label: &str,
+    host: &str,
+    port: u16,
+    client: &ClientOptions,
+    counters: &ConnectionCounters,
+    config: &SoftShutdownConfig,
+    emitter: Option<&EventEmitter>,
+) -> Result<SoftShutdownOutcome, SidecarError> {
+    let mut ws_stream = connection::open(host, port, &client.headers).await?;
+    counters.record_connect(false);
+
+    let (request_id, request_text) = connection::encode_request(&config.method, config.params.clone(), None, client)?;
+    counters.record_sent(request_text.len());
+    ws_stream.send(Message::Text(request_text)).await
+        .map_err(|e| connection::map_ws_error("Failed to send shutdown request", e))?;
+
+    loop {
+        let msg = match tokio::time::timeout(config.stall_timeout, next_relevant(&mut ws_stream, counters, config, &request_id)).await {
+            Ok(msg) => msg?,
+            Err(_) => return Ok(SoftShutdownOutcome::Stalled),
+        };
+        match msg {
+            Relevant::Progress(params) => {
+                if let Some(emitter) = emitter {
+                    emitter("shutdown://progress", serde_json::json!({
+                        "window_label": window_label,
+                        "progress": params,
+                    }));
+                }
+            }
+            Relevant::Response(response) => return error::check_response(response).map(|_| SoftShutdownOutcome::Completed),
+            // A sidecar that exits right after flushing may not answer first
+            Relevant::Closed => return Ok(SoftShutdownOutcome::Completed),
+        }
+    }
+}
+
+enum Relevant {
+    Progress(serde_json::Value),
+    Response(serde_json::Value),
+    Closed,
+}
+
+/// Next message that counts as progress; other traffic (e.g. periodic events) is skipped so it
+/// can't hide a stalled flush
+async fn next_relevant(
+    ws_stream: &mut connection::WsStream,
+    counters: &ConnectionCounters,
+    config: &SoftShutdownConfig,
+    request_id: &str,
+) -> Result<Relevant, SidecarError> {
+    while let Some(msg) = ws_stream.next().await {
+        let text = match msg {
+            Ok(Message::Text(text)) => text,
+            Ok(Message::Close(_)) => break,
+            Ok(_) => continue,
+            Err(e) if connection::is_peer_closed(&e) => break,
+            Err(e) => return Err(connection::map_ws_error("WebSocket stream error", e)),
+        };
+        counters.record_received(text.len());
+        let message: serde_json::Value = serde_json::from_str(&text)
+            .map_err(|e| SidecarError::Protocol(e.to_string()))?;
+
+        if connection::RpcId::key_of(&message).as_deref() == Some(request_id) {
+            return Ok(Relevant::Response(message));
+        }
+        if message.get("method").and_then(|m| m.as_str()) == Some(config.progress_method.as_str()) {
+            return Ok(Relevant::Progress(message.get("params").cloned().unwrap_or(serde_json::Value::Null)));
+        }
+    }
+    Ok(Relevant::Closed)
+}
@@ -0,0 +1,171 @@
+//! Startup stages of a sidecar launch, reported as `spawn://stage` events and bounded by
+//! `SidecarManagerConfig::spawn_deadline`.
+
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use tokio::sync::watch;
+
+use super::error::SidecarError;
+use super::EventEmitter;
+
+/// Overall startup budget unless `SidecarManagerConfig::spawn_deadline` says otherwise
+pub const DEFAULT_SPAWN_DEADLINE: Duration = Duration::from_secs(60);
+
+/// Milestones of a launch, in the order they are reached
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SpawnStage {
+    /// An interpreter was found and its version checked
+    PythonResolved,
+    /// The process (or its launcher) is running
+    ProcessStarted,
+    /// The sidecar wrote its ready file and accepts connections
+    PortListening,
+    /// State restore and warmup methods are done; `sidecar://ready` follows
+    WarmedUp,
+}
+
+impl SpawnStage {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::PythonResolved => "python-resolved",
+            Self::ProcessStarted => "process-started",
+            Self::PortListening => "port-listening",
+            Self::WarmedUp => "warmed-up",
+        }
+    }
+
+    /// The stage a launch that has reached `last` is working towards
+    pub fn after(last: Option<SpawnStage>) -> SpawnStage {
+        match last {
+            None => Self::PythonResolved,
+            Some(Self::PythonResolved) => Self::ProcessStarted,
+            Some(Self::ProcessStarted) => Self::PortListening,
+            Some(Self::PortListening | Self::WarmedUp) => Self::WarmedUp,
+        }
+    }
+}
+
+impl fmt::Display for SpawnStage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// One launch's progress through the [`SpawnStage`]s against its deadline
+pub struct SpawnProgress {
+    window_label: String,
+    emitter: Option<EventEmitter>,
+    started: Instant,
+    deadline: Duration,
+    reached: Option<SpawnStage>,
+}
+
+impl SpawnProgress {
+    pub fn new(window_label: &str, emitter: Option<EventEmitter>, deadline: Duration) -> Self {
+        Self { window_label: window_label.to_string(), emitter, started: Instant::now(), deadline, reached: None }
+    }
+
+    /// Record `stage` and emit `spawn://stage`
+    pub fn reach(&mut self, stage: SpawnStage) {
+        self.reached = Some(stage);
+        if let Some(emitter) = &self.emitter {
+            emitter("spawn://stage", serde_json::json!({
+                "window_label": self.window_label,
+                "stage": stage,
+                "elapsed_ms": self.started.elapsed().as_millis() as u64,
+            }));
+        }
+    }
+
+    /// Time left before the deadline
+    pub fn remaining(&self) -> Duration {
+        self.deadline.saturating_sub(self.started.elapsed())
+    }
+
+    /// [`SidecarError::SpawnTimeout`] naming the stage the launch is stuck on, if the deadline
+    /// has passed
+    pub fn check(&self) -> Result<(), SidecarError> {
+        match self.remaining().is_zero() {
+            true => Err(self.timed_out()),
+            false => Ok(()),
+        }
+    }
+
+    pub fn timed_out(&self) -> SidecarError {
+        SidecarError::SpawnTimeout { stage: SpawnStage::after(self.reached), deadline: self.deadline }
+    }
+
+    /// Emit `spawn://failed` for a launch that will not become ready
+    pub fn fail(&self, error: &SidecarError) {
+        if let Some(emitter) = &self.emitter {
+            emitter("spawn://failed", serde_json::json!({
+                "window_label": self.window_label,
+                "stage": SpawnStage::after(self.reached),
+                "error": error.to_string(),
+            }));
+        }
+    }
+}
+
+/// Aborts a launch started with `SidecarManager::spawn_sidecar_cancellable`; clones share the
+/// cancellation
+#[derive(Clone)]
+pub struct SpawnCancel(Arc<watch::Sender<bool>>);
+
+impl SpawnCancel {
+    pub fn new() -> Self {
+        Self(Arc::new(watch::channel(false).0))
+    }
+
+    pub fn cancel(&self) {
+        self.0.send_replace(true);
+    }
+
+    /// Whether `other` is a clone of this token
+    pub fn same_launch(&self, other: &SpawnCancel) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        *self.0.borrow()
+    }
+
+    /// [`SidecarError::Cancelled`] once cancelled
+    pub fn check(&self) -> Result<(), SidecarError> {
+        match self.is_cancelled() {
+            true => Err(SidecarError::Cancelled),
+            false => Ok(()),
+        }
+    }
+
+    /// Resolves once cancelled
+    pub async fn cancelled(&self) {
+        let _ = self.0.subscribe().wait_for(|cancelled| *cancelled).await;
+    }
+}
+
+impl Default for SpawnCancel {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timeout_names_the_stage_in_progress() {
+        let mut progress = SpawnProgress::new("w", None, Duration::ZERO);
+        assert!(progress.check().is_err());
+        progress.reach(SpawnStage::ProcessStarted);
+        let err = progress.timed_out();
+        assert!(matches!(err, SidecarError::SpawnTimeout { stage: SpawnStage::PortListening, .. }), "{:?}", err);
+        assert!(err.to_string().contains("'port-listening'"), "unexpected error: {}", err);
+        assert!(SpawnProgress::new("w", None, DEFAULT_SPAWN_DEADLINE).check().is_ok());
+    }
+}
@@ -0,0 +1,81 @@
+//! Lifecycle state of each window's sidecar, published on a per-window `watch` channel.
+
+use std::collections::HashMap;
+
+use serde::Serialize;
+use tokio::sync::watch;
+
+/// Where a window's sidecar is in its lifecycle
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SidecarState {
+    /// Spawned, waiting for the ready file and warmup
+    Starting,
+    Ready,
+    /// Ready with at least one command in flight
+    Busy,
+    /// Being stopped by `restart_sidecar`; `Starting` follows
+    Restarting,
+    /// Stopped with SIGSTOP/SIGTSTP through `signal_sidecar`, until SIGCONT
+    Suspended,
+    /// The vault directory vanished (moved or renamed) while the sidecar ran; commands are
+    /// refused until it reappears or `rebind_vault` points the sidecar at the new location
+    #[serde(rename = "path_lost")]
+    PathLost,
+    /// Failed to spawn or become ready, crashed, or could not be stopped
+    Failed,
+    /// Not running: terminated, or never spawned
+    Terminated,
+}
+
+/// One state channel per window. Channels outlive restarts so watchers follow a window across
+/// launches, and are dropped on terminate once nobody watches them.
+#[derive(Default)]
+pub struct StateRegistry {
+    senders: std::sync::Mutex<HashMap<String, watch::Sender<SidecarState>>>,
+}
+
+impl StateRegistry {
+    /// The window's channel, created as `Terminated` if it has none yet
+    pub fn sender(&self, window_label: &str) -> watch::Sender<SidecarState> {
+        self.senders.lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry(window_label.to_string())
+            .or_insert_with(|| watch::channel(SidecarState::Terminated).0)
+            .clone()
+    }
+
+    pub fn set(&self, window_label: &str, state: SidecarState) {
+        self.sender(window_label).send_replace(state);
+    }
+
+    /// Record that the window's sidecar is gone, forgetting the channel if nobody watches it
+    pub fn terminated(&self, window_label: &str, state: SidecarState) {
+        let mut senders = self.senders.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(sender) = senders.get(window_label) {
+            sender.send_replace(state);
+            if sender.receiver_count() == 0 {
+                senders.remove(window_label);
+            }
+        }
+    }
+
+    /// A receiver whose first `changed()` resolves at once with the current state
+    pub fn subscribe(&self, window_label: &str) -> watch::Receiver<SidecarState> {
+        let mut receiver = self.sender(window_label).subscribe();
+        receiver.mark_changed();
+        receiver
+    }
+}
+
+/// Move `sender` from `from` to `to`, leaving any other state alone (e.g. a sidecar terminated
+/// while its readiness check was still running stays `Terminated`); returns whether it moved
+pub fn transition(sender: &watch::Sender<SidecarState>, from: SidecarState, to: SidecarState) -> bool {
+    sender.send_if_modified(|state| {
+        if *state != from {
+            return false;
+        }
+        *state = to;
+        true
+    })
+}
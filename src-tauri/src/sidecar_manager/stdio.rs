@@ -0,0 +1,257 @@
+//! JSON-RPC over a sidecar's stdin/stdout, for minimal sidecars that don't open a WebSocket
+//! (`SpawnOptions::transport = Transport::Stdio`).
+//!
+//! Messages are framed LSP-style: `Content-Length: <bytes>\r\n\r\n` followed by the JSON body;
+//! other headers are ignored. Responses are matched to requests by id like on a WebSocket
+//! (including [`NullIdErrors`] for error responses without one), and
+//! id-less messages are published as [`SidecarNotification::Event`]s. Binary frames, blobs and
+//! handshake headers have no stdio equivalent. stderr is still read as the sidecar's log.
+
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Write};
+use std::process::{ChildStdin, ChildStdout};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use serde::Serialize;
+use tokio::sync::{broadcast, oneshot};
+
+use super::connection::{encode_request, is_null_id_error, unattributed_error, ClientOptions, ConnectionCounters, NullIdErrors,
+                        RpcId};
+use super::error::SidecarError;
+use super::notifications::SidecarNotification;
+
+/// Largest message body accepted from a sidecar's stdout
+pub const MAX_FRAME_BYTES: usize = 64 * 1024 * 1024;
+
+/// How the manager talks to a sidecar
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Transport {
+    /// JSON-RPC over a WebSocket on an allocated port
+    #[default]
+    WebSocket,
+    /// `Content-Length` framed JSON-RPC over stdin/stdout; no port is allocated
+    Stdio,
+}
+
+type Pending = Arc<std::sync::Mutex<HashMap<String, oneshot::Sender<Result<serde_json::Value, SidecarError>>>>>;
+
+/// Handle to one sidecar's stdio channel. Cheap to clone; the reader thread ends with the pipe.
+#[derive(Clone)]
+pub struct StdioTransport {
+    stdin: Arc<std::sync::Mutex<ChildStdin>>,
+    pending: Pending,
+    open: Arc<AtomicBool>,
+    counters: Arc<ConnectionCounters>,
+}
+
+impl StdioTransport {
+    /// Take over the child's `stdin` and `stdout`, reading responses on a thread that is returned
+    /// for joining once the process exits
+    pub fn start(
+        window_label: &str,
+        stdin: ChildStdin,
+        stdout: ChildStdout,
+        counters: Arc<ConnectionCounters>,
+        notifications: broadcast::Sender<SidecarNotification>,
+        null_ids: NullIdErrors,
+    ) -> (Self, std::thread::JoinHandle<()>) {
+        let transport = Self {
+            stdin: Arc::new(std::sync::Mutex::new(stdin)),
+            pending: Pending::default(),
+            open: Arc::new(AtomicBool::new(true)),
+            counters,
+        };
+        let reader = transport.clone();
+        let label = window_label.to_string();
+        let thread = std::thread::spawn(move || {
+            reader.read_responses(&label, BufReader::new(stdout), &notifications, null_ids);
+        });
+        (transport, thread)
+    }
+
+    /// False once the sidecar closed its stdout or sent something unframeable
+    pub fn is_open(&self) -> bool {
+        self.open.load(Ordering::SeqCst)
+    }
+
+    /// Send a JSON-RPC request and wait for the response with the matching id
+    pub async fn request(
+        &self,
+        method: &str,
+        params: serde_json::Value,
+        client: &ClientOptions,
+    ) -> Result<serde_json::Value, SidecarError> {
+        let (id, text) = encode_request(method, params, None, client)?;
+        let (reply, response) = oneshot::channel();
+        {
+            // Checked under the lock the reader closes the channel with, so the waiter can't be
+            // registered after the reader dropped the others
+            let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+            if !self.is_open() {
+                return Err(SidecarError::ConnectionClosed("Sidecar closed its stdout".to_string()));
+            }
+            pending.insert(id.clone(), reply);
+        }
+        // Forget the request if the caller gives up (e.g. its timeout passes)
+        let _waiting = Waiting { pending: &self.pending, id };
+        self.write(encode_frame(text.as_bytes())).await?;
+        response.await.map_err(|_| {
+            SidecarError::ConnectionClosed("Sidecar closed its stdout without a response".to_string())
+        })?
+    }
+
+    async fn write(&self, frame: Vec<u8>) -> Result<(), SidecarError> {
+        let stdin = self.stdin.clone();
+        let len = frame.len();
+        // Pipe writes block while the sidecar isn't reading
+        tokio::task::spawn_blocking(move || {
+            let mut stdin = stdin.lock().unwrap_or_else(|e| e.into_inner());
+            stdin.write_all(&frame).and_then(|_| stdin.flush())
+        })
+        .await
+        .map_err(|e| SidecarError::Connection(format!("Stdin writer panicked: {}", e)))?
+        .map_err(|e| SidecarError::ConnectionClosed(format!("Failed to write to sidecar stdin: {}", e)))?;
+        self.counters.record_sent(len);
+        Ok(())
+    }
+
+    fn read_responses(
+        &self,
+        window_label: &str,
+        mut stdout: impl BufRead,
+        notifications: &broadcast::Sender<SidecarNotification>,
+        null_ids: NullIdErrors,
+    ) {
+        loop {
+            let body = match read_frame(&mut stdout) {
+                Ok(Some(body)) => body,
+                Ok(None) => break,
+                Err(e) => {
+                    eprintln!("Unreadable stdout frame from sidecar '{}', closing its stdio channel: {}",
+                              window_label, e);
+                    break;
+                }
+            };
+            self.counters.record_received(body.len());
+            let message: serde_json::Value = match serde_json::from_slice(&body) {
+                Ok(message) => message,
+                Err(e) => {
+                    eprintln!("Ignoring non-JSON message from sidecar '{}': {}", window_label, e);
+                    continue;
+                }
+            };
+            if let Some(notification) = SidecarNotification::from_message(&message) {
+                // No subscribers is not an error
+                let _ = notifications.send(notification);
+                continue;
+            }
+            if is_null_id_error(&message) {
+                let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+                match null_ids.owner(pending.keys()).cloned() {
+                    Some(id) => {
+                        let _ = pending.remove(&id).map(|reply| reply.send(Ok(message)));
+                    }
+                    None if pending.is_empty() => {
+                        eprintln!("Ignoring error response with a null id from sidecar '{}': {}", window_label, message);
+                    }
+                    None => {
+                        let in_flight = pending.len();
+                        for (_, reply) in pending.drain() {
+                            let _ = reply.send(Err(unattributed_error(&message, in_flight)));
+                        }
+                    }
+                }
+                continue;
+            }
+            let waiter = RpcId::key_of(&message)
+                .and_then(|id| self.pending.lock().unwrap_or_else(|e| e.into_inner()).remove(&id));
+            match waiter {
+                Some(reply) => {
+                    let _ = reply.send(Ok(message));
+                }
+                None => eprintln!("Dropping response from sidecar '{}' to an unknown request: {}",
+                                  window_label, message),
+            }
+        }
+        // Waiters see their sender dropped and fail with `ConnectionClosed`
+        let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+        self.open.store(false, Ordering::SeqCst);
+        pending.clear();
+    }
+}
+
+/// Removes a request's waiter once it is answered or abandoned
+struct Waiting<'a> {
+    pending: &'a Pending,
+    id: String,
+}
+
+impl Drop for Waiting<'_> {
+    fn drop(&mut self) {
+        self.pending.lock().unwrap_or_else(|e| e.into_inner()).remove(&self.id);
+    }
+}
+
+/// `body` with its `Content-Length` header
+pub fn encode_frame(body: &[u8]) -> Vec<u8> {
+    let mut frame = format!("Content-Length: {}\r\n\r\n", body.len()).into_bytes();
+    frame.extend_from_slice(body);
+    frame
+}
+
+/// Read one framed message body; `None` at a clean end of stream
+pub fn read_frame(reader: &mut impl BufRead) -> io::Result<Option<Vec<u8>>> {
+    let mut length = None;
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return match line.is_empty() && length.is_none() {
+                true => Ok(None),
+                false => Err(io::ErrorKind::UnexpectedEof.into()),
+            };
+        }
+        let header = line.trim_end_matches(['\r', '\n']);
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                let value = value.trim().parse::<usize>().map_err(|_| invalid(format!("bad Content-Length {:?}", value)))?;
+                length = Some(value);
+            }
+        }
+    }
+    let length = length.ok_or_else(|| invalid("missing Content-Length header".to_string()))?;
+    if length > MAX_FRAME_BYTES {
+        return Err(invalid(format!("{} byte message exceeds the {} byte limit", length, MAX_FRAME_BYTES)));
+    }
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+    Ok(Some(body))
+}
+
+fn invalid(reason: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, reason)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frames_round_trip() {
+        let mut stream = encode_frame(br#"{"id":1}"#);
+        stream.extend_from_slice(b"content-length: 2\r\nContent-Type: application/json\r\n\r\n{}");
+        let mut reader = io::Cursor::new(stream);
+        assert_eq!(read_frame(&mut reader).unwrap().unwrap(), br#"{"id":1}"#);
+        assert_eq!(read_frame(&mut reader).unwrap().unwrap(), b"{}");
+        assert!(read_frame(&mut reader).unwrap().is_none());
+
+        for broken in [&b"Content-Type: x\r\n\r\n{}"[..], b"Content-Length: 10\r\n\r\n{}", b"Content-Length: 2\r\n"] {
+            assert!(read_frame(&mut io::Cursor::new(broken)).is_err(), "{:?}", String::from_utf8_lossy(broken));
+        }
+    }
+}
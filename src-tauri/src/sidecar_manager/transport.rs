@@ -0,0 +1,115 @@
+//! The message channel an RPC exchange runs over. Sidecars are reached over WebSockets; tests
+//! inject an in-memory transport scripted from the sidecar's end instead, which drives id
+//! matching, duplicate responses, notifications and closes deterministically.
+
+use futures::future::BoxFuture;
+use futures::{SinkExt, StreamExt};
+use tokio_tungstenite::tungstenite::error::Error as WsError;
+use tokio_tungstenite::tungstenite::protocol::Message;
+
+use super::connection::WsStream;
+
+/// A bidirectional stream of WebSocket messages to one sidecar
+pub trait RpcTransport: Send {
+    fn send(&mut self, message: Message) -> BoxFuture<'_, Result<(), WsError>>;
+
+    /// The next message from the sidecar; None once it has closed the stream
+    fn next(&mut self) -> BoxFuture<'_, Option<Result<Message, WsError>>>;
+
+    /// Write out anything the transport queued by itself, e.g. the pong answering a ping
+    fn flush(&mut self) -> BoxFuture<'_, Result<(), WsError>> {
+        Box::pin(async { Ok(()) })
+    }
+}
+
+impl RpcTransport for WsStream {
+    fn send(&mut self, message: Message) -> BoxFuture<'_, Result<(), WsError>> {
+        Box::pin(SinkExt::send(self, message))
+    }
+
+    fn next(&mut self) -> BoxFuture<'_, Option<Result<Message, WsError>>> {
+        Box::pin(StreamExt::next(self))
+    }
+
+    fn flush(&mut self) -> BoxFuture<'_, Result<(), WsError>> {
+        Box::pin(SinkExt::flush(self))
+    }
+}
+
+#[cfg(test)]
+pub use memory::MemoryTransport;
+
+#[cfg(test)]
+mod memory {
+    use tokio::sync::mpsc;
+
+    use super::*;
+
+    /// The manager's end of an in-memory transport
+    pub struct MemoryTransport {
+        incoming: mpsc::UnboundedReceiver<Result<Message, WsError>>,
+        outgoing: mpsc::UnboundedSender<Message>,
+    }
+
+    /// The sidecar's end, driven by the test
+    pub struct MemoryPeer {
+        incoming: mpsc::UnboundedReceiver<Message>,
+        outgoing: Option<mpsc::UnboundedSender<Result<Message, WsError>>>,
+    }
+
+    impl MemoryTransport {
+        pub fn pair() -> (Self, MemoryPeer) {
+            let (to_peer, from_manager) = mpsc::unbounded_channel();
+            let (to_manager, from_peer) = mpsc::unbounded_channel();
+            (
+                Self { incoming: from_peer, outgoing: to_peer },
+                MemoryPeer { incoming: from_manager, outgoing: Some(to_manager) },
+            )
+        }
+    }
+
+    impl RpcTransport for MemoryTransport {
+        fn send(&mut self, message: Message) -> BoxFuture<'_, Result<(), WsError>> {
+            let sent = self.outgoing.send(message).map_err(|_| WsError::ConnectionClosed);
+            Box::pin(async move { sent })
+        }
+
+        fn next(&mut self) -> BoxFuture<'_, Option<Result<Message, WsError>>> {
+            Box::pin(self.incoming.recv())
+        }
+    }
+
+    impl MemoryPeer {
+        /// The next message the manager sent, pings included; None once it dropped its end
+        pub async fn recv(&mut self) -> Option<Message> {
+            self.incoming.recv().await
+        }
+
+        /// The next JSON-RPC message the manager sent, answering pings on the way like a
+        /// WebSocket peer would
+        pub async fn recv_json(&mut self) -> serde_json::Value {
+            loop {
+                match self.recv().await.expect("manager closed the transport") {
+                    Message::Text(text) => return serde_json::from_str(&text).unwrap(),
+                    Message::Ping(payload) => self.send(Message::Pong(payload)),
+                    _ => {}
+                }
+            }
+        }
+
+        pub fn send(&self, message: Message) {
+            if let Some(outgoing) = &self.outgoing {
+                let _ = outgoing.send(Ok(message));
+            }
+        }
+
+        pub fn send_json(&self, message: serde_json::Value) {
+            self.send(Message::Text(message.to_string()));
+        }
+
+        /// End the stream without a close frame, like a sidecar that exited
+        pub fn close(&mut self) {
+            self.outgoing = None;
+        }
+    }
+}
@@ -0,0 +1,230 @@
+//! Per-vault sidecar settings read from `tailor.toml` or `sidecar.json` in the vault root.
+//!
+//! ```toml
+//! log_level = "DEBUG"
+//! python_version = ">=3.10, <3.13"
+//! extra_args = ["--verbose"]
+//! warmup_methods = ["system.info"]
+//! working_dir = "vault"
+//! watch_plugins = true
+//! capture = "lines"
+//! priority = "low"
+//!
+//! [env]
+//! HF_HOME = "/data/hf"
+//!
+//! [rate_limit]
+//! per_second = 5.0
+//! burst = 10
+//! ```
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+use super::capture::CaptureMode;
+use super::process::ProcessPriority;
+use super::python::VersionConstraint;
+use super::rate_limit::RateLimitConfig;
+use super::{SpawnOptions, WorkingDir};
+
+/// Config file names, in lookup order
+const CONFIG_FILES: [&str; 2] = ["tailor.toml", "sidecar.json"];
+
+/// Keys understood by this version; anything else is warned about and ignored
+const KNOWN_KEYS: [&str; 10] = [
+    "extra_args", "env", "log_level", "warmup_methods", "working_dir", "watch_plugins", "python_version", "rate_limit",
+    "capture", "priority",
+];
+
+/// Log levels accepted by the sidecar's `--log-level`
+pub const LOG_LEVELS: [&str; 5] = ["DEBUG", "INFO", "WARNING", "ERROR", "CRITICAL"];
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct VaultConfig {
+    pub extra_args: Vec<String>,
+    pub env: HashMap<String, String>,
+    pub log_level: Option<String>,
+    pub warmup_methods: Vec<String>,
+    /// Relative `path`s are resolved against the vault when loading
+    pub working_dir: Option<WorkingDir>,
+    /// Reload the sidecar when its plugins change, for plugin development
+    pub watch_plugins: bool,
+    /// Python version the vault's plugins need, see [`VersionConstraint`]
+    pub python_version: Option<String>,
+    /// Cap on the commands sent to the sidecar, e.g. when its plugins wrap a rate-limited API
+    pub rate_limit: Option<RateLimitConfig>,
+    /// `raw` or `length_prefixed` for plugins writing binary output, see [`CaptureMode`]
+    pub capture: Option<CaptureMode>,
+    /// Scheduling priority, e.g. `low` for a vault whose plugins index in the background
+    pub priority: Option<ProcessPriority>,
+}
+
+impl VaultConfig {
+    /// Fill in whatever `options` leaves unset. Explicit options always win; env vars merge per key.
+    pub fn apply_to(self, options: &mut SpawnOptions) {
+        if options.extra_args.is_empty() {
+            options.extra_args = self.extra_args;
+        }
+        for (key, value) in self.env {
+            options.env.entry(key).or_insert(value);
+        }
+        if options.log_level.is_none() {
+            options.log_level = self.log_level;
+        }
+        if options.warmup_methods.is_empty() {
+            options.warmup_methods = self.warmup_methods;
+        }
+        if options.working_dir.is_none() {
+            options.working_dir = self.working_dir;
+        }
+        options.watch_plugins |= self.watch_plugins;
+        if options.python_version.is_none() {
+            options.python_version = self.python_version;
+        }
+        if options.rate_limit.is_none() {
+            options.rate_limit = self.rate_limit;
+        }
+        if options.capture == CaptureMode::Lines {
+            options.capture = self.capture.unwrap_or_default();
+        }
+        if options.priority == ProcessPriority::Normal {
+            options.priority = self.priority.unwrap_or_default();
+        }
+    }
+}
+
+/// Locate the vault's sidecar config file, if any
+fn find_config_file(vault_path: &Path) -> Option<PathBuf> {
+    CONFIG_FILES.iter()
+        .map(|name| vault_path.join(name))
+        .find(|path| path.is_file())
+}
+
+/// Load the vault's sidecar config. A vault without a config file yields the empty default.
+pub fn load_vault_config(vault_path: &Path) -> Result<VaultConfig> {
+    let Some(path) = find_config_file(vault_path) else {
+        return Ok(VaultConfig::default());
+    };
+
+    let contents = std::fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    let value: serde_json::Value = if path.extension().is_some_and(|ext| ext == "json") {
+        serde_json::from_str(&contents)
+            .with_context(|| format!("Invalid JSON in {}", path.display()))?
+    } else {
+        toml::from_str(&contents)
+            .with_context(|| format!("Invalid TOML in {}", path.display()))?
+    };
+
+    let table = value.as_object()
+        .with_context(|| format!("{} must contain a table of settings", path.display()))?;
+    for key in table.keys().filter(|key| !KNOWN_KEYS.contains(&key.as_str())) {
+        // Unknown keys may belong to a newer version; don't fail on them
+        eprintln!("Warning: ignoring unknown key '{}' in {}", key, path.display());
+    }
+
+    let mut config: VaultConfig = serde_json::from_value(value)
+        .with_context(|| format!("Invalid sidecar settings in {}", path.display()))?;
+
+    if let Some(level) = &config.log_level {
+        let level = level.to_uppercase();
+        if !LOG_LEVELS.contains(&level.as_str()) {
+            anyhow::bail!(
+                "Invalid log_level '{}' in {} (expected one of {})",
+                level, path.display(), LOG_LEVELS.join(", ")
+            );
+        }
+        config.log_level = Some(level);
+    }
+    if let Some(version) = &config.python_version {
+        VersionConstraint::parse(version).map_err(|e| anyhow::anyhow!("{} in {}", e, path.display()))?;
+    }
+    if let Some(rate_limit) = &config.rate_limit {
+        rate_limit.validate().map_err(|e| anyhow::anyhow!("Invalid rate_limit in {}: {}", path.display(), e))?;
+    }
+    if let Some(WorkingDir::Path(dir)) = &mut config.working_dir {
+        *dir = vault_path.join(&*dir);
+    }
+
+    Ok(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_vault() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("tailor-vault-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_load_and_merge_vault_config() {
+        let vault = temp_vault();
+        std::fs::write(vault.join("tailor.toml"), r#"
+log_level = "debug"
+warmup_methods = ["system.info"]
+working_dir = { path = "data" }
+watch_plugins = true
+python_version = "3.12"
+capture = "length_prefixed"
+priority = { nice = 5 }
+future_option = true
+
+[env]
+A = "file"
+B = "file"
+
+[rate_limit]
+per_second = 2.5
+burst = 5
+"#).unwrap();
+
+        let config = load_vault_config(&vault).unwrap();
+        assert_eq!(config.log_level.as_deref(), Some("DEBUG"));
+
+        let mut options = SpawnOptions {
+            env: HashMap::from([("A".to_string(), "explicit".to_string())]),
+            ..Default::default()
+        };
+        config.apply_to(&mut options);
+        assert_eq!(options.env["A"], "explicit");
+        assert_eq!(options.env["B"], "file");
+        assert_eq!(options.warmup_methods, vec!["system.info"]);
+        assert_eq!(options.working_dir, Some(WorkingDir::Path(vault.join("data"))));
+        assert!(options.watch_plugins);
+        assert_eq!(options.python_version.as_deref(), Some("3.12"));
+        assert_eq!(options.rate_limit, Some(RateLimitConfig { per_second: 2.5, burst: 5 }));
+        assert_eq!(options.capture, CaptureMode::LengthPrefixed);
+        assert_eq!(options.priority, ProcessPriority::Nice(5));
+
+        // An explicit working directory wins over the file's
+        let mut options = SpawnOptions { working_dir: Some(WorkingDir::ProjectRoot), ..Default::default() };
+        load_vault_config(&vault).unwrap().apply_to(&mut options);
+        assert_eq!(options.working_dir, Some(WorkingDir::ProjectRoot));
+        std::fs::write(vault.join("tailor.toml"), "working_dir = \"vault\"\npriority = \"low\"").unwrap();
+        let config = load_vault_config(&vault).unwrap();
+        assert_eq!(config.working_dir, Some(WorkingDir::Vault));
+        assert_eq!(config.priority, Some(ProcessPriority::Low));
+        std::fs::write(vault.join("tailor.toml"), "python_version = \"~3\"").unwrap();
+        assert!(load_vault_config(&vault).is_err());
+        std::fs::write(vault.join("tailor.toml"), "rate_limit = { per_second = 0.0, burst = 1 }").unwrap();
+        assert!(load_vault_config(&vault).is_err());
+
+        std::fs::remove_dir_all(vault).unwrap();
+    }
+
+    #[test]
+    fn test_invalid_vault_config_is_rejected() {
+        let vault = temp_vault();
+        std::fs::write(vault.join("sidecar.json"), r#"{"log_level": "LOUD"}"#).unwrap();
+        assert!(load_vault_config(&vault).is_err());
+
+        std::fs::remove_dir_all(&vault).unwrap();
+        assert!(load_vault_config(&vault).unwrap().env.is_empty());
+    }
+}
@@ -0,0 +1,34 @@
+//! Noticing a vault directory that was moved or renamed while its sidecar runs, and pointing
+//! the sidecar at the new location.
+//!
+//! `SidecarManager::run_vault_monitor` stats every sidecar's vault periodically. A vanished one
+//! moves the sidecar to `SidecarState::PathLost`, where commands fail with
+//! `SidecarError::VaultPathLost` instead of whatever the sidecar would make of missing files.
+//! `rebind_vault` then hands the sidecar the new path with [`REBIND_METHOD`], or restarts it
+//! there if it can't switch in-process. A vault moved back where it was recovers on its own.
+
+use std::path::Path;
+use std::time::Duration;
+
+use serde::Serialize;
+
+/// RPC switching a running sidecar to another vault directory (`{"vault_path"}`)
+pub const REBIND_METHOD: &str = "vault.rebind";
+
+/// How often vault directories are checked unless configured otherwise
+pub const DEFAULT_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How `SidecarManager::rebind_vault` got the sidecar onto the new path
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Rebind {
+    /// The running sidecar switched over
+    HotSwitched,
+    /// The sidecar doesn't support switching and was restarted on the new path
+    Restarted,
+}
+
+/// Whether the vault directory is still where the sidecar was told it is
+pub fn is_present(vault_path: &str) -> bool {
+    Path::new(vault_path).is_dir()
+}
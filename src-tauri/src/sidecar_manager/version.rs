@@ -0,0 +1,52 @@
+//! The version and build a sidecar reports about itself with [`VERSION_METHOD`], for bug
+//! reports and compatibility checks.
+//!
+//! The manager asks once per launch and caches the answer on the process, so a restart (which
+//! may bring a different version) asks again. Sidecars without the RPC are [`VersionInfo::Unknown`].
+
+use serde::Serialize;
+
+/// RPC returning `{"version", "git_commit", "python"}`
+pub const VERSION_METHOD: &str = "system.version";
+
+/// What a sidecar said about its build
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum VersionInfo {
+    Reported {
+        version: String,
+        /// None when the sidecar doesn't run from a known commit
+        git_commit: Option<String>,
+        /// The interpreter version as the sidecar sees it
+        python: Option<String>,
+    },
+    /// The sidecar doesn't implement [`VERSION_METHOD`] or answered without a version
+    Unknown,
+}
+
+/// The build info in a [`VERSION_METHOD`] response
+pub fn from_response(response: &serde_json::Value) -> VersionInfo {
+    let field = |name: &str| response.pointer(&format!("/result/{}", name))
+        .and_then(|value| value.as_str())
+        .map(str::to_string);
+    match field("version") {
+        Some(version) => VersionInfo::Reported { version, git_commit: field("git_commit"), python: field("python") },
+        None => VersionInfo::Unknown,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_version_is_read_from_the_result() {
+        let response = serde_json::json!({"result": {"version": "0.1.0", "git_commit": null, "python": "3.11.7"}});
+        assert_eq!(from_response(&response), VersionInfo::Reported {
+            version: "0.1.0".to_string(),
+            git_commit: None,
+            python: Some("3.11.7".to_string()),
+        });
+        assert_eq!(from_response(&serde_json::json!({"result": {"status": "success"}})), VersionInfo::Unknown);
+    }
+}
@@ -1,5 +1,5 @@
 use std::collections::HashMap;
-use tauri::{AppHandle, WebviewWindowBuilder};
+use tauri::{AppHandle, Manager, WebviewWindowBuilder};
 use anyhow::Result;
 
 pub struct WindowManager {
@@ -27,11 +27,22 @@ impl WindowManager {
     ) -> Result<String> {
         // Generate unique window label
         let window_label = format!("vault_{}", uuid::Uuid::new_v4());
+        self.open_vault_window(app, &window_label, vault_path)?;
+        Ok(window_label)
+    }
 
+    /// Create a vault window under a given label, e.g. to reopen the window of a sidecar
+    /// adopted by `SidecarManager::reattach_all`
+    pub fn open_vault_window(
+        &mut self,
+        app: &AppHandle,
+        window_label: &str,
+        vault_path: String,
+    ) -> Result<()> {
         // Create the window
         let _window = WebviewWindowBuilder::new(
             app,
-            &window_label,
+            window_label,
             tauri::WebviewUrl::App("vault.html".into()),
         )
         .title(format!("Tailor - {}", Self::extract_vault_name(&vault_path)))
@@ -41,11 +52,11 @@ impl WindowManager {
         .build()?;
 
         // Store window reference
-        self.windows.insert(window_label.clone(), vault_path.clone());
+        self.windows.insert(window_label.to_string(), vault_path.clone());
 
         println!("Created window '{}' for vault: {}", window_label, vault_path);
 
-        Ok(window_label)
+        Ok(())
     }
 
     /// Get vault path for a window
@@ -53,6 +64,15 @@ impl WindowManager {
         self.windows.get(window_label)
     }
 
+    /// Track the window's vault at a new location (it was moved or renamed) and retitle the window
+    pub fn set_vault_path(&mut self, app: &AppHandle, window_label: &str, vault_path: String) -> Result<()> {
+        if let Some(window) = app.get_webview_window(window_label) {
+            window.set_title(&format!("Tailor - {}", Self::extract_vault_name(&vault_path)))?;
+        }
+        self.windows.insert(window_label.to_string(), vault_path);
+        Ok(())
+    }
+
     /// Remove window from tracking
     pub fn remove_window(&mut self, window_label: &str) {
         self.windows.remove(window_label);
@@ -154,6 +154,21 @@ export async function autoConnect(loadPluginsFn, handleEventFn) {
         }
     }
 
+    // 3. Follow the sidecar if a restart moves it to another port
+    try {
+        const { listen } = await import('@tauri-apps/api/event');
+        const { getCurrentWindow } = await import('@tauri-apps/api/window');
+        const label = getCurrentWindow().label;
+        await listen('sidecar://port-changed', (event) => {
+            if (event.payload.window_label === label) {
+                log(`Sidecar moved from port ${event.payload.old_port} to ${event.payload.new_port}`);
+                currentPort = event.payload.new_port;
+            }
+        });
+    } catch (e) {
+        // Not in Tauri
+    }
+
     if (autoPort) {
         setTimeout(() => connect(autoPort, loadPluginsFn, handleEventFn), 300);
     } else {